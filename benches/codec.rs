@@ -0,0 +1,134 @@
+use std::{
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use simple_modbus::{
+    calc_crc,
+    codec::{decode_response, encode_request, Request},
+    error::Result,
+    stream::Stream,
+    Client, Function, Protocol,
+};
+
+/// 每次请求之后返回同一个响应, 只测量客户端封装, 校验和解析的开销
+struct Loopback {
+    reply: Vec<u8>,
+    pos: usize,
+    tcp: bool,
+}
+
+impl Loopback {
+    /// pdu 为响应的 PDU, 从设备ID为 1
+    fn new(protocol: Protocol, pdu: &[u8]) -> Self {
+        let reply = match protocol {
+            Protocol::Tcp => {
+                let mut reply = vec![0, 0, 0, 0];
+                reply.extend_from_slice(&(1 + pdu.len() as u16).to_be_bytes());
+                reply.push(1);
+                reply.extend_from_slice(pdu);
+                reply
+            }
+            _ => {
+                let mut reply = vec![1];
+                reply.extend_from_slice(pdu);
+                let crc = calc_crc(&reply);
+                reply.extend_from_slice(&crc.to_be_bytes());
+                reply
+            }
+        };
+        Self {
+            reply,
+            pos: 0,
+            tcp: protocol == Protocol::Tcp,
+        }
+    }
+}
+
+impl Read for Loopback {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.reply.len() - self.pos);
+        buf[..n].copy_from_slice(&self.reply[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Write for Loopback {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // 响应使用请求的事务标识
+        if self.tcp {
+            self.reply[..2].copy_from_slice(&buf[..2]);
+        }
+        self.pos = 0;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Stream for Loopback {
+    fn set_timeout(&mut self, _timeout: Duration) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 读取 125 个寄存器的响应 PDU: FUN(1) + 字节数(1) + 数据
+fn read_reply() -> Vec<u8> {
+    let mut pdu = vec![0x03, 250];
+    pdu.extend(std::iter::repeat_n(0x12, 250));
+    pdu
+}
+
+/// 请求和响应的编解码
+fn bench_codec(c: &mut Criterion) {
+    let read = Request::from(Function::ReadHoldingRegisters(1, 0, 125u16.into()));
+    let write = Request::from(Function::WriteMultipleRegisters(1, 0, vec![0x1234; 123]));
+    let reply = Loopback::new(Protocol::Rtu, &read_reply()).reply;
+
+    let mut group = c.benchmark_group("codec");
+    for protocol in [Protocol::Rtu, Protocol::Tcp, Protocol::Ascii] {
+        group.bench_function(format!("encode_read/{:?}", protocol), |b| {
+            b.iter(|| encode_request(protocol, black_box(&read)).unwrap())
+        });
+        group.bench_function(format!("encode_write/{:?}", protocol), |b| {
+            b.iter(|| encode_request(protocol, black_box(&write)).unwrap())
+        });
+    }
+    group.bench_function("decode_response/Rtu", |b| {
+        b.iter(|| decode_response(Protocol::Rtu, black_box(&reply)).unwrap())
+    });
+    group.finish();
+}
+
+/// 完成一次请求和响应, 包括封装, 校验和解析
+fn bench_transfer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transfer");
+    for protocol in [Protocol::Rtu, Protocol::Tcp] {
+        let client = |pdu: &[u8]| {
+            let mut client = Client::new(Box::new(Loopback::new(protocol, pdu))).unwrap();
+            client.set_protocol(protocol);
+            client
+        };
+        let mut reader = client(&read_reply());
+        group.bench_function(format!("read_holding_registers/{:?}", protocol), |b| {
+            b.iter(|| reader.read_holding_registers(1, 0u16, 125u16).unwrap())
+        });
+        // 响应: FUN(1) + 起始地址(2) + 数量(2)
+        let mut writer = client(&[0x10, 0, 0, 0, 123]);
+        group.bench_function(format!("write_multiple_registers/{:?}", protocol), |b| {
+            b.iter(|| {
+                writer
+                    .write_multiple_registers(1, 0u16, vec![0x1234; 123])
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_codec, bench_transfer);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use simple_modbus::crc::crc16;
+
+/// 原来逐位计算的实现, 用于对比
+fn crc16_bitwise(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    for x in data {
+        crc ^= u16::from(*x);
+        for _ in 0..8 {
+            let crc_odd = (crc & 0x0001) != 0;
+            crc >>= 1;
+            if crc_odd {
+                crc ^= 0xA001;
+            }
+        }
+    }
+    crc
+}
+
+fn bench_crc(c: &mut Criterion) {
+    // RTU 帧的最大长度
+    let frame = (0..256).map(|i| i as u8).collect::<Vec<_>>();
+    for len in 0..frame.len() {
+        assert_eq!(crc16(&frame[..len]), crc16_bitwise(&frame[..len]));
+    }
+
+    let mut group = c.benchmark_group("crc16");
+    group.throughput(Throughput::Bytes(frame.len() as u64));
+    group.bench_function("slice_by_4", |b| b.iter(|| crc16(black_box(&frame))));
+    group.bench_function("bitwise", |b| b.iter(|| crc16_bitwise(black_box(&frame))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_crc);
+criterion_main!(benches);
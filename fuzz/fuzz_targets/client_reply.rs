@@ -0,0 +1,61 @@
+//! 把任意的数据作为响应交给客户端, 检查响应的校验和解析不会 panic
+//!
+//! 第一个字节选择协议和请求, 之后的数据为响应帧
+
+#![no_main]
+
+use std::time::Duration;
+
+use libfuzzer_sys::fuzz_target;
+use simple_modbus::{
+    device_id::DeviceIdCategory, file_record::FileRecordRead, mock::MockStream, Client,
+    Protocol,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, reply)) = data.split_first() else {
+        return;
+    };
+    let protocol = match selector % 3 {
+        0 => Protocol::Rtu,
+        1 => Protocol::Tcp,
+        _ => Protocol::Ascii,
+    };
+    let stream = MockStream::new();
+    stream.push_reply(reply);
+    let mut client = Client::new(Box::new(stream)).unwrap();
+    client.set_protocol(protocol);
+    client.set_timeout(Duration::from_millis(1)).unwrap();
+
+    let _ = match selector / 3 % 16 {
+        0 => client.read_coils(1, 0u16, 10).map(drop),
+        1 => client.read_discrete_inputs(1, 0u16, 3).map(drop),
+        2 => client.read_holding_registers(1, 0u16, 3).map(drop),
+        3 => client.read_input_registers(1, 0u16, 2).map(drop),
+        4 => client.write_single_register(1, 0u16, 5),
+        5 => client.write_multiple_registers(1, 0u16, vec![1, 2]),
+        6 => client.mask_write_register(1, 0u16, 1, 2),
+        7 => client
+            .read_write_multiple_registers(1, 0u16, 2, 0u16, &[1])
+            .map(drop),
+        8 => client.read_exception_status(1).map(drop),
+        9 => client.diagnostics(1, 0, 0x1234).map(drop),
+        10 => client.get_comm_event_counter(1).map(drop),
+        11 => client.get_comm_event_log(1).map(drop),
+        12 => client.report_server_id(1).map(drop),
+        13 => client
+            .read_file_record(
+                1,
+                &[FileRecordRead {
+                    file: 1,
+                    record: 0,
+                    length: 2,
+                }],
+            )
+            .map(drop),
+        14 => client.read_fifo_queue(1, 0u16).map(drop),
+        _ => client
+            .read_device_identification(1, DeviceIdCategory::Basic)
+            .map(drop),
+    };
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use simple_modbus::codec::decode_frame;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(frame) = decode_frame(data) {
+        let _ = frame.to_request();
+        let _ = frame.to_response();
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use simple_modbus::{
+    codec::{decode_request, decode_response, decode_rtu, decode_tcp, rtu_remaining},
+    Protocol,
+};
+
+fuzz_target!(|data: &[u8]| {
+    for protocol in [Protocol::Rtu, Protocol::Tcp, Protocol::Ascii] {
+        let _ = decode_request(protocol, data);
+        let _ = decode_response(protocol, data);
+    }
+    let _ = decode_rtu(data);
+    let _ = decode_tcp(data);
+    let _ = rtu_remaining(data, 2);
+});
@@ -0,0 +1,216 @@
+//! simple_modbus 的派生宏, 通过 simple_modbus 的 `derive` feature 使用
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitInt, LitStr, Result, Type};
+
+/// 为结构体实现 `simple_modbus::block::ModbusBlock`
+///
+/// 每个字段需要 `#[register(addr = 0x10, ty = "f32", order = "cdab")]` 属性, ty 和 order 可以省略
+#[proc_macro_derive(ModbusBlock, attributes(register))]
+pub fn derive_modbus_block(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// 一个字段对应的寄存器
+struct Register {
+    field: syn::Ident,
+    field_ty: Type,
+    addr: u16,
+    /// 寄存器中的数值类型, 例如 f32
+    ty: syn::Ident,
+    order: syn::Ident,
+}
+
+impl Register {
+    fn words(&self) -> u16 {
+        words_of(&self.ty.to_string()).unwrap_or(0)
+    }
+
+    /// ty 与字段类型不同时需要转换
+    fn needs_cast(&self) -> bool {
+        let field_ty = &self.field_ty;
+        self.ty != quote!(#field_ty).to_string()
+    }
+}
+
+fn words_of(ty: &str) -> Option<u16> {
+    match ty {
+        "u16" | "i16" => Some(1),
+        "u32" | "i32" | "f32" => Some(2),
+        "u64" | "i64" | "f64" => Some(4),
+        _ => None,
+    }
+}
+
+fn parse_register(field: &syn::Field) -> Result<Register> {
+    let ident = field.ident.clone().expect("named field");
+    let attr = field
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("register"))
+        .ok_or_else(|| Error::new_spanned(field, "字段缺少 #[register(addr = ...)] 属性"))?;
+
+    let mut addr = None;
+    let mut ty = None;
+    let mut order = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("addr") {
+            addr = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<u16>()?);
+        } else if meta.path.is_ident("ty") {
+            ty = Some(meta.value()?.parse::<LitStr>()?);
+        } else if meta.path.is_ident("order") {
+            order = Some(meta.value()?.parse::<LitStr>()?);
+        } else {
+            return Err(meta.error("未知的属性, 可用的属性为 addr, ty, order"));
+        }
+        Ok(())
+    })?;
+
+    let addr = addr.ok_or_else(|| Error::new_spanned(attr, "缺少 addr"))?;
+    let ty = match ty {
+        Some(ty) => {
+            if words_of(&ty.value()).is_none() {
+                return Err(Error::new_spanned(
+                    ty,
+                    "ty 必须是 u16, i16, u32, i32, f32, u64, i64, f64",
+                ));
+            }
+            syn::Ident::new(&ty.value(), ty.span())
+        }
+        None => {
+            let field_ty = &field.ty;
+            let name = quote!(#field_ty).to_string();
+            if words_of(&name).is_none() {
+                return Err(Error::new_spanned(
+                    field_ty,
+                    "无法确定寄存器类型, 请指定 ty",
+                ));
+            }
+            syn::Ident::new(&name, Span::call_site())
+        }
+    };
+    let order = match order {
+        Some(order) => {
+            let value = order.value().to_ascii_uppercase();
+            if !matches!(value.as_str(), "ABCD" | "CDAB" | "BADC" | "DCBA") {
+                return Err(Error::new_spanned(
+                    order,
+                    "order 必须是 abcd, cdab, badc, dcba",
+                ));
+            }
+            format_ident!("{}", value)
+        }
+        None => format_ident!("ABCD"),
+    };
+    Ok(Register {
+        field: ident,
+        field_ty: field.ty.clone(),
+        addr,
+        ty,
+        order,
+    })
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input, "只支持具名字段的结构体")),
+        },
+        _ => return Err(Error::new_spanned(&input, "只支持结构体")),
+    };
+    let registers = fields
+        .iter()
+        .map(parse_register)
+        .collect::<Result<Vec<_>>>()?;
+    if registers.is_empty() {
+        return Err(Error::new_spanned(&input, "至少需要一个字段"));
+    }
+
+    let start = registers.iter().map(|r| r.addr).min().unwrap_or(0);
+    let end = registers
+        .iter()
+        .map(|r| r.addr as u32 + r.words() as u32)
+        .max()
+        .unwrap_or(0);
+    if end > 0x10000 {
+        return Err(Error::new_spanned(&input, "寄存器地址超出范围"));
+    }
+    // 检查字段之间是否重叠
+    let mut sorted = registers.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|r| r.addr);
+    for pair in sorted.windows(2) {
+        if pair[0].addr as u32 + pair[0].words() as u32 > pair[1].addr as u32 {
+            return Err(Error::new_spanned(
+                &pair[1].field,
+                format!("字段 {} 与 {} 的寄存器重叠", pair[0].field, pair[1].field),
+            ));
+        }
+    }
+    let words = (end - start as u32) as u16;
+    if words > 123 {
+        return Err(Error::new_spanned(
+            &input,
+            "寄存器数量超过 123, 无法一次写入",
+        ));
+    }
+    let len = words as usize;
+
+    let decode = registers.iter().map(|r| {
+        let (field, ty, order) = (&r.field, &r.ty, &r.order);
+        let begin = (r.addr - start) as usize;
+        let end = begin + r.words() as usize;
+        let value = quote! {
+            ::simple_modbus::value::WordOrder::#order.decode::<#ty>(&words[#begin..#end])?
+        };
+        if r.needs_cast() {
+            let field_ty = &r.field_ty;
+            quote!(#field: #value as #field_ty)
+        } else {
+            quote!(#field: #value)
+        }
+    });
+    let encode = registers.iter().map(|r| {
+        let (field, ty, order) = (&r.field, &r.ty, &r.order);
+        let begin = (r.addr - start) as usize;
+        let end = begin + r.words() as usize;
+        let value = if r.needs_cast() {
+            quote!(self.#field as #ty)
+        } else {
+            quote!(self.#field)
+        };
+        quote! {
+            words[#begin..#end]
+                .copy_from_slice(&::simple_modbus::value::WordOrder::#order.encode::<#ty>(#value));
+        }
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::simple_modbus::block::ModbusBlock for #name #ty_generics #where_clause {
+            const ADDRESS: u16 = #start;
+            const WORDS: u16 = #words;
+
+            fn from_words(words: &[u16]) -> ::simple_modbus::error::Result<Self> {
+                if words.len() != #len {
+                    return Err(::simple_modbus::error::Error::InvalidResponse(
+                        ::simple_modbus::error::Reason::UnexpectedReplySize,
+                    ));
+                }
+                Ok(Self { #(#decode,)* })
+            }
+
+            fn to_words(&self) -> ::std::vec::Vec<u16> {
+                let mut words = ::std::vec![0u16; #len];
+                #(#encode)*
+                words
+            }
+        }
+    })
+}
@@ -0,0 +1,108 @@
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Sub},
+};
+
+macro_rules! address_type {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+        pub struct $name(u16);
+
+        impl $name {
+            pub const fn new(addr: u16) -> Self {
+                Self(addr)
+            }
+
+            /// 协议中使用的原始地址
+            pub const fn get(self) -> u16 {
+                self.0
+            }
+
+            /// 地址偏移, 溢出时返回 None
+            pub const fn checked_add(self, offset: u16) -> Option<Self> {
+                match self.0.checked_add(offset) {
+                    Some(addr) => Some(Self(addr)),
+                    None => None,
+                }
+            }
+
+            /// 地址回退, 溢出时返回 None
+            pub const fn checked_sub(self, offset: u16) -> Option<Self> {
+                match self.0.checked_sub(offset) {
+                    Some(addr) => Some(Self(addr)),
+                    None => None,
+                }
+            }
+        }
+
+        impl From<u16> for $name {
+            fn from(addr: u16) -> Self {
+                Self(addr)
+            }
+        }
+
+        impl From<$name> for u16 {
+            fn from(addr: $name) -> u16 {
+                addr.0
+            }
+        }
+
+        impl Add<u16> for $name {
+            type Output = Self;
+
+            fn add(self, offset: u16) -> Self {
+                Self(self.0 + offset)
+            }
+        }
+
+        impl AddAssign<u16> for $name {
+            fn add_assign(&mut self, offset: u16) {
+                self.0 += offset;
+            }
+        }
+
+        impl Sub<u16> for $name {
+            type Output = Self;
+
+            fn sub(self, offset: u16) -> Self {
+                Self(self.0 - offset)
+            }
+        }
+
+        /// 两个同类地址之间的距离
+        impl Sub for $name {
+            type Output = u16;
+
+            fn sub(self, other: Self) -> u16 {
+                self.0 - other.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{:#06X}", self.0)
+            }
+        }
+    };
+}
+
+address_type!(
+    /// 保持寄存器地址 (功能码 0x03, 0x06, 0x10)
+    HoldingAddress
+);
+
+address_type!(
+    /// 输入寄存器地址 (功能码 0x04)
+    InputAddress
+);
+
+address_type!(
+    /// 线圈地址 (功能码 0x01, 0x05, 0x0F)
+    CoilAddress
+);
+
+address_type!(
+    /// 离散输入地址 (功能码 0x02)
+    DiscreteAddress
+);
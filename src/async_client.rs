@@ -0,0 +1,785 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use std::{
+    future::poll_fn,
+    pin::Pin,
+    task::Poll,
+    time::{Duration, Instant},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::{
+    address::{CoilAddress, DiscreteAddress, HoldingAddress, InputAddress},
+    check_loopback, check_not_broadcast,
+    checksum::Checksum,
+    config::Config,
+    custom::{CustomFunction, CustomRequest, ReplyLength},
+    device_id::{DeviceIdCategory, DeviceIdentification},
+    diagnostics::{self, CommEventCounter, CommEventLog, ServerId},
+    error::{Error, Reason, Result},
+    file_record::{self, FileRecord, FileRecordRead},
+    frame::Framer,
+    pack_bytes,
+    quantity::{Quantity, MAX_READ_COILS, MAX_READ_REGISTERS, MAX_WRITE_REGISTERS},
+    serial::SerialConfig,
+    slice_quantity, split_request,
+    stats::Stats,
+    trace::{self, Direction, FrameEvent, FrameObserver, WireLogging},
+    unpack_diagnostics, unpack_fifo, unpack_reply_bits,
+    value::{self, RegisterValue, WordOrder},
+    Coil, Function, Id, Protocol, ValidationMode, Word, DEFAULT_SILENCE_GAP,
+    DEFAULT_TURNAROUND_DELAY, LOOPBACK_DATA, MBAP_HEADER_SIZE, MODBUS_MAX_PACKET_SIZE,
+};
+
+/// 异步的数据传输, 所有实现了 tokio 的 AsyncRead + AsyncWrite 的类型都可以使用
+pub trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncStream for T {}
+
+/// 基于 tokio 的异步客户端, 接口与 `Client` 相同
+pub struct AsyncClient {
+    stream: Box<dyn AsyncStream>,
+    need_reply: bool,
+    framer: Framer,
+    config: Config,
+    timeout: Duration,
+    /// 串行链路上最近一次广播请求的时间
+    last_broadcast: Option<Instant>,
+    /// 最近一次传输结束的时间
+    last_frame: Option<Instant>,
+    turnaround_delay: Duration,
+    inter_request_delay: Duration,
+    word_order: WordOrder,
+    swap_string_bytes: bool,
+    observer: Option<FrameObserver>,
+    wire_logging: Option<WireLogging>,
+    stats: Stats,
+}
+
+impl AsyncClient {
+    pub fn new(stream: Box<dyn AsyncStream>) -> Result<Self> {
+        Ok(Self {
+            stream,
+            need_reply: true,
+            framer: Framer::new(Protocol::Rtu),
+            config: Config::default(),
+            timeout: Duration::from_millis(5000),
+            last_broadcast: None,
+            last_frame: None,
+            turnaround_delay: DEFAULT_TURNAROUND_DELAY,
+            inter_request_delay: Duration::ZERO,
+            word_order: WordOrder::default(),
+            swap_string_bytes: false,
+            observer: None,
+            wire_logging: None,
+            stats: Stats::default(),
+        })
+    }
+
+    /// 创建 Modbus TCP 客户端, 使用 MBAP 报文头封装请求
+    pub fn new_tcp(stream: Box<dyn AsyncStream>, config: Config) -> Result<Self> {
+        let mut client = Self::new(stream)?;
+        client.framer.protocol = Protocol::Tcp;
+        client.config = config;
+        Ok(client)
+    }
+
+    /// 打开串口, 使用 Modbus RTU 通信. 需要在 tokio 运行时中调用
+    pub fn open_serial(port: &str, baud_rate: u32) -> Result<Self> {
+        let stream = tokio_serial::SerialStream::open(&tokio_serial::new(port, baud_rate))?;
+        Self::new(Box::new(stream))
+    }
+
+    /// 按照指定的串口参数打开串口, 使用 Modbus RTU 通信. 需要在 tokio 运行时中调用
+    pub fn open_serial_with(port: &str, config: &SerialConfig) -> Result<Self> {
+        let stream = tokio_serial::SerialStream::open(&config.builder(port))?;
+        let mut client = Self::new(Box::new(stream))?;
+        client.timeout = config.timeout;
+        Ok(client)
+    }
+
+    /// 连接 Modbus TCP 服务器, addr 格式为 `host:port`
+    pub async fn connect_tcp(addr: &str, config: Config) -> Result<Self> {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Self::new_tcp(Box::new(stream), config)
+    }
+
+    /// 连接串口服务器, 通过 TCP 直接传输 RTU 帧 (没有 MBAP报文头, 使用 CRC 校验)
+    pub async fn connect_rtu_over_tcp(addr: &str) -> Result<Self> {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Self::new(Box::new(stream))
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.framer.protocol
+    }
+
+    /// 设置传输帧格式, 例如只支持 Modbus ASCII 的设备使用 `Protocol::Ascii`
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.framer.protocol = protocol;
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// 设置 32/64 位数值在寄存器中的字节顺序, 默认为 `WordOrder::ABCD`
+    pub fn set_word_order(&mut self, word_order: WordOrder) {
+        self.word_order = word_order;
+    }
+
+    /// 设置字符串在寄存器中是否为低字节在前, 默认第一个字符在高字节
+    pub fn set_string_byte_swap(&mut self, swap_bytes: bool) {
+        self.swap_string_bytes = swap_bytes;
+    }
+
+    /// 设置 一次请求 的超时时间
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_need_reply(&mut self, need_reply: bool) {
+        self.need_reply = need_reply;
+    }
+
+    /// 设置串行链路上广播请求之后的等待时间, 让所有从设备处理完广播请求, 默认为 100ms
+    pub fn set_turnaround_delay(&mut self, delay: Duration) {
+        self.turnaround_delay = delay;
+    }
+
+    /// 设置上一次传输结束到下一个请求之间的最小间隔, 用于响应之后需要一段时间才能接收请求的慢速设备,
+    /// 默认为 0
+    pub fn set_inter_request_delay(&mut self, delay: Duration) {
+        self.inter_request_delay = delay;
+    }
+
+    /// 设置响应检查的严格程度, 默认为 [`ValidationMode::Strict`]
+    pub fn set_validation_mode(&mut self, mode: ValidationMode) {
+        self.framer.validation = mode;
+    }
+
+    pub fn validation_mode(&self) -> ValidationMode {
+        self.framer.validation
+    }
+
+    /// 设置帧校验算法, 默认为 CRC-16/MODBUS
+    pub fn set_checksum(&mut self, checksum: Box<dyn Checksum>) {
+        self.framer.checksum = checksum;
+    }
+
+    /// 设置帧的观察者, 发送的每个请求和收到的每个响应都会通知它
+    pub fn set_frame_observer(&mut self, observer: impl Fn(&FrameEvent) + Send + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    pub fn clear_frame_observer(&mut self) {
+        self.observer = None;
+    }
+
+    /// 开启或关闭线路日志: 以 DEBUG 级别输出每一帧的十六进制数据, log target 为 `simple_modbus::wire`
+    pub fn set_wire_logging(&mut self, enabled: bool) {
+        self.wire_logging = enabled.then(WireLogging::default);
+    }
+
+    /// 开启线路日志, 发送和接收分别使用指定的日志级别
+    pub fn set_wire_log_levels(&mut self, tx: log::Level, rx: log::Level) {
+        self.wire_logging = Some(WireLogging { tx, rx });
+    }
+
+    /// 通知帧观察者, 开启了线路日志时输出日志. elapsed 为发送请求到收到响应的时间
+    fn notify(&self, direction: Direction, data: &[u8], elapsed: Option<Duration>) {
+        trace::notify(&self.observer, direction, data);
+        if let Some(logging) = self.wire_logging {
+            let level = match direction {
+                Direction::Tx => logging.tx,
+                Direction::Rx => logging.rx,
+            };
+            let id = self.framer.slave_id(data);
+            let function_code = self.framer.function_code(data);
+            trace::log_frame(level, direction, id, function_code, elapsed, data);
+        }
+    }
+
+    /// 通信统计
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// 清零通信统计
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    pub async fn read_coils(
+        &mut self,
+        id: Id,
+        address: impl Into<CoilAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        let address = address.into().get();
+        let quantity = quantity.into();
+        let mut values = Vec::with_capacity(quantity.get() as usize);
+        for (address, quantity) in split_request(address, quantity, MAX_READ_COILS)? {
+            let bytes = self
+                .read(Function::ReadCoils(id, address, quantity))
+                .await?;
+            values.extend(unpack_reply_bits(&bytes, quantity.get())?);
+        }
+        Ok(values)
+    }
+
+    pub async fn read_discrete_inputs(
+        &mut self,
+        id: Id,
+        address: impl Into<DiscreteAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        let address = address.into().get();
+        let quantity = quantity.into();
+        let mut values = Vec::with_capacity(quantity.get() as usize);
+        for (address, quantity) in split_request(address, quantity, MAX_READ_COILS)? {
+            let bytes = self
+                .read(Function::ReadDiscreteInputs(id, address, quantity))
+                .await?;
+            values.extend(unpack_reply_bits(&bytes, quantity.get())?);
+        }
+        Ok(values)
+    }
+
+    /// 数量超过 125 时自动拆分为多个请求
+    pub async fn read_holding_registers(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        let address = address.into().get();
+        let quantity = quantity.into();
+        let mut values = Vec::with_capacity(quantity.get() as usize);
+        for (address, quantity) in split_request(address, quantity, MAX_READ_REGISTERS)? {
+            let bytes = self
+                .read(Function::ReadHoldingRegisters(id, address, quantity))
+                .await?;
+            values.extend(pack_bytes(bytes)?);
+        }
+        Ok(values)
+    }
+
+    pub async fn read_input_registers(
+        &mut self,
+        id: Id,
+        address: impl Into<InputAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        let address = address.into().get();
+        let quantity = quantity.into();
+        let mut values = Vec::with_capacity(quantity.get() as usize);
+        for (address, quantity) in split_request(address, quantity, MAX_READ_REGISTERS)? {
+            let bytes = self
+                .read(Function::ReadInputRegisters(id, address, quantity))
+                .await?;
+            values.extend(pack_bytes(bytes)?);
+        }
+        Ok(values)
+    }
+
+    pub async fn write_single_register(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        value: Word,
+    ) -> Result<()> {
+        let address = address.into().get();
+        self.write(Function::WriteSingleRegister(id, address, value))
+            .await
+    }
+
+    /// 数量超过 123 时自动拆分为多个请求, 其中一个请求失败时, 之前的请求已经写入
+    pub async fn write_multiple_registers(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        values: Vec<Word>,
+    ) -> Result<()> {
+        let address = address.into().get();
+        let quantity = slice_quantity(values.len())?;
+        let mut values = &values[..];
+        for (address, quantity) in split_request(address, quantity, MAX_WRITE_REGISTERS)? {
+            let (chunk, rest) = values.split_at(quantity.get() as usize);
+            values = rest;
+            self.write(Function::WriteMultipleRegisters(
+                id,
+                address,
+                chunk.to_vec(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// 功能码 0x16, 由从设备按位修改寄存器, 避免客户端 读-改-写 时的竞争
+    pub async fn mask_write_register(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        and_mask: Word,
+        or_mask: Word,
+    ) -> Result<()> {
+        let address = address.into().get();
+        self.write(Function::MaskWriteRegister(id, address, and_mask, or_mask))
+            .await
+    }
+
+    /// 功能码 0x17, 先写入 values, 再读取保持寄存器, 两个操作在同一次传输中完成
+    pub async fn read_write_multiple_registers(
+        &mut self,
+        id: Id,
+        read_address: impl Into<HoldingAddress>,
+        read_quantity: impl Into<Quantity>,
+        write_address: impl Into<HoldingAddress>,
+        values: &[Word],
+    ) -> Result<Vec<Word>> {
+        let bytes = self
+            .read(Function::ReadWriteMultipleRegisters(
+                id,
+                read_address.into().get(),
+                read_quantity.into(),
+                write_address.into().get(),
+                values.to_vec(),
+            ))
+            .await?;
+        pack_bytes(bytes)
+    }
+
+    /// 从保持寄存器读取一个数值, 按照设置的字节顺序解码
+    pub async fn read_value<T: RegisterValue>(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+    ) -> Result<T> {
+        let words = self
+            .read_holding_registers(id, address, T::WORDS as u16)
+            .await?;
+        self.word_order.decode(&words)
+    }
+
+    /// 按照设置的字节顺序编码, 写入保持寄存器
+    pub async fn write_value<T: RegisterValue>(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        value: T,
+    ) -> Result<()> {
+        let words = self.word_order.encode(value);
+        self.write_multiple_registers(id, address, words).await
+    }
+
+    /// 从保持寄存器读取 byte_len 个字节的字符串, 去掉末尾填充的 0 和空格
+    pub async fn read_string(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        byte_len: usize,
+    ) -> Result<String> {
+        let quantity = slice_quantity(byte_len.div_ceil(2))?;
+        let words = self.read_holding_registers(id, address, quantity).await?;
+        Ok(value::unpack_string(
+            &words,
+            byte_len,
+            self.swap_string_bytes,
+        ))
+    }
+
+    /// 把字符串写入保持寄存器, 每个寄存器两个字节, 长度为奇数时末尾补 0
+    pub async fn write_string(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        s: &str,
+    ) -> Result<()> {
+        let words = value::pack_string(s, self.swap_string_bytes);
+        self.write_multiple_registers(id, address, words).await
+    }
+
+    /// 诊断功能 (功能码 0x08), 返回响应中的数据, 子功能码见 `diagnostics` 模块
+    pub async fn diagnostics(&mut self, id: Id, sub_function: u16, data: Word) -> Result<Word> {
+        let reply = self
+            .read_pdu(Function::Diagnostics(id, sub_function, data))
+            .await?;
+        unpack_diagnostics(&reply, sub_function)
+    }
+
+    /// 回环测试, 检查从设备原样返回了请求的数据
+    pub async fn loopback_test(&mut self, id: Id) -> Result<()> {
+        let data = self
+            .diagnostics(id, diagnostics::RETURN_QUERY_DATA, LOOPBACK_DATA)
+            .await?;
+        check_loopback(data)
+    }
+
+    /// 重启从设备的通信端口, clear_log 为 true 时同时清除通信事件日志
+    pub async fn restart_communications(&mut self, id: Id, clear_log: bool) -> Result<()> {
+        let data = if clear_log { 0xFF00 } else { 0x0000 };
+        self.diagnostics(id, diagnostics::RESTART_COMMUNICATIONS, data)
+            .await?;
+        Ok(())
+    }
+
+    /// 清除从设备的所有计数器和诊断寄存器
+    pub async fn clear_counters(&mut self, id: Id) -> Result<()> {
+        self.diagnostics(id, diagnostics::CLEAR_COUNTERS, 0).await?;
+        Ok(())
+    }
+
+    /// 读取计数器, counter 为 `diagnostics::BUS_MESSAGE_COUNT` 等子功能码
+    pub async fn diagnostic_counter(&mut self, id: Id, counter: u16) -> Result<Word> {
+        self.diagnostics(id, counter, 0).await
+    }
+
+    /// 读异常状态 (功能码 0x07), 返回 8 个异常状态位
+    pub async fn read_exception_status(&mut self, id: Id) -> Result<u8> {
+        let reply = self.read_pdu(Function::ReadExceptionStatus(id)).await?;
+        match reply[..] {
+            [status] => Ok(status),
+            _ => Err(Error::InvalidResponse(Reason::UnexpectedReplySize)),
+        }
+    }
+
+    /// 获取通信事件计数 (功能码 0x0B)
+    pub async fn get_comm_event_counter(&mut self, id: Id) -> Result<CommEventCounter> {
+        let reply = self.read_pdu(Function::GetCommEventCounter(id)).await?;
+        CommEventCounter::parse(&reply)
+    }
+
+    /// 获取通信事件日志 (功能码 0x0C)
+    pub async fn get_comm_event_log(&mut self, id: Id) -> Result<CommEventLog> {
+        let bytes = self.read(Function::GetCommEventLog(id)).await?;
+        CommEventLog::parse(&bytes)
+    }
+
+    /// 报告从设备ID (功能码 0x11), 返回从设备ID, 运行状态和设备相关的附加数据
+    pub async fn report_server_id(&mut self, id: Id) -> Result<ServerId> {
+        let bytes = self.read(Function::ReportServerId(id)).await?;
+        ServerId::parse(&bytes)
+    }
+
+    /// 读 FIFO 队列 (功能码 0x18), 返回队列中的数据, 最多 31 个
+    pub async fn read_fifo_queue(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+    ) -> Result<Vec<Word>> {
+        let address = address.into().get();
+        let reply = self.read_pdu(Function::ReadFifoQueue(id, address)).await?;
+        unpack_fifo(reply)
+    }
+
+    /// 读文件记录 (功能码 0x14), 按子请求的顺序返回读取到的记录
+    pub async fn read_file_record(
+        &mut self,
+        id: Id,
+        requests: &[FileRecordRead],
+    ) -> Result<Vec<FileRecord>> {
+        let bytes = self
+            .read(Function::ReadFileRecord(id, requests.to_vec()))
+            .await?;
+        file_record::decode_read(&bytes, requests)
+    }
+
+    /// 写文件记录 (功能码 0x15)
+    pub async fn write_file_record(&mut self, id: Id, records: &[FileRecord]) -> Result<()> {
+        self.write(Function::WriteFileRecord(id, records.to_vec()))
+            .await
+    }
+
+    /// 读设备标识, 响应中设置了后续标志时会继续读取剩余的对象
+    pub async fn read_device_identification(
+        &mut self,
+        id: Id,
+        category: DeviceIdCategory,
+    ) -> Result<DeviceIdentification> {
+        let mut identification = DeviceIdentification::default();
+        let mut object_id = 0;
+        loop {
+            let fun = Function::ReadDeviceIdentification(id, category.code(), object_id);
+            let data = self.read_pdu(fun).await?;
+            match identification.parse(&data)? {
+                Some(next) if next > object_id => object_id = next,
+                Some(_) => return Err(Error::InvalidResponse(Reason::UnexpectedReplySize)),
+                None => return Ok(identification),
+            }
+        }
+    }
+
+    /// 发送定制功能码的请求, 返回响应中功能码之后的数据, 广播请求返回空的数据
+    ///
+    /// `ReplyLength::ByteCount` 时返回的数据不包含字节数字段
+    pub async fn custom(&mut self, req: CustomRequest) -> Result<Bytes> {
+        let reply_length = req.reply_length;
+        self.framer.custom_reply = reply_length;
+        let res = self.transact_custom(req).await;
+        self.framer.custom_reply = ReplyLength::Unknown;
+        let reply = res?;
+        if reply.is_empty() {
+            return Ok(reply);
+        }
+        match reply_length {
+            ReplyLength::ByteCount => self.framer.get_reply_data(reply),
+            ReplyLength::Fixed(_) | ReplyLength::Unknown => self.framer.get_reply_pdu(reply),
+        }
+    }
+
+    /// 调用可以复用的设备私有功能
+    pub async fn call<F: CustomFunction>(&mut self, id: Id, function: &F) -> Result<F::Output> {
+        let data = self.custom(function.request(id)).await?;
+        function.parse_reply(&data)
+    }
+
+    /// 返回完整的响应帧, 广播请求返回空的数据
+    async fn transact_custom(&mut self, req: CustomRequest) -> Result<Bytes> {
+        let (req, mut reply) = self.framer.build_buffer(Function::Custom(req))?;
+        self.transfer_timeout(&req, &mut reply, false).await?;
+        if self.framer.is_broadcast(&req) {
+            return Ok(Bytes::new());
+        }
+        Ok(reply.freeze())
+    }
+
+    /// 按照 MBAP报文头 中的长度字段接收一个完整的帧
+    async fn read_tcp_frame(&mut self, reply: &mut BytesMut) -> std::io::Result<usize> {
+        reply.resize(MBAP_HEADER_SIZE, 0);
+        self.stream.read_exact(&mut reply[..]).await?;
+        reply.resize(Framer::tcp_frame_size(reply), 0);
+        self.stream
+            .read_exact(&mut reply[MBAP_HEADER_SIZE..])
+            .await?;
+        Ok(reply.len())
+    }
+
+    /// 接收与请求的事务标识一致的 TCP 帧, 丢弃之前超时的请求的响应
+    async fn read_tcp_reply(&mut self, req: &[u8], reply: &mut BytesMut) -> std::io::Result<usize> {
+        loop {
+            let n = self.read_tcp_frame(reply).await?;
+            if self.framer.is_reply_to(req, reply) {
+                return Ok(n);
+            }
+            self.notify(Direction::Rx, reply, None);
+            log::warn!(
+                "丢弃过期的响应, 事务标识: {}",
+                u16::from_be_bytes([reply[0], reply[1]])
+            );
+        }
+    }
+
+    /// 根据功能码和字节数字段接收一个完整的 RTU 帧
+    ///
+    /// 无法确定长度的功能码 (例如 `ReplyLength::Unknown` 的定制请求), 接收数据直到静默的时间超过 DEFAULT_SILENCE_GAP
+    async fn read_rtu_frame(&mut self, reply: &mut BytesMut) -> std::io::Result<usize> {
+        let hint = reply.len();
+        reply.clear();
+        loop {
+            let len = reply.len();
+            match self.framer.rtu_remaining(reply) {
+                Some(0) => return Ok(len),
+                Some(n) => {
+                    reply.resize(len + n, 0);
+                    self.stream.read_exact(&mut reply[len..]).await?;
+                }
+                None => {
+                    reply.resize(hint.max(len + 1), 0);
+                    let mut n = len + self.stream.read(&mut reply[len..]).await?;
+                    reply.resize(reply.len().max(MODBUS_MAX_PACKET_SIZE), 0);
+                    while n < reply.len() {
+                        let read = self.stream.read(&mut reply[n..]);
+                        match tokio::time::timeout(DEFAULT_SILENCE_GAP, read).await {
+                            Ok(Ok(0)) | Err(_) => break,
+                            Ok(Ok(k)) => n += k,
+                            Ok(Err(e)) => return Err(e),
+                        }
+                    }
+                    return Ok(n);
+                }
+            }
+        }
+    }
+
+    /// 接收一个 Modbus ASCII 帧, 直到收到 LF
+    async fn read_ascii_frame(&mut self, reply: &mut BytesMut) -> std::io::Result<usize> {
+        reply.clear();
+        loop {
+            let b = self.stream.read_u8().await?;
+            reply.put_u8(b);
+            if Framer::ascii_complete(reply) {
+                return Ok(reply.len());
+            }
+        }
+    }
+
+    /// 以非阻塞方式读取并丢弃输入中残留的数据
+    ///
+    /// 超时被取消的请求可能只接收了半个响应帧 (例如只收到 MBAP报文头), 剩下的数据会在下一个请求之前到达
+    async fn clear_input(&mut self) -> std::io::Result<()> {
+        let mut buf = [0u8; 256];
+        loop {
+            let mut read_buf = ReadBuf::new(&mut buf);
+            let poll =
+                poll_fn(|cx| Poll::Ready(Pin::new(&mut self.stream).poll_read(cx, &mut read_buf)))
+                    .await;
+            match poll {
+                Poll::Ready(Ok(())) if !read_buf.filled().is_empty() => {
+                    log::warn!("丢弃过期的数据: {:02X?}", read_buf.filled())
+                }
+                Poll::Ready(Err(e)) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Err(e),
+                // 没有数据, 或者连接已经关闭, 留给之后的读操作报告错误
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    async fn transfer(&mut self, req: &Bytes, reply: &mut BytesMut, write: bool) -> Result<()> {
+        if let Some(last_broadcast) = self.last_broadcast.take() {
+            let elapsed = last_broadcast.elapsed();
+            if elapsed < self.turnaround_delay {
+                tokio::time::sleep(self.turnaround_delay - elapsed).await;
+            }
+        }
+
+        // 超时可能在一帧的中间取消接收, TCP 也不能只靠事务标识跳过过期的响应
+        self.clear_input().await?;
+        self.stream.write_all(req).await?;
+        self.stream.flush().await?;
+        let sent = Instant::now();
+        self.notify(Direction::Tx, req, None);
+        if self.framer.is_broadcast(req) {
+            self.last_broadcast = Some(Instant::now());
+            return Ok(());
+        }
+        // 写操作 且设置为 不响应
+        if !self.framer.expects_reply(req, write, self.need_reply) {
+            return Ok(());
+        }
+
+        let n = match self.framer.protocol {
+            Protocol::Rtu => self.read_rtu_frame(reply).await?,
+            Protocol::Tcp => self.read_tcp_reply(req, reply).await?,
+            Protocol::Ascii => self.read_ascii_frame(reply).await?,
+        };
+        reply.truncate(n);
+        self.notify(Direction::Rx, reply, Some(sent.elapsed()));
+        self.framer.validate_reply(req, reply)
+    }
+
+    async fn transfer_timeout(
+        &mut self,
+        req: &Bytes,
+        reply: &mut BytesMut,
+        write: bool,
+    ) -> Result<()> {
+        // 等待的时间不计入超时时间
+        if let Some(last_frame) = self.last_frame {
+            let elapsed = last_frame.elapsed();
+            if elapsed < self.inter_request_delay {
+                tokio::time::sleep(self.inter_request_delay - elapsed).await;
+            }
+        }
+        let timeout = self.timeout;
+        let start = Instant::now();
+        let res = match tokio::time::timeout(timeout, self.transfer(req, reply, write)).await {
+            Ok(res) => res,
+            Err(_) => Err(Error::Timeout),
+        };
+        self.last_frame = Some(Instant::now());
+        let expect_reply = self.framer.expects_reply(req, write, self.need_reply);
+        let id = self.framer.slave_id(req).unwrap_or_default();
+        self.stats.record(id, &res, expect_reply, start.elapsed());
+        res
+    }
+
+    async fn read(&mut self, fun: Function) -> Result<Bytes> {
+        check_not_broadcast(self.framer.protocol, &fun)?;
+        let (req, mut reply) = self.framer.build_buffer(fun)?;
+        self.transfer_timeout(&req, &mut reply, false).await?;
+        self.framer.get_reply_data(reply.freeze())
+    }
+
+    /// 不包含字节数字段的响应, 返回功能码之后的数据
+    async fn read_pdu(&mut self, fun: Function) -> Result<Bytes> {
+        check_not_broadcast(self.framer.protocol, &fun)?;
+        let (req, mut reply) = self.framer.build_buffer(fun)?;
+        self.transfer_timeout(&req, &mut reply, false).await?;
+        self.framer.get_reply_pdu(reply.freeze())
+    }
+
+    async fn write(&mut self, fun: Function) -> Result<()> {
+        let (req, mut reply) = self.framer.build_buffer(fun)?;
+        self.transfer_timeout(&req, &mut reply, true).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use std::time::Duration;
+
+    use super::AsyncClient;
+    use crate::{calc_crc, config::Config, error::Error};
+
+    fn rtu_frame(data: &[u8]) -> Vec<u8> {
+        let mut frame = data.to_vec();
+        frame.extend_from_slice(&calc_crc(data).to_be_bytes());
+        frame
+    }
+
+    #[tokio::test]
+    async fn stale_input_is_discarded_before_rtu_request() {
+        let (client_side, mut device) = duplex(256);
+        let mut client = AsyncClient::new(Box::new(client_side)).unwrap();
+
+        // 上一个超时的请求剩下的半个响应帧
+        device.write_all(&[0x01, 0x03, 0x02, 0x12]).await.unwrap();
+
+        let req = rtu_frame(&[0x01, 0x03, 0x00, 0x10, 0x00, 0x01]);
+        let reply = rtu_frame(&[0x01, 0x03, 0x02, 0xAB, 0xCD]);
+        let device = async {
+            let mut buf = vec![0u8; req.len()];
+            device.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, req);
+            device.write_all(&reply).await.unwrap();
+            device
+        };
+        let (values, _device) = tokio::join!(client.read_holding_registers(1, 0x0010, 1), device);
+        assert_eq!(values.unwrap(), [0xABCD]);
+    }
+
+    #[tokio::test]
+    async fn tcp_timeout_in_the_middle_of_a_frame() {
+        let (client_side, mut device) = duplex(256);
+        let mut client = AsyncClient::new_tcp(Box::new(client_side), Config::default()).unwrap();
+        client.set_timeout(Duration::from_millis(50));
+
+        // 只发送 MBAP报文头 和 一部分 PDU, 客户端在接收 PDU 时超时
+        let mut req = [0u8; 12];
+        let first = async {
+            device.read_exact(&mut req).await.unwrap();
+            device
+                .write_all(&[req[0], req[1], 0, 0, 0, 5, 1, 0x03])
+                .await
+                .unwrap();
+        };
+        let (res, _) = tokio::join!(client.read_holding_registers(1, 0x0000, 1), first);
+        assert!(matches!(res, Err(Error::Timeout)));
+
+        // 超时的响应剩下的数据在下一个请求之前到达
+        device.write_all(&[0x02, 0x12, 0x34]).await.unwrap();
+        let second = async {
+            device.read_exact(&mut req).await.unwrap();
+            let reply = [req[0], req[1], 0, 0, 0, 5, 1, 0x03, 0x02, 0xAB, 0xCD];
+            device.write_all(&reply).await.unwrap();
+        };
+        let (values, _) = tokio::join!(client.read_holding_registers(1, 0x0000, 1), second);
+        assert_eq!(values.unwrap(), [0xABCD]);
+    }
+}
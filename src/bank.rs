@@ -0,0 +1,466 @@
+use bytes::{Buf, BufMut};
+use std::sync::{PoisonError, RwLock};
+
+use crate::{
+    address::{CoilAddress, DiscreteAddress, HoldingAddress, InputAddress},
+    error::{Error, ExceptionCode, Result},
+    pack_bits,
+    quantity::{
+        MAX_READ_COILS, MAX_READ_REGISTERS, MAX_READ_WRITE_REGISTERS, MAX_WRITE_COILS,
+        MAX_WRITE_REGISTERS,
+    },
+    unpack_bits, Coil, Word,
+};
+
+/// 内存中的寄存器数据, 包括 线圈, 离散输入, 输入寄存器, 保持寄存器
+///
+/// 所有方法都只需要 `&self`, 可以通过 `Arc` 在多个线程之间共享,
+/// 既可以作为服务端的数据存储, 也可以作为客户端单元测试的模拟设备.
+/// 访问超出范围的地址时返回 `ExceptionCode::IllegalDataAddress` 异常
+pub struct RegisterBank {
+    coils: RwLock<Vec<Coil>>,
+    discrete_inputs: RwLock<Vec<Coil>>,
+    input_registers: RwLock<Vec<Word>>,
+    holding_registers: RwLock<Vec<Word>>,
+}
+
+impl RegisterBank {
+    /// 指定每个区域的大小创建, 初始值都为 0
+    pub fn new(
+        coils: usize,
+        discrete_inputs: usize,
+        input_registers: usize,
+        holding_registers: usize,
+    ) -> Self {
+        Self {
+            coils: RwLock::new(vec![Coil::Off; coils]),
+            discrete_inputs: RwLock::new(vec![Coil::Off; discrete_inputs]),
+            input_registers: RwLock::new(vec![0; input_registers]),
+            holding_registers: RwLock::new(vec![0; holding_registers]),
+        }
+    }
+
+    pub fn get_coils(&self, address: impl Into<CoilAddress>, quantity: u16) -> Result<Vec<Coil>> {
+        get_range(&self.coils, address.into().get(), quantity)
+    }
+
+    pub fn set_coils(&self, address: impl Into<CoilAddress>, values: &[Coil]) -> Result<()> {
+        set_range(&self.coils, address.into().get(), values)
+    }
+
+    pub fn get_coil(&self, address: impl Into<CoilAddress>) -> Result<Coil> {
+        Ok(self.get_coils(address, 1)?[0])
+    }
+
+    pub fn set_coil(&self, address: impl Into<CoilAddress>, value: Coil) -> Result<()> {
+        self.set_coils(address, &[value])
+    }
+
+    pub fn get_discrete_inputs(
+        &self,
+        address: impl Into<DiscreteAddress>,
+        quantity: u16,
+    ) -> Result<Vec<Coil>> {
+        get_range(&self.discrete_inputs, address.into().get(), quantity)
+    }
+
+    pub fn set_discrete_inputs(
+        &self,
+        address: impl Into<DiscreteAddress>,
+        values: &[Coil],
+    ) -> Result<()> {
+        set_range(&self.discrete_inputs, address.into().get(), values)
+    }
+
+    pub fn get_input_registers(
+        &self,
+        address: impl Into<InputAddress>,
+        quantity: u16,
+    ) -> Result<Vec<Word>> {
+        get_range(&self.input_registers, address.into().get(), quantity)
+    }
+
+    pub fn set_input_registers(
+        &self,
+        address: impl Into<InputAddress>,
+        values: &[Word],
+    ) -> Result<()> {
+        set_range(&self.input_registers, address.into().get(), values)
+    }
+
+    pub fn get_holding_registers(
+        &self,
+        address: impl Into<HoldingAddress>,
+        quantity: u16,
+    ) -> Result<Vec<Word>> {
+        get_range(&self.holding_registers, address.into().get(), quantity)
+    }
+
+    pub fn set_holding_registers(
+        &self,
+        address: impl Into<HoldingAddress>,
+        values: &[Word],
+    ) -> Result<()> {
+        set_range(&self.holding_registers, address.into().get(), values)
+    }
+
+    pub fn get_holding_register(&self, address: impl Into<HoldingAddress>) -> Result<Word> {
+        Ok(self.get_holding_registers(address, 1)?[0])
+    }
+
+    pub fn set_holding_register(
+        &self,
+        address: impl Into<HoldingAddress>,
+        value: Word,
+    ) -> Result<()> {
+        self.set_holding_registers(address, &[value])
+    }
+
+    /// 处理一个请求 PDU (功能码 + 数据), 返回响应 PDU
+    ///
+    /// 无法处理的请求返回异常响应 PDU: FUN|0x80 + 异常码
+    pub fn process(&self, request: &[u8]) -> Vec<u8> {
+        let function = request.first().copied().unwrap_or(0);
+        match self.try_process(request) {
+            Ok(reply) => reply,
+            Err(code) => vec![function | 0x80, code.code()],
+        }
+    }
+
+    fn try_process(&self, mut request: &[u8]) -> std::result::Result<Vec<u8>, ExceptionCode> {
+        if request.len() < 5 {
+            return Err(ExceptionCode::IllegalDataValue);
+        }
+        let function = request.get_u8();
+        let address = request.get_u16();
+        let value = request.get_u16();
+        let illegal_address = |_| ExceptionCode::IllegalDataAddress;
+
+        // 功能码 0x01 ~ 0x06 只有 地址(2) + 数量或数值(2)
+        if (0x01..=0x06).contains(&function) && !request.is_empty() {
+            return Err(ExceptionCode::IllegalDataValue);
+        }
+
+        let mut reply = Vec::with_capacity(5);
+        reply.put_u8(function);
+        match function {
+            0x01 | 0x02 => {
+                if value == 0 || value > MAX_READ_COILS {
+                    return Err(ExceptionCode::IllegalDataValue);
+                }
+                let bits = if function == 0x01 {
+                    self.get_coils(address, value)
+                } else {
+                    self.get_discrete_inputs(address, value)
+                }
+                .map_err(illegal_address)?;
+                let bytes = pack_bits(&bits);
+                reply.put_u8(bytes.len() as u8);
+                reply.put_slice(&bytes);
+            }
+            0x03 | 0x04 => {
+                if value == 0 || value > MAX_READ_REGISTERS {
+                    return Err(ExceptionCode::IllegalDataValue);
+                }
+                let words = if function == 0x03 {
+                    self.get_holding_registers(address, value)
+                } else {
+                    self.get_input_registers(address, value)
+                }
+                .map_err(illegal_address)?;
+                reply.put_u8(2 * words.len() as u8);
+                for word in words {
+                    reply.put_u16(word);
+                }
+            }
+            0x05 => {
+                let coil = match value {
+                    v if v == Coil::On.code() => Coil::On,
+                    v if v == Coil::Off.code() => Coil::Off,
+                    _ => return Err(ExceptionCode::IllegalDataValue),
+                };
+                self.set_coil(address, coil).map_err(illegal_address)?;
+                reply.put_u16(address);
+                reply.put_u16(value);
+            }
+            0x06 => {
+                self.set_holding_register(address, value)
+                    .map_err(illegal_address)?;
+                reply.put_u16(address);
+                reply.put_u16(value);
+            }
+            // 诊断, 只支持回环测试
+            0x08 => {
+                if address != 0x00 || !request.is_empty() {
+                    return Err(ExceptionCode::IllegalFunction);
+                }
+                reply.put_u16(address);
+                reply.put_u16(value);
+            }
+            0x0F | 0x10 => {
+                let max = if function == 0x0F {
+                    MAX_WRITE_COILS
+                } else {
+                    MAX_WRITE_REGISTERS
+                };
+                if value == 0 || value > max || request.is_empty() {
+                    return Err(ExceptionCode::IllegalDataValue);
+                }
+                let byte_cnt = request.get_u8() as usize;
+                let expected = if function == 0x0F {
+                    (value as usize).div_ceil(8)
+                } else {
+                    2 * value as usize
+                };
+                if byte_cnt != expected || request.len() != byte_cnt {
+                    return Err(ExceptionCode::IllegalDataValue);
+                }
+                if function == 0x0F {
+                    let bits = unpack_bits(request, value);
+                    self.set_coils(address, &bits).map_err(illegal_address)?;
+                } else {
+                    let words = (0..value).map(|_| request.get_u16()).collect::<Vec<_>>();
+                    self.set_holding_registers(address, &words)
+                        .map_err(illegal_address)?;
+                }
+                reply.put_u16(address);
+                reply.put_u16(value);
+            }
+            0x16 => {
+                if request.len() != 2 {
+                    return Err(ExceptionCode::IllegalDataValue);
+                }
+                let and_mask = value;
+                let or_mask = request.get_u16();
+                let current = self
+                    .get_holding_register(address)
+                    .map_err(illegal_address)?;
+                let result = (current & and_mask) | (or_mask & !and_mask);
+                self.set_holding_register(address, result)
+                    .map_err(illegal_address)?;
+                reply.put_u16(address);
+                reply.put_u16(and_mask);
+                reply.put_u16(or_mask);
+            }
+            0x17 => {
+                // 读地址 + 读数量 + 写地址(2) + 写数量(2) + 字节数(1) + 写入的数据
+                if value == 0 || value > MAX_READ_REGISTERS || request.len() < 5 {
+                    return Err(ExceptionCode::IllegalDataValue);
+                }
+                let write_address = request.get_u16();
+                let write_cnt = request.get_u16();
+                let byte_cnt = request.get_u8() as usize;
+                if write_cnt == 0
+                    || write_cnt > MAX_READ_WRITE_REGISTERS
+                    || byte_cnt != 2 * write_cnt as usize
+                    || request.len() != byte_cnt
+                {
+                    return Err(ExceptionCode::IllegalDataValue);
+                }
+                // 先写后读
+                let words = (0..write_cnt)
+                    .map(|_| request.get_u16())
+                    .collect::<Vec<_>>();
+                self.set_holding_registers(write_address, &words)
+                    .map_err(illegal_address)?;
+                let words = self
+                    .get_holding_registers(address, value)
+                    .map_err(illegal_address)?;
+                reply.put_u8(2 * words.len() as u8);
+                for word in words {
+                    reply.put_u16(word);
+                }
+            }
+            _ => return Err(ExceptionCode::IllegalFunction),
+        }
+        Ok(reply)
+    }
+}
+
+impl Default for RegisterBank {
+    /// 每个区域都覆盖完整的 0x0000..=0xFFFF 地址空间
+    fn default() -> Self {
+        Self::new(0x10000, 0x10000, 0x10000, 0x10000)
+    }
+}
+
+/// 检查 [address, address + quantity) 是否在表的范围内
+fn check_range(len: usize, address: u16, quantity: usize) -> Result<std::ops::Range<usize>> {
+    let start = address as usize;
+    let end = start + quantity;
+    if quantity == 0 || end > len {
+        return Err(Error::Exception(ExceptionCode::IllegalDataAddress));
+    }
+    Ok(start..end)
+}
+
+/// 其它线程持有锁时 panic 不影响数据的完整性, 每次读写都是完整的切片操作
+fn get_range<T: Copy>(table: &RwLock<Vec<T>>, address: u16, quantity: u16) -> Result<Vec<T>> {
+    let table = table.read().unwrap_or_else(PoisonError::into_inner);
+    let range = check_range(table.len(), address, quantity as usize)?;
+    Ok(table[range].to_vec())
+}
+
+fn set_range<T: Copy>(table: &RwLock<Vec<T>>, address: u16, values: &[T]) -> Result<()> {
+    let mut table = table.write().unwrap_or_else(PoisonError::into_inner);
+    let range = check_range(table.len(), address, values.len())?;
+    table[range].copy_from_slice(values);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::RegisterBank;
+    use crate::Coil;
+
+    fn bank() -> RegisterBank {
+        let bank = RegisterBank::new(16, 16, 8, 8);
+        bank.set_coils(0, &[Coil::On, Coil::Off, Coil::On]).unwrap();
+        bank.set_discrete_inputs(7, &[Coil::On; 9]).unwrap();
+        bank.set_input_registers(6, &[0x1234, 0x5678]).unwrap();
+        bank.set_holding_registers(0, &[0x00FF, 0x0001]).unwrap();
+        bank
+    }
+
+    #[test]
+    fn read_bits() {
+        let bank = bank();
+        assert_eq!(
+            bank.process(&[0x01, 0x00, 0x00, 0x00, 0x03]),
+            [0x01, 0x01, 0x05]
+        );
+        assert_eq!(
+            bank.process(&[0x02, 0x00, 0x07, 0x00, 0x09]),
+            [0x02, 0x02, 0xFF, 0x01]
+        );
+        // 超出范围, 数量为 0, 数量超过 2000
+        assert_eq!(bank.process(&[0x01, 0x00, 0x0E, 0x00, 0x03]), [0x81, 0x02]);
+        assert_eq!(bank.process(&[0x02, 0x00, 0x00, 0x00, 0x00]), [0x82, 0x03]);
+        assert_eq!(bank.process(&[0x01, 0x00, 0x00, 0x07, 0xD1]), [0x81, 0x03]);
+    }
+
+    #[test]
+    fn read_registers() {
+        let bank = bank();
+        assert_eq!(
+            bank.process(&[0x03, 0x00, 0x00, 0x00, 0x02]),
+            [0x03, 0x04, 0x00, 0xFF, 0x00, 0x01]
+        );
+        assert_eq!(
+            bank.process(&[0x04, 0x00, 0x06, 0x00, 0x02]),
+            [0x04, 0x04, 0x12, 0x34, 0x56, 0x78]
+        );
+        assert_eq!(bank.process(&[0x04, 0x00, 0x07, 0x00, 0x02]), [0x84, 0x02]);
+        assert_eq!(bank.process(&[0x03, 0x00, 0x00, 0x00, 0x7E]), [0x83, 0x03]);
+    }
+
+    #[test]
+    fn write_single() {
+        let bank = bank();
+        let req = [0x05, 0x00, 0x01, 0xFF, 0x00];
+        assert_eq!(bank.process(&req), req);
+        assert_eq!(bank.get_coil(1).unwrap(), Coil::On);
+        assert_eq!(bank.process(&[0x05, 0x00, 0x01, 0x12, 0x34]), [0x85, 0x03]);
+        assert_eq!(bank.process(&[0x05, 0x00, 0x10, 0xFF, 0x00]), [0x85, 0x02]);
+
+        let req = [0x06, 0x00, 0x07, 0xAB, 0xCD];
+        assert_eq!(bank.process(&req), req);
+        assert_eq!(bank.get_holding_register(7).unwrap(), 0xABCD);
+        assert_eq!(bank.process(&[0x06, 0x00, 0x08, 0x00, 0x01]), [0x86, 0x02]);
+    }
+
+    #[test]
+    fn write_multiple() {
+        let bank = bank();
+        assert_eq!(
+            bank.process(&[0x0F, 0x00, 0x04, 0x00, 0x0A, 0x02, 0xCD, 0x01]),
+            [0x0F, 0x00, 0x04, 0x00, 0x0A]
+        );
+        assert_eq!(
+            bank.get_coils(4, 10).unwrap(),
+            [1, 0, 1, 1, 0, 0, 1, 1, 1, 0].map(|b| if b == 1 { Coil::On } else { Coil::Off })
+        );
+        assert_eq!(
+            bank.process(&[0x10, 0x00, 0x02, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]),
+            [0x10, 0x00, 0x02, 0x00, 0x02]
+        );
+        assert_eq!(bank.get_holding_registers(2, 2).unwrap(), [0x000A, 0x0102]);
+        // 字节数与数量不一致, 超出范围
+        assert_eq!(
+            bank.process(&[0x10, 0x00, 0x02, 0x00, 0x02, 0x02, 0x00, 0x0A]),
+            [0x90, 0x03]
+        );
+        assert_eq!(
+            bank.process(&[0x10, 0x00, 0x07, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]),
+            [0x90, 0x02]
+        );
+    }
+
+    #[test]
+    fn mask_write_register() {
+        let bank = bank();
+        bank.set_holding_register(4, 0x0012).unwrap();
+        let req = [0x16, 0x00, 0x04, 0x00, 0xF2, 0x00, 0x25];
+        assert_eq!(bank.process(&req), req);
+        assert_eq!(bank.get_holding_register(4).unwrap(), 0x0017);
+        assert_eq!(bank.process(&[0x16, 0x00, 0x04, 0x00, 0xF2]), [0x96, 0x03]);
+        assert_eq!(
+            bank.process(&[0x16, 0x00, 0x08, 0x00, 0xF2, 0x00, 0x25]),
+            [0x96, 0x02]
+        );
+    }
+
+    #[test]
+    fn read_write_multiple_registers() {
+        let bank = bank();
+        assert_eq!(
+            bank.process(&[0x17, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01, 0x02, 0x00, 0x09]),
+            [0x17, 0x04, 0x00, 0xFF, 0x00, 0x09]
+        );
+        assert_eq!(
+            bank.process(&[0x17, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01, 0x04, 0x00, 0x09]),
+            [0x97, 0x03]
+        );
+        assert_eq!(
+            bank.process(&[0x17, 0x00, 0x07, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01, 0x02, 0x00, 0x09]),
+            [0x97, 0x02]
+        );
+    }
+
+    #[test]
+    fn diagnostics_and_unknown_functions() {
+        let bank = bank();
+        let req = [0x08, 0x00, 0x00, 0xA5, 0x37];
+        assert_eq!(bank.process(&req), req);
+        assert_eq!(bank.process(&[0x08, 0x00, 0x01, 0x00, 0x00]), [0x88, 0x01]);
+        assert_eq!(bank.process(&[0x2B, 0x0E, 0x01, 0x00, 0x00]), [0xAB, 0x01]);
+    }
+
+    #[test]
+    fn bad_lengths() {
+        let bank = bank();
+        assert_eq!(bank.process(&[]), [0x80, 0x03]);
+        assert_eq!(bank.process(&[0x03, 0x00, 0x00, 0x00]), [0x83, 0x03]);
+        for function in 0x01..=0x06 {
+            let req = [function, 0x00, 0x00, 0x00, 0x01, 0x00];
+            assert_eq!(bank.process(&req), [function | 0x80, 0x03]);
+        }
+        assert_eq!(bank.get_holding_register(0).unwrap(), 0x00FF);
+    }
+
+    #[test]
+    fn poisoned_lock_is_recovered() {
+        let bank = Arc::new(bank());
+        let poison = bank.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poison.holding_registers.write().unwrap();
+            panic!("持有锁时 panic");
+        })
+        .join();
+        assert!(bank.holding_registers.is_poisoned());
+        bank.set_holding_register(1, 2).unwrap();
+        assert_eq!(bank.get_holding_registers(0, 2).unwrap(), [0x00FF, 2]);
+    }
+}
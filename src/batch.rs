@@ -0,0 +1,118 @@
+use crate::{
+    address::HoldingAddress,
+    error::{Error, Reason, Result},
+    quantity::{Quantity, MAX_READ_REGISTERS},
+    Client, Id, Word,
+};
+
+/// 合并后的一次读请求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchRequest {
+    pub address: HoldingAddress,
+    pub quantity: u16,
+    /// 包含在这次请求中的项目的序号
+    pub items: Vec<usize>,
+}
+
+/// 批量读取保持寄存器
+///
+/// 把多个 (地址, 长度) 项目中连续或者接近的地址范围合并成尽量少的 `ReadHoldingRegisters` 请求,
+/// 每个请求最多读 125 个寄存器, 读取后按照项目拆分数据
+#[derive(Debug, Clone)]
+pub struct BatchReader {
+    items: Vec<(u16, u16)>,
+    max_gap: u16,
+}
+
+impl BatchReader {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            max_gap: 0,
+        }
+    }
+
+    /// 两个项目之间最多相隔 max_gap 个寄存器时也合并到一个请求中, 默认为 0, 即只合并连续的地址
+    pub fn with_max_gap(mut self, max_gap: u16) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+
+    /// 添加一个项目, 返回它的序号. 长度范围 1..=125
+    pub fn add(&mut self, address: impl Into<HoldingAddress>, len: u16) -> Result<usize> {
+        Quantity::read_registers(len)?;
+        let address = address.into().get();
+        if address as u32 + len as u32 > 0x10000 {
+            return Err(Error::InvalidData(Reason::AddressOverflow {
+                address,
+                quantity: len,
+            }));
+        }
+        self.items.push((address, len));
+        Ok(self.items.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// 计算合并后的请求
+    pub fn plan(&self) -> Vec<BatchRequest> {
+        let mut order = (0..self.items.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| self.items[i]);
+
+        let mut requests: Vec<BatchRequest> = Vec::new();
+        // 当前请求的结束地址 (不包含)
+        let mut end = 0u32;
+        for i in order {
+            let (address, len) = self.items[i];
+            let item_end = address as u32 + len as u32;
+            if let Some(req) = requests.last_mut() {
+                let start = req.address.get() as u32;
+                let new_end = end.max(item_end);
+                if address as u32 <= end + self.max_gap as u32
+                    && new_end - start <= MAX_READ_REGISTERS as u32
+                {
+                    end = new_end;
+                    req.quantity = (end - start) as u16;
+                    req.items.push(i);
+                    continue;
+                }
+            }
+            end = item_end;
+            requests.push(BatchRequest {
+                address: HoldingAddress::new(address),
+                quantity: len,
+                items: vec![i],
+            });
+        }
+        requests
+    }
+
+    /// 执行合并后的请求, 按照添加的顺序返回每个项目的数据
+    pub fn read(&self, client: &mut Client, id: Id) -> Result<Vec<Vec<Word>>> {
+        let mut values = vec![Vec::new(); self.items.len()];
+        for req in self.plan() {
+            let words = client.read_holding_registers(id, req.address, req.quantity)?;
+            if words.len() != req.quantity as usize {
+                return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+            }
+            for i in req.items {
+                let (address, len) = self.items[i];
+                let offset = (address - req.address.get()) as usize;
+                values[i] = words[offset..offset + len as usize].to_vec();
+            }
+        }
+        Ok(values)
+    }
+}
+
+impl Default for BatchReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
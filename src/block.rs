@@ -0,0 +1,41 @@
+//! 结构体与一段连续的保持寄存器之间的映射, 通常使用 `#[derive(ModbusBlock)]` 生成
+
+use crate::{error::Result, Client, Id, Word};
+
+/// 映射到一段连续保持寄存器的结构体
+///
+/// 派生宏的用法:
+/// ```ignore
+/// #[derive(ModbusBlock)]
+/// struct Motor {
+///     #[register(addr = 0x10)]
+///     status: u16,
+///     #[register(addr = 0x12, ty = "f32", order = "cdab")]
+///     speed: f32,
+/// }
+/// ```
+/// ty 省略时使用字段的类型, order 省略时为 abcd.
+/// 字段之间没有定义的寄存器在写入时填 0
+pub trait ModbusBlock: Sized {
+    /// 起始地址
+    const ADDRESS: u16;
+    /// 寄存器数量
+    const WORDS: u16;
+
+    /// 从 ADDRESS 开始的 WORDS 个寄存器解析结构体
+    fn from_words(words: &[Word]) -> Result<Self>;
+
+    /// 转换为从 ADDRESS 开始的 WORDS 个寄存器
+    fn to_words(&self) -> Vec<Word>;
+
+    /// 一次读取整个结构体
+    fn read_from(client: &mut Client, id: Id) -> Result<Self> {
+        let words = client.read_holding_registers(id, Self::ADDRESS, Self::WORDS)?;
+        Self::from_words(&words)
+    }
+
+    /// 一次写入整个结构体
+    fn write_to(&self, client: &mut Client, id: Id) -> Result<()> {
+        client.write_multiple_registers(id, Self::ADDRESS, self.to_words())
+    }
+}
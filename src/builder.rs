@@ -0,0 +1,153 @@
+use serialport::{DataBits, FlowControl, Parity, StopBits};
+use std::time::Duration;
+
+use crate::{
+    config::{invalid_config, Config},
+    error::Result,
+    serial::SerialStream,
+    stream::Stream,
+    tcp::{Keepalive, TcpStream},
+    udp::UdpStream,
+    Client, Id, Protocol,
+};
+
+/// 根据 Config 创建 Client, 打开串口或者连接服务器之前先检查配置
+#[derive(Debug, Clone, Default)]
+pub struct ClientBuilder {
+    config: Config,
+    protocol: Option<Protocol>,
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 替换全部配置
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// 传输帧格式, 默认串口为 RTU, TCP 连接为 TCP
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// 默认的单元标识符
+    pub fn unit_id(mut self, id: Id) -> Self {
+        self.config.modbus_uid = id;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.config.read_timeout = timeout;
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.config.write_timeout = timeout;
+        self
+    }
+
+    pub fn data_bits(mut self, data_bits: DataBits) -> Self {
+        self.config.data_bits = data_bits;
+        self
+    }
+
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.config.parity = parity;
+        self
+    }
+
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.config.stop_bits = stop_bits;
+        self
+    }
+
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.config.flow_control = flow_control;
+        self
+    }
+
+    /// TCP 连接是否禁用 Nagle 算法, 默认为 true
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.config.tcp.nodelay = nodelay;
+        self
+    }
+
+    /// TCP 保活探测, 及时发现断开的网关. None 表示使用操作系统的设置
+    pub fn tcp_keepalive(mut self, keepalive: Option<Keepalive>) -> Self {
+        self.config.tcp.keepalive = keepalive;
+        self
+    }
+
+    /// TCP 连接绑定到指定的网络接口, 例如 eth1, 只支持 Linux
+    pub fn bind_interface(mut self, interface: &str) -> Self {
+        self.config.tcp.interface = Some(interface.to_string());
+        self
+    }
+
+    /// 打开串口, 创建 RTU (或 ASCII) 客户端
+    pub fn open_serial(self, port: &str, baud_rate: u32) -> Result<Client> {
+        let protocol = self.protocol.unwrap_or(Protocol::Rtu);
+        match protocol {
+            Protocol::Tcp => return Err(invalid_config("串口不能使用 TCP 帧格式")),
+            // RTU 的每个字节都是 8 位数据, ASCII 可以使用 7 位
+            Protocol::Rtu if self.config.data_bits != DataBits::Eight => {
+                return Err(invalid_config("RTU 模式的数据位必须为 8"))
+            }
+            _ => {}
+        }
+        if baud_rate == 0 {
+            return Err(invalid_config("波特率不能为 0"));
+        }
+        self.config.validate()?;
+
+        let stream = SerialStream::with_config(port, baud_rate, &self.config)?;
+        self.build_with(Box::new(stream), protocol)
+    }
+
+    /// 连接 Modbus TCP 服务器, addr 格式为 `host:port`
+    pub fn connect_tcp(self, addr: &str) -> Result<Client> {
+        self.config.validate()?;
+        let stream = TcpStream::with_config(addr, &self.config)?;
+        let protocol = self.protocol.unwrap_or(Protocol::Tcp);
+        self.build_with(Box::new(stream), protocol)
+    }
+
+    /// 连接串口服务器, 通过 TCP 直接传输 RTU 帧 (没有 MBAP报文头, 使用 CRC 校验)
+    pub fn connect_rtu_over_tcp(self, addr: &str) -> Result<Client> {
+        self.protocol(Protocol::Rtu).connect_tcp(addr)
+    }
+
+    /// 连接 Modbus UDP 服务器, addr 格式为 `host:port`, 默认使用 MBAP报文头
+    pub fn connect_udp(self, addr: &str) -> Result<Client> {
+        self.config.validate()?;
+        let stream = UdpStream::with_config(addr, &self.config)?;
+        let protocol = self.protocol.unwrap_or(Protocol::Tcp);
+        self.build_with(Box::new(stream), protocol)
+    }
+
+    /// 使用已经打开的数据传输创建客户端, 默认为 RTU 帧格式, 读写的超时时间为 read_timeout 和 write_timeout
+    pub fn build(self, stream: Box<dyn Stream>) -> Result<Client> {
+        let protocol = self.protocol.unwrap_or(Protocol::Rtu);
+        let mut client = Client::with_config(stream, self.config)?;
+        client.set_protocol(protocol);
+        Ok(client)
+    }
+
+    /// 传输已经按照配置打开, 不再重新设置超时时间
+    fn build_with(self, stream: Box<dyn Stream>, protocol: Protocol) -> Result<Client> {
+        let mut client = Client::new(stream)?;
+        client.set_protocol(protocol);
+        client.apply_config(self.config);
+        Ok(client)
+    }
+}
@@ -0,0 +1,193 @@
+//! 一条总线上的多个从设备: 共享一个传输, 每个从设备有自己的设置
+
+use std::time::Duration;
+
+use crate::{
+    address::{CoilAddress, DiscreteAddress, HoldingAddress, InputAddress},
+    error::{Error, Result},
+    quantity::Quantity,
+    shared::SharedClient,
+    value::{RegisterValue, WordOrder},
+    Client, Coil, Id, Word,
+};
+
+/// 一条总线, 拥有唯一的传输, 通过 [`Bus::slave`] 得到每个从设备的句柄
+///
+/// 所有句柄共享同一个客户端, 每次请求都持有锁直到事务完成, 不同线程的请求不会在总线上交错
+#[derive(Clone)]
+pub struct Bus {
+    client: SharedClient,
+}
+
+impl Bus {
+    pub fn new(client: impl Into<SharedClient>) -> Self {
+        Self {
+            client: client.into(),
+        }
+    }
+
+    /// 从设备的句柄, 初始时使用客户端的设置
+    pub fn slave(&self, id: Id) -> Slave {
+        Slave {
+            client: self.client.clone(),
+            id,
+            timeout: None,
+            retries: 0,
+            word_order: None,
+        }
+    }
+
+    pub fn client(&self) -> &SharedClient {
+        &self.client
+    }
+}
+
+impl From<Client> for Bus {
+    fn from(client: Client) -> Self {
+        Self::new(client)
+    }
+}
+
+/// 总线上一个从设备的句柄, clone 的开销很小
+///
+/// 超时时间, 重试次数和字节顺序只对这个句柄的请求生效, 请求结束后恢复客户端原来的设置
+#[derive(Clone)]
+pub struct Slave {
+    client: SharedClient,
+    id: Id,
+    timeout: Option<Duration>,
+    retries: u32,
+    word_order: Option<WordOrder>,
+}
+
+impl Slave {
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// 设置这个从设备的读超时时间, None 表示使用客户端的设置
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// 设置超时, 校验错误 或者 无效的响应 之后重试的次数, 默认为 0
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    /// 设置这个从设备的字节顺序, None 表示使用客户端的设置
+    pub fn set_word_order(&mut self, word_order: Option<WordOrder>) {
+        self.word_order = word_order;
+    }
+
+    /// 在一次锁定中使用这个从设备的设置执行 f, 可以重试的错误按照 retries 重新执行
+    pub fn with<R>(&self, mut f: impl FnMut(&mut Client, Id) -> Result<R>) -> Result<R> {
+        let mut client = self.client.lock();
+        let old_timeout = client.timeout();
+        let old_word_order = client.word_order();
+        if let Some(timeout) = self.timeout {
+            client.set_read_timeout(timeout)?;
+        }
+        if let Some(word_order) = self.word_order {
+            client.set_word_order(word_order);
+        }
+
+        let mut res = f(&mut client, self.id);
+        for _ in 0..self.retries {
+            match &res {
+                Err(
+                    Error::Timeout | Error::Crc | Error::ShortFrame | Error::InvalidResponse(_),
+                ) => {
+                    res = f(&mut client, self.id);
+                }
+                _ => break,
+            }
+        }
+
+        client.set_word_order(old_word_order);
+        if self.timeout.is_some() {
+            client.set_read_timeout(old_timeout)?;
+        }
+        res
+    }
+
+    pub fn read_coils(
+        &self,
+        address: impl Into<CoilAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        let (address, quantity) = (address.into(), quantity.into());
+        self.with(|client, id| client.read_coils(id, address, quantity))
+    }
+
+    pub fn read_discrete_inputs(
+        &self,
+        address: impl Into<DiscreteAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        let (address, quantity) = (address.into(), quantity.into());
+        self.with(|client, id| client.read_discrete_inputs(id, address, quantity))
+    }
+
+    pub fn read_holding_registers(
+        &self,
+        address: impl Into<HoldingAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        let (address, quantity) = (address.into(), quantity.into());
+        self.with(|client, id| client.read_holding_registers(id, address, quantity))
+    }
+
+    pub fn read_input_registers(
+        &self,
+        address: impl Into<InputAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        let (address, quantity) = (address.into(), quantity.into());
+        self.with(|client, id| client.read_input_registers(id, address, quantity))
+    }
+
+    pub fn write_single_register(
+        &self,
+        address: impl Into<HoldingAddress>,
+        value: Word,
+    ) -> Result<()> {
+        let address = address.into();
+        self.with(|client, id| client.write_single_register(id, address, value))
+    }
+
+    pub fn write_multiple_registers(
+        &self,
+        address: impl Into<HoldingAddress>,
+        values: &[Word],
+    ) -> Result<()> {
+        let address = address.into();
+        self.with(|client, id| client.write_multiple_registers(id, address, values.to_vec()))
+    }
+
+    pub fn mask_write_register(
+        &self,
+        address: impl Into<HoldingAddress>,
+        and_mask: Word,
+        or_mask: Word,
+    ) -> Result<()> {
+        let address = address.into();
+        self.with(|client, id| client.mask_write_register(id, address, and_mask, or_mask))
+    }
+
+    /// 从保持寄存器读取一个数值, 按照这个从设备的字节顺序解码
+    pub fn read_value<T: RegisterValue>(&self, address: impl Into<HoldingAddress>) -> Result<T> {
+        let address = address.into();
+        self.with(|client, id| client.read_value(id, address))
+    }
+
+    /// 按照这个从设备的字节顺序编码, 写入保持寄存器
+    pub fn write_value<T: RegisterValue + Copy>(
+        &self,
+        address: impl Into<HoldingAddress>,
+        value: T,
+    ) -> Result<()> {
+        let address = address.into();
+        self.with(|client, id| client.write_value(id, address, value))
+    }
+}
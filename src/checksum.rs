@@ -0,0 +1,120 @@
+use crate::codec::{calc_crc, calc_lrc};
+
+/// 帧校验算法
+///
+/// 默认使用标准的 CRC-16/MODBUS, 某些私有的 "类Modbus" 设备使用其它校验方式,
+/// 可以通过 `Client::set_checksum` 替换
+pub trait Checksum: Send {
+    /// 校验值占用的字节数
+    fn size(&self) -> usize;
+
+    /// 计算 data 的校验值, 按照在帧中的字节顺序返回
+    fn calc(&self, data: &[u8]) -> Vec<u8>;
+
+    /// 检查一个完整的帧 (数据 + 校验值) 是否正确
+    fn verify(&self, frame: &[u8]) -> bool {
+        let size = self.size();
+        if frame.len() < size {
+            return false;
+        }
+        let (data, checksum) = frame.split_at(frame.len() - size);
+        self.calc(data) == checksum
+    }
+}
+
+/// 标准的 CRC-16/MODBUS, 低字节在前
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModbusCrc;
+
+impl Checksum for ModbusCrc {
+    fn size(&self) -> usize {
+        2
+    }
+
+    fn calc(&self, data: &[u8]) -> Vec<u8> {
+        calc_crc(data).to_be_bytes().to_vec()
+    }
+
+    /// 每个响应都要检查, 直接比较, 不分配内存
+    fn verify(&self, frame: &[u8]) -> bool {
+        match frame.len().checked_sub(2) {
+            Some(n) => calc_crc(&frame[..n]).to_be_bytes() == frame[n..],
+            None => false,
+        }
+    }
+}
+
+/// 可配置多项式的 CRC-16 (反射输入输出)
+#[derive(Debug, Clone, Copy)]
+pub struct Crc16 {
+    /// 反射后的多项式, 例如 CRC-16/MODBUS 为 0xA001
+    pub poly: u16,
+    /// 初始值
+    pub init: u16,
+    /// 结果异或值
+    pub xor_out: u16,
+    /// 帧中是否低字节在前
+    pub low_byte_first: bool,
+}
+
+impl Crc16 {
+    pub const fn new(poly: u16, init: u16) -> Self {
+        Self {
+            poly,
+            init,
+            xor_out: 0,
+            low_byte_first: true,
+        }
+    }
+
+    pub fn value(&self, data: &[u8]) -> u16 {
+        let mut crc = self.init;
+        for x in data {
+            crc ^= u16::from(*x);
+            for _ in 0..8 {
+                let crc_odd = (crc & 0x0001) != 0;
+                crc >>= 1;
+                if crc_odd {
+                    crc ^= self.poly;
+                }
+            }
+        }
+        crc ^ self.xor_out
+    }
+}
+
+impl Checksum for Crc16 {
+    fn size(&self) -> usize {
+        2
+    }
+
+    fn calc(&self, data: &[u8]) -> Vec<u8> {
+        let crc = self.value(data);
+        if self.low_byte_first {
+            crc.to_le_bytes().to_vec()
+        } else {
+            crc.to_be_bytes().to_vec()
+        }
+    }
+}
+
+/// 8位累加和取补码 (所有字节相加后取二进制补码)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SumComplement;
+
+impl Checksum for SumComplement {
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn calc(&self, data: &[u8]) -> Vec<u8> {
+        vec![calc_lrc(data)]
+    }
+
+    fn verify(&self, frame: &[u8]) -> bool {
+        match frame.split_last() {
+            Some((&lrc, data)) => calc_lrc(data) == lrc,
+            None => false,
+        }
+    }
+}
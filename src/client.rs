@@ -0,0 +1,1958 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    address::{CoilAddress, DiscreteAddress, HoldingAddress, InputAddress},
+    checksum::Checksum,
+    config::{invalid_config, Config, BROADCAST_ID},
+    custom::{CustomFunction, CustomRequest, ReplyLength},
+    device_id::{DeviceIdCategory, DeviceIdentification},
+    diagnostics::{self, CommEventCounter, CommEventLog, ServerId},
+    endian::{self, ByteOrder},
+    error::{Cancelled, Error, Reason, Result, VerificationError},
+    file_record::{self, FileRecord, FileRecordRead},
+    frame::Framer,
+    quantity::{Quantity, MAX_READ_COILS, MAX_READ_REGISTERS, MAX_WRITE_REGISTERS},
+    rate_limit::RateLimiter,
+    response::Response,
+    serial::RtuTiming,
+    snapshot::{DeviceSnapshot, SnapshotBlock},
+    stats::Stats,
+    stream::Stream,
+    trace::{self, Direction, FrameEvent, FrameObserver, WireLogging},
+    unit::UnitClient,
+    value::{self, RegisterValue, WordOrder},
+    Address, Id, Protocol, Word, MBAP_HEADER_SIZE, MODBUS_ASCII_MAX_FRAME_SIZE,
+    MODBUS_MAX_PACKET_SIZE,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Function {
+    /// 读指定数量的线圈的状态
+    /// (modbus从设备ID, 要读的线圈的起始地址, 要读的线圈的数量)
+    ReadCoils(Id, Address, Quantity),
+
+    /// 读指定数量的离散输入的状态
+    /// (modbus从设备ID, 要读的离散输入的起始地址, 要读的离散输入的数量)
+    ReadDiscreteInputs(Id, Address, Quantity),
+
+    /// 读指定数量的保持寄存器的数据
+    /// (modbus从设备ID, 要读的保持寄存器的起始地址, 要读的保持寄存器的数量)
+    ReadHoldingRegisters(Id, Address, Quantity),
+
+    /// 读指定数量的输入寄存器的数据
+    /// (modbus从设备ID, 要读的输入寄存器的起始地址, 要读的输入寄存器的数量)
+    ReadInputRegisters(Id, Address, Quantity),
+
+    /// 写单个寄存器
+    /// (modbus从设备ID, 要写入的寄存器地址, 要写入这个寄存器的单个数据)
+    WriteSingleRegister(Id, Address, Word),
+
+    /// 写多个寄存器
+    /// (modbus从设备ID, 要写入的寄存器的起始地址, 要写入这些寄存器的数据列表)
+    WriteMultipleRegisters(Id, Address, Vec<Word>),
+
+    /// 按位修改单个寄存器, 结果为 (当前值 & and_mask) | (or_mask & !and_mask)
+    /// (modbus从设备ID, 要修改的寄存器地址, AND掩码, OR掩码)
+    MaskWriteRegister(Id, Address, Word, Word),
+
+    /// 诊断
+    /// (modbus从设备ID, 子功能码, 数据)
+    Diagnostics(Id, u16, Word),
+
+    /// 读异常状态, 只用于串行链路
+    /// (modbus从设备ID)
+    ReadExceptionStatus(Id),
+
+    /// 获取通信事件计数, 只用于串行链路
+    /// (modbus从设备ID)
+    GetCommEventCounter(Id),
+
+    /// 获取通信事件日志, 只用于串行链路
+    /// (modbus从设备ID)
+    GetCommEventLog(Id),
+
+    /// 报告从设备ID, 只用于串行链路
+    /// (modbus从设备ID)
+    ReportServerId(Id),
+
+    /// 读 FIFO 队列
+    /// (modbus从设备ID, FIFO指针地址)
+    ReadFifoQueue(Id, Address),
+
+    /// 读文件记录, 一次请求中可以包含多个子请求
+    /// (modbus从设备ID, 子请求列表)
+    ReadFileRecord(Id, Vec<FileRecordRead>),
+
+    /// 写文件记录, 一次请求中可以包含多个子请求
+    /// (modbus从设备ID, 要写入的记录列表)
+    WriteFileRecord(Id, Vec<FileRecord>),
+
+    /// 读设备标识 (功能码 0x2B, MEI类型 0x0E)
+    /// (modbus从设备ID, 读设备ID码, 起始对象ID)
+    ReadDeviceIdentification(Id, u8, u8),
+
+    /// 在一次传输中先写多个保持寄存器, 再读多个保持寄存器
+    /// (modbus从设备ID, 要读的起始地址, 要读的数量, 要写入的起始地址, 要写入的数据列表)
+    ReadWriteMultipleRegisters(Id, Address, Quantity, Address, Vec<Word>),
+
+    /// 定制功能码, 报文头和校验码由客户端添加
+    Custom(CustomRequest),
+}
+
+impl Function {
+    /// 请求的从设备ID
+    pub fn id(&self) -> Id {
+        match self {
+            Function::ReadCoils(id, ..)
+            | Function::ReadDiscreteInputs(id, ..)
+            | Function::ReadHoldingRegisters(id, ..)
+            | Function::ReadInputRegisters(id, ..)
+            | Function::WriteSingleRegister(id, ..)
+            | Function::WriteMultipleRegisters(id, ..)
+            | Function::MaskWriteRegister(id, ..)
+            | Function::Diagnostics(id, ..)
+            | Function::ReadExceptionStatus(id)
+            | Function::GetCommEventCounter(id)
+            | Function::GetCommEventLog(id)
+            | Function::ReportServerId(id)
+            | Function::ReadFifoQueue(id, ..)
+            | Function::ReadFileRecord(id, ..)
+            | Function::WriteFileRecord(id, ..)
+            | Function::ReadDeviceIdentification(id, ..)
+            | Function::ReadWriteMultipleRegisters(id, ..) => *id,
+            Function::Custom(req) => req.id,
+        }
+    }
+}
+
+macro_rules! typed_value {
+    ($($ty:ty: $read:ident, $write:ident;)*) => {
+        impl Client {
+            $(
+                #[doc = concat!("从保持寄存器读取一个 ", stringify!($ty))]
+                pub fn $read(&mut self, id: Id, address: impl Into<HoldingAddress>) -> Result<$ty> {
+                    self.read_value(id, address)
+                }
+
+                #[doc = concat!("写入一个 ", stringify!($ty), " 到保持寄存器")]
+                pub fn $write(
+                    &mut self,
+                    id: Id,
+                    address: impl Into<HoldingAddress>,
+                    value: $ty,
+                ) -> Result<()> {
+                    self.write_value(id, address, value)
+                }
+            )*
+        }
+    };
+}
+
+typed_value! {
+    u32: read_u32, write_u32;
+    i32: read_i32, write_i32;
+    f32: read_f32, write_f32;
+    u64: read_u64, write_u64;
+    i64: read_i64, write_i64;
+    f64: read_f64, write_f64;
+}
+
+/// 把超过协议限制的读写请求拆分为多个请求, 返回每个请求的 (起始地址, 数量)
+pub(crate) fn split_request(
+    address: Address,
+    quantity: Quantity,
+    max: u16,
+) -> Result<Vec<(Address, Quantity)>> {
+    let quantity = quantity.get();
+    if address as u32 + quantity as u32 > 0x10000 {
+        return Err(Error::InvalidRequest(Reason::AddressOverflow {
+            address,
+            quantity,
+        }));
+    }
+    // 数量为 0 时保持原样, 发送前的检查会返回错误
+    if quantity == 0 {
+        return Ok(vec![(address, Quantity::from(0))]);
+    }
+    Ok((0..quantity)
+        .step_by(max as usize)
+        .map(|offset| {
+            let count = max.min(quantity - offset);
+            (address + offset, Quantity::from(count))
+        })
+        .collect())
+}
+
+/// 切片的长度作为请求的数量, 超过 65535 时返回错误而不是截断
+pub(crate) fn slice_quantity(len: usize) -> Result<Quantity> {
+    u16::try_from(len).map(Quantity::from).map_err(|_| {
+        Error::InvalidRequest(Reason::QuantityOutOfRange {
+            quantity: u16::MAX,
+            max: u16::MAX,
+        })
+    })
+}
+
+/// 串行链路上的广播请求不会有响应, 读操作不能使用广播地址
+///
+/// Modbus TCP 的单元标识 0 也用于直接访问设备, 不是广播
+pub(crate) fn check_not_broadcast(protocol: Protocol, fun: &Function) -> Result<()> {
+    // 定制请求可能是写操作, 广播时不读取响应
+    if protocol != Protocol::Tcp && fun.id() == BROADCAST_ID && !matches!(fun, Function::Custom(..))
+    {
+        return Err(Error::InvalidData(Reason::BroadcastRead));
+    }
+    Ok(())
+}
+
+/// 响应检查的严格程度, 用于与不完全符合规范的设备通信
+///
+/// 校验值错误和帧长度不足总是返回错误
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ValidationMode {
+    /// 所有检查都必须通过
+    #[default]
+    Strict,
+    /// 忽略从设备ID不一致, 以及写操作的响应没有原样返回请求数据, 只记录警告
+    Lenient,
+    /// 由函数决定, 返回 true 表示这一项检查失败时返回错误
+    Custom(fn(&Reason) -> bool),
+}
+
+impl ValidationMode {
+    /// 这一项检查失败时是否返回错误
+    pub fn is_fatal(&self, reason: &Reason) -> bool {
+        match self {
+            ValidationMode::Strict => true,
+            ValidationMode::Lenient => {
+                !matches!(reason, Reason::UnexpectedId | Reason::UnexpectedEcho)
+            }
+            ValidationMode::Custom(fatal) => fatal(reason),
+        }
+    }
+}
+
+/// 取消客户端正在进行的传输, 由 [`Client::canceller`] 创建, 可以在其它线程中使用
+///
+/// 取消之后客户端的请求都返回 [`Error::Cancelled`], 直到调用 reset
+#[derive(Debug, Clone, Default)]
+pub struct Canceller(Arc<AtomicBool>);
+
+impl Canceller {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// 恢复正常, 之后的请求可以继续执行
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// 可以取消时, 每次从传输读取数据最多等待的时间
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct Client {
+    stream: Box<dyn Stream>,
+    need_reply: bool,
+    framer: Framer,
+    config: Config,
+    /// 读超时时间, 也是接收一个完整响应的最长时间
+    timeout: Duration,
+    write_timeout: Duration,
+    /// 当前传输的读超时时间, 接收静默间隔等场合会临时修改
+    stream_timeout: Duration,
+    cancel: Option<Canceller>,
+    timing: Option<RtuTiming>,
+    last_frame: Option<Instant>,
+    /// 上一个请求是否为广播
+    last_broadcast: bool,
+    turnaround_delay: Duration,
+    inter_request_delay: Duration,
+    reconnects: u32,
+    word_order: WordOrder,
+    swap_string_bytes: bool,
+    observer: Option<FrameObserver>,
+    wire_logging: Option<WireLogging>,
+    stats: Stats,
+    rate_limiter: Option<RateLimiter>,
+    /// 关闭时发送的写请求
+    safe_state: Vec<Function>,
+    closed: bool,
+}
+
+impl Client {
+    pub fn new(stream: Box<dyn Stream>) -> Result<Self> {
+        let timing = stream.baud_rate().map(RtuTiming::from_baud_rate);
+        Ok(Self {
+            stream,
+            need_reply: true,
+            framer: Framer::new(Protocol::Rtu),
+            config: Config::default(),
+            timeout: Duration::from_millis(5000),
+            write_timeout: Duration::from_millis(5000),
+            stream_timeout: Duration::from_millis(5000),
+            cancel: None,
+            timing,
+            last_frame: None,
+            last_broadcast: false,
+            turnaround_delay: DEFAULT_TURNAROUND_DELAY,
+            inter_request_delay: Duration::ZERO,
+            reconnects: 0,
+            word_order: WordOrder::default(),
+            swap_string_bytes: false,
+            observer: None,
+            wire_logging: None,
+            stats: Stats::default(),
+            rate_limiter: None,
+            safe_state: Vec::new(),
+            closed: false,
+        })
+    }
+
+    /// 创建 Modbus TCP 客户端, 使用 MBAP 报文头封装请求
+    pub fn new_tcp(stream: Box<dyn Stream>, config: Config) -> Result<Self> {
+        let mut client = Self::new(stream)?;
+        client.framer.protocol = Protocol::Tcp;
+        client.config = config;
+        Ok(client)
+    }
+
+    /// 使用 Config 创建客户端, 读写的超时时间分别设置为 read_timeout 和 write_timeout
+    pub fn with_config(stream: Box<dyn Stream>, config: Config) -> Result<Self> {
+        config.validate()?;
+        let mut client = Self::new(stream)?;
+        client.set_timeout(config.read_timeout)?;
+        client.set_write_timeout(config.write_timeout)?;
+        client.apply_config(config);
+        Ok(client)
+    }
+
+    pub(crate) fn apply_config(&mut self, config: Config) {
+        self.timeout = config.read_timeout;
+        self.write_timeout = config.write_timeout;
+        self.stream_timeout = config.read_timeout;
+        self.config = config;
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.framer.protocol
+    }
+
+    /// 设置传输帧格式, 例如只支持 Modbus ASCII 的设备使用 `Protocol::Ascii`
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.framer.protocol = protocol;
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// 默认的从设备ID, 即 [`Config::modbus_uid`]
+    pub fn unit_id(&self) -> Id {
+        self.config.modbus_uid
+    }
+
+    /// 设置默认的从设备ID, 通过 [`Client::unit`] 发送的请求使用这个ID
+    pub fn set_unit_id(&mut self, id: Id) {
+        self.config.modbus_uid = id;
+    }
+
+    /// 发送给默认从设备的句柄, 调用时不需要指定ID
+    pub fn unit(&mut self) -> UnitClient<'_> {
+        let id = self.unit_id();
+        UnitClient::new(self, id)
+    }
+
+    pub fn word_order(&self) -> WordOrder {
+        self.word_order
+    }
+
+    /// 设置 32/64 位数值在寄存器中的字节顺序, 默认为 `WordOrder::ABCD`
+    pub fn set_word_order(&mut self, word_order: WordOrder) {
+        self.word_order = word_order;
+    }
+
+    /// 设置字符串在寄存器中是否为低字节在前, 默认第一个字符在高字节
+    pub fn set_string_byte_swap(&mut self, swap_bytes: bool) {
+        self.swap_string_bytes = swap_bytes;
+    }
+
+    /// 读超时时间
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// 同时设置读写的超时时间
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.stream.set_timeout(timeout)?;
+        self.set_stream_read_timeout(timeout)?;
+        self.timeout = timeout;
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    /// 设置等待响应的超时时间
+    pub fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.set_stream_read_timeout(timeout)?;
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// 设置发送请求的超时时间, 传输不支持单独设置时忽略
+    pub fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.stream.set_write_timeout(timeout)?;
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    /// 设置发生传输错误 (串口拔出, TCP连接断开等) 后, 自动重新连接并重试请求的最大次数,
+    /// 默认为 0, 即不自动重连
+    pub fn set_auto_reconnect(&mut self, reconnects: u32) {
+        self.reconnects = reconnects;
+    }
+
+    /// 重新建立连接, 关闭之后也可以重新连接
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.stream.reconnect()?;
+        self.closed = false;
+        self.stream.set_timeout(self.timeout)?;
+        self.set_stream_read_timeout(self.timeout)?;
+        self.stream.set_write_timeout(self.write_timeout)
+    }
+
+    /// 设置关闭客户端时依次发送的写请求, 使设备进入安全状态, 例如停止电机, 关闭输出
+    ///
+    /// [`Client::close`] 和 drop 时发送. 进程被强制结束 (例如 SIGKILL) 时不会发送
+    pub fn set_safe_state(&mut self, requests: Vec<Function>) {
+        self.safe_state = requests;
+    }
+
+    /// 关闭客户端: 发送安全状态的写请求, 然后关闭传输, 立即释放串口或者断开连接
+    ///
+    /// 某个写请求失败时仍然发送其余的请求并关闭传输, 返回第一个错误.
+    /// 关闭之后的请求返回 NotConnected 错误, 直到调用 [`Client::reconnect`]
+    pub fn close(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        // 已经取消的请求不影响安全状态的发送
+        self.cancel = None;
+        let mut res = Ok(());
+        for fun in self.safe_state.clone() {
+            if let Err(e) = self.write(fun.clone()) {
+                log::warn!("发送安全状态 {:?} 失败, E: {}", fun, e);
+                res = res.and(Err(e));
+            }
+        }
+        self.closed = true;
+        res.and(self.stream.close())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// 取消传输的句柄, 用于在其它线程中中止阻塞的请求, 例如程序退出时
+    ///
+    /// 创建之后从传输读取数据时每次最多等待 50ms, 以便及时响应取消, 超时时间不变
+    pub fn canceller(&mut self) -> Result<Canceller> {
+        if let Some(cancel) = &self.cancel {
+            return Ok(cancel.clone());
+        }
+        let cancel = Canceller::default();
+        self.cancel = Some(cancel.clone());
+        self.set_stream_read_timeout(self.stream_timeout)?;
+        Ok(cancel)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(Canceller::is_cancelled)
+    }
+
+    /// 设置传输的读超时时间, 可以取消时分段等待
+    fn set_stream_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        let poll = match self.cancel {
+            Some(_) => timeout.min(CANCEL_POLL_INTERVAL),
+            None => timeout,
+        };
+        self.stream.set_read_timeout(poll)?;
+        self.stream_timeout = timeout;
+        Ok(())
+    }
+
+    /// 从传输读取数据, 可以取消时每等待一段时间检查一次是否已经取消
+    fn read_stream(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(cancel) = self.cancel.clone() else {
+            return self.stream.read(buf);
+        };
+        let deadline = Instant::now() + self.stream_timeout;
+        loop {
+            if cancel.is_cancelled() {
+                return Err(io::Error::other(Cancelled));
+            }
+            match self.stream.read(buf) {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) && Instant::now() < deadline => {}
+                res => return res,
+            }
+        }
+    }
+
+    /// 设置 RTU 帧的时间间隔, 默认根据串口的波特率计算, None 表示不控制帧间隔
+    pub fn set_rtu_timing(&mut self, timing: Option<RtuTiming>) {
+        self.timing = timing;
+    }
+
+    pub fn read_coils(
+        &mut self,
+        id: Id,
+        address: impl Into<CoilAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        let address = address.into().get();
+        let quantity = quantity.into();
+        let mut values = Vec::with_capacity(quantity.get() as usize);
+        for (address, quantity) in split_request(address, quantity, MAX_READ_COILS)? {
+            let bytes = self.read(Function::ReadCoils(id, address, quantity))?;
+            values.extend(unpack_reply_bits(&bytes, quantity.get())?);
+        }
+        Ok(values)
+    }
+
+    /// 读线圈到 out 中, out 的长度不能小于 quantity, 不需要为结果分配内存
+    pub fn read_coils_into(
+        &mut self,
+        id: Id,
+        address: impl Into<CoilAddress>,
+        quantity: impl Into<Quantity>,
+        out: &mut [Coil],
+    ) -> Result<()> {
+        let address = address.into().get();
+        self.read_bits_into(address, quantity.into(), out, |address, quantity| {
+            Function::ReadCoils(id, address, quantity)
+        })
+    }
+
+    pub fn read_discrete_inputs(
+        &mut self,
+        id: Id,
+        address: impl Into<DiscreteAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        let address = address.into().get();
+        let quantity = quantity.into();
+        let mut values = Vec::with_capacity(quantity.get() as usize);
+        for (address, quantity) in split_request(address, quantity, MAX_READ_COILS)? {
+            let bytes = self.read(Function::ReadDiscreteInputs(id, address, quantity))?;
+            values.extend(unpack_reply_bits(&bytes, quantity.get())?);
+        }
+        Ok(values)
+    }
+
+    /// 读离散输入到 out 中, out 的长度不能小于 quantity, 不需要为结果分配内存.
+    /// 数量超过 2000 时自动拆分为多个请求
+    pub fn read_discrete_inputs_into(
+        &mut self,
+        id: Id,
+        address: impl Into<DiscreteAddress>,
+        quantity: impl Into<Quantity>,
+        out: &mut [Coil],
+    ) -> Result<()> {
+        let address = address.into().get();
+        self.read_bits_into(address, quantity.into(), out, |address, quantity| {
+            Function::ReadDiscreteInputs(id, address, quantity)
+        })
+    }
+
+    /// 数量超过 125 时自动拆分为多个请求
+    pub fn read_holding_registers(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        let address = address.into().get();
+        let quantity = quantity.into();
+        let mut values = Vec::with_capacity(quantity.get() as usize);
+        for (address, quantity) in split_request(address, quantity, MAX_READ_REGISTERS)? {
+            let bytes = self.read(Function::ReadHoldingRegisters(id, address, quantity))?;
+            values.extend(pack_bytes(bytes)?);
+        }
+        Ok(values)
+    }
+
+    /// 读保持寄存器到 out 中, out 的长度不能小于 quantity, 不需要为结果分配内存
+    pub fn read_holding_registers_into(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        quantity: impl Into<Quantity>,
+        out: &mut [Word],
+    ) -> Result<()> {
+        let address = address.into().get();
+        self.read_words_into(address, quantity.into(), out, |address, quantity| {
+            Function::ReadHoldingRegisters(id, address, quantity)
+        })
+    }
+
+    /// 读输入寄存器到 out 中, out 的长度不能小于 quantity, 不需要为结果分配内存
+    pub fn read_input_registers_into(
+        &mut self,
+        id: Id,
+        address: impl Into<InputAddress>,
+        quantity: impl Into<Quantity>,
+        out: &mut [Word],
+    ) -> Result<()> {
+        let address = address.into().get();
+        self.read_words_into(address, quantity.into(), out, |address, quantity| {
+            Function::ReadInputRegisters(id, address, quantity)
+        })
+    }
+
+    fn read_words_into(
+        &mut self,
+        address: Address,
+        quantity: Quantity,
+        out: &mut [Word],
+        fun: impl Fn(Address, Quantity) -> Function,
+    ) -> Result<()> {
+        check_output_len(quantity, out.len())?;
+        let mut offset = 0;
+        for (address, quantity) in split_request(address, quantity, MAX_READ_REGISTERS)? {
+            let bytes = self.read(fun(address, quantity))?;
+            let len = quantity.get() as usize;
+            unpack_words_into(&bytes, &mut out[offset..offset + len])?;
+            offset += len;
+        }
+        Ok(())
+    }
+
+    fn read_bits_into(
+        &mut self,
+        address: Address,
+        quantity: Quantity,
+        out: &mut [Coil],
+        fun: impl Fn(Address, Quantity) -> Function,
+    ) -> Result<()> {
+        check_output_len(quantity, out.len())?;
+        let mut offset = 0;
+        for (address, quantity) in split_request(address, quantity, MAX_READ_COILS)? {
+            let bytes = self.read(fun(address, quantity))?;
+            let len = quantity.get() as usize;
+            unpack_reply_bits_into(&bytes, &mut out[offset..offset + len])?;
+            offset += len;
+        }
+        Ok(())
+    }
+
+    pub fn read_input_registers(
+        &mut self,
+        id: Id,
+        address: impl Into<InputAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        let address = address.into().get();
+        let quantity = quantity.into();
+        let mut values = Vec::with_capacity(quantity.get() as usize);
+        for (address, quantity) in split_request(address, quantity, MAX_READ_REGISTERS)? {
+            let bytes = self.read(Function::ReadInputRegisters(id, address, quantity))?;
+            values.extend(pack_bytes(bytes)?);
+        }
+        Ok(values)
+    }
+
+    pub fn write_single_register(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        value: Word,
+    ) -> Result<()> {
+        let address = address.into().get();
+        self.write(Function::WriteSingleRegister(id, address, value))
+    }
+
+    /// 数量超过 123 时自动拆分为多个请求, 其中一个请求失败时, 之前的请求已经写入
+    pub fn write_multiple_registers(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        values: Vec<Word>,
+    ) -> Result<()> {
+        let address = address.into().get();
+        let quantity = slice_quantity(values.len())?;
+        let mut values = &values[..];
+        for (address, quantity) in split_request(address, quantity, MAX_WRITE_REGISTERS)? {
+            let (chunk, rest) = values.split_at(quantity.get() as usize);
+            values = rest;
+            self.write(Function::WriteMultipleRegisters(
+                id,
+                address,
+                chunk.to_vec(),
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// 写入多个保持寄存器后读回同一范围, 读回的值与写入的不一致时返回 [`Error::Verification`]
+    pub fn write_multiple_registers_verified(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        values: &[Word],
+    ) -> Result<()> {
+        let address = address.into();
+        let quantity = slice_quantity(values.len())?;
+        self.write_multiple_registers(id, address, values.to_vec())?;
+        let actual = self.read_holding_registers(id, address, quantity)?;
+        match values.iter().zip(&actual).position(|(a, b)| a != b) {
+            Some(index) => Err(Error::Verification(VerificationError {
+                index,
+                expected: values[index],
+                actual: actual[index],
+            })),
+            None => Ok(()),
+        }
+    }
+
+    /// 读取几段保持寄存器, 保存为参数快照
+    pub fn snapshot(&mut self, id: Id, ranges: &[RangeInclusive<u16>]) -> Result<DeviceSnapshot> {
+        let mut blocks = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let quantity = range
+                .end()
+                .checked_sub(*range.start())
+                .and_then(|n| n.checked_add(1))
+                .ok_or_else(|| invalid_config(&format!("无效的地址范围: {:?}", range)))?;
+            blocks.push(SnapshotBlock {
+                address: *range.start(),
+                values: self.read_holding_registers(id, *range.start(), quantity)?,
+            });
+        }
+        Ok(DeviceSnapshot { blocks })
+    }
+
+    /// 把参数快照写回从设备, 可以是读取快照的设备, 也可以是同型号的其它设备
+    pub fn restore(&mut self, id: Id, snapshot: &DeviceSnapshot) -> Result<()> {
+        for block in snapshot
+            .blocks
+            .iter()
+            .filter(|block| !block.values.is_empty())
+        {
+            self.write_multiple_registers(id, block.address, block.values.clone())?;
+        }
+        Ok(())
+    }
+
+    /// 功能码 0x16, 由从设备按位修改寄存器, 避免客户端 读-改-写 时的竞争
+    pub fn mask_write_register(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        and_mask: Word,
+        or_mask: Word,
+    ) -> Result<()> {
+        let address = address.into().get();
+        self.write(Function::MaskWriteRegister(id, address, and_mask, or_mask))
+    }
+
+    /// 功能码 0x17, 先写入 values, 再读取保持寄存器, 两个操作在同一次传输中完成
+    pub fn read_write_multiple_registers(
+        &mut self,
+        id: Id,
+        read_address: impl Into<HoldingAddress>,
+        read_quantity: impl Into<Quantity>,
+        write_address: impl Into<HoldingAddress>,
+        values: &[Word],
+    ) -> Result<Vec<Word>> {
+        let bytes = self.read(Function::ReadWriteMultipleRegisters(
+            id,
+            read_address.into().get(),
+            read_quantity.into(),
+            write_address.into().get(),
+            values.to_vec(),
+        ))?;
+        pack_bytes(bytes)
+    }
+
+    /// 从保持寄存器读取一个数值, 按照设置的字节顺序解码
+    pub fn read_value<T: RegisterValue>(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+    ) -> Result<T> {
+        let words = self.read_holding_registers(id, address, T::WORDS as u16)?;
+        self.word_order.decode(&words)
+    }
+
+    /// 按照设置的字节顺序编码, 写入保持寄存器
+    pub fn write_value<T: RegisterValue>(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        value: T,
+    ) -> Result<()> {
+        let words = self.word_order.encode(value);
+        self.write_multiple_registers(id, address, words)
+    }
+
+    /// 从保持寄存器读取 byte_len 个字节的字符串, 去掉末尾填充的 0 和空格
+    pub fn read_string(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        byte_len: usize,
+    ) -> Result<String> {
+        let quantity = slice_quantity(byte_len.div_ceil(2))?;
+        let words = self.read_holding_registers(id, address, quantity)?;
+        Ok(value::unpack_string(
+            &words,
+            byte_len,
+            self.swap_string_bytes,
+        ))
+    }
+
+    /// 把字符串写入保持寄存器, 每个寄存器两个字节, 长度为奇数时末尾补 0
+    pub fn write_string(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        s: &str,
+    ) -> Result<()> {
+        let words = value::pack_string(s, self.swap_string_bytes);
+        self.write_multiple_registers(id, address, words)
+    }
+
+    /// 诊断功能 (功能码 0x08), 返回响应中的数据, 子功能码见 `diagnostics` 模块
+    pub fn diagnostics(&mut self, id: Id, sub_function: u16, data: Word) -> Result<Word> {
+        let reply = self.read_pdu(Function::Diagnostics(id, sub_function, data))?;
+        unpack_diagnostics(&reply, sub_function)
+    }
+
+    /// 回环测试, 检查从设备原样返回了请求的数据
+    pub fn loopback_test(&mut self, id: Id) -> Result<()> {
+        let data = self.diagnostics(id, diagnostics::RETURN_QUERY_DATA, LOOPBACK_DATA)?;
+        check_loopback(data)
+    }
+
+    /// 重启从设备的通信端口, clear_log 为 true 时同时清除通信事件日志
+    pub fn restart_communications(&mut self, id: Id, clear_log: bool) -> Result<()> {
+        let data = if clear_log { 0xFF00 } else { 0x0000 };
+        self.diagnostics(id, diagnostics::RESTART_COMMUNICATIONS, data)?;
+        Ok(())
+    }
+
+    /// 清除从设备的所有计数器和诊断寄存器
+    pub fn clear_counters(&mut self, id: Id) -> Result<()> {
+        self.diagnostics(id, diagnostics::CLEAR_COUNTERS, 0)?;
+        Ok(())
+    }
+
+    /// 读取计数器, counter 为 `diagnostics::BUS_MESSAGE_COUNT` 等子功能码
+    pub fn diagnostic_counter(&mut self, id: Id, counter: u16) -> Result<Word> {
+        self.diagnostics(id, counter, 0)
+    }
+
+    /// 读异常状态 (功能码 0x07), 返回 8 个异常状态位
+    pub fn read_exception_status(&mut self, id: Id) -> Result<u8> {
+        let reply = self.read_pdu(Function::ReadExceptionStatus(id))?;
+        match reply[..] {
+            [status] => Ok(status),
+            _ => Err(Error::InvalidResponse(Reason::UnexpectedReplySize)),
+        }
+    }
+
+    /// 获取通信事件计数 (功能码 0x0B)
+    pub fn get_comm_event_counter(&mut self, id: Id) -> Result<CommEventCounter> {
+        let reply = self.read_pdu(Function::GetCommEventCounter(id))?;
+        CommEventCounter::parse(&reply)
+    }
+
+    /// 获取通信事件日志 (功能码 0x0C)
+    pub fn get_comm_event_log(&mut self, id: Id) -> Result<CommEventLog> {
+        let bytes = self.read(Function::GetCommEventLog(id))?;
+        CommEventLog::parse(&bytes)
+    }
+
+    /// 报告从设备ID (功能码 0x11), 返回从设备ID, 运行状态和设备相关的附加数据
+    pub fn report_server_id(&mut self, id: Id) -> Result<ServerId> {
+        let bytes = self.read(Function::ReportServerId(id))?;
+        ServerId::parse(&bytes)
+    }
+
+    /// 读 FIFO 队列 (功能码 0x18), 返回队列中的数据, 最多 31 个
+    pub fn read_fifo_queue(
+        &mut self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+    ) -> Result<Vec<Word>> {
+        let address = address.into().get();
+        let reply = self.read_pdu(Function::ReadFifoQueue(id, address))?;
+        unpack_fifo(reply)
+    }
+
+    /// 读文件记录 (功能码 0x14), 按子请求的顺序返回读取到的记录
+    pub fn read_file_record(
+        &mut self,
+        id: Id,
+        requests: &[FileRecordRead],
+    ) -> Result<Vec<FileRecord>> {
+        let bytes = self.read(Function::ReadFileRecord(id, requests.to_vec()))?;
+        file_record::decode_read(&bytes, requests)
+    }
+
+    /// 写文件记录 (功能码 0x15)
+    pub fn write_file_record(&mut self, id: Id, records: &[FileRecord]) -> Result<()> {
+        self.write(Function::WriteFileRecord(id, records.to_vec()))
+    }
+
+    /// 读设备标识, 响应中设置了后续标志时会继续读取剩余的对象
+    pub fn read_device_identification(
+        &mut self,
+        id: Id,
+        category: DeviceIdCategory,
+    ) -> Result<DeviceIdentification> {
+        let mut identification = DeviceIdentification::default();
+        let mut object_id = 0;
+        loop {
+            let fun = Function::ReadDeviceIdentification(id, category.code(), object_id);
+            let data = self.read_pdu(fun)?;
+            match identification.parse(&data)? {
+                Some(next) if next > object_id => object_id = next,
+                Some(_) => return Err(Error::InvalidResponse(Reason::UnexpectedReplySize)),
+                None => return Ok(identification),
+            }
+        }
+    }
+
+    /// 扫描总线, 依次对 ids 中的每个从设备ID执行 probe, 返回有响应的ID
+    ///
+    /// 扫描期间使用较短的超时时间 timeout, 结束后恢复原来的设置.
+    /// 返回异常响应的设备也认为存在; 传输层出错 (例如串口被拔出) 时停止扫描
+    pub fn scan_bus(
+        &mut self,
+        ids: impl IntoIterator<Item = Id>,
+        timeout: Duration,
+        mut probe: impl FnMut(&mut Self, Id) -> Result<()>,
+    ) -> Result<Vec<Id>> {
+        let old_timeout = self.timeout;
+        self.set_read_timeout(timeout)?;
+        let mut found = Vec::new();
+        let mut res = Ok(());
+        for id in ids {
+            // 串行链路上的广播地址不会有响应
+            if id == BROADCAST_ID && self.framer.protocol != Protocol::Tcp {
+                continue;
+            }
+            match probe(self, id) {
+                Ok(_) | Err(Error::Exception(_)) => found.push(id),
+                Err(Error::Io(e)) => {
+                    res = Err(Error::Io(e));
+                    break;
+                }
+                Err(e) => log::debug!("扫描 ID: {}, 没有响应, E: {}", id, e),
+            }
+        }
+        self.set_read_timeout(old_timeout)?;
+        res.map(|_| found)
+    }
+
+    /// 扫描总线时默认的探测请求: 读 0 号保持寄存器
+    pub fn probe_holding_register(&mut self, id: Id) -> Result<()> {
+        self.read_holding_registers(id, 0u16, 1u16).map(|_| ())
+    }
+
+    /// 发送原始的请求 PDU (功能码 + 数据), 返回完整的响应 PDU. 广播请求返回空的数据
+    pub fn transact_pdu(&mut self, id: Id, pdu: &[u8]) -> Result<Bytes> {
+        let (req, mut reply) = self.framer.build_pdu(id, pdu)?;
+        self.transfer(&req, &mut reply, false)?;
+        if self.framer.is_broadcast(&req) {
+            return Ok(Bytes::new());
+        }
+        self.framer.strip_frame(reply.freeze())
+    }
+
+    /// 直接发送 frame, 不添加帧头. append_checksum 为 true 时在末尾加上校验值 (使用设置的校验算法)
+    ///
+    /// 用于协议调试, 与 `recv_raw` 配合使用. 仍然遵守帧间隔, 但是不检查内容, 不计入统计
+    pub fn send_raw(&mut self, frame: &[u8], append_checksum: bool) -> Result<()> {
+        let mut req = BytesMut::from(frame);
+        if append_checksum {
+            let checksum = self.framer.checksum.calc(frame);
+            req.put_slice(&checksum);
+        }
+        self.wait_frame_gap();
+        self.stream.write_all(&req)?;
+        self.stream.flush()?;
+        self.notify(Direction::Tx, &req, None);
+        self.last_frame = Some(Instant::now());
+        self.last_broadcast = false;
+        Ok(())
+    }
+
+    /// 接收原始的数据, 最多等待 timeout, 收到数据后继续接收直到静默 (RTU 的 t3.5 或者 20ms).
+    /// verify_checksum 为 true 时检查末尾的校验值, 返回的数据仍然包含校验值
+    pub fn recv_raw(&mut self, timeout: Duration, verify_checksum: bool) -> Result<Bytes> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        self.set_stream_read_timeout(timeout)?;
+        let mut reply = BytesMut::zeroed(MODBUS_ASCII_MAX_FRAME_SIZE);
+        let gap = self
+            .rtu_timing()
+            .map_or(DEFAULT_SILENCE_GAP, |timing| timing.frame_gap);
+        let res = self
+            .read_stream(&mut reply)
+            .and_then(|n| self.read_until_silence(&mut reply, n, gap));
+        self.set_stream_read_timeout(self.timeout)?;
+        reply.truncate(res?);
+        self.notify(Direction::Rx, &reply, None);
+        self.last_frame = Some(Instant::now());
+        if verify_checksum && !self.framer.checksum.verify(&reply) {
+            return Err(Error::Crc);
+        }
+        Ok(reply.freeze())
+    }
+
+    /// Modbus TCP 流水线: 最多同时发出 window 个请求, 按照事务标识匹配响应
+    ///
+    /// 返回每个请求的完整响应 PDU, 顺序与 requests 相同. 单元标识 0 也会等待响应.
+    /// 单个请求超时或者出错不影响其它请求, 传输层出错时返回错误
+    pub fn pipeline(
+        &mut self,
+        requests: Vec<Function>,
+        window: usize,
+    ) -> Result<Vec<Result<Bytes>>> {
+        if self.framer.protocol != Protocol::Tcp {
+            return Err(invalid_config("流水线只支持 Modbus TCP"));
+        }
+        let window = window.max(1);
+        self.stream.clear_input()?;
+        let mut results = (0..requests.len()).map(|_| None).collect::<Vec<_>>();
+        let mut requests = requests.into_iter().enumerate();
+        // 事务标识 => (序号, 请求, 发送时间)
+        let mut in_flight: HashMap<u16, (usize, Bytes, Instant)> = HashMap::new();
+        loop {
+            while in_flight.len() < window {
+                let Some((i, fun)) = requests.next() else {
+                    break;
+                };
+                let req = match self.framer.build_buffer(fun) {
+                    Ok((req, _)) => req,
+                    Err(e) => {
+                        results[i] = Some(Err(e));
+                        continue;
+                    }
+                };
+                if let Some(limiter) = &mut self.rate_limiter {
+                    limiter.acquire(self.framer.slave_id(&req).unwrap_or_default());
+                }
+                self.stream.write_all(&req)?;
+                self.stream.flush()?;
+                self.notify(Direction::Tx, &req, None);
+                let transaction_id = u16::from_be_bytes([req[0], req[1]]);
+                in_flight.insert(transaction_id, (i, req, Instant::now()));
+            }
+            if in_flight.is_empty() {
+                break;
+            }
+
+            let mut reply = BytesMut::new();
+            if let Err(e) = self.read_tcp_frame(&mut reply) {
+                let e = Error::from(e);
+                if !matches!(e, Error::Timeout) {
+                    return Err(e);
+                }
+                // 超时的请求之后收到的响应, 因为事务标识未知而被丢弃
+                for (_, (i, req, start)) in in_flight.drain() {
+                    let id = self.framer.slave_id(&req).unwrap_or_default();
+                    self.stats
+                        .record(id, &Err(Error::Timeout), true, start.elapsed());
+                    results[i] = Some(Err(Error::Timeout));
+                }
+                continue;
+            }
+            self.notify(Direction::Rx, &reply, None);
+            let transaction_id = u16::from_be_bytes([reply[0], reply[1]]);
+            let Some((i, req, start)) = in_flight.remove(&transaction_id) else {
+                log::warn!("丢弃未知事务标识的响应: {}", transaction_id);
+                continue;
+            };
+            let res = self.framer.validate_reply(&req, &mut reply);
+            let id = self.framer.slave_id(&req).unwrap_or_default();
+            self.stats.record(id, &res, true, start.elapsed());
+            results[i] = Some(res.and_then(|_| self.framer.strip_frame(reply.freeze())));
+        }
+        self.last_frame = Some(Instant::now());
+        Ok(results
+            .into_iter()
+            .map(|res| res.unwrap_or(Err(Error::Timeout)))
+            .collect())
+    }
+
+    /// 使用流水线读多段保持寄存器, 每段最多 125 个, 返回每一段的结果
+    pub fn read_holding_registers_pipelined(
+        &mut self,
+        id: Id,
+        ranges: &[(u16, u16)],
+        window: usize,
+    ) -> Result<Vec<Result<Vec<Word>>>> {
+        let mut requests = Vec::with_capacity(ranges.len());
+        for &(address, quantity) in ranges {
+            let quantity = Quantity::read_registers(quantity)?;
+            requests.push(Function::ReadHoldingRegisters(id, address, quantity));
+        }
+        let results = self.pipeline(requests, window)?;
+        Ok(results
+            .into_iter()
+            .zip(ranges)
+            .map(|(res, &(_, quantity))| {
+                // FUN(1) + 字节数(1) + 数据
+                let mut pdu = res?;
+                if pdu.len() != 2 + 2 * quantity as usize || pdu[1] as usize != pdu.len() - 2 {
+                    return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+                }
+                pack_bytes(pdu.split_off(2))
+            })
+            .collect())
+    }
+
+    /// 发送定制功能码的请求, 返回响应中功能码之后的数据, 广播请求返回空的数据
+    ///
+    /// `ReplyLength::ByteCount` 时返回的数据不包含字节数字段
+    pub fn custom(&mut self, req: CustomRequest) -> Result<Bytes> {
+        let reply_length = req.reply_length;
+        self.framer.custom_reply = reply_length;
+        let res = self.transact_custom(req);
+        self.framer.custom_reply = ReplyLength::Unknown;
+        let reply = res?;
+        if reply.is_empty() {
+            return Ok(reply);
+        }
+        match reply_length {
+            ReplyLength::ByteCount => self.framer.get_reply_data(reply),
+            ReplyLength::Fixed(_) | ReplyLength::Unknown => self.framer.get_reply_pdu(reply),
+        }
+    }
+
+    /// 调用可以复用的设备私有功能
+    pub fn call<F: CustomFunction>(&mut self, id: Id, function: &F) -> Result<F::Output> {
+        let data = self.custom(function.request(id))?;
+        function.parse_reply(&data)
+    }
+
+    /// 发送任意的请求, 按照功能码解析响应, 可以在它之上实现通用的中间层
+    ///
+    /// 数量超过协议限制时不会拆分请求, 异常响应返回 `Response::Exception`.
+    /// 广播的写请求, 以及不需要响应时的写请求, 返回根据请求生成的确认
+    pub fn request(&mut self, fun: Function) -> Result<Response> {
+        let res = match fun {
+            Function::Custom(req) => self.custom(req).map(Response::Custom),
+            fun => self.transact_function(fun),
+        };
+        match res {
+            Err(Error::Exception(code)) => Ok(Response::Exception(code)),
+            res => res,
+        }
+    }
+
+    fn transact_function(&mut self, fun: Function) -> Result<Response> {
+        let ack = Response::ack(&fun);
+        if ack.is_none() {
+            check_not_broadcast(self.framer.protocol, &fun)?;
+        }
+        let (req, mut reply) = self.framer.build_buffer(fun.clone())?;
+        self.transfer(&req, &mut reply, ack.is_some())?;
+        match ack {
+            Some(ack) if self.framer.is_broadcast(&req) || !self.need_reply => Ok(ack),
+            _ => Response::decode(&fun, self.framer.get_reply_pdu(reply.freeze())?),
+        }
+    }
+
+    /// 返回完整的响应帧, 广播请求返回空的数据
+    fn transact_custom(&mut self, req: CustomRequest) -> Result<Bytes> {
+        let (req, mut reply) = self.framer.build_buffer(Function::Custom(req))?;
+        self.transfer(&req, &mut reply, false)?;
+        if self.framer.is_broadcast(&req) {
+            return Ok(Bytes::new());
+        }
+        Ok(reply.freeze())
+    }
+
+    pub fn set_need_reply(&mut self, need_reply: bool) {
+        self.need_reply = need_reply;
+    }
+
+    /// 设置串行链路上广播请求之后的等待时间, 让所有从设备处理完广播请求, 默认为 100ms
+    pub fn set_turnaround_delay(&mut self, delay: Duration) {
+        self.turnaround_delay = delay;
+    }
+
+    /// 设置上一次传输结束到下一个请求之间的最小间隔, 用于响应之后需要一段时间才能接收请求的慢速设备,
+    /// 默认为 0
+    pub fn set_inter_request_delay(&mut self, delay: Duration) {
+        self.inter_request_delay = delay;
+    }
+
+    /// 设置响应检查的严格程度, 默认为 [`ValidationMode::Strict`]
+    pub fn set_validation_mode(&mut self, mode: ValidationMode) {
+        self.framer.validation = mode;
+    }
+
+    pub fn validation_mode(&self) -> ValidationMode {
+        self.framer.validation
+    }
+
+    /// 设置帧校验算法, 默认为 CRC-16/MODBUS
+    pub fn set_checksum(&mut self, checksum: Box<dyn Checksum>) {
+        self.framer.checksum = checksum;
+    }
+
+    /// 设置帧的观察者, 发送的每个请求和收到的每个响应都会通知它
+    pub fn set_frame_observer(&mut self, observer: impl Fn(&FrameEvent) + Send + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    pub fn clear_frame_observer(&mut self) {
+        self.observer = None;
+    }
+
+    /// 开启或关闭线路日志: 以 DEBUG 级别输出每一帧的十六进制数据, log target 为 `simple_modbus::wire`
+    pub fn set_wire_logging(&mut self, enabled: bool) {
+        self.wire_logging = enabled.then(WireLogging::default);
+    }
+
+    /// 开启线路日志, 发送和接收分别使用指定的日志级别
+    pub fn set_wire_log_levels(&mut self, tx: log::Level, rx: log::Level) {
+        self.wire_logging = Some(WireLogging { tx, rx });
+    }
+
+    /// 通知帧观察者, 开启了线路日志时输出日志. elapsed 为发送请求到收到响应的时间
+    fn notify(&self, direction: Direction, data: &[u8], elapsed: Option<Duration>) {
+        trace::notify(&self.observer, direction, data);
+        if let Some(logging) = self.wire_logging {
+            let level = match direction {
+                Direction::Tx => logging.tx,
+                Direction::Rx => logging.rx,
+            };
+            let id = self.framer.slave_id(data);
+            let function_code = self.framer.function_code(data);
+            trace::log_frame(level, direction, id, function_code, elapsed, data);
+        }
+    }
+
+    /// 通信统计
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// 清零通信统计
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// 设置请求的速率限制, None 表示不限制. 默认不限制
+    pub fn set_rate_limiter(&mut self, limiter: Option<RateLimiter>) {
+        self.rate_limiter = limiter;
+    }
+
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// 按照 MBAP报文头 中的长度字段接收一个完整的帧
+    fn read_tcp_frame(&mut self, reply: &mut BytesMut) -> std::io::Result<usize> {
+        let deadline = Instant::now() + self.timeout;
+        reply.resize(MBAP_HEADER_SIZE, 0);
+        self.read_full(&mut reply[..], deadline)?;
+        reply.resize(Framer::tcp_frame_size(reply), 0);
+        self.read_full(&mut reply[MBAP_HEADER_SIZE..], deadline)?;
+        Ok(reply.len())
+    }
+
+    /// 读满 buf, 一帧分多次到达时继续读取, 直到超过 deadline
+    fn read_full(&mut self, mut buf: &mut [u8], deadline: Instant) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_stream(buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => buf = &mut buf[n..],
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+            if !buf.is_empty() && Instant::now() >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "接收完整的帧超时"));
+            }
+        }
+        Ok(())
+    }
+
+    /// 接收与请求的事务标识一致的 TCP 帧, 丢弃之前超时的请求的响应
+    fn read_tcp_reply(&mut self, req: &[u8], reply: &mut BytesMut) -> std::io::Result<usize> {
+        loop {
+            let n = self.read_tcp_frame(reply)?;
+            if self.framer.is_reply_to(req, reply) {
+                return Ok(n);
+            }
+            self.notify(Direction::Rx, reply, None);
+            log::warn!(
+                "丢弃过期的响应, 事务标识: {}",
+                u16::from_be_bytes([reply[0], reply[1]])
+            );
+        }
+    }
+
+    /// 根据功能码和字节数字段接收一个完整的 RTU 帧
+    ///
+    /// 无法确定长度的功能码 (例如 `ReplyLength::Unknown` 的定制请求), 接收数据直到总线静默
+    fn read_rtu_frame(&mut self, reply: &mut BytesMut) -> std::io::Result<usize> {
+        let deadline = Instant::now() + self.timeout;
+        let hint = reply.len();
+        reply.clear();
+        loop {
+            let len = reply.len();
+            match self.framer.rtu_remaining(reply) {
+                Some(0) => return Ok(len),
+                Some(n) => {
+                    reply.resize(len + n, 0);
+                    self.read_full(&mut reply[len..], deadline)?;
+                }
+                None => {
+                    reply.resize(hint.max(len + 1), 0);
+                    let n = len + self.read_stream(&mut reply[len..])?;
+                    let gap = self
+                        .rtu_timing()
+                        .map_or(DEFAULT_SILENCE_GAP, |timing| timing.frame_gap);
+                    return self.read_until_silence(reply, n, gap);
+                }
+            }
+        }
+    }
+
+    /// 继续接收数据, 直到总线静默的时间超过 gap, 低波特率下一帧可能分多次到达
+    fn read_until_silence(
+        &mut self,
+        reply: &mut BytesMut,
+        mut n: usize,
+        gap: Duration,
+    ) -> io::Result<usize> {
+        if self.set_stream_read_timeout(gap).is_err() {
+            return Ok(n);
+        }
+        reply.resize(reply.len().max(MODBUS_MAX_PACKET_SIZE), 0);
+        let res = loop {
+            if n >= reply.len() {
+                break Ok(n);
+            }
+            match self.read_stream(&mut reply[n..]) {
+                Ok(0) => break Ok(n),
+                Ok(k) => n += k,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    break Ok(n)
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        let _ = self.set_stream_read_timeout(self.timeout);
+        res
+    }
+
+    /// 只有 RTU 模式需要控制帧间隔
+    fn rtu_timing(&self) -> Option<RtuTiming> {
+        match self.framer.protocol {
+            Protocol::Rtu => self.timing,
+            _ => None,
+        }
+    }
+
+    /// 保证与上一帧之间至少有 t3.5 的静默间隔 和 inter_request_delay,
+    /// 串行链路上广播之后还要等待 turnaround_delay
+    fn wait_frame_gap(&self) {
+        let Some(last_frame) = self.last_frame else {
+            return;
+        };
+        let mut gap = self
+            .rtu_timing()
+            .map(|timing| timing.frame_gap)
+            .unwrap_or_default()
+            .max(self.inter_request_delay);
+        if self.last_broadcast && self.framer.protocol != Protocol::Tcp {
+            gap = gap.max(self.turnaround_delay);
+        }
+        let elapsed = last_frame.elapsed();
+        if elapsed < gap {
+            std::thread::sleep(gap - elapsed);
+        }
+    }
+
+    /// 接收一个 Modbus ASCII 帧, 直到收到 LF
+    fn read_ascii_frame(&mut self, reply: &mut BytesMut) -> std::io::Result<usize> {
+        let deadline = Instant::now() + self.timeout;
+        reply.clear();
+        let mut b = [0u8; 1];
+        loop {
+            self.read_full(&mut b, deadline)?;
+            reply.put_u8(b[0]);
+            if Framer::ascii_complete(reply) {
+                return Ok(reply.len());
+            }
+        }
+    }
+
+    fn transfer(&mut self, req: &Bytes, reply: &mut BytesMut, write: bool) -> Result<()> {
+        if self.closed {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "客户端已关闭",
+            )));
+        }
+        let mut reconnects = self.reconnects;
+        loop {
+            if self.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let id = self.framer.slave_id(req).unwrap_or_default();
+            if let Some(limiter) = &mut self.rate_limiter {
+                limiter.acquire(id);
+            }
+            self.wait_frame_gap();
+            let start = Instant::now();
+            let res = self.exchange(req, reply, write);
+            let expect_reply = self.framer.expects_reply(req, write, self.need_reply);
+            self.stats.record(id, &res, expect_reply, start.elapsed());
+            if let Some(limiter) = &mut self.rate_limiter {
+                limiter.record_busy(start.elapsed());
+            }
+            self.last_frame = Some(Instant::now());
+            self.last_broadcast = self.framer.is_broadcast(req);
+            match res {
+                Err(Error::Io(e)) if reconnects > 0 => {
+                    reconnects -= 1;
+                    log::warn!("传输异常, 重新连接, E: {}", e);
+                    if let Err(e) = self.reconnect() {
+                        log::warn!("重新连接失败, E: {}", e);
+                    }
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn exchange(&mut self, req: &Bytes, reply: &mut BytesMut, write: bool) -> Result<()> {
+        self.stream.clear_input()?;
+        match self.stream.write_all(req) {
+            Ok(_) => {
+                if let Err(e) = self.stream.flush() {
+                    return Err(e.into());
+                }
+                let sent = Instant::now();
+                self.notify(Direction::Tx, req, None);
+                // 写操作 且设置为 不响应, 或者是广播请求
+                if !self.framer.expects_reply(req, write, self.need_reply) {
+                    return Ok(());
+                }
+
+                let n = match self.framer.protocol {
+                    Protocol::Rtu => self.read_rtu_frame(reply),
+                    Protocol::Tcp => self.read_tcp_reply(req, reply),
+                    Protocol::Ascii => self.read_ascii_frame(reply),
+                };
+                match n {
+                    Ok(n) => {
+                        reply.truncate(n);
+                        // log::info!("reply: {:?}", &reply);
+                        self.notify(Direction::Rx, reply, Some(sent.elapsed()));
+                        self.framer.validate_reply(req, reply)?;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, fun: Function) -> Result<Bytes> {
+        check_not_broadcast(self.framer.protocol, &fun)?;
+        let (req, mut reply) = self.framer.build_buffer(fun)?;
+        self.transfer(&req, &mut reply, false)?;
+        self.framer.get_reply_data(reply.freeze())
+    }
+
+    /// 不包含字节数字段的响应, 返回功能码之后的数据
+    fn read_pdu(&mut self, fun: Function) -> Result<Bytes> {
+        check_not_broadcast(self.framer.protocol, &fun)?;
+        let (req, mut reply) = self.framer.build_buffer(fun)?;
+        self.transfer(&req, &mut reply, false)?;
+        self.framer.get_reply_pdu(reply.freeze())
+    }
+
+    fn write(&mut self, fun: Function) -> Result<()> {
+        let (req, mut reply) = self.framer.build_buffer(fun)?;
+        self.transfer(&req, &mut reply, true)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            log::warn!("关闭客户端失败, E: {}", e);
+        }
+    }
+}
+
+/// 串行链路上广播请求之后默认的等待时间
+pub(crate) const DEFAULT_TURNAROUND_DELAY: Duration = Duration::from_millis(100);
+
+/// 没有波特率 (例如 RTU over TCP) 时, 判断长度未知的响应已经结束的静默时间
+pub(crate) const DEFAULT_SILENCE_GAP: Duration = Duration::from_millis(20);
+
+/// FIFO 队列中最多的数据数量
+const MAX_FIFO_COUNT: usize = 31;
+
+/// FIFO 响应: 字节数(2) + FIFO数量(2) + 数据, 字节数包含 FIFO数量 字段
+pub(crate) fn unpack_fifo(mut reply: Bytes) -> Result<Vec<Word>> {
+    if reply.len() < 4 {
+        return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+    }
+    let byte_cnt = reply.get_u16() as usize;
+    let fifo_cnt = reply.get_u16() as usize;
+    if fifo_cnt > MAX_FIFO_COUNT || byte_cnt != 2 + 2 * fifo_cnt || reply.len() != 2 * fifo_cnt {
+        return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+    }
+    Ok((0..fifo_cnt).map(|_| reply.get_u16()).collect())
+}
+
+/// 回环测试使用的数据
+pub(crate) const LOOPBACK_DATA: Word = 0xA55A;
+
+/// 诊断响应: 子功能码(2) + 数据(2)
+pub(crate) fn unpack_diagnostics(reply: &[u8], sub_function: u16) -> Result<Word> {
+    if reply.len() != 4 {
+        return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+    }
+    if u16::from_be_bytes([reply[0], reply[1]]) != sub_function {
+        return Err(Error::InvalidResponse(Reason::UnexpectedEcho));
+    }
+    Ok(u16::from_be_bytes([reply[2], reply[3]]))
+}
+
+pub(crate) fn check_loopback(data: Word) -> Result<()> {
+    if data != LOOPBACK_DATA {
+        return Err(Error::InvalidResponse(Reason::UnexpectedEcho));
+    }
+    Ok(())
+}
+
+/// 把大端的字节数据转换为寄存器, 其它字节顺序见 [`endian`]
+pub fn pack_bytes(bytes: Bytes) -> Result<Vec<u16>> {
+    endian::words_from_bytes(&bytes, ByteOrder::Big)
+}
+
+/// 把寄存器转换为大端的字节数据, 其它字节顺序见 [`endian`]
+pub fn unpack_bytes(data: &[u16]) -> Vec<u8> {
+    endian::bytes_from_words(data, ByteOrder::Big)
+}
+
+pub fn pack_bits(bits: &[Coil]) -> Vec<u8> {
+    let bitcount = bits.len();
+    let packed_size = bitcount.div_ceil(8);
+    let mut res = vec![0; packed_size];
+    for (i, b) in bits.iter().enumerate() {
+        let v = match *b {
+            Coil::On => 1u8,
+            Coil::Off => 0u8,
+        };
+        res[i / 8] |= v << (i % 8);
+    }
+    res
+}
+
+/// 保存 count 个位需要的字节数
+pub(crate) fn bit_bytes(count: u16) -> usize {
+    (count as usize).div_ceil(8)
+}
+
+/// 解析响应中的位数据, 检查字节数与请求的数量是否一致
+pub(crate) fn unpack_reply_bits(bytes: &[u8], count: u16) -> Result<Vec<Coil>> {
+    if bytes.len() != bit_bytes(count) {
+        return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+    }
+    Ok(unpack_bits(bytes, count))
+}
+
+/// 解析响应中的位数据到 out 中, 字节数必须与 out 的长度一致
+fn unpack_reply_bits_into(bytes: &[u8], out: &mut [Coil]) -> Result<()> {
+    if bytes.len() != out.len().div_ceil(8) {
+        return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+    }
+    for (i, bit) in out.iter_mut().enumerate() {
+        *bit = Coil::from((bytes[i / 8] >> (i % 8)) & 0b1 > 0);
+    }
+    Ok(())
+}
+
+/// 解析响应中的寄存器数据到 out 中, 字节数必须为 out 长度的 2 倍
+fn unpack_words_into(bytes: &[u8], out: &mut [Word]) -> Result<()> {
+    if bytes.len() != out.len() * 2 {
+        return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+    }
+    for (word, b) in out.iter_mut().zip(bytes.chunks_exact(2)) {
+        *word = u16::from_be_bytes([b[0], b[1]]);
+    }
+    Ok(())
+}
+
+fn check_output_len(quantity: Quantity, len: usize) -> Result<()> {
+    let quantity = quantity.get();
+    if len < quantity as usize {
+        return Err(Error::InvalidData(Reason::OutputBufferTooSmall {
+            quantity,
+            len,
+        }));
+    }
+    Ok(())
+}
+
+pub fn unpack_bits(bytes: &[u8], count: u16) -> Vec<Coil> {
+    let mut res = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        if (bytes[(i / 8u16) as usize] >> (i % 8)) & 0b1 > 0 {
+            res.push(Coil::On);
+        } else {
+            res.push(Coil::Off);
+        }
+    }
+    res
+}
+
+/// Single bit status values, used in read or write coil functions
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Coil {
+    On,
+    Off,
+}
+
+impl Coil {
+    pub(crate) fn code(self) -> u16 {
+        match self {
+            Coil::On => 0xff00,
+            Coil::Off => 0x0000,
+        }
+    }
+}
+
+impl From<bool> for Coil {
+    fn from(b: bool) -> Coil {
+        if b {
+            Coil::On
+        } else {
+            Coil::Off
+        }
+    }
+}
+
+impl std::ops::Not for Coil {
+    type Output = Coil;
+
+    fn not(self) -> Coil {
+        match self {
+            Coil::On => Coil::Off,
+            Coil::Off => Coil::On,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bank::RegisterBank, calc_crc, error::ExceptionCode, mock::MockStream};
+
+    fn mock_client(bank: &Arc<RegisterBank>) -> (Client, MockStream) {
+        let stream = MockStream::with_bank(bank.clone(), Protocol::Rtu);
+        let client = Client::new(Box::new(stream.clone())).unwrap();
+        (client, stream)
+    }
+
+    #[test]
+    fn write_too_many_registers_is_rejected() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 16));
+        let (mut client, stream) = mock_client(&bank);
+        let values = vec![0; u16::MAX as usize + 1];
+        for res in [
+            client.write_multiple_registers(1, 0, values.clone()),
+            client.write_multiple_registers_verified(1, 0, &values),
+        ] {
+            assert!(matches!(
+                res,
+                Err(Error::InvalidRequest(Reason::QuantityOutOfRange { .. }))
+            ));
+        }
+        assert!(stream.requests().is_empty());
+    }
+
+    #[test]
+    fn read_too_long_string_is_rejected() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 16));
+        let (mut client, stream) = mock_client(&bank);
+        let res = client.read_string(1, 0, 2 * u16::MAX as usize + 1);
+        assert!(matches!(
+            res,
+            Err(Error::InvalidRequest(Reason::QuantityOutOfRange { .. }))
+        ));
+        assert!(stream.requests().is_empty());
+    }
+
+    /// 每个请求的 (功能码, 起始地址, 数量)
+    fn request_ranges(stream: &MockStream) -> Vec<(u8, u16, u16)> {
+        stream
+            .requests()
+            .iter()
+            .map(|req| {
+                let word = |i: usize| u16::from_be_bytes([req[i], req[i + 1]]);
+                (req[1], word(2), word(4))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn large_reads_are_split() {
+        let bank = Arc::new(RegisterBank::new(4100, 0, 0, 400));
+        let values: Vec<Word> = (0..300).collect();
+        bank.set_holding_registers(10, &values).unwrap();
+        let (mut client, stream) = mock_client(&bank);
+        client.set_timeout(Duration::from_millis(500)).unwrap();
+
+        assert_eq!(client.read_holding_registers(1, 10, 300).unwrap(), values);
+        let mut out = vec![0; 300];
+        client
+            .read_holding_registers_into(1, 10, 300, &mut out)
+            .unwrap();
+        assert_eq!(out, values);
+        let expect = [(0x03, 10, 125), (0x03, 135, 125), (0x03, 260, 50)];
+        assert_eq!(request_ranges(&stream), [expect, expect].concat());
+
+        let coils: Vec<Coil> = (0..4100).map(|i| Coil::from(i % 3 == 0)).collect();
+        bank.set_coils(0, &coils).unwrap();
+        let (mut client, stream) = mock_client(&bank);
+        assert_eq!(client.read_coils(1, 0, 4100).unwrap(), coils);
+        assert_eq!(
+            request_ranges(&stream),
+            [(0x01, 0, 2000), (0x01, 2000, 2000), (0x01, 4000, 100)]
+        );
+    }
+
+    #[test]
+    fn large_writes_are_split() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 300));
+        let (mut client, stream) = mock_client(&bank);
+        client.set_timeout(Duration::from_millis(500)).unwrap();
+
+        let values: Vec<Word> = (0..250).map(|i| i * 3).collect();
+        client
+            .write_multiple_registers(1, 20, values.clone())
+            .unwrap();
+        assert_eq!(bank.get_holding_registers(20, 250).unwrap(), values);
+        assert_eq!(
+            request_ranges(&stream),
+            [(0x10, 20, 123), (0x10, 143, 123), (0x10, 266, 4)]
+        );
+    }
+
+    #[test]
+    fn split_request_checks_address_overflow() {
+        assert!(matches!(
+            split_request(0xFFFF, 2.into(), MAX_READ_REGISTERS),
+            Err(Error::InvalidRequest(Reason::AddressOverflow { .. }))
+        ));
+        assert_eq!(
+            split_request(0xFF00, 256.into(), MAX_READ_REGISTERS).unwrap(),
+            [
+                (0xFF00, 125.into()),
+                (0xFF7D, 125.into()),
+                (0xFFFA, 6.into())
+            ]
+        );
+    }
+
+    fn tcp_client(bank: &Arc<RegisterBank>) -> (Client, MockStream) {
+        let stream = MockStream::with_bank(bank.clone(), Protocol::Tcp);
+        let mut client = Client::new_tcp(Box::new(stream.clone()), Config::default()).unwrap();
+        client.set_timeout(Duration::from_millis(500)).unwrap();
+        (client, stream)
+    }
+
+    #[test]
+    fn pipeline_returns_results_in_order() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 16));
+        bank.set_holding_registers(0, &(0..16).collect::<Vec<_>>())
+            .unwrap();
+        let (mut client, stream) = tcp_client(&bank);
+
+        let ranges = [(0, 2), (4, 1), (8, 3), (15, 1), (1, 1)];
+        let results = client
+            .read_holding_registers_pipelined(1, &ranges, 2)
+            .unwrap();
+        let results: Vec<_> = results.into_iter().map(|res| res.unwrap()).collect();
+        assert_eq!(
+            results,
+            [vec![0, 1], vec![4], vec![8, 9, 10], vec![15], vec![1]]
+        );
+
+        // 每个请求使用不同的事务标识
+        let mut ids: Vec<_> = stream
+            .requests()
+            .iter()
+            .map(|req| u16::from_be_bytes([req[0], req[1]]))
+            .collect();
+        ids.dedup();
+        assert_eq!(ids.len(), ranges.len());
+    }
+
+    #[test]
+    fn pipeline_errors_are_per_request() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 16));
+        let (mut client, stream) = tcp_client(&bank);
+
+        let results = client
+            .pipeline(
+                vec![
+                    Function::WriteSingleRegister(1, 0, 0x1234),
+                    // 编码失败, 不发送
+                    Function::WriteMultipleRegisters(1, 0, vec![0; 200]),
+                    // 地址超出范围, 异常响应
+                    Function::ReadHoldingRegisters(1, 20, 1.into()),
+                    Function::ReadHoldingRegisters(1, 0, 1.into()),
+                ],
+                3,
+            )
+            .unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            &results[0].as_ref().unwrap()[..],
+            [0x06, 0x00, 0x00, 0x12, 0x34]
+        );
+        assert!(matches!(
+            results[1],
+            Err(Error::InvalidRequest(Reason::QuantityOutOfRange { .. }))
+        ));
+        assert!(matches!(
+            results[2],
+            Err(Error::Exception(ExceptionCode::IllegalDataAddress))
+        ));
+        assert_eq!(&results[3].as_ref().unwrap()[..], [0x03, 0x02, 0x12, 0x34]);
+        assert_eq!(stream.requests().len(), 3);
+    }
+
+    #[test]
+    fn pipeline_requires_tcp() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 16));
+        let (mut client, stream) = mock_client(&bank);
+        let res = client.pipeline(vec![Function::ReadHoldingRegisters(1, 0, 1.into())], 2);
+        assert!(matches!(
+            res,
+            Err(Error::InvalidData(Reason::InvalidConfig(_)))
+        ));
+        assert!(stream.requests().is_empty());
+    }
+
+    #[test]
+    fn write_servo_parameters() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 0x20));
+        let (mut client, stream) = mock_client(&bank);
+        client.set_timeout(Duration::from_millis(500)).unwrap();
+
+        let pos: u32 = 0x0001_86A0;
+        for id in [15, 16] {
+            // 使能, 速度, 加速度, 目标位置 (低 16 位在前)
+            client.write_single_register(id, 0x0000, 0x01).unwrap();
+            client.write_single_register(id, 0x0002, 1000).unwrap();
+            client.write_single_register(id, 0x0003, 2000).unwrap();
+            client
+                .write_multiple_registers(
+                    id,
+                    0x0016,
+                    vec![(pos & 0xffff) as u16, (pos >> 16) as u16],
+                )
+                .unwrap();
+            assert_eq!(
+                client.read_holding_registers(id, 0x0016, 2).unwrap(),
+                [0x86A0, 0x0001]
+            );
+        }
+        assert_eq!(
+            bank.get_holding_registers(0x0000, 4).unwrap(),
+            [1, 0, 1000, 2000]
+        );
+
+        let requests = stream.requests();
+        assert_eq!(requests.len(), 10);
+        assert_eq!(&requests[0][..6], [15, 0x06, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(
+            &requests[8][..11],
+            [16, 0x10, 0x00, 0x16, 0x00, 0x02, 0x04, 0x86, 0xA0, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn custom_function_change_address() {
+        let stream = MockStream::new();
+        let mut client = Client::new(Box::new(stream.clone())).unwrap();
+        client.set_timeout(Duration::from_millis(500)).unwrap();
+
+        // 使用功能码 0x7A 修改设备地址: ID 0x7A 0x00 0x00 新地址 CRC
+        let mut reply = vec![15, 0x7A, 0x02];
+        reply.extend_from_slice(&calc_crc(&reply).to_be_bytes());
+        stream.push_reply(&reply);
+
+        let req = CustomRequest::new(15, 0x7A)
+            .payload([0x00, 0x00, 2])
+            .reply_length(ReplyLength::Fixed(1));
+        assert_eq!(&client.custom(req).unwrap()[..], [0x02]);
+        assert_eq!(&stream.requests()[0][..5], [15, 0x7A, 0x00, 0x00, 0x02]);
+    }
+
+    #[test]
+    fn tcp_unit_id_zero_is_not_broadcast() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 16));
+        let stream = MockStream::with_bank(bank.clone(), Protocol::Tcp);
+        let mut client = Client::new_tcp(Box::new(stream.clone()), Config::default()).unwrap();
+        client.set_timeout(Duration::from_millis(500)).unwrap();
+
+        client.write_single_register(0, 0x0002, 0x1234).unwrap();
+        assert_eq!(
+            client.read_holding_registers(0, 0x0002, 1).unwrap(),
+            [0x1234]
+        );
+        assert_eq!(
+            &client
+                .transact_pdu(0, &[0x03, 0x00, 0x02, 0x00, 0x01])
+                .unwrap()[..],
+            [0x03, 0x02, 0x12, 0x34]
+        );
+        assert_eq!(bank.get_holding_registers(0x0002, 1).unwrap(), [0x1234]);
+        assert_eq!(stream.requests().len(), 3);
+    }
+}
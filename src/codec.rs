@@ -0,0 +1,222 @@
+//! 与传输方式无关的帧编码和解码, 只依赖 `core`, 不需要分配内存
+//!
+//! 关闭默认的 `std` feature 时只有这个模块可用, 可以在单片机上配合 embedded-hal 等串口驱动使用.
+//! `Client` 的帧校验也建立在这个模块之上.
+//!
+//! 开启 `std` feature 时还提供基于 `Function` 的完整帧编解码: [`encode_request`], [`decode_request`],
+//! [`decode_response`] 和 [`decode_frame`], 可以用于实现抓包工具, 模糊测试或者其它的传输方式
+
+use core::fmt;
+
+use crate::{crc, Id, MBAP_HEADER_SIZE, MODBUS_MAX_PDU_SIZE};
+
+#[cfg(feature = "std")]
+mod message;
+
+#[cfg(feature = "std")]
+pub use message::{
+    decode_frame, decode_request, decode_response, encode_request, Frame, Request, Response,
+};
+
+/// 串行链路上的广播地址, 所有从设备都会执行广播的写请求, 但是不会响应
+pub const BROADCAST_ID: Id = 0;
+
+/// 一次最多读取的寄存器数量 (功能码 0x03, 0x04)
+pub const MAX_READ_REGISTERS: u16 = 125;
+
+/// 一次最多写入的寄存器数量 (功能码 0x10)
+pub const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// 读写多个寄存器时, 一次最多写入的寄存器数量 (功能码 0x17)
+pub const MAX_READ_WRITE_REGISTERS: u16 = 121;
+
+/// 一次最多读取的线圈/离散输入数量 (功能码 0x01, 0x02)
+pub const MAX_READ_COILS: u16 = 2000;
+
+/// 一次最多写入的线圈数量 (功能码 0x0F)
+pub const MAX_WRITE_COILS: u16 = 1968;
+
+/// 编码和解码的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecError {
+    /// 输出缓冲区太小
+    BufferTooSmall,
+    /// PDU 为空
+    EmptyPdu,
+    /// PDU 超过 253 字节
+    PduTooBig,
+    /// 帧不完整
+    ShortFrame,
+    /// CRC 校验错误
+    Crc,
+    /// MBAP报文头 中的协议标识不是 0
+    InvalidProtocolId,
+    /// MBAP报文头 中的长度字段与帧的长度不一致
+    LengthMismatch,
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let desc = match self {
+            CodecError::BufferTooSmall => "缓冲区太小",
+            CodecError::EmptyPdu => "PDU 为空",
+            CodecError::PduTooBig => "PDU 太长",
+            CodecError::ShortFrame => "帧不完整",
+            CodecError::Crc => "CRC 校验错误",
+            CodecError::InvalidProtocolId => "无效的协议标识",
+            CodecError::LengthMismatch => "长度字段与帧长度不一致",
+        };
+        write!(f, "{}", desc)
+    }
+}
+
+/// 计算 CRC-16/MODBUS, 返回值按大端写入帧中即为规范要求的 低字节在前
+///
+/// 与 `crc::crc16` 的结果高低字节相反
+pub fn calc_crc(data: &[u8]) -> u16 {
+    crc::crc16(data).swap_bytes()
+}
+
+/// 计算 Modbus ASCII 使用的 LRC: 所有字节累加后取二进制补码
+pub fn calc_lrc(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |sum, x| sum.wrapping_add(*x))
+        .wrapping_neg()
+}
+
+fn check_pdu(pdu: &[u8]) -> Result<(), CodecError> {
+    if pdu.is_empty() {
+        return Err(CodecError::EmptyPdu);
+    }
+    if pdu.len() > MODBUS_MAX_PDU_SIZE {
+        return Err(CodecError::PduTooBig);
+    }
+    Ok(())
+}
+
+/// 把 PDU 封装为 RTU 帧: 从设备ID(1) + PDU + CRC(2), 返回帧的长度
+pub fn encode_rtu(id: Id, pdu: &[u8], buf: &mut [u8]) -> Result<usize, CodecError> {
+    check_pdu(pdu)?;
+    let len = 1 + pdu.len() + 2;
+    if buf.len() < len {
+        return Err(CodecError::BufferTooSmall);
+    }
+    buf[0] = id;
+    buf[1..1 + pdu.len()].copy_from_slice(pdu);
+    let crc = calc_crc(&buf[..len - 2]);
+    buf[len - 2..len].copy_from_slice(&crc.to_be_bytes());
+    Ok(len)
+}
+
+/// 校验一个完整的 RTU 帧, 返回从设备ID 和 PDU
+pub fn decode_rtu(frame: &[u8]) -> Result<(Id, &[u8]), CodecError> {
+    // ID(1) + FUN(1) + CRC(2)
+    if frame.len() < 4 {
+        return Err(CodecError::ShortFrame);
+    }
+    if !crc::verify_crc(frame) {
+        return Err(CodecError::Crc);
+    }
+    Ok((frame[0], &frame[1..frame.len() - 2]))
+}
+
+/// 把 PDU 封装为 TCP 帧: MBAP报文头(7) + PDU, 返回帧的长度
+pub fn encode_tcp(
+    transaction_id: u16,
+    unit_id: Id,
+    pdu: &[u8],
+    buf: &mut [u8],
+) -> Result<usize, CodecError> {
+    check_pdu(pdu)?;
+    let len = MBAP_HEADER_SIZE + pdu.len();
+    if buf.len() < len {
+        return Err(CodecError::BufferTooSmall);
+    }
+    buf[0..2].copy_from_slice(&transaction_id.to_be_bytes());
+    buf[2..4].copy_from_slice(&[0, 0]);
+    buf[4..6].copy_from_slice(&(1 + pdu.len() as u16).to_be_bytes());
+    buf[6] = unit_id;
+    buf[MBAP_HEADER_SIZE..len].copy_from_slice(pdu);
+    Ok(len)
+}
+
+/// 检查一个完整的 TCP 帧, 返回 事务标识, 单元标识 和 PDU
+pub fn decode_tcp(frame: &[u8]) -> Result<(u16, Id, &[u8]), CodecError> {
+    // 至少包含 MBAP报文头 和 功能码
+    if frame.len() <= MBAP_HEADER_SIZE {
+        return Err(CodecError::ShortFrame);
+    }
+    // 协议标识, Modbus 协议固定为 0
+    if frame[2..4] != [0, 0] {
+        return Err(CodecError::InvalidProtocolId);
+    }
+    // 长度字段表示 单元标识 及之后的字节数
+    let len = u16::from_be_bytes([frame[4], frame[5]]) as usize;
+    if len + 6 != frame.len() {
+        return Err(CodecError::LengthMismatch);
+    }
+    let transaction_id = u16::from_be_bytes([frame[0], frame[1]]);
+    Ok((transaction_id, frame[6], &frame[MBAP_HEADER_SIZE..]))
+}
+
+/// 根据已经收到的 RTU 响应数据, 计算这一帧还需要接收的字节数, 校验值占用 checksum_size 个字节
+///
+/// 返回 None 表示无法根据功能码确定帧的长度
+pub fn rtu_remaining(frame: &[u8], checksum_size: usize) -> Option<usize> {
+    let len = frame.len();
+
+    // ID(1) + FUN(1)
+    if len < 2 {
+        return Some(2 - len);
+    }
+
+    let function = frame[1];
+    let total = if function & 0x80 != 0 {
+        // 异常响应: ID(1) + FUN(1) + 异常码(1)
+        3 + checksum_size
+    } else {
+        match function {
+            // ID(1) + FUN(1) + 字节数(1) + 数据
+            0x01..=0x04 | 0x0C | 0x11 | 0x14 | 0x15 | 0x17 => {
+                if len < 3 {
+                    return Some(3 - len);
+                }
+                3 + frame[2] as usize + checksum_size
+            }
+            // ID(1) + FUN(1) + 地址或子功能码(2) + 数量或数据(2)
+            0x05 | 0x06 | 0x08 | 0x0B | 0x0F | 0x10 => 6 + checksum_size,
+            // ID(1) + FUN(1) + 数据(1)
+            0x07 => 3 + checksum_size,
+            // ID(1) + FUN(1) + 地址(2) + AND掩码(2) + OR掩码(2)
+            0x16 => 8 + checksum_size,
+            // ID(1) + FUN(1) + MEI类型(1) + 读设备ID码(1) + 一致性等级(1)
+            // + 后续标志(1) + 下一个对象ID(1) + 对象数量(1) + 对象列表
+            0x2B => {
+                if len < 8 {
+                    return Some(8 - len);
+                }
+                if frame[2] != 0x0E {
+                    return None;
+                }
+                // 每个对象: 对象ID(1) + 长度(1) + 值
+                let mut total = 8;
+                for _ in 0..frame[7] {
+                    if len < total + 2 {
+                        return Some(total + 2 - len);
+                    }
+                    total += 2 + frame[total + 1] as usize;
+                }
+                total + checksum_size
+            }
+            // ID(1) + FUN(1) + 字节数(2) + 数据
+            0x18 => {
+                if len < 4 {
+                    return Some(4 - len);
+                }
+                4 + u16::from_be_bytes([frame[2], frame[3]]) as usize + checksum_size
+            }
+            _ => return None,
+        }
+    };
+    Some(total.saturating_sub(len))
+}
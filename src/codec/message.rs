@@ -0,0 +1,453 @@
+//! 基于 [`Function`] 的完整帧编解码, 需要 `std` feature
+
+use bytes::{Buf, Bytes};
+
+use super::{decode_rtu, decode_tcp};
+use crate::{
+    checksum::{Checksum, SumComplement},
+    custom::CustomRequest,
+    error::{Error, ExceptionCode, Reason, Result},
+    file_record,
+    frame::{ascii_decode, Framer},
+    Function, Id, Protocol, Word,
+};
+
+/// 一个请求帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    /// 事务标识, 只用于 TCP, 其它协议为 0
+    pub transaction_id: u16,
+    pub function: Function,
+}
+
+impl Request {
+    pub fn new(transaction_id: u16, function: Function) -> Self {
+        Self {
+            transaction_id,
+            function,
+        }
+    }
+}
+
+impl From<Function> for Request {
+    fn from(function: Function) -> Self {
+        Self::new(0, function)
+    }
+}
+
+/// 编码为完整的 RTU 帧, 包含 CRC. 数量或者地址范围不符合协议的请求返回错误
+///
+/// 编码可能失败, 所以使用 `TryFrom` 而不是 `From`: `Bytes::try_from(fun)?` 或者 `fun.try_into()?`
+impl TryFrom<Function> for Bytes {
+    type Error = Error;
+
+    fn try_from(fun: Function) -> Result<Self> {
+        Framer::new(Protocol::Rtu)
+            .build_buffer(fun)
+            .map(|(req, _)| req)
+    }
+}
+
+/// 解码完整的 RTU 请求帧, 见 [`decode_request`]
+impl TryFrom<&[u8]> for Function {
+    type Error = Error;
+
+    fn try_from(frame: &[u8]) -> Result<Self> {
+        decode_request(Protocol::Rtu, frame).map(|req| req.function)
+    }
+}
+
+/// 一个响应帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// 事务标识, 只用于 TCP, 其它协议为 0
+    pub transaction_id: u16,
+    pub id: Id,
+    /// 功能码, 异常响应时为去掉 0x80 之后的功能码
+    pub function_code: u8,
+    /// 功能码之后的数据, 或者异常码
+    pub result: std::result::Result<Bytes, ExceptionCode>,
+}
+
+impl Response {
+    pub fn is_exception(&self) -> bool {
+        self.result.is_err()
+    }
+}
+
+/// 把请求编码为完整的帧
+pub fn encode_request(protocol: Protocol, request: &Request) -> Result<Bytes> {
+    let mut framer = Framer::new(protocol);
+    // 封装帧时事务标识先加 1
+    framer.transaction_id = request.transaction_id.wrapping_sub(1);
+    let (req, _) = framer.build_buffer(request.function.clone())?;
+    Ok(req)
+}
+
+/// 一个校验通过的帧, 还没有区分请求和响应
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub protocol: Protocol,
+    /// 事务标识, 只用于 TCP, 其它协议为 0
+    pub transaction_id: u16,
+    pub id: Id,
+    /// 功能码及之后的数据, 不为空
+    pub pdu: Bytes,
+}
+
+impl Frame {
+    /// 功能码, 异常响应时为去掉 0x80 之后的功能码
+    pub fn function_code(&self) -> u8 {
+        self.pdu[0] & 0x7F
+    }
+
+    pub fn is_exception(&self) -> bool {
+        self.pdu[0] & 0x80 != 0
+    }
+
+    /// 作为请求解码, 见 [`decode_request`]
+    pub fn to_request(&self) -> Result<Request> {
+        Ok(Request {
+            transaction_id: self.transaction_id,
+            function: decode_function(self.id, &self.pdu)?,
+        })
+    }
+
+    /// 作为响应解码, 见 [`decode_response`]
+    pub fn to_response(&self) -> Result<Response> {
+        let result = if self.is_exception() {
+            if self.pdu.len() != 2 {
+                return Err(Error::InvalidResponse(Reason::InvalidException));
+            }
+            Err(ExceptionCode::from(self.pdu[1]))
+        } else {
+            Ok(self.pdu.slice(1..))
+        };
+        Ok(Response {
+            transaction_id: self.transaction_id,
+            id: self.id,
+            function_code: self.function_code(),
+            result,
+        })
+    }
+}
+
+/// 解码任意的字节数据, 自动识别帧的格式, 对任何输入都不会 panic
+///
+/// `:` 开头 `\r\n` 结尾的为 ASCII 帧, 协议标识为 0 并且长度字段与帧长度一致的为 TCP 帧, 其它的按照 RTU 帧解码
+pub fn decode_frame(frame: &[u8]) -> Result<Frame> {
+    let protocol = if frame.starts_with(b":") && frame.ends_with(b"\r\n") {
+        Protocol::Ascii
+    } else if decode_tcp(frame).is_ok() {
+        Protocol::Tcp
+    } else {
+        Protocol::Rtu
+    };
+    unframe(protocol, frame)
+}
+
+/// 解码一个完整的响应帧, 检查校验值 (RTU, ASCII) 或者 MBAP报文头 (TCP)
+pub fn decode_response(protocol: Protocol, frame: &[u8]) -> Result<Response> {
+    unframe(protocol, frame)?.to_response()
+}
+
+/// 解码一个完整的请求帧
+///
+/// 标准功能码的数据长度不符时返回 UnexpectedRequestSize 错误,
+/// 没有对应 [`Function`] 的功能码 (例如 0x05, 0x0F) 解码为 [`Function::Custom`]
+pub fn decode_request(protocol: Protocol, frame: &[u8]) -> Result<Request> {
+    unframe(protocol, frame)?.to_request()
+}
+
+/// 去掉帧头和校验值, 得到 PDU 不为空的帧
+fn unframe(protocol: Protocol, frame: &[u8]) -> Result<Frame> {
+    let (transaction_id, id, pdu) = match protocol {
+        Protocol::Rtu => {
+            let (id, pdu) = decode_rtu(frame)?;
+            (0, id, Bytes::copy_from_slice(pdu))
+        }
+        Protocol::Tcp => {
+            let (transaction_id, id, pdu) = decode_tcp(frame)?;
+            (transaction_id, id, Bytes::copy_from_slice(pdu))
+        }
+        Protocol::Ascii => {
+            let frame = ascii_decode(frame)?.freeze();
+            // ID(1) + FUN(1) + LRC(1)
+            if frame.len() < 3 {
+                return Err(Error::ShortFrame);
+            }
+            if !SumComplement.verify(&frame) {
+                return Err(Error::Crc);
+            }
+            (0, frame[0], frame.slice(1..frame.len() - 1))
+        }
+    };
+    Ok(Frame {
+        protocol,
+        transaction_id,
+        id,
+        pdu,
+    })
+}
+
+/// 字节数(1) + 数据, 返回数据
+fn byte_count(data: &[u8]) -> Result<&[u8]> {
+    match data.split_first() {
+        Some((&n, rest)) if rest.len() == n as usize => Ok(rest),
+        _ => Err(Error::InvalidData(Reason::UnexpectedRequestSize)),
+    }
+}
+
+/// 数量(2) + 字节数(1) + 寄存器数据
+fn words(mut data: &[u8]) -> Result<Vec<Word>> {
+    if data.len() < 2 {
+        return Err(Error::InvalidData(Reason::UnexpectedRequestSize));
+    }
+    let count = data.get_u16() as usize;
+    let data = byte_count(data)?;
+    if data.len() != 2 * count {
+        return Err(Error::InvalidData(Reason::UnexpectedRequestSize));
+    }
+    Ok(data
+        .chunks(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]))
+        .collect())
+}
+
+fn decode_function(id: Id, pdu: &[u8]) -> Result<Function> {
+    let invalid = || Error::InvalidData(Reason::UnexpectedRequestSize);
+    let (&code, mut data) = pdu.split_first().ok_or(Error::ShortFrame)?;
+    // 检查功能码之后的数据长度
+    let expect = |data: &[u8], len: usize| {
+        if data.len() == len {
+            Ok(())
+        } else {
+            Err(invalid())
+        }
+    };
+    let function = match code {
+        0x01..=0x04 => {
+            expect(data, 4)?;
+            let (addr, quantity) = (data.get_u16(), data.get_u16().into());
+            match code {
+                0x01 => Function::ReadCoils(id, addr, quantity),
+                0x02 => Function::ReadDiscreteInputs(id, addr, quantity),
+                0x03 => Function::ReadHoldingRegisters(id, addr, quantity),
+                _ => Function::ReadInputRegisters(id, addr, quantity),
+            }
+        }
+        0x06 => {
+            expect(data, 4)?;
+            Function::WriteSingleRegister(id, data.get_u16(), data.get_u16())
+        }
+        0x07 => {
+            expect(data, 0)?;
+            Function::ReadExceptionStatus(id)
+        }
+        0x08 => {
+            expect(data, 4)?;
+            Function::Diagnostics(id, data.get_u16(), data.get_u16())
+        }
+        0x0B => {
+            expect(data, 0)?;
+            Function::GetCommEventCounter(id)
+        }
+        0x0C => {
+            expect(data, 0)?;
+            Function::GetCommEventLog(id)
+        }
+        0x10 => {
+            if data.len() < 2 {
+                return Err(invalid());
+            }
+            let addr = data.get_u16();
+            Function::WriteMultipleRegisters(id, addr, words(data)?)
+        }
+        0x11 => {
+            expect(data, 0)?;
+            Function::ReportServerId(id)
+        }
+        0x14 => Function::ReadFileRecord(id, file_record::decode_read_request(byte_count(data)?)?),
+        0x15 => {
+            Function::WriteFileRecord(id, file_record::decode_write_request(byte_count(data)?)?)
+        }
+        0x16 => {
+            expect(data, 6)?;
+            Function::MaskWriteRegister(id, data.get_u16(), data.get_u16(), data.get_u16())
+        }
+        0x17 => {
+            if data.len() < 6 {
+                return Err(invalid());
+            }
+            let (read_addr, quantity, write_addr) =
+                (data.get_u16(), data.get_u16().into(), data.get_u16());
+            Function::ReadWriteMultipleRegisters(id, read_addr, quantity, write_addr, words(data)?)
+        }
+        0x18 => {
+            expect(data, 2)?;
+            Function::ReadFifoQueue(id, data.get_u16())
+        }
+        // MEI类型 0x0E: 读设备ID码(1) + 对象ID(1)
+        0x2B if data.first() == Some(&0x0E) => {
+            expect(data, 3)?;
+            Function::ReadDeviceIdentification(id, data[1], data[2])
+        }
+        _ => Function::Custom(CustomRequest::new(id, code).payload(data)),
+    };
+    Ok(function)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn function_to_rtu_frame() {
+        let frame = Bytes::try_from(Function::WriteMultipleRegisters(1, 0x10, vec![1, 2])).unwrap();
+        assert_eq!(&frame[..7], [0x01, 0x10, 0x00, 0x10, 0x00, 0x02, 0x04]);
+        assert_eq!(
+            Function::try_from(&frame[..]).unwrap(),
+            Function::WriteMultipleRegisters(1, 0x10, vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn function_to_bytes_rejects_too_many_registers() {
+        for len in [124, 128, 200] {
+            let res = Bytes::try_from(Function::WriteMultipleRegisters(1, 0, vec![0; len]));
+            assert!(matches!(
+                res,
+                Err(Error::InvalidRequest(Reason::QuantityOutOfRange { .. }))
+            ));
+        }
+        let res = Bytes::try_from(Function::ReadWriteMultipleRegisters(
+            1,
+            0,
+            1.into(),
+            0,
+            vec![0; 128],
+        ));
+        assert!(matches!(
+            res,
+            Err(Error::InvalidRequest(Reason::QuantityOutOfRange { .. }))
+        ));
+    }
+
+    fn rtu(data: &[u8]) -> Vec<u8> {
+        let mut frame = data.to_vec();
+        frame.extend_from_slice(&crate::calc_crc(data).to_be_bytes());
+        frame
+    }
+
+    #[test]
+    fn decode_frame_detects_protocol() {
+        let fun = Function::ReadHoldingRegisters(1, 0x10, 2.into());
+        for protocol in [Protocol::Rtu, Protocol::Tcp, Protocol::Ascii] {
+            let req = encode_request(protocol, &Request::new(7, fun.clone())).unwrap();
+            let frame = decode_frame(&req).unwrap();
+            assert_eq!(frame.protocol, protocol);
+            assert_eq!(frame.id, 1);
+            assert_eq!(&frame.pdu[..], [0x03, 0x00, 0x10, 0x00, 0x02]);
+            let tid = if protocol == Protocol::Tcp { 7 } else { 0 };
+            assert_eq!(frame.to_request().unwrap(), Request::new(tid, fun.clone()));
+        }
+    }
+
+    #[test]
+    fn decode_frame_never_panics() {
+        assert!(decode_frame(&[]).is_err());
+        assert!(decode_frame(b":").is_err());
+        assert!(decode_frame(b":\r\n").is_err());
+        assert!(decode_frame(b":0103\r\n").is_err());
+        assert!(decode_frame(&[0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x01]).is_err());
+        // 截断的帧
+        let req = encode_request(
+            Protocol::Tcp,
+            &Request::new(1, Function::ReadCoils(1, 0, 8.into())),
+        )
+        .unwrap();
+        for len in 0..req.len() {
+            let _ = decode_frame(&req[..len]);
+        }
+        // 伪随机数据
+        let mut x = 0x2545_f491_u32;
+        for len in 0..64 {
+            let data: Vec<u8> = (0..len)
+                .map(|_| {
+                    x ^= x << 13;
+                    x ^= x >> 17;
+                    x ^= x << 5;
+                    x as u8
+                })
+                .collect();
+            if let Ok(frame) = decode_frame(&data) {
+                let _ = frame.to_request();
+                let _ = frame.to_response();
+            }
+        }
+    }
+
+    #[test]
+    fn exception_response() {
+        let res = decode_response(Protocol::Rtu, &rtu(&[0x01, 0x83, 0x02])).unwrap();
+        assert!(res.is_exception());
+        assert_eq!(res.function_code, 0x03);
+        assert_eq!(res.result, Err(ExceptionCode::IllegalDataAddress));
+
+        // 异常响应必须只有一个异常码
+        for pdu in [&[0x01, 0x83][..], &[0x01, 0x83, 0x02, 0x00]] {
+            assert!(matches!(
+                decode_response(Protocol::Rtu, &rtu(pdu)),
+                Err(Error::InvalidResponse(Reason::InvalidException))
+            ));
+        }
+    }
+
+    #[test]
+    fn normal_response() {
+        let res = decode_response(Protocol::Rtu, &rtu(&[0x01, 0x03, 0x02, 0x12, 0x34])).unwrap();
+        assert!(!res.is_exception());
+        assert_eq!(res.id, 1);
+        assert_eq!(res.result.unwrap()[..], [0x02, 0x12, 0x34]);
+    }
+
+    #[test]
+    fn decode_read_device_identification() {
+        let req = decode_request(Protocol::Rtu, &rtu(&[0x01, 0x2B, 0x0E, 0x01, 0x00])).unwrap();
+        assert_eq!(
+            req.function,
+            Function::ReadDeviceIdentification(1, 0x01, 0x00)
+        );
+        assert!(matches!(
+            decode_request(Protocol::Rtu, &rtu(&[0x01, 0x2B, 0x0E, 0x01])),
+            Err(Error::InvalidData(Reason::UnexpectedRequestSize))
+        ));
+        // 其它 MEI 类型作为自定义请求
+        let req = decode_request(Protocol::Rtu, &rtu(&[0x01, 0x2B, 0x0D, 0x00])).unwrap();
+        assert!(matches!(req.function, Function::Custom(_)));
+    }
+
+    #[test]
+    fn decode_request_checks_length() {
+        for pdu in [
+            &[0x01, 0x03, 0x00, 0x00, 0x00][..],
+            &[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00],
+            &[0x01, 0x07, 0x00],
+            &[0x01, 0x10, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00, 0x01],
+            &[
+                0x01, 0x10, 0x00, 0x00, 0x00, 0x01, 0x04, 0x00, 0x01, 0x00, 0x02,
+            ],
+        ] {
+            assert!(matches!(
+                decode_request(Protocol::Rtu, &rtu(pdu)),
+                Err(Error::InvalidData(Reason::UnexpectedRequestSize))
+            ));
+        }
+        let mut frame = rtu(&[0x01, 0x07]);
+        frame[2] ^= 0xFF;
+        assert!(matches!(
+            decode_request(Protocol::Rtu, &frame),
+            Err(Error::Crc)
+        ));
+    }
+}
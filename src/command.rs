@@ -0,0 +1,159 @@
+//! 按需命令: 在 [`Poller`](crate::poll::Poller) 占用总线时, 从其它线程插入优先执行的请求
+//!
+//! 命令在下一个帧边界执行, 即当前的传输结束之后, 下一个轮询任务开始之前.
+//! 优先级高的命令先执行, 相同优先级按照提交的顺序执行
+//! ```no_run
+//! # use simple_modbus::{command::Priority, poll::Poller, Client, Function};
+//! # fn run(client: Client) -> simple_modbus::error::Result<()> {
+//! let mut poller = Poller::new(client);
+//! let commands = poller.command_sender();
+//! std::thread::spawn(move || poller.run());
+//! // 急停
+//! commands.request(Function::WriteSingleRegister(1, 0x0100, 0), Priority::High)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
+use crate::{
+    error::{Error, Result},
+    response::Response,
+    Function,
+};
+
+/// 命令的优先级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// 在到期的轮询任务之后执行
+    Low,
+    /// 在到期的轮询任务之前执行
+    Normal,
+    /// 在所有其它命令之前执行, 例如急停
+    High,
+}
+
+pub(crate) struct Command {
+    pub(crate) function: Function,
+    pub(crate) priority: Priority,
+    pub(crate) reply: Sender<Result<Response>>,
+}
+
+/// 提交命令的句柄, 可以复制到多个线程使用
+#[derive(Clone)]
+pub struct CommandSender(Sender<Command>);
+
+impl CommandSender {
+    /// 提交命令, 从返回的 Receiver 接收响应. Poller 已经销毁时返回 [`Error::Cancelled`]
+    pub fn submit(
+        &self,
+        function: Function,
+        priority: Priority,
+    ) -> Result<Receiver<Result<Response>>> {
+        let (reply, receiver) = mpsc::channel();
+        self.0
+            .send(Command {
+                function,
+                priority,
+                reply,
+            })
+            .map_err(|_| Error::Cancelled)?;
+        Ok(receiver)
+    }
+
+    /// 提交命令并等待响应
+    pub fn request(&self, function: Function, priority: Priority) -> Result<Response> {
+        self.submit(function, priority)?
+            .recv()
+            .map_err(|_| Error::Cancelled)?
+    }
+}
+
+/// 按照优先级和提交顺序排序的命令
+struct Queued {
+    command: Command,
+    seq: u64,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    /// BinaryHeap 先取出最大的元素: 优先级高的, 序号小的
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.command
+            .priority
+            .cmp(&other.command.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Poller 中的命令队列
+pub(crate) struct CommandQueue {
+    sender: Sender<Command>,
+    receiver: Receiver<Command>,
+    heap: BinaryHeap<Queued>,
+    seq: u64,
+}
+
+impl CommandQueue {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            heap: BinaryHeap::new(),
+            seq: 0,
+        }
+    }
+
+    pub(crate) fn sender(&self) -> CommandSender {
+        CommandSender(self.sender.clone())
+    }
+
+    fn push(&mut self, command: Command) {
+        self.heap.push(Queued {
+            command,
+            seq: self.seq,
+        });
+        self.seq += 1;
+    }
+
+    /// 取出优先级不低于 min 的下一个命令
+    pub(crate) fn pop(&mut self, min: Priority) -> Option<Command> {
+        while let Ok(command) = self.receiver.try_recv() {
+            self.push(command);
+        }
+        if self.heap.peek()?.command.priority < min {
+            return None;
+        }
+        self.heap.pop().map(|queued| queued.command)
+    }
+
+    /// 最多等待 timeout, 直到收到命令. 队列中已经有命令时立即返回
+    pub(crate) fn wait(&mut self, timeout: Duration) {
+        if !self.heap.is_empty() {
+            return;
+        }
+        match self.receiver.recv_timeout(timeout) {
+            Ok(command) => self.push(command),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {}
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use serialport::{DataBits, FlowControl, Parity, StopBits};
+use std::time::Duration;
+
+use crate::{
+    error::{Error, Reason, Result},
+    serial::SerialConfig,
+    tcp::TcpOptions,
+    Id,
+};
+
+pub use crate::codec::BROADCAST_ID;
+
+/// Modbus/TCP 中表示 "忽略单元标识符" 的值
+///
+/// 直接连接在以太网上的设备通过IP地址寻址, 规范建议此时单元标识符使用 0xFF;
+/// 经过 TCP⇄RTU 网关转发的设备则需要使用真实的从设备ID
+pub const TCP_UNIT_ID_IGNORE: Id = 0xFF;
+
+/// 连接配置
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 连接的默认单元标识符, 调用时没有指定从设备ID时使用
+    pub modbus_uid: Id,
+    /// 建立 TCP 连接的超时时间
+    pub connect_timeout: Duration,
+    /// 等待响应的超时时间
+    pub read_timeout: Duration,
+    /// 发送请求的超时时间
+    pub write_timeout: Duration,
+    /// 串口数据位
+    pub data_bits: DataBits,
+    /// 串口校验位
+    pub parity: Parity,
+    /// 串口停止位
+    pub stop_bits: StopBits,
+    /// 串口流控
+    pub flow_control: FlowControl,
+    /// TCP 连接的套接字选项
+    pub tcp: TcpOptions,
+}
+
+impl Config {
+    /// 检查配置是否有效
+    pub fn validate(&self) -> Result<()> {
+        if self.connect_timeout.is_zero()
+            || self.read_timeout.is_zero()
+            || self.write_timeout.is_zero()
+        {
+            return Err(invalid_config("超时时间不能为 0"));
+        }
+        Ok(())
+    }
+
+    /// 指定波特率的串口参数, 超时时间为 read_timeout
+    pub fn serial_config(&self, baud_rate: u32) -> SerialConfig {
+        SerialConfig {
+            baud_rate,
+            data_bits: self.data_bits,
+            parity: self.parity,
+            stop_bits: self.stop_bits,
+            flow_control: self.flow_control,
+            timeout: self.read_timeout,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            modbus_uid: TCP_UNIT_ID_IGNORE,
+            connect_timeout: Duration::from_millis(5000),
+            read_timeout: Duration::from_millis(5000),
+            write_timeout: Duration::from_millis(5000),
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            tcp: TcpOptions::default(),
+        }
+    }
+}
+
+pub(crate) fn invalid_config(reason: &str) -> Error {
+    Error::InvalidData(Reason::InvalidConfig(reason.to_string()))
+}
@@ -0,0 +1,215 @@
+//! 累加计数器: 电能表的 kWh, 脉冲计数等保存在 2 个或者 4 个寄存器中的计数值
+//!
+//! 每次读取后与上一次的值比较, 计数器溢出回绕时仍然得到正确的增量
+//! ```no_run
+//! # use simple_modbus::{counter::{Counter, CounterWidth}, Client};
+//! # fn run(client: &mut Client) -> simple_modbus::error::Result<()> {
+//! let mut energy = Counter::new(1, 0x0100, CounterWidth::U32).input();
+//! loop {
+//!     let delta = energy.poll(client)?;
+//!     println!("+{} Wh, 累计 {} Wh", delta, energy.total());
+//!     std::thread::sleep(std::time::Duration::from_secs(60));
+//! }
+//! # }
+//! ```
+
+use crate::{error::Result, value::WordOrder, Address, Client, Id, Word};
+
+/// 计数器的位数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterWidth {
+    /// 2 个寄存器
+    U32,
+    /// 4 个寄存器
+    U64,
+}
+
+impl CounterWidth {
+    pub fn words(&self) -> u16 {
+        match self {
+            CounterWidth::U32 => 2,
+            CounterWidth::U64 => 4,
+        }
+    }
+
+    /// 计数器的最大值
+    pub fn max(&self) -> u64 {
+        match self {
+            CounterWidth::U32 => u32::MAX as u64,
+            CounterWidth::U64 => u64::MAX,
+        }
+    }
+}
+
+/// 一个累加计数器
+#[derive(Debug, Clone)]
+pub struct Counter {
+    id: Id,
+    address: Address,
+    width: CounterWidth,
+    word_order: WordOrder,
+    input: bool,
+    max_delta: Option<u64>,
+    last: Option<u64>,
+    total: u64,
+}
+
+impl Counter {
+    /// 从设备 id 的保持寄存器 address 处的计数器, 字节顺序为 ABCD
+    pub fn new(id: Id, address: Address, width: CounterWidth) -> Self {
+        Self {
+            id,
+            address,
+            width,
+            word_order: WordOrder::default(),
+            input: false,
+            max_delta: None,
+            last: None,
+            total: 0,
+        }
+    }
+
+    /// 计数器在输入寄存器中
+    pub fn input(mut self) -> Self {
+        self.input = true;
+        self
+    }
+
+    pub fn word_order(mut self, word_order: WordOrder) -> Self {
+        self.word_order = word_order;
+        self
+    }
+
+    /// 两次读取之间可能的最大增量
+    ///
+    /// 计数值变小时, 回绕计算的增量超过这个值, 认为计数器被复位 (例如从设备断电), 增量为当前的计数值
+    pub fn max_delta(mut self, max_delta: u64) -> Self {
+        self.max_delta = Some(max_delta);
+        self
+    }
+
+    /// 读取计数器, 返回与上一次读取相比的增量, 第一次读取返回 0
+    pub fn poll(&mut self, client: &mut Client) -> Result<u64> {
+        let quantity = self.width.words();
+        let words = if self.input {
+            client.read_input_registers(self.id, self.address, quantity)?
+        } else {
+            client.read_holding_registers(self.id, self.address, quantity)?
+        };
+        self.update_words(&words)
+    }
+
+    /// 使用其它方式读取到的寄存器更新计数器, 例如 [`Poller`](crate::poll::Poller) 的结果
+    pub fn update_words(&mut self, words: &[Word]) -> Result<u64> {
+        let value = match self.width {
+            CounterWidth::U32 => self.word_order.decode::<u32>(words)? as u64,
+            CounterWidth::U64 => self.word_order.decode::<u64>(words)?,
+        };
+        Ok(self.update(value))
+    }
+
+    /// 使用新的计数值更新计数器, 返回增量
+    pub fn update(&mut self, value: u64) -> u64 {
+        let value = value.min(self.width.max());
+        let delta = match self.last {
+            None => 0,
+            Some(last) if value >= last => value - last,
+            Some(last) => {
+                let wrapped = (self.width.max() - last)
+                    .saturating_add(value)
+                    .saturating_add(1);
+                if self.max_delta.is_some_and(|max| wrapped > max) {
+                    log::debug!(
+                        "计数器 {}@{} 复位: {} -> {}",
+                        self.id,
+                        self.address,
+                        last,
+                        value
+                    );
+                    value
+                } else {
+                    wrapped
+                }
+            }
+        };
+        self.last = Some(value);
+        self.total = self.total.saturating_add(delta);
+        delta
+    }
+
+    /// 最后一次读取到的计数值
+    pub fn last(&self) -> Option<u64> {
+        self.last
+    }
+
+    /// 所有增量之和, 只增不减
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// 清零累计值, 下一次读取重新开始计算增量
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.total = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Counter, CounterWidth};
+    use crate::{bank::RegisterBank, mock::MockStream, value::WordOrder, Client, Protocol};
+
+    #[test]
+    fn wraps_at_u32_max() {
+        let mut counter = Counter::new(1, 0, CounterWidth::U32);
+        assert_eq!(counter.update(u32::MAX as u64 - 9), 0);
+        assert_eq!(counter.update(u32::MAX as u64), 9);
+        assert_eq!(counter.update(4), 5);
+        assert_eq!(counter.total(), 14);
+        assert_eq!(counter.last(), Some(4));
+    }
+
+    #[test]
+    fn wraps_at_u64_max() {
+        let mut counter = Counter::new(1, 0, CounterWidth::U64);
+        counter.update(u64::MAX - 1);
+        assert_eq!(counter.update(2), 4);
+        assert_eq!(counter.total(), 4);
+    }
+
+    #[test]
+    fn max_delta_detects_reset() {
+        let mut counter = Counter::new(1, 0, CounterWidth::U32).max_delta(1000);
+        counter.update(50_000);
+        // 回绕的增量超过 1000, 认为计数器从 0 重新开始
+        assert_eq!(counter.update(30), 30);
+        counter.update(u32::MAX as u64 - 100);
+        assert_eq!(counter.update(100), 201);
+        counter.reset();
+        assert_eq!(counter.total(), 0);
+        assert_eq!(counter.update(7), 0);
+    }
+
+    #[test]
+    fn update_words_uses_word_order() {
+        let mut counter = Counter::new(1, 0, CounterWidth::U32).word_order(WordOrder::CDAB);
+        counter.update_words(&[0xFFFF, 0xFFFF]).unwrap();
+        assert_eq!(counter.update_words(&[0x0001, 0x0000]).unwrap(), 2);
+        assert!(counter.update_words(&[0x0001]).is_err());
+    }
+
+    #[test]
+    fn poll_input_registers() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 4, 0));
+        let stream = MockStream::with_bank(bank.clone(), Protocol::Rtu);
+        let mut client = Client::new(Box::new(stream)).unwrap();
+        let mut counter = Counter::new(1, 0x0002, CounterWidth::U32).input();
+
+        bank.set_input_registers(2, &[0xFFFF, 0xFFF0]).unwrap();
+        assert_eq!(counter.poll(&mut client).unwrap(), 0);
+        bank.set_input_registers(2, &[0x0000, 0x0010]).unwrap();
+        assert_eq!(counter.poll(&mut client).unwrap(), 0x20);
+    }
+}
@@ -0,0 +1,72 @@
+//! 定制功能码, 用于设备私有的功能
+
+use crate::{error::Result, Id};
+
+/// 定制功能码的响应长度, 用于在 RTU 模式下确定响应帧的结束位置
+///
+/// 只对标准功能码以外的功能码生效, TCP 和 ASCII 模式不需要
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplyLength {
+    /// 功能码之后固定的字节数
+    Fixed(usize),
+    /// 功能码之后是 1 个字节的字节数字段, 然后是数据
+    ByteCount,
+    /// 长度未知, 接收数据直到总线静默
+    #[default]
+    Unknown,
+}
+
+/// 定制功能码的请求, 客户端负责添加报文头和校验码, 以及校验响应
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomRequest {
+    pub id: Id,
+    pub function_code: u8,
+    /// 功能码之后的数据
+    pub payload: Vec<u8>,
+    pub reply_length: ReplyLength,
+}
+
+impl CustomRequest {
+    pub fn new(id: Id, function_code: u8) -> Self {
+        Self {
+            id,
+            function_code,
+            payload: Vec::new(),
+            reply_length: ReplyLength::Unknown,
+        }
+    }
+
+    pub fn payload(mut self, payload: impl Into<Vec<u8>>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
+    pub fn reply_length(mut self, reply_length: ReplyLength) -> Self {
+        self.reply_length = reply_length;
+        self
+    }
+}
+
+/// 可以复用的设备私有功能, 通过 `Client::call` 调用
+pub trait CustomFunction {
+    type Output;
+
+    fn function_code(&self) -> u8;
+
+    /// 请求中功能码之后的数据
+    fn payload(&self) -> Vec<u8>;
+
+    fn reply_length(&self) -> ReplyLength {
+        ReplyLength::Unknown
+    }
+
+    /// 解析响应数据, 不包含功能码, `ReplyLength::ByteCount` 时也不包含字节数字段
+    fn parse_reply(&self, data: &[u8]) -> Result<Self::Output>;
+
+    /// 生成发送给 id 的请求
+    fn request(&self, id: Id) -> CustomRequest {
+        CustomRequest::new(id, self.function_code())
+            .payload(self.payload())
+            .reply_length(self.reply_length())
+    }
+}
@@ -0,0 +1,271 @@
+//! 设备驱动: 把 `set_speed_rpm`, `read_energy_kwh` 这样的语义操作映射到寄存器读写
+//!
+//! 每个设备类型有一个默认的寄存器表, 其它厂商的设备使用 `with_map` 传入自己的寄存器表,
+//! 只要包含相同名称的数据点即可, 例如
+//! ```
+//! use simple_modbus::{device::Vfd, register_map::RegisterMap};
+//!
+//! let map = RegisterMap::from_csv(
+//!     1,
+//!     "control,holding,0x2000,u16
+//!      speed_setpoint,holding,0x2001,u16,1,rpm
+//!      speed,holding,0x3000,u16,1,rpm
+//!      current,holding,0x3001,u16,0.01,A
+//!      fault,holding,0x3002,u16",
+//! )?;
+//! let vfd = Vfd::with_map(map)?;
+//! # Ok::<(), simple_modbus::error::Error>(())
+//! ```
+
+use crate::{config::invalid_config, error::Result, register_map::RegisterMap, Client, Id};
+
+/// 通过寄存器表访问的设备
+pub trait Device {
+    /// 设备的寄存器表
+    fn map(&self) -> &RegisterMap;
+
+    fn id(&self) -> Id {
+        self.map().id()
+    }
+
+    /// 按名称读取数据点
+    fn read(&self, client: &mut Client, name: &str) -> Result<f64> {
+        self.map().read(client, name)
+    }
+
+    /// 按名称写入数据点
+    fn write(&self, client: &mut Client, name: &str, value: f64) -> Result<()> {
+        self.map().write(client, name, value)
+    }
+}
+
+/// 检查寄存器表包含设备需要的所有数据点
+fn check_points(map: &RegisterMap, names: &[&str]) -> Result<()> {
+    match names.iter().find(|name| map.point(name).is_none()) {
+        Some(name) => Err(invalid_config(&format!(
+            "设备的寄存器表缺少数据点: {}",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// 变频器的默认寄存器表
+const VFD_MAP: &str = "
+control,holding,0x0000,u16
+speed_setpoint,holding,0x0001,u16,1,rpm
+speed,input,0x0000,u16,1,rpm
+current,input,0x0001,u16,0.1,A
+fault,input,0x0002,u16
+";
+
+/// 变频器控制字的取值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VfdControl {
+    pub forward: u16,
+    pub reverse: u16,
+    pub stop: u16,
+}
+
+impl Default for VfdControl {
+    /// 1: 正转, 2: 反转, 5: 停止
+    fn default() -> Self {
+        Self {
+            forward: 1,
+            reverse: 2,
+            stop: 5,
+        }
+    }
+}
+
+/// 通用的变频器
+///
+/// 数据点: control (控制字), speed_setpoint (rpm), speed (rpm), current (A), fault (故障码)
+#[derive(Debug, Clone)]
+pub struct Vfd {
+    map: RegisterMap,
+    control: VfdControl,
+}
+
+impl Vfd {
+    /// 使用默认的寄存器表
+    pub fn new(id: Id) -> Self {
+        Self {
+            map: RegisterMap::from_csv(id, VFD_MAP).expect("默认的寄存器表有效"),
+            control: VfdControl::default(),
+        }
+    }
+
+    pub fn with_map(map: RegisterMap) -> Result<Self> {
+        check_points(
+            &map,
+            &["control", "speed_setpoint", "speed", "current", "fault"],
+        )?;
+        Ok(Self {
+            map,
+            control: VfdControl::default(),
+        })
+    }
+
+    /// 设置控制字的取值
+    pub fn control(mut self, control: VfdControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub fn run_forward(&self, client: &mut Client) -> Result<()> {
+        self.write(client, "control", self.control.forward as f64)
+    }
+
+    pub fn run_reverse(&self, client: &mut Client) -> Result<()> {
+        self.write(client, "control", self.control.reverse as f64)
+    }
+
+    pub fn stop(&self, client: &mut Client) -> Result<()> {
+        self.write(client, "control", self.control.stop as f64)
+    }
+
+    pub fn set_speed_rpm(&self, client: &mut Client, rpm: f64) -> Result<()> {
+        self.write(client, "speed_setpoint", rpm)
+    }
+
+    /// 实际转速
+    pub fn read_speed_rpm(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "speed")
+    }
+
+    pub fn read_current_a(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "current")
+    }
+
+    /// 故障码, 0 表示没有故障
+    pub fn read_fault_code(&self, client: &mut Client) -> Result<u16> {
+        Ok(self.read(client, "fault")? as u16)
+    }
+}
+
+impl Device for Vfd {
+    fn map(&self) -> &RegisterMap {
+        &self.map
+    }
+}
+
+/// 电能表的默认寄存器表, 输入寄存器中的 f32 数据, 常见的单相电能表布局
+const POWER_METER_MAP: &str = "
+voltage,input,0x0000,f32,1,V
+current,input,0x0006,f32,1,A
+power,input,0x000C,f32,0.001,kW
+frequency,input,0x0046,f32,1,Hz
+energy,input,0x0156,f32,1,kWh
+";
+
+/// 电能表
+///
+/// 数据点: voltage (V), current (A), power (kW), frequency (Hz), energy (kWh)
+#[derive(Debug, Clone)]
+pub struct PowerMeter {
+    map: RegisterMap,
+}
+
+impl PowerMeter {
+    /// 使用默认的寄存器表
+    pub fn new(id: Id) -> Self {
+        Self {
+            map: RegisterMap::from_csv(id, POWER_METER_MAP).expect("默认的寄存器表有效"),
+        }
+    }
+
+    pub fn with_map(map: RegisterMap) -> Result<Self> {
+        check_points(
+            &map,
+            &["voltage", "current", "power", "frequency", "energy"],
+        )?;
+        Ok(Self { map })
+    }
+
+    pub fn read_voltage_v(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "voltage")
+    }
+
+    pub fn read_current_a(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "current")
+    }
+
+    /// 有功功率
+    pub fn read_power_kw(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "power")
+    }
+
+    pub fn read_frequency_hz(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "frequency")
+    }
+
+    /// 累计有功电能
+    pub fn read_energy_kwh(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "energy")
+    }
+}
+
+impl Device for PowerMeter {
+    fn map(&self) -> &RegisterMap {
+        &self.map
+    }
+}
+
+/// 温控器的默认寄存器表, 温度为 0.1°C
+const TEMPERATURE_CONTROLLER_MAP: &str = "
+temperature,input,0x0000,i16,0.1,°C
+output,input,0x0001,u16,0.1,%
+setpoint,holding,0x0000,i16,0.1,°C
+run,holding,0x0001,u16
+";
+
+/// 温控器
+///
+/// 数据点: temperature (°C), output (%), setpoint (°C), run (0: 停止, 1: 运行)
+#[derive(Debug, Clone)]
+pub struct TemperatureController {
+    map: RegisterMap,
+}
+
+impl TemperatureController {
+    /// 使用默认的寄存器表
+    pub fn new(id: Id) -> Self {
+        Self {
+            map: RegisterMap::from_csv(id, TEMPERATURE_CONTROLLER_MAP).expect("默认的寄存器表有效"),
+        }
+    }
+
+    pub fn with_map(map: RegisterMap) -> Result<Self> {
+        check_points(&map, &["temperature", "output", "setpoint", "run"])?;
+        Ok(Self { map })
+    }
+
+    /// 实际温度
+    pub fn read_temperature_c(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "temperature")
+    }
+
+    pub fn read_setpoint_c(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "setpoint")
+    }
+
+    pub fn set_setpoint_c(&self, client: &mut Client, celsius: f64) -> Result<()> {
+        self.write(client, "setpoint", celsius)
+    }
+
+    /// 加热输出的百分比
+    pub fn read_output_percent(&self, client: &mut Client) -> Result<f64> {
+        self.read(client, "output")
+    }
+
+    pub fn set_running(&self, client: &mut Client, running: bool) -> Result<()> {
+        self.write(client, "run", if running { 1.0 } else { 0.0 })
+    }
+}
+
+impl Device for TemperatureController {
+    fn map(&self) -> &RegisterMap {
+        &self.map
+    }
+}
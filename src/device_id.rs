@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Reason, Result};
+
+/// 厂商名称
+pub const VENDOR_NAME: u8 = 0x00;
+/// 产品代码
+pub const PRODUCT_CODE: u8 = 0x01;
+/// 主次版本号
+pub const MAJOR_MINOR_REVISION: u8 = 0x02;
+
+/// 读设备标识时请求的对象类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceIdCategory {
+    /// 基本信息: 厂商名称, 产品代码, 版本号
+    Basic,
+    /// 常规信息: 在基本信息之上增加 厂商网址, 产品名称, 型号名称 等
+    Regular,
+    /// 扩展信息: 在常规信息之上增加 设备私有的对象
+    Extended,
+}
+
+impl DeviceIdCategory {
+    /// 请求中的 读设备ID码
+    pub fn code(&self) -> u8 {
+        match self {
+            DeviceIdCategory::Basic => 0x01,
+            DeviceIdCategory::Regular => 0x02,
+            DeviceIdCategory::Extended => 0x03,
+        }
+    }
+}
+
+/// 功能码 0x2B / MEI 类型 0x0E 读取到的设备标识
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceIdentification {
+    /// 一致性等级
+    pub conformity_level: u8,
+    /// 所有读取到的对象, 对象ID => 值
+    pub objects: BTreeMap<u8, String>,
+}
+
+impl DeviceIdentification {
+    pub fn vendor_name(&self) -> Option<&str> {
+        self.object(VENDOR_NAME)
+    }
+
+    pub fn product_code(&self) -> Option<&str> {
+        self.object(PRODUCT_CODE)
+    }
+
+    pub fn revision(&self) -> Option<&str> {
+        self.object(MAJOR_MINOR_REVISION)
+    }
+
+    pub fn object(&self, object_id: u8) -> Option<&str> {
+        self.objects.get(&object_id).map(String::as_str)
+    }
+
+    /// 解析一次响应中功能码之后的数据, 把对象加入到 objects 中
+    ///
+    /// 还有后续对象时, 返回下一次请求的起始对象ID
+    pub(crate) fn parse(&mut self, data: &[u8]) -> Result<Option<u8>> {
+        let invalid = || Error::InvalidResponse(Reason::UnexpectedReplySize);
+        // MEI类型(1) + 读设备ID码(1) + 一致性等级(1) + 后续标志(1) + 下一个对象ID(1) + 对象数量(1)
+        if data.len() < 6 || data[0] != 0x0E {
+            return Err(invalid());
+        }
+        self.conformity_level = data[2];
+        let more_follows = data[3] == 0xFF;
+        let next_object_id = data[4];
+
+        let mut objects = &data[6..];
+        for _ in 0..data[5] {
+            // 对象ID(1) + 长度(1) + 值
+            if objects.len() < 2 || objects.len() < 2 + objects[1] as usize {
+                return Err(invalid());
+            }
+            let (object, rest) = objects.split_at(2 + objects[1] as usize);
+            let value = String::from_utf8_lossy(&object[2..]).into_owned();
+            self.objects.insert(object[0], value);
+            objects = rest;
+        }
+        if !objects.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(more_follows.then_some(next_object_id))
+    }
+}
@@ -0,0 +1,116 @@
+//! 诊断功能 (功能码 0x08) 的子功能码, 以及串行链路的状态查询 (功能码 0x07, 0x0B, 0x0C, 0x11)
+
+use crate::{
+    error::{Error, Reason, Result},
+    Word,
+};
+
+/// 返回请求数据, 即回环测试
+pub const RETURN_QUERY_DATA: u16 = 0x00;
+/// 重启通信选项, 数据为 0xFF00 时同时清除通信事件日志
+pub const RESTART_COMMUNICATIONS: u16 = 0x01;
+/// 返回诊断寄存器
+pub const RETURN_DIAGNOSTIC_REGISTER: u16 = 0x02;
+/// 强制进入只听模式, 从设备不会响应
+pub const FORCE_LISTEN_ONLY_MODE: u16 = 0x04;
+/// 清除计数器和诊断寄存器
+pub const CLEAR_COUNTERS: u16 = 0x0A;
+/// 返回总线报文计数
+pub const BUS_MESSAGE_COUNT: u16 = 0x0B;
+/// 返回总线通信错误计数 (CRC 错误)
+pub const BUS_COMMUNICATION_ERROR_COUNT: u16 = 0x0C;
+/// 返回总线异常响应计数
+pub const BUS_EXCEPTION_ERROR_COUNT: u16 = 0x0D;
+/// 返回从设备报文计数
+pub const SERVER_MESSAGE_COUNT: u16 = 0x0E;
+/// 返回从设备无响应计数
+pub const SERVER_NO_RESPONSE_COUNT: u16 = 0x0F;
+/// 返回从设备 NAK 计数
+pub const SERVER_NAK_COUNT: u16 = 0x10;
+/// 返回从设备忙计数
+pub const SERVER_BUSY_COUNT: u16 = 0x11;
+/// 返回总线字符溢出计数
+pub const BUS_CHARACTER_OVERRUN_COUNT: u16 = 0x12;
+
+/// 获取通信事件计数 (功能码 0x0B) 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommEventCounter {
+    /// 状态字, 0xFFFF 表示从设备正在处理之前的程序命令
+    pub status: Word,
+    /// 通信事件计数
+    pub event_count: Word,
+}
+
+impl CommEventCounter {
+    /// 响应: 状态字(2) + 事件计数(2)
+    pub(crate) fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() != 4 {
+            return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+        }
+        Ok(Self {
+            status: u16::from_be_bytes([data[0], data[1]]),
+            event_count: u16::from_be_bytes([data[2], data[3]]),
+        })
+    }
+}
+
+/// 获取通信事件日志 (功能码 0x0C) 的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommEventLog {
+    /// 状态字, 0xFFFF 表示从设备正在处理之前的程序命令
+    pub status: Word,
+    /// 通信事件计数
+    pub event_count: Word,
+    /// 报文计数
+    pub message_count: Word,
+    /// 事件列表, 最多 64 个, 最新的事件在前
+    pub events: Vec<u8>,
+}
+
+impl CommEventLog {
+    /// 响应 (字节数之后): 状态字(2) + 事件计数(2) + 报文计数(2) + 事件列表
+    pub(crate) fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 6 {
+            return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+        }
+        Ok(Self {
+            status: u16::from_be_bytes([data[0], data[1]]),
+            event_count: u16::from_be_bytes([data[2], data[3]]),
+            message_count: u16::from_be_bytes([data[4], data[5]]),
+            events: data[6..].to_vec(),
+        })
+    }
+}
+
+/// 报告从设备ID (功能码 0x11) 的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServerId {
+    /// 从设备ID, 含义由设备决定
+    pub server_id: u8,
+    /// 运行状态, 0xFF 表示运行, 0x00 表示停止
+    pub run_indicator: u8,
+    /// 设备相关的附加数据
+    pub data: Vec<u8>,
+}
+
+impl ServerId {
+    /// 设备是否处于运行状态
+    pub fn is_running(&self) -> bool {
+        self.run_indicator == 0xFF
+    }
+
+    /// 响应 (字节数之后): 从设备ID(1) + 运行状态(1) + 附加数据
+    pub(crate) fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 2 {
+            return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+        }
+        Ok(Self {
+            server_id: data[0],
+            run_indicator: data[1],
+            data: data[2..].to_vec(),
+        })
+    }
+}
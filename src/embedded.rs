@@ -0,0 +1,460 @@
+//! 基于 embedded-io 串口驱动的 Modbus RTU 主站
+//!
+//! [`RtuMaster`] 不依赖标准库, 也不需要分配内存, 可以在 RP2040, STM32 等单片机上直接驱动 UART.
+//! 帧的编码和校验使用 [`codec`] 模块, 超时和帧间隔由调用者提供的 [`DelayNs`] 计时.
+//!
+//! 开启 `std` feature 时还提供 [`EmbeddedStream`], 在带有标准库的平台 (例如 esp-idf) 上使用完整的 `Client`
+//!
+//! ```ignore
+//! let mut master = RtuMaster::new(uart, delay).baud_rate(9600).timeout_us(500_000);
+//! let mut values = [0u16; 4];
+//! master.read_holding_registers(1, 0x0000, &mut values)?;
+//! master.write_single_register(1, 0x0010, values[0])?;
+//! ```
+
+use core::fmt;
+
+use embedded_io::{Read, ReadReady, Write};
+
+use crate::{
+    codec::{self, CodecError, BROADCAST_ID, MAX_READ_REGISTERS, MAX_WRITE_REGISTERS},
+    Id, MODBUS_MAX_PDU_SIZE,
+};
+
+#[cfg(feature = "std")]
+mod stream;
+
+#[cfg(feature = "std")]
+pub use stream::EmbeddedStream;
+
+/// RTU 帧的最大长度: 从设备ID(1) + PDU(253) + CRC(2)
+const RTU_MAX_FRAME_SIZE: usize = 256;
+
+/// 等待响应时轮询串口的间隔, 单位为微秒
+const POLL_INTERVAL_US: u32 = 100;
+
+/// 延时, 与 embedded-hal 1.0 的 `DelayNs` 相同, 使用 embedded-hal 的驱动时转发到它的 `delay_ns` 即可
+pub trait DelayNs {
+    fn delay_ns(&mut self, ns: u32);
+
+    fn delay_us(&mut self, mut us: u32) {
+        const MAX_US: u32 = u32::MAX / 1000;
+        while us > MAX_US {
+            self.delay_ns(MAX_US * 1000);
+            us -= MAX_US;
+        }
+        self.delay_ns(us * 1000);
+    }
+}
+
+/// 主站通信的错误, E 为串口驱动的错误类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtuError<E> {
+    /// 串口读写错误
+    Io(E),
+    /// 在超时时间内没有收到完整的响应
+    Timeout,
+    /// 帧编码或者校验错误
+    Codec(CodecError),
+    /// 从设备返回的异常码
+    Exception(u8),
+    /// 请求的数量超出范围
+    InvalidQuantity,
+    /// 读操作使用了广播地址
+    BroadcastRead,
+    /// 响应的从设备ID, 功能码或者长度与请求不一致
+    UnexpectedReply,
+}
+
+impl<E> From<CodecError> for RtuError<E> {
+    fn from(e: CodecError) -> Self {
+        RtuError::Codec(e)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for RtuError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtuError::Io(e) => write!(f, "串口读写错误: {:?}", e),
+            RtuError::Timeout => write!(f, "等待响应超时"),
+            RtuError::Codec(e) => write!(f, "{}", e),
+            RtuError::Exception(code) => write!(f, "从设备返回异常码: {}", code),
+            RtuError::InvalidQuantity => write!(f, "数量超出范围"),
+            RtuError::BroadcastRead => write!(f, "读操作不能使用广播地址"),
+            RtuError::UnexpectedReply => write!(f, "响应与请求不一致"),
+        }
+    }
+}
+
+/// 基于 embedded-io 串口的 Modbus RTU 主站, 不需要标准库
+///
+/// 串口驱动的读操作会一直阻塞, 这里在读之前通过 `read_ready` 轮询, 用 delay 计算超时
+pub struct RtuMaster<T, D> {
+    serial: T,
+    delay: D,
+    timeout_us: u32,
+    frame_gap_us: u32,
+    buf: [u8; RTU_MAX_FRAME_SIZE],
+}
+
+impl<T, D> RtuMaster<T, D>
+where
+    T: Read + Write + ReadReady,
+    D: DelayNs,
+{
+    /// 默认超时 1s, 帧之间的静默间隔按 19200 波特率计算
+    pub fn new(serial: T, delay: D) -> Self {
+        Self {
+            serial,
+            delay,
+            timeout_us: 1_000_000,
+            frame_gap_us: frame_gap_us(19200),
+            buf: [0; RTU_MAX_FRAME_SIZE],
+        }
+    }
+
+    /// 等待一个完整响应的最长时间, 单位为微秒
+    pub fn timeout_us(mut self, timeout_us: u32) -> Self {
+        self.timeout_us = timeout_us;
+        self
+    }
+
+    /// 串口的波特率, 用于计算帧之间的静默间隔 t3.5
+    pub fn baud_rate(mut self, baud_rate: u32) -> Self {
+        self.frame_gap_us = frame_gap_us(baud_rate);
+        self
+    }
+
+    /// 取回串口和延时
+    pub fn release(self) -> (T, D) {
+        (self.serial, self.delay)
+    }
+
+    /// 发送请求 PDU, 返回响应 PDU (包含功能码). 广播请求不等待响应, 返回空的数据
+    pub fn transact(&mut self, id: Id, pdu: &[u8]) -> Result<&[u8], RtuError<T::Error>> {
+        self.clear_input()?;
+        let len = codec::encode_rtu(id, pdu, &mut self.buf)?;
+        self.delay.delay_us(self.frame_gap_us);
+        self.serial
+            .write_all(&self.buf[..len])
+            .map_err(RtuError::Io)?;
+        self.serial.flush().map_err(RtuError::Io)?;
+        if id == BROADCAST_ID {
+            return Ok(&[]);
+        }
+
+        let len = self.read_frame()?;
+        let (reply_id, reply) = codec::decode_rtu(&self.buf[..len])?;
+        if reply_id != id {
+            return Err(RtuError::UnexpectedReply);
+        }
+        match reply {
+            [function, code] if *function == pdu[0] | 0x80 => Err(RtuError::Exception(*code)),
+            [function, ..] if *function == pdu[0] => Ok(reply),
+            _ => Err(RtuError::UnexpectedReply),
+        }
+    }
+
+    /// 读保持寄存器 (功能码 0x03), 读取 out.len() 个寄存器到 out 中, 最多 125 个
+    pub fn read_holding_registers(
+        &mut self,
+        id: Id,
+        address: u16,
+        out: &mut [u16],
+    ) -> Result<(), RtuError<T::Error>> {
+        self.read_registers(0x03, id, address, out)
+    }
+
+    /// 读输入寄存器 (功能码 0x04), 读取 out.len() 个寄存器到 out 中, 最多 125 个
+    pub fn read_input_registers(
+        &mut self,
+        id: Id,
+        address: u16,
+        out: &mut [u16],
+    ) -> Result<(), RtuError<T::Error>> {
+        self.read_registers(0x04, id, address, out)
+    }
+
+    /// 写单个寄存器 (功能码 0x06)
+    pub fn write_single_register(
+        &mut self,
+        id: Id,
+        address: u16,
+        value: u16,
+    ) -> Result<(), RtuError<T::Error>> {
+        let [a0, a1] = address.to_be_bytes();
+        let [v0, v1] = value.to_be_bytes();
+        let pdu = [0x06, a0, a1, v0, v1];
+        let reply = self.transact(id, &pdu)?;
+        if !reply.is_empty() && reply != pdu {
+            return Err(RtuError::UnexpectedReply);
+        }
+        Ok(())
+    }
+
+    /// 写多个寄存器 (功能码 0x10), 最多 123 个
+    pub fn write_multiple_registers(
+        &mut self,
+        id: Id,
+        address: u16,
+        values: &[u16],
+    ) -> Result<(), RtuError<T::Error>> {
+        if values.is_empty() || values.len() > MAX_WRITE_REGISTERS as usize {
+            return Err(RtuError::InvalidQuantity);
+        }
+        let mut pdu = [0u8; MODBUS_MAX_PDU_SIZE];
+        pdu[0] = 0x10;
+        pdu[1..3].copy_from_slice(&address.to_be_bytes());
+        pdu[3..5].copy_from_slice(&(values.len() as u16).to_be_bytes());
+        pdu[5] = (values.len() * 2) as u8;
+        for (chunk, value) in pdu[6..].chunks_exact_mut(2).zip(values) {
+            chunk.copy_from_slice(&value.to_be_bytes());
+        }
+        let len = 6 + values.len() * 2;
+        let reply = self.transact(id, &pdu[..len])?;
+        if !reply.is_empty() && reply != &pdu[..5] {
+            return Err(RtuError::UnexpectedReply);
+        }
+        Ok(())
+    }
+
+    fn read_registers(
+        &mut self,
+        function: u8,
+        id: Id,
+        address: u16,
+        out: &mut [u16],
+    ) -> Result<(), RtuError<T::Error>> {
+        // 广播请求不会有响应, 不发送
+        if id == BROADCAST_ID {
+            return Err(RtuError::BroadcastRead);
+        }
+        if out.is_empty() || out.len() > MAX_READ_REGISTERS as usize {
+            return Err(RtuError::InvalidQuantity);
+        }
+        let [a0, a1] = address.to_be_bytes();
+        let [q0, q1] = (out.len() as u16).to_be_bytes();
+        let reply = self.transact(id, &[function, a0, a1, q0, q1])?;
+        // FUN(1) + 字节数(1) + 数据
+        match reply {
+            [_, count, data @ ..]
+                if *count as usize == data.len() && data.len() == out.len() * 2 =>
+            {
+                for (value, bytes) in out.iter_mut().zip(data.chunks_exact(2)) {
+                    *value = u16::from_be_bytes([bytes[0], bytes[1]]);
+                }
+                Ok(())
+            }
+            _ => Err(RtuError::UnexpectedReply),
+        }
+    }
+
+    /// 丢弃之前超时的请求残留在串口中的数据
+    fn clear_input(&mut self) -> Result<(), RtuError<T::Error>> {
+        while self.serial.read_ready().map_err(RtuError::Io)? {
+            if self.serial.read(&mut self.buf).map_err(RtuError::Io)? == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// 根据功能码和字节数字段接收一个完整的 RTU 帧, 返回帧的长度
+    fn read_frame(&mut self) -> Result<usize, RtuError<T::Error>> {
+        let mut waited_us = 0;
+        let mut len = 0;
+        loop {
+            let end = match codec::rtu_remaining(&self.buf[..len], 2) {
+                Some(0) => return Ok(len),
+                Some(n) if len + n <= self.buf.len() => len + n,
+                // 无法确定长度, 或者超过 RTU 帧的最大长度
+                _ => return Err(RtuError::UnexpectedReply),
+            };
+            while !self.serial.read_ready().map_err(RtuError::Io)? {
+                if waited_us >= self.timeout_us {
+                    return Err(RtuError::Timeout);
+                }
+                self.delay.delay_us(POLL_INTERVAL_US);
+                waited_us += POLL_INTERVAL_US;
+            }
+            len += self
+                .serial
+                .read(&mut self.buf[len..end])
+                .map_err(RtuError::Io)?;
+        }
+    }
+}
+
+/// 帧之间的静默间隔 t3.5, 一个字符按 11 位计算, 波特率高于 19200 时固定为 1750us
+fn frame_gap_us(baud_rate: u32) -> u32 {
+    if baud_rate > 19200 {
+        return 1750;
+    }
+    11_000_000 / baud_rate.max(1) * 7 / 2
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{collections::VecDeque, convert::Infallible, vec::Vec};
+
+    use embedded_io::{ErrorType, Read, ReadReady, Write};
+
+    use super::{DelayNs, RtuError, RtuMaster};
+    use crate::calc_crc;
+
+    /// 模拟的 UART: 记录写入的数据, 按顺序返回预先准备的数据
+    #[derive(Default)]
+    struct MockUart {
+        rx: VecDeque<u8>,
+        tx: Vec<u8>,
+        /// 主站发送请求之后才能收到的数据
+        reply: Vec<u8>,
+    }
+
+    impl ErrorType for MockUart {
+        type Error = Infallible;
+    }
+
+    impl Read for MockUart {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            let n = buf.len().min(self.rx.len());
+            for (b, x) in buf.iter_mut().zip(self.rx.drain(..n)) {
+                *b = x;
+            }
+            Ok(n)
+        }
+    }
+
+    impl ReadReady for MockUart {
+        fn read_ready(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.rx.is_empty())
+        }
+    }
+
+    impl Write for MockUart {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            self.rx.extend(self.reply.drain(..));
+            Ok(())
+        }
+    }
+
+    /// 只累计延时的时间
+    #[derive(Default)]
+    struct MockDelay(u64);
+
+    impl DelayNs for MockDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0 += ns as u64;
+        }
+    }
+
+    fn rtu_frame(data: &[u8]) -> Vec<u8> {
+        let mut frame = data.to_vec();
+        frame.extend_from_slice(&calc_crc(data).to_be_bytes());
+        frame
+    }
+
+    fn master(reply: &[u8]) -> RtuMaster<MockUart, MockDelay> {
+        let uart = MockUart {
+            reply: reply.to_vec(),
+            ..Default::default()
+        };
+        RtuMaster::new(uart, MockDelay::default())
+    }
+
+    #[test]
+    fn read_holding_registers() {
+        let mut master = master(&rtu_frame(&[0x01, 0x03, 0x04, 0x12, 0x34, 0xAB, 0xCD]));
+        // 之前超时的请求残留的数据
+        master.serial.rx.extend([0x01, 0x03, 0x02]);
+        let mut values = [0u16; 2];
+        master
+            .read_holding_registers(1, 0x0010, &mut values)
+            .unwrap();
+        assert_eq!(values, [0x1234, 0xABCD]);
+        let (uart, _) = master.release();
+        assert_eq!(uart.tx, rtu_frame(&[0x01, 0x03, 0x00, 0x10, 0x00, 0x02]));
+    }
+
+    #[test]
+    fn write_multiple_registers() {
+        let mut master = master(&rtu_frame(&[0x02, 0x10, 0x00, 0x20, 0x00, 0x02]));
+        master
+            .write_multiple_registers(2, 0x0020, &[0x0102, 0x0304])
+            .unwrap();
+        let (uart, _) = master.release();
+        assert_eq!(
+            uart.tx,
+            rtu_frame(&[0x02, 0x10, 0x00, 0x20, 0x00, 0x02, 0x04, 0x01, 0x02, 0x03, 0x04])
+        );
+    }
+
+    #[test]
+    fn exception_reply() {
+        let mut master = master(&rtu_frame(&[0x01, 0x86, 0x02]));
+        assert_eq!(
+            master.write_single_register(1, 0x0100, 1),
+            Err(RtuError::Exception(0x02))
+        );
+    }
+
+    #[test]
+    fn timeout_uses_delay() {
+        let mut master = master(&[]).timeout_us(10_000);
+        let mut values = [0u16; 1];
+        assert_eq!(
+            master.read_input_registers(1, 0, &mut values),
+            Err(RtuError::Timeout)
+        );
+        let (_, delay) = master.release();
+        assert!(delay.0 >= 10_000_000);
+    }
+
+    #[test]
+    fn broadcast_does_not_wait() {
+        let mut master = master(&[]);
+        master.write_single_register(0, 0x0001, 1).unwrap();
+        let (uart, delay) = master.release();
+        assert_eq!(uart.tx, rtu_frame(&[0x00, 0x06, 0x00, 0x01, 0x00, 0x01]));
+        // 只有发送之前的帧间隔
+        assert_eq!(delay.0, 1_000 * super::frame_gap_us(19200) as u64);
+    }
+
+    #[test]
+    fn broadcast_read_is_rejected() {
+        let mut master = master(&[]);
+        let mut values = [0u16; 1];
+        assert_eq!(
+            master.read_holding_registers(0, 0, &mut values),
+            Err(RtuError::BroadcastRead)
+        );
+        let (uart, _) = master.release();
+        assert!(uart.tx.is_empty());
+    }
+
+    #[test]
+    fn invalid_quantity() {
+        let mut master = master(&[]);
+        assert_eq!(
+            master.read_holding_registers(1, 0, &mut []),
+            Err(RtuError::InvalidQuantity)
+        );
+        assert_eq!(
+            master.read_input_registers(1, 0, &mut [0; 126]),
+            Err(RtuError::InvalidQuantity)
+        );
+        assert_eq!(
+            master.write_multiple_registers(1, 0, &[0; 124]),
+            Err(RtuError::InvalidQuantity)
+        );
+        let (uart, _) = master.release();
+        assert!(uart.tx.is_empty());
+    }
+}
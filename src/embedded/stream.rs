@@ -0,0 +1,106 @@
+use std::{
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+use embedded_io::ReadReady;
+
+use crate::{error::Result, stream::Stream};
+
+/// 把实现了 embedded-io 的 Read + Write + ReadReady 的串口包装为 `Stream`
+///
+/// embedded-io 的读操作会一直阻塞, 这里在读之前通过 `read_ready` 轮询, 超时后返回 TimedOut
+pub struct EmbeddedStream<T> {
+    inner: T,
+    timeout: Duration,
+    baud_rate: Option<u32>,
+}
+
+impl<T> EmbeddedStream<T>
+where
+    T: embedded_io::Read + embedded_io::Write + ReadReady + Send,
+{
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            timeout: Duration::from_millis(5000),
+            baud_rate: None,
+        }
+    }
+
+    /// 设置串口的波特率, 用于计算 RTU 帧的时间间隔
+    pub fn with_baud_rate(mut self, baud_rate: u32) -> Self {
+        self.baud_rate = Some(baud_rate);
+        self
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// 等待有数据可读, 超时返回 TimedOut
+    fn wait_ready(&mut self) -> io::Result<()> {
+        let start = Instant::now();
+        loop {
+            if self.inner.read_ready().map_err(to_io_error)? {
+                return Ok(());
+            }
+            if start.elapsed() >= self.timeout {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "读数据超时"));
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+fn to_io_error(e: impl embedded_io::Error) -> io::Error {
+    io::Error::new(e.kind().into(), format!("{:?}", e))
+}
+
+impl<T> Read for EmbeddedStream<T>
+where
+    T: embedded_io::Read + embedded_io::Write + ReadReady + Send,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.wait_ready()?;
+        self.inner.read(buf).map_err(to_io_error)
+    }
+}
+
+impl<T> Write for EmbeddedStream<T>
+where
+    T: embedded_io::Read + embedded_io::Write + ReadReady + Send,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).map_err(to_io_error)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(to_io_error)
+    }
+}
+
+impl<T> Stream for EmbeddedStream<T>
+where
+    T: embedded_io::Read + embedded_io::Write + ReadReady + Send,
+{
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn baud_rate(&self) -> Option<u32> {
+        self.baud_rate
+    }
+}
@@ -0,0 +1,225 @@
+//! 寄存器与字节数据, 数值之间的批量转换
+//!
+//! Modbus 规范中寄存器内为大端, 但是很多设备使用其它顺序.
+//! 单个数值的字节顺序见 [`WordOrder`], 这里的函数处理任意长度的数据
+
+use crate::{
+    error::{Error, Reason, Result},
+    value::{RegisterValue, WordOrder},
+    Word,
+};
+
+/// 寄存器内两个字节的顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ByteOrder {
+    /// 高字节在前, Modbus 规范的顺序
+    #[default]
+    Big,
+    /// 低字节在前
+    Little,
+}
+
+/// 把字节数据转换为寄存器, 长度必须为偶数
+pub fn words_from_bytes(bytes: &[u8], order: ByteOrder) -> Result<Vec<Word>> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(Error::InvalidData(Reason::BytecountNotEven));
+    }
+    Ok(bytes
+        .chunks_exact(2)
+        .map(|b| match order {
+            ByteOrder::Big => u16::from_be_bytes([b[0], b[1]]),
+            ByteOrder::Little => u16::from_le_bytes([b[0], b[1]]),
+        })
+        .collect())
+}
+
+/// 把寄存器转换为字节数据
+pub fn bytes_from_words(words: &[Word], order: ByteOrder) -> Vec<u8> {
+    words
+        .iter()
+        .flat_map(|w| match order {
+            ByteOrder::Big => u16::to_be_bytes(*w),
+            ByteOrder::Little => u16::to_le_bytes(*w),
+        })
+        .collect()
+}
+
+/// 交换每个寄存器内的两个字节
+pub fn swap_bytes(words: &mut [Word]) {
+    for word in words {
+        *word = word.swap_bytes();
+    }
+}
+
+/// 每两个寄存器交换顺序, 用于低位寄存器在前的 32 位数据, 长度为奇数时最后一个寄存器不变
+pub fn swap_words(words: &mut [Word]) {
+    for pair in words.chunks_exact_mut(2) {
+        pair.swap(0, 1);
+    }
+}
+
+/// 把寄存器按照字节顺序转换为一组数值, 长度必须是 `T::WORDS` 的整数倍
+pub fn decode_slice<T: RegisterValue>(words: &[Word], order: WordOrder) -> Result<Vec<T>> {
+    if !words.len().is_multiple_of(T::WORDS) {
+        return Err(Error::InvalidData(Reason::UnexpectedReplySize));
+    }
+    words
+        .chunks_exact(T::WORDS)
+        .map(|chunk| order.decode(chunk))
+        .collect()
+}
+
+/// 把一组数值按照字节顺序转换为寄存器
+pub fn encode_slice<T: RegisterValue + Copy>(values: &[T], order: WordOrder) -> Vec<Word> {
+    values.iter().flat_map(|&v| order.encode(v)).collect()
+}
+
+/// 两个寄存器组成 u32, hi 为高 16 位
+pub fn u32_from_words(hi: Word, lo: Word) -> u32 {
+    (hi as u32) << 16 | lo as u32
+}
+
+/// 把 u32 拆分为 [高 16 位, 低 16 位]
+pub fn words_from_u32(value: u32) -> [Word; 2] {
+    [(value >> 16) as Word, value as Word]
+}
+
+/// 两个寄存器组成 f32, hi 为高 16 位
+pub fn f32_from_words(hi: Word, lo: Word) -> f32 {
+    f32::from_bits(u32_from_words(hi, lo))
+}
+
+/// 把 f32 拆分为 [高 16 位, 低 16 位]
+pub fn words_from_f32(value: f32) -> [Word; 2] {
+    words_from_u32(value.to_bits())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [WordOrder; 4] = [
+        WordOrder::ABCD,
+        WordOrder::CDAB,
+        WordOrder::BADC,
+        WordOrder::DCBA,
+    ];
+
+    #[test]
+    fn words_bytes_round_trip() {
+        let bytes = [0x12, 0x34, 0x56, 0x78];
+        let big = words_from_bytes(&bytes, ByteOrder::Big).unwrap();
+        assert_eq!(big, [0x1234, 0x5678]);
+        let little = words_from_bytes(&bytes, ByteOrder::Little).unwrap();
+        assert_eq!(little, [0x3412, 0x7856]);
+        assert_eq!(bytes_from_words(&big, ByteOrder::Big), bytes);
+        assert_eq!(bytes_from_words(&little, ByteOrder::Little), bytes);
+        assert!(words_from_bytes(&[], ByteOrder::Big).unwrap().is_empty());
+        assert!(bytes_from_words(&[], ByteOrder::Little).is_empty());
+    }
+
+    #[test]
+    fn words_from_odd_bytes() {
+        for order in [ByteOrder::Big, ByteOrder::Little] {
+            assert!(matches!(
+                words_from_bytes(&[0x12, 0x34, 0x56], order),
+                Err(Error::InvalidData(Reason::BytecountNotEven))
+            ));
+        }
+    }
+
+    #[test]
+    fn swap_helpers() {
+        let mut words = [0x1234, 0x5678, 0x9ABC];
+        swap_bytes(&mut words);
+        assert_eq!(words, [0x3412, 0x7856, 0xBC9A]);
+        swap_bytes(&mut words);
+        assert_eq!(words, [0x1234, 0x5678, 0x9ABC]);
+
+        // 长度为奇数时最后一个寄存器不变
+        swap_words(&mut words);
+        assert_eq!(words, [0x5678, 0x1234, 0x9ABC]);
+        swap_words(&mut words);
+        assert_eq!(words, [0x1234, 0x5678, 0x9ABC]);
+
+        let mut empty: [Word; 0] = [];
+        swap_bytes(&mut empty);
+        swap_words(&mut empty);
+    }
+
+    #[test]
+    fn slice_orders() {
+        let values = [0x1234_5678u32, 0x9ABC_DEF0];
+        let expected: [[Word; 4]; 4] = [
+            [0x1234, 0x5678, 0x9ABC, 0xDEF0],
+            [0x5678, 0x1234, 0xDEF0, 0x9ABC],
+            [0x3412, 0x7856, 0xBC9A, 0xF0DE],
+            [0x7856, 0x3412, 0xF0DE, 0xBC9A],
+        ];
+        for (order, expected) in ORDERS.into_iter().zip(expected) {
+            let words = encode_slice(&values, order);
+            assert_eq!(words, expected, "{:?}", order);
+            assert_eq!(decode_slice::<u32>(&words, order).unwrap(), values);
+        }
+    }
+
+    #[test]
+    fn slice_round_trip() {
+        for order in ORDERS {
+            let floats = [1.5f32, -0.25, f32::MAX];
+            let words = encode_slice(&floats, order);
+            assert_eq!(decode_slice::<f32>(&words, order).unwrap(), floats);
+
+            let wide = [0x0102_0304_0506_0708u64, u64::MAX];
+            let words = encode_slice(&wide, order);
+            assert_eq!(words.len(), 8);
+            assert_eq!(decode_slice::<u64>(&words, order).unwrap(), wide);
+
+            let small = [-2i16, 7, 0x1234];
+            let words = encode_slice(&small, order);
+            assert_eq!(decode_slice::<i16>(&words, order).unwrap(), small);
+
+            assert!(encode_slice::<u32>(&[], order).is_empty());
+            assert!(decode_slice::<u32>(&[], order).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn decode_slice_odd_length() {
+        for order in ORDERS {
+            assert!(matches!(
+                decode_slice::<u32>(&[1, 2, 3], order),
+                Err(Error::InvalidData(Reason::UnexpectedReplySize))
+            ));
+            assert!(matches!(
+                decode_slice::<f64>(&[1, 2, 3, 4, 5], order),
+                Err(Error::InvalidData(Reason::UnexpectedReplySize))
+            ));
+        }
+    }
+
+    #[test]
+    fn u32_combinators() {
+        assert_eq!(u32_from_words(0x1234, 0x5678), 0x1234_5678);
+        assert_eq!(words_from_u32(0x1234_5678), [0x1234, 0x5678]);
+        for value in [0, 1, 0xFFFF, 0x1_0000, 0xDEAD_BEEF, u32::MAX] {
+            let [hi, lo] = words_from_u32(value);
+            assert_eq!(u32_from_words(hi, lo), value);
+            assert_eq!(WordOrder::ABCD.encode(value), [hi, lo]);
+        }
+    }
+
+    #[test]
+    fn f32_combinators() {
+        // 1.0 = 0x3F800000
+        assert_eq!(words_from_f32(1.0), [0x3F80, 0x0000]);
+        assert_eq!(f32_from_words(0x3F80, 0x0000), 1.0);
+        for value in [0.0f32, -1.5, 123.456, f32::MIN_POSITIVE, f32::INFINITY] {
+            let [hi, lo] = words_from_f32(value);
+            assert_eq!(f32_from_words(hi, lo).to_bits(), value.to_bits());
+        }
+        let [hi, lo] = words_from_f32(f32::NAN);
+        assert!(f32_from_words(hi, lo).is_nan());
+    }
+}
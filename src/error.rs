@@ -0,0 +1,342 @@
+use std::{fmt, io};
+
+use crate::{codec::CodecError, value::ValueType, Word};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Modbus 异常码, 从设备无法处理请求时在异常响应中返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExceptionCode {
+    IllegalFunction,
+    IllegalDataAddress,
+    IllegalDataValue,
+    SlaveOrServerFailure,
+    Acknowledge,
+    SlaveOrServerBusy,
+    NegativeAcknowledge,
+    MemoryParity,
+    GatewayPath,
+    GatewayTarget,
+    /// 规范中没有定义的异常码
+    Unknown(u8),
+}
+
+impl ExceptionCode {
+    pub fn code(self) -> u8 {
+        match self {
+            ExceptionCode::IllegalFunction => 0x01,
+            ExceptionCode::IllegalDataAddress => 0x02,
+            ExceptionCode::IllegalDataValue => 0x03,
+            ExceptionCode::SlaveOrServerFailure => 0x04,
+            ExceptionCode::Acknowledge => 0x05,
+            ExceptionCode::SlaveOrServerBusy => 0x06,
+            ExceptionCode::NegativeAcknowledge => 0x07,
+            ExceptionCode::MemoryParity => 0x08,
+            ExceptionCode::GatewayPath => 0x0A,
+            ExceptionCode::GatewayTarget => 0x0B,
+            ExceptionCode::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<u8> for ExceptionCode {
+    fn from(code: u8) -> Self {
+        match code {
+            0x01 => ExceptionCode::IllegalFunction,
+            0x02 => ExceptionCode::IllegalDataAddress,
+            0x03 => ExceptionCode::IllegalDataValue,
+            0x04 => ExceptionCode::SlaveOrServerFailure,
+            0x05 => ExceptionCode::Acknowledge,
+            0x06 => ExceptionCode::SlaveOrServerBusy,
+            0x07 => ExceptionCode::NegativeAcknowledge,
+            0x08 => ExceptionCode::MemoryParity,
+            0x0A => ExceptionCode::GatewayPath,
+            0x0B => ExceptionCode::GatewayTarget,
+            code => ExceptionCode::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for ExceptionCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let desc = match self {
+            ExceptionCode::IllegalFunction => "非法功能码",
+            ExceptionCode::IllegalDataAddress => "非法数据地址",
+            ExceptionCode::IllegalDataValue => "非法数据值",
+            ExceptionCode::SlaveOrServerFailure => "从设备故障",
+            ExceptionCode::Acknowledge => "已确认, 正在处理",
+            ExceptionCode::SlaveOrServerBusy => "从设备忙",
+            ExceptionCode::NegativeAcknowledge => "否定确认",
+            ExceptionCode::MemoryParity => "存储奇偶性差错",
+            ExceptionCode::GatewayPath => "网关路径不可用",
+            ExceptionCode::GatewayTarget => "网关目标设备响应失败",
+            ExceptionCode::Unknown(_) => "未知异常",
+        };
+        write!(f, "{} ({:#04X})", desc, self.code())
+    }
+}
+
+/// 数据无效的具体原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reason {
+    /// 响应的从设备ID与请求不一致
+    UnexpectedId,
+    /// 响应的功能码与请求不一致
+    UnexpectedFunction,
+    /// 响应的事务标识与请求不一致 (TCP)
+    UnexpectedTransactionId,
+    /// 协议标识不为 0 (TCP)
+    InvalidProtocolId,
+    /// MBAP报文头中的长度字段与实际长度不一致 (TCP)
+    LengthMismatch,
+    /// 响应的长度与字节数字段不一致
+    UnexpectedReplySize,
+    /// 请求的长度与功能码或者字节数字段不一致
+    UnexpectedRequestSize,
+    /// 异常响应的格式错误
+    InvalidException,
+    /// 响应中应当原样返回的数据与请求不一致
+    UnexpectedEcho,
+    /// 无效的 Modbus ASCII 帧
+    InvalidAsciiFrame,
+    /// 字节数据非偶数, 无法转换为寄存器数据
+    BytecountNotEven,
+    /// 无效的配置
+    InvalidConfig(String),
+    /// 读操作使用了广播地址
+    BroadcastRead,
+    /// 发送的数据为空
+    SendBufferEmpty,
+    /// 发送的数据长度太大
+    SendBufferTooBig,
+    /// 数量超出范围
+    QuantityOutOfRange { quantity: u16, max: u16 },
+    /// 起始地址加上数量超出了 0xFFFF
+    AddressOverflow { address: u16, quantity: u16 },
+    /// 调用者提供的缓冲区小于请求的数量
+    OutputBufferTooSmall { quantity: u16, len: usize },
+    /// 写入的数值超出了数据类型的范围
+    ValueOutOfRange(ValueType),
+    /// 寄存器表中没有这个名称的数据点
+    UnknownPoint(String),
+    /// 没有路由到这个从设备ID的传输
+    NoRoute(u8),
+    /// 其它原因
+    Custom(String),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::UnexpectedId => write!(f, "响应ID与请求ID不一致"),
+            Reason::UnexpectedFunction => write!(f, "响应功能码与请求功能码不一致"),
+            Reason::UnexpectedTransactionId => write!(f, "响应事务标识与请求事务标识不一致"),
+            Reason::InvalidProtocolId => write!(f, "无效的协议标识"),
+            Reason::LengthMismatch => write!(f, "MBAP长度字段与实际长度不一致"),
+            Reason::UnexpectedReplySize => write!(f, "没有取到有效的数据"),
+            Reason::UnexpectedRequestSize => write!(f, "请求的长度与功能码不符"),
+            Reason::InvalidException => write!(f, "无效的异常响应"),
+            Reason::UnexpectedEcho => write!(f, "响应返回的数据与请求不一致"),
+            Reason::InvalidAsciiFrame => write!(f, "无效的ASCII帧"),
+            Reason::BytecountNotEven => write!(f, "字节数据非偶数"),
+            Reason::InvalidConfig(reason) => write!(f, "无效的配置, {}", reason),
+            Reason::BroadcastRead => write!(f, "读操作不能使用广播地址"),
+            Reason::SendBufferEmpty => write!(f, "发送的数据为空"),
+            Reason::SendBufferTooBig => write!(f, "发送的数据长度太大"),
+            Reason::QuantityOutOfRange { quantity, max } => {
+                write!(f, "数量 {} 超出范围 1..={}", quantity, max)
+            }
+            Reason::AddressOverflow { address, quantity } => {
+                write!(
+                    f,
+                    "地址 {:#06X} 开始的 {} 个数据超出地址范围",
+                    address, quantity
+                )
+            }
+            Reason::OutputBufferTooSmall { quantity, len } => {
+                write!(f, "缓冲区长度 {} 小于请求的数量 {}", len, quantity)
+            }
+            Reason::ValueOutOfRange(ty) => write!(f, "数值超出 {} 类型的范围", ty),
+            Reason::UnknownPoint(name) => write!(f, "未定义的数据点: {}", name),
+            Reason::NoRoute(id) => write!(f, "没有到从设备 {} 的路由", id),
+            Reason::Custom(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// 写入后读回的寄存器与写入的值不一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VerificationError {
+    /// 不一致的寄存器在写入数据中的下标
+    pub index: usize,
+    pub expected: Word,
+    pub actual: Word,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "第 {} 个寄存器写入 {:#06X}, 读回 {:#06X}",
+            self.index, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+#[derive(Debug)]
+pub enum Error {
+    /// 从设备返回了异常响应
+    Exception(ExceptionCode),
+    /// 等待响应超时
+    Timeout,
+    /// 响应数据的校验值错误
+    Crc,
+    /// 响应帧太短, 不足以包含完整的帧
+    ShortFrame,
+    /// 响应与请求不匹配
+    InvalidResponse(Reason),
+    /// 请求或响应中的数据无效
+    InvalidData(Reason),
+    /// 请求的数量或者地址范围不符合协议, 没有发送
+    InvalidRequest(Reason),
+    /// 写入后读回校验失败
+    Verification(VerificationError),
+    /// 传输被 [`Canceller`](crate::Canceller) 取消
+    Cancelled,
+    /// 传输层的错误
+    Io(io::Error),
+}
+
+/// 取消传输时 io::Error 携带的错误, 转换为 [`Error::Cancelled`]
+#[derive(Debug)]
+pub(crate) struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "传输已取消")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Exception(code) => write!(f, "异常响应: {}", code),
+            Error::Timeout => write!(f, "传输异常, 等待响应超时"),
+            Error::Crc => write!(f, "数据异常, 响应数据CRC错误"),
+            Error::ShortFrame => write!(f, "数据异常, 响应帧长度不足"),
+            Error::InvalidResponse(reason) => write!(f, "数据异常, {}", reason),
+            Error::InvalidData(reason) => write!(f, "无效的数据: {}", reason),
+            Error::InvalidRequest(reason) => write!(f, "无效的请求: {}", reason),
+            Error::Verification(e) => write!(f, "校验失败, {}", e),
+            Error::Cancelled => write!(f, "传输已取消"),
+            Error::Io(e) => write!(f, "传输异常, E: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Verification(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Error::Timeout,
+            _ if e.get_ref().is_some_and(|e| e.is::<Cancelled>()) => Error::Cancelled,
+            _ => Error::Io(e),
+        }
+    }
+}
+
+impl From<serialport::Error> for Error {
+    fn from(e: serialport::Error) -> Self {
+        Error::Io(e.into())
+    }
+}
+
+impl From<CodecError> for Error {
+    fn from(e: CodecError) -> Self {
+        match e {
+            CodecError::BufferTooSmall | CodecError::PduTooBig => {
+                Error::InvalidData(Reason::SendBufferTooBig)
+            }
+            CodecError::EmptyPdu => Error::InvalidData(Reason::SendBufferEmpty),
+            CodecError::ShortFrame => Error::ShortFrame,
+            CodecError::Crc => Error::Crc,
+            CodecError::InvalidProtocolId => Error::InvalidResponse(Reason::InvalidProtocolId),
+            CodecError::LengthMismatch => Error::InvalidResponse(Reason::LengthMismatch),
+        }
+    }
+}
+
+/// Error 的序列化格式, Io 错误只保留错误信息
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+enum ErrorRepr {
+    Exception(ExceptionCode),
+    Timeout,
+    Crc,
+    ShortFrame,
+    InvalidResponse(Reason),
+    InvalidData(Reason),
+    InvalidRequest(Reason),
+    Verification(VerificationError),
+    Cancelled,
+    Io(String),
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let repr = match self {
+            Error::Exception(code) => ErrorRepr::Exception(*code),
+            Error::Timeout => ErrorRepr::Timeout,
+            Error::Crc => ErrorRepr::Crc,
+            Error::ShortFrame => ErrorRepr::ShortFrame,
+            Error::InvalidResponse(reason) => ErrorRepr::InvalidResponse(reason.clone()),
+            Error::InvalidData(reason) => ErrorRepr::InvalidData(reason.clone()),
+            Error::InvalidRequest(reason) => ErrorRepr::InvalidRequest(reason.clone()),
+            Error::Verification(e) => ErrorRepr::Verification(*e),
+            Error::Cancelled => ErrorRepr::Cancelled,
+            Error::Io(e) => ErrorRepr::Io(e.to_string()),
+        };
+        repr.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Error {
+    /// Io 错误反序列化为 `io::ErrorKind::Other`
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        Ok(match ErrorRepr::deserialize(deserializer)? {
+            ErrorRepr::Exception(code) => Error::Exception(code),
+            ErrorRepr::Timeout => Error::Timeout,
+            ErrorRepr::Crc => Error::Crc,
+            ErrorRepr::ShortFrame => Error::ShortFrame,
+            ErrorRepr::InvalidResponse(reason) => Error::InvalidResponse(reason),
+            ErrorRepr::InvalidData(reason) => Error::InvalidData(reason),
+            ErrorRepr::InvalidRequest(reason) => Error::InvalidRequest(reason),
+            ErrorRepr::Verification(e) => Error::Verification(e),
+            ErrorRepr::Cancelled => Error::Cancelled,
+            ErrorRepr::Io(message) => Error::Io(io::Error::other(message)),
+        })
+    }
+}
@@ -0,0 +1,130 @@
+use bytes::{Buf, BufMut, BytesMut};
+
+use crate::{
+    error::{Error, Reason, Result},
+    Word,
+};
+
+/// 子请求的参考类型, 固定为 6
+const REFERENCE_TYPE: u8 = 6;
+
+/// 文件记录, 一个文件最多包含 10000 条记录, 每条记录为一个寄存器
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileRecord {
+    /// 文件号
+    pub file: u16,
+    /// 起始记录号
+    pub record: u16,
+    /// 记录数据
+    pub data: Vec<Word>,
+}
+
+/// 读文件记录的子请求
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileRecordRead {
+    /// 文件号
+    pub file: u16,
+    /// 起始记录号
+    pub record: u16,
+    /// 要读的记录数量
+    pub length: u16,
+}
+
+/// 读文件记录 (功能码 0x14) 的请求数据: 字节数(1) + 子请求列表
+pub(crate) fn encode_read(pdu: &mut BytesMut, requests: &[FileRecordRead]) {
+    // 每个子请求: 参考类型(1) + 文件号(2) + 记录号(2) + 记录数量(2)
+    pdu.put_u8((7 * requests.len()) as u8);
+    for req in requests {
+        pdu.put_u8(REFERENCE_TYPE);
+        pdu.put_u16(req.file);
+        pdu.put_u16(req.record);
+        pdu.put_u16(req.length);
+    }
+}
+
+/// 写文件记录 (功能码 0x15) 的请求数据: 字节数(1) + 子请求列表, 响应与请求相同
+pub(crate) fn encode_write(pdu: &mut BytesMut, records: &[FileRecord]) {
+    // 每个子请求: 参考类型(1) + 文件号(2) + 记录号(2) + 记录数量(2) + 记录数据
+    let byte_cnt: usize = records.iter().map(|r| 7 + 2 * r.data.len()).sum();
+    pdu.put_u8(byte_cnt as u8);
+    for rec in records {
+        pdu.put_u8(REFERENCE_TYPE);
+        pdu.put_u16(rec.file);
+        pdu.put_u16(rec.record);
+        pdu.put_u16(rec.data.len() as u16);
+        for d in &rec.data {
+            pdu.put_u16(*d);
+        }
+    }
+}
+
+/// 解析读文件记录的响应数据 (字节数之后), 按子请求的顺序返回
+pub(crate) fn decode_read(mut data: &[u8], requests: &[FileRecordRead]) -> Result<Vec<FileRecord>> {
+    let invalid = || Error::InvalidResponse(Reason::UnexpectedReplySize);
+    let mut records = Vec::with_capacity(requests.len());
+    for req in requests {
+        // 子响应: 长度(1) + 参考类型(1) + 记录数据, 长度包含参考类型
+        if data.len() < 2 {
+            return Err(invalid());
+        }
+        let len = data.get_u8() as usize;
+        if len != 1 + 2 * req.length as usize || data.len() < len || data.get_u8() != REFERENCE_TYPE
+        {
+            return Err(invalid());
+        }
+        let words = (0..req.length).map(|_| data.get_u16()).collect();
+        records.push(FileRecord {
+            file: req.file,
+            record: req.record,
+            data: words,
+        });
+    }
+    if !data.is_empty() {
+        return Err(invalid());
+    }
+    Ok(records)
+}
+
+/// 解析读文件记录的请求数据 (字节数之后)
+pub(crate) fn decode_read_request(mut data: &[u8]) -> Result<Vec<FileRecordRead>> {
+    if !data.len().is_multiple_of(7) {
+        return Err(Error::InvalidData(Reason::UnexpectedRequestSize));
+    }
+    let mut requests = Vec::with_capacity(data.len() / 7);
+    while !data.is_empty() {
+        if data.get_u8() != REFERENCE_TYPE {
+            return Err(Error::InvalidData(Reason::UnexpectedRequestSize));
+        }
+        requests.push(FileRecordRead {
+            file: data.get_u16(),
+            record: data.get_u16(),
+            length: data.get_u16(),
+        });
+    }
+    Ok(requests)
+}
+
+/// 解析写文件记录的请求数据 (字节数之后)
+pub(crate) fn decode_write_request(mut data: &[u8]) -> Result<Vec<FileRecord>> {
+    let invalid = || Error::InvalidData(Reason::UnexpectedRequestSize);
+    let mut records = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 7 || data.get_u8() != REFERENCE_TYPE {
+            return Err(invalid());
+        }
+        let file = data.get_u16();
+        let record = data.get_u16();
+        let length = data.get_u16() as usize;
+        if data.len() < 2 * length {
+            return Err(invalid());
+        }
+        records.push(FileRecord {
+            file,
+            record,
+            data: (0..length).map(|_| data.get_u16()).collect(),
+        });
+    }
+    Ok(records)
+}
@@ -0,0 +1,249 @@
+//! 固件升级: 把固件分块, 通过厂商定义的定制功能码发送给从设备
+//!
+//! 每个厂商的帧格式不同, 通过实现 [`TransferProtocol`] 描述开始, 数据块和结束的请求,
+//! [`FirmwareTransfer`] 负责分块, 重试和报告进度. [`BlockProtocol`] 是一个简单的参考实现
+//! ```no_run
+//! # use simple_modbus::{firmware::{BlockProtocol, FirmwareTransfer}, Client};
+//! # fn run(client: &mut Client, image: &[u8]) -> simple_modbus::error::Result<()> {
+//! let mut transfer = FirmwareTransfer::new(1, BlockProtocol::new(0x41))
+//!     .retries(5)
+//!     .on_progress(|p| println!("{}/{} 字节", p.sent, p.total));
+//! transfer.run(client, image)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    calc_crc,
+    config::invalid_config,
+    custom::{CustomRequest, ReplyLength},
+    error::{Error, Reason, Result},
+    Client, Id,
+};
+
+/// 定制功能码请求中功能码之后的数据的最大字节数
+pub const MAX_PAYLOAD: usize = 252;
+
+/// 传输的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Begin,
+    /// 第 index 个数据块, 在固件中的偏移为 offset
+    Chunk {
+        index: usize,
+        offset: usize,
+    },
+    Finish,
+}
+
+/// 厂商定义的固件传输协议
+pub trait TransferProtocol {
+    /// 每个数据块的字节数, 加上数据块的头部不能超过 [`MAX_PAYLOAD`]
+    fn chunk_size(&self) -> usize;
+
+    /// 开始传输的请求, 例如进入升级模式, 发送固件的大小. 默认没有这个阶段
+    fn begin(&self, _id: Id, _image: &[u8]) -> Option<CustomRequest> {
+        None
+    }
+
+    /// 发送一个数据块的请求
+    fn chunk(&self, id: Id, index: usize, offset: usize, data: &[u8]) -> CustomRequest;
+
+    /// 结束传输的请求, 例如发送校验值, 让从设备校验并重启. 默认没有这个阶段
+    fn finish(&self, _id: Id, _image: &[u8]) -> Option<CustomRequest> {
+        None
+    }
+
+    /// 检查从设备的确认, 不包含功能码. 返回错误时重发这个请求
+    fn check_reply(&self, _stage: Stage, _reply: &[u8]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// 传输的进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub stage: Stage,
+    /// 已经确认的字节数
+    pub sent: usize,
+    pub total: usize,
+    /// 到目前为止重试的总次数
+    pub retries: u32,
+}
+
+type ProgressCallback = Box<dyn FnMut(&Progress)>;
+
+/// 固件传输, 每个请求失败后最多重试 retries 次
+pub struct FirmwareTransfer<P: TransferProtocol> {
+    id: Id,
+    protocol: P,
+    retries: u32,
+    progress: Option<ProgressCallback>,
+}
+
+impl<P: TransferProtocol> FirmwareTransfer<P> {
+    /// 发送给从设备 id, 默认每个请求重试 3 次
+    pub fn new(id: Id, protocol: P) -> Self {
+        Self {
+            id,
+            protocol,
+            retries: 3,
+            progress: None,
+        }
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// 每个阶段完成之后调用 callback
+    pub fn on_progress(mut self, callback: impl FnMut(&Progress) + 'static) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// 发送整个固件, 任何一个请求重试之后仍然失败时返回错误
+    pub fn run(&mut self, client: &mut Client, image: &[u8]) -> Result<()> {
+        let chunk_size = self.protocol.chunk_size();
+        if chunk_size == 0 || chunk_size > MAX_PAYLOAD {
+            return Err(invalid_config(&format!("无效的数据块大小: {}", chunk_size)));
+        }
+        let mut progress = Progress {
+            stage: Stage::Begin,
+            sent: 0,
+            total: image.len(),
+            retries: 0,
+        };
+
+        if let Some(req) = self.protocol.begin(self.id, image) {
+            self.send(client, req, Stage::Begin, &mut progress)?;
+        }
+        for (index, data) in image.chunks(chunk_size).enumerate() {
+            let offset = index * chunk_size;
+            let stage = Stage::Chunk { index, offset };
+            let req = self.protocol.chunk(self.id, index, offset, data);
+            progress.sent = offset + data.len();
+            self.send(client, req, stage, &mut progress)?;
+        }
+        if let Some(req) = self.protocol.finish(self.id, image) {
+            self.send(client, req, Stage::Finish, &mut progress)?;
+        }
+        Ok(())
+    }
+
+    fn send(
+        &mut self,
+        client: &mut Client,
+        req: CustomRequest,
+        stage: Stage,
+        progress: &mut Progress,
+    ) -> Result<()> {
+        let mut retries = self.retries;
+        loop {
+            let res = client
+                .custom(req.clone())
+                .and_then(|reply| self.protocol.check_reply(stage, &reply));
+            match res {
+                Ok(()) => break,
+                Err(Error::Cancelled) => return Err(Error::Cancelled),
+                Err(e) if retries > 0 => {
+                    log::warn!("固件传输 {:?} 失败, 重试, E: {}", stage, e);
+                    retries -= 1;
+                    progress.retries += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        progress.stage = stage;
+        if let Some(callback) = &mut self.progress {
+            callback(progress);
+        }
+        Ok(())
+    }
+}
+
+/// 简单的分块协议, 所有请求使用同一个功能码, 第一个字节为子功能:
+///
+/// - 开始: `0x01` + 固件大小(4)
+/// - 数据块: `0x02` + 偏移(4) + 数据
+/// - 结束: `0x03` + 整个固件的 CRC16(2)
+///
+/// 从设备的响应为 子功能(1) + 偏移(4), 开始和结束的偏移为 0, 多字节数据为大端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockProtocol {
+    function_code: u8,
+    chunk_size: usize,
+}
+
+impl BlockProtocol {
+    const BEGIN: u8 = 0x01;
+    const CHUNK: u8 = 0x02;
+    const FINISH: u8 = 0x03;
+    /// 子功能(1) + 偏移(4)
+    const HEADER: usize = 5;
+
+    /// 数据块为允许的最大长度
+    pub fn new(function_code: u8) -> Self {
+        Self {
+            function_code,
+            chunk_size: MAX_PAYLOAD - Self::HEADER,
+        }
+    }
+
+    /// 设置数据块的字节数, 不超过 247
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.min(MAX_PAYLOAD - Self::HEADER);
+        self
+    }
+
+    fn request(&self, id: Id, payload: Vec<u8>) -> CustomRequest {
+        CustomRequest::new(id, self.function_code)
+            .payload(payload)
+            .reply_length(ReplyLength::Fixed(Self::HEADER))
+    }
+}
+
+impl TransferProtocol for BlockProtocol {
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn begin(&self, id: Id, image: &[u8]) -> Option<CustomRequest> {
+        let mut payload = vec![Self::BEGIN];
+        payload.extend_from_slice(&(image.len() as u32).to_be_bytes());
+        Some(self.request(id, payload))
+    }
+
+    fn chunk(&self, id: Id, _index: usize, offset: usize, data: &[u8]) -> CustomRequest {
+        let mut payload = vec![Self::CHUNK];
+        payload.extend_from_slice(&(offset as u32).to_be_bytes());
+        payload.extend_from_slice(data);
+        self.request(id, payload)
+    }
+
+    fn finish(&self, id: Id, image: &[u8]) -> Option<CustomRequest> {
+        let mut payload = vec![Self::FINISH];
+        payload.extend_from_slice(&calc_crc(image).to_be_bytes());
+        Some(self.request(id, payload))
+    }
+
+    fn check_reply(&self, stage: Stage, reply: &[u8]) -> Result<()> {
+        let expected = match stage {
+            Stage::Begin => (Self::BEGIN, 0),
+            Stage::Chunk { offset, .. } => (Self::CHUNK, offset as u32),
+            Stage::Finish => (Self::FINISH, 0),
+        };
+        match reply {
+            [sub_function, offset @ ..] if offset.len() == 4 => {
+                let offset = u32::from_be_bytes([offset[0], offset[1], offset[2], offset[3]]);
+                if (*sub_function, offset) == expected {
+                    Ok(())
+                } else {
+                    Err(Error::InvalidResponse(Reason::UnexpectedEcho))
+                }
+            }
+            _ => Err(Error::InvalidResponse(Reason::UnexpectedReplySize)),
+        }
+    }
+}
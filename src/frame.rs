@@ -0,0 +1,618 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::{
+    bit_bytes,
+    checksum::{Checksum, ModbusCrc, SumComplement},
+    codec,
+    config::BROADCAST_ID,
+    custom::ReplyLength,
+    error::{Error, ExceptionCode, Reason, Result},
+    file_record,
+    quantity::{MAX_READ_COILS, MAX_READ_REGISTERS, MAX_READ_WRITE_REGISTERS, MAX_WRITE_REGISTERS},
+    Address, Function, Id, Protocol, ValidationMode, Word, MBAP_HEADER_SIZE,
+    MODBUS_ASCII_MAX_FRAME_SIZE, MODBUS_MAX_PACKET_SIZE, MODBUS_MAX_PDU_SIZE,
+};
+
+/// Modbus ASCII 使用的 LRC 校验: 所有字节累加后取二进制补码
+const LRC: SumComplement = SumComplement;
+
+/// 请求帧的封装 和 响应帧的校验, 与具体的传输方式无关
+pub(crate) struct Framer {
+    pub(crate) protocol: Protocol,
+    pub(crate) checksum: Box<dyn Checksum>,
+    pub(crate) transaction_id: u16,
+    pub(crate) validation: ValidationMode,
+    /// 当前定制请求的响应长度
+    pub(crate) custom_reply: ReplyLength,
+    /// 请求帧和响应帧的缓冲区, 上一次的帧释放后可以重复使用同一块内存
+    req_buf: BytesMut,
+    reply_buf: BytesMut,
+    /// 封装帧之前的 PDU, 以及编码为 ASCII 之前的二进制帧, 每次使用前清空
+    pdu_buf: BytesMut,
+    ascii_buf: BytesMut,
+}
+
+/// 从 pool 中取出一块至少有 capacity 字节的缓冲区
+///
+/// 之前取出的缓冲区都已经释放时, reserve 会回收原来的内存, 不需要重新分配
+fn take_buf(pool: &mut BytesMut, capacity: usize) -> BytesMut {
+    pool.reserve(capacity);
+    pool.split_off(0)
+}
+
+impl Framer {
+    pub(crate) fn new(protocol: Protocol) -> Self {
+        Self {
+            protocol,
+            checksum: Box::new(ModbusCrc),
+            transaction_id: 0,
+            validation: ValidationMode::Strict,
+            custom_reply: ReplyLength::Unknown,
+            req_buf: BytesMut::new(),
+            reply_buf: BytesMut::new(),
+            pdu_buf: BytesMut::new(),
+            ascii_buf: BytesMut::new(),
+        }
+    }
+
+    /// PDU 之前的字节数
+    fn header_size(&self) -> usize {
+        match self.protocol {
+            Protocol::Rtu | Protocol::Ascii => 1,
+            Protocol::Tcp => MBAP_HEADER_SIZE,
+        }
+    }
+
+    /// PDU 之后的字节数
+    fn trailer_size(&self) -> usize {
+        match self.protocol {
+            Protocol::Rtu => self.checksum.size(),
+            Protocol::Ascii => LRC.size(),
+            Protocol::Tcp => 0,
+        }
+    }
+
+    /// 根据已经收到的 RTU 响应数据, 计算这一帧还需要接收的字节数
+    ///
+    /// 返回 None 表示无法根据功能码确定帧的长度
+    pub(crate) fn rtu_remaining(&self, frame: &[u8]) -> Option<usize> {
+        let checksum_size = self.checksum.size();
+        codec::rtu_remaining(frame, checksum_size)
+            .or_else(|| self.custom_remaining(frame, checksum_size))
+    }
+
+    /// 标准功能码以外的响应, 按照定制请求的响应长度计算
+    fn custom_remaining(&self, frame: &[u8], checksum_size: usize) -> Option<usize> {
+        let len = frame.len();
+        let total = match self.custom_reply {
+            // ID(1) + FUN(1) + 数据
+            ReplyLength::Fixed(n) => 2 + n + checksum_size,
+            // ID(1) + FUN(1) + 字节数(1) + 数据
+            ReplyLength::ByteCount => {
+                if len < 3 {
+                    return Some(3 - len);
+                }
+                3 + frame[2] as usize + checksum_size
+            }
+            ReplyLength::Unknown => return None,
+        };
+        Some(total.saturating_sub(len))
+    }
+
+    /// 根据 MBAP报文头 中的长度字段, 计算整个 TCP 帧的字节数
+    pub(crate) fn tcp_frame_size(header: &[u8]) -> usize {
+        let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        (6 + len).max(MBAP_HEADER_SIZE)
+    }
+
+    /// 收到的 ASCII 数据是否已经是完整的帧: 收到 LF, 或者达到最大长度
+    pub(crate) fn ascii_complete(frame: &[u8]) -> bool {
+        frame.last() == Some(&b'\n') || frame.len() >= MODBUS_ASCII_MAX_FRAME_SIZE
+    }
+
+    /// 响应是否属于这个请求, TCP 按事务标识判断, 串行链路上总是属于当前请求
+    pub(crate) fn is_reply_to(&self, req: &[u8], reply: &[u8]) -> bool {
+        self.protocol != Protocol::Tcp || reply[0..2] == req[0..2]
+    }
+
+    /// 请求是否需要等待响应: 广播请求 和 设置为不响应的写操作 不需要
+    pub(crate) fn expects_reply(&self, req: &[u8], write: bool, need_reply: bool) -> bool {
+        !(write && !need_reply || self.is_broadcast(req))
+    }
+
+    pub(crate) fn get_reply_data(&self, mut reply: Bytes) -> Result<Bytes> {
+        // 2 表示: FUN(1) + 字节数(1)
+        let header_size = self.header_size() + 2;
+        let trailer_size = self.trailer_size();
+        if reply.len() <= header_size + trailer_size {
+            log::info!("data: {:?}", &reply);
+            return Err(Error::ShortFrame);
+        }
+        let len = reply[header_size - 1];
+        if header_size + len as usize + trailer_size != reply.len() {
+            log::info!("data: {:?}", &reply);
+            return Err(Error::InvalidResponse(Reason::UnexpectedReplySize));
+        }
+
+        let _ = reply.split_to(header_size);
+        Ok(reply.split_to(len as usize))
+    }
+
+    /// 请求帧中的从设备ID是否为广播地址
+    ///
+    /// 只有串行链路 (RTU / ASCII) 有广播, Modbus TCP 的单元标识 0 是直接访问设备
+    pub(crate) fn is_broadcast(&self, req: &[u8]) -> bool {
+        self.protocol != Protocol::Tcp && self.slave_id(req) == Some(BROADCAST_ID)
+    }
+
+    /// 请求帧中的从站地址
+    pub(crate) fn slave_id(&self, req: &[u8]) -> Option<Id> {
+        match self.protocol {
+            Protocol::Rtu => req.first().copied(),
+            Protocol::Tcp => req.get(MBAP_HEADER_SIZE - 1).copied(),
+            Protocol::Ascii => req
+                .get(1..3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+        }
+    }
+
+    /// 帧中的功能码, 异常响应包含 0x80
+    pub(crate) fn function_code(&self, frame: &[u8]) -> Option<u8> {
+        match self.protocol {
+            Protocol::Rtu => frame.get(1).copied(),
+            Protocol::Tcp => frame.get(MBAP_HEADER_SIZE).copied(),
+            Protocol::Ascii => frame
+                .get(3..5)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+        }
+    }
+
+    /// 去掉帧头, 功能码 和 校验值, 返回响应 PDU 中功能码之后的数据
+    pub(crate) fn get_reply_pdu(&self, reply: Bytes) -> Result<Bytes> {
+        let mut pdu = self.strip_frame(reply)?;
+        if pdu.is_empty() {
+            return Err(Error::ShortFrame);
+        }
+        Ok(pdu.split_off(1))
+    }
+
+    /// 去掉帧头 和 校验值, 返回完整的响应 PDU
+    pub(crate) fn strip_frame(&self, mut reply: Bytes) -> Result<Bytes> {
+        let header_size = self.header_size();
+        let trailer_size = self.trailer_size();
+        if reply.len() < header_size + trailer_size {
+            return Err(Error::ShortFrame);
+        }
+        reply.truncate(reply.len() - trailer_size);
+        Ok(reply.split_off(header_size))
+    }
+
+    /// 检查响应帧, ASCII 格式的响应帧会被转换为二进制格式: ID + PDU + LRC
+    pub(crate) fn validate_reply(&self, req: &Bytes, reply: &mut BytesMut) -> Result<()> {
+        match self.protocol {
+            Protocol::Rtu => self.validate_rtu_reply(req, reply, self.checksum.as_ref()),
+            Protocol::Tcp => self.validate_tcp_reply(req, reply),
+            Protocol::Ascii => {
+                let req = ascii_decode(req)?;
+                *reply = ascii_decode(reply)?;
+                self.validate_rtu_reply(&req, reply, &LRC)
+            }
+        }
+    }
+
+    fn validate_rtu_reply(&self, req: &[u8], reply: &[u8], checksum: &dyn Checksum) -> Result<()> {
+        let req_len = req.len();
+        let reply_len = reply.len();
+
+        // 检查数据长度: ID(1) + FUN(1) + 数据 + 校验值, 校验值的长度由 Checksum 决定
+        let min_len = 3.max(2 + checksum.size());
+        if req_len < min_len || reply_len < min_len {
+            return Err(Error::ShortFrame);
+        }
+
+        // 检查ID
+        self.check(req.first() == reply.first(), Reason::UnexpectedId)?;
+
+        // 检查异常响应: ID(1) + FUN|0x80(1) + 异常码(1) + 校验值
+        if reply[1] == req[1] | 0x80 {
+            if reply_len != 3 + checksum.size() || !checksum.verify(reply) {
+                return Err(Error::InvalidResponse(Reason::InvalidException));
+            }
+            return Err(Error::Exception(ExceptionCode::from(reply[2])));
+        }
+
+        // 检查功能码
+        self.check(req.get(1) == reply.get(1), Reason::UnexpectedFunction)?;
+
+        // 检查reply的校验值
+        if !checksum.verify(reply) {
+            return Err(Error::Crc);
+        }
+        let size = checksum.size();
+        self.check(
+            echo_matches(&req[1..req_len - size], &reply[1..reply_len - size]),
+            Reason::UnexpectedEcho,
+        )
+    }
+
+    fn validate_tcp_reply(&self, req: &Bytes, reply: &BytesMut) -> Result<()> {
+        if req.len() <= MBAP_HEADER_SIZE {
+            return Err(Error::ShortFrame);
+        }
+
+        // 检查 协议标识 和 长度字段
+        let (transaction_id, _, _) = codec::decode_tcp(reply)?;
+
+        // 检查事务标识
+        self.check(
+            req[0..2] == transaction_id.to_be_bytes(),
+            Reason::UnexpectedTransactionId,
+        )?;
+
+        // 检查单元标识
+        self.check(req[6] == reply[6], Reason::UnexpectedId)?;
+
+        // 检查异常响应: FUN|0x80(1) + 异常码(1)
+        if reply[7] == req[7] | 0x80 {
+            if reply.len() != MBAP_HEADER_SIZE + 2 {
+                return Err(Error::InvalidResponse(Reason::InvalidException));
+            }
+            return Err(Error::Exception(ExceptionCode::from(reply[8])));
+        }
+
+        // 检查功能码
+        self.check(req[7] == reply[7], Reason::UnexpectedFunction)?;
+        self.check(
+            echo_matches(&req[MBAP_HEADER_SIZE..], &reply[MBAP_HEADER_SIZE..]),
+            Reason::UnexpectedEcho,
+        )
+    }
+
+    /// 按照检查模式处理一项检查的结果, 检查失败但不是致命错误时只记录警告
+    fn check(&self, ok: bool, reason: Reason) -> Result<()> {
+        if ok {
+            return Ok(());
+        }
+        if self.validation.is_fatal(&reason) {
+            return Err(Error::InvalidResponse(reason));
+        }
+        log::warn!("忽略响应检查的错误: {}", reason);
+        Ok(())
+    }
+
+    /// 给 PDU 加上 从设备ID 和 校验值 (RTU), MBAP报文头 (TCP), 或者编码为 ASCII 帧
+    fn frame(&mut self, id: Id, pdu: &[u8]) -> BytesMut {
+        match self.protocol {
+            Protocol::Rtu => {
+                let capacity = 1 + pdu.len() + self.checksum.size();
+                let mut req = take_buf(&mut self.req_buf, capacity);
+                req.put_u8(id);
+                req.put_slice(pdu);
+                let checksum = self.checksum.calc(&req);
+                req.put_slice(&checksum);
+                req
+            }
+            Protocol::Tcp => {
+                self.transaction_id = self.transaction_id.wrapping_add(1);
+                let mut req = take_buf(&mut self.req_buf, MBAP_HEADER_SIZE + pdu.len());
+                req.put_u16(self.transaction_id);
+                req.put_u16(0);
+                req.put_u16(1 + pdu.len() as u16);
+                req.put_u8(id);
+                req.put_slice(pdu);
+                req
+            }
+            Protocol::Ascii => {
+                let bin = &mut self.ascii_buf;
+                bin.clear();
+                bin.reserve(1 + pdu.len() + LRC.size());
+                bin.put_u8(id);
+                bin.put_slice(pdu);
+                let lrc = LRC.calc(bin);
+                bin.put_slice(&lrc);
+                let mut req = take_buf(&mut self.req_buf, 1 + bin.len() * 2 + 2);
+                ascii_encode_into(bin, &mut req);
+                req
+            }
+        }
+    }
+
+    /// 封装原始的请求 PDU, 响应长度未知, 按最大长度准备
+    pub(crate) fn build_pdu(&mut self, id: Id, pdu: &[u8]) -> Result<(Bytes, BytesMut)> {
+        if pdu.is_empty() {
+            return Err(Error::InvalidData(Reason::SendBufferEmpty));
+        }
+        let req = self.frame(id, pdu);
+        let reply = self.reply_buffer(MODBUS_MAX_PDU_SIZE);
+        self.check_request(req, reply)
+    }
+
+    pub(crate) fn build_buffer(&mut self, fun: Function) -> Result<(Bytes, BytesMut)> {
+        validate_request(&fun)?;
+        let (req, reply_len) = self.frame_request(fun)?;
+        let reply = self.reply_buffer(reply_len);
+        self.check_request(req, reply)
+    }
+
+    /// 在 pdu_buf 中生成 PDU 后封装为完整的帧, 返回请求帧和响应 PDU 的长度
+    fn frame_request(&mut self, fun: Function) -> Result<(BytesMut, usize)> {
+        let mut pdu = std::mem::take(&mut self.pdu_buf);
+        pdu.clear();
+        pdu.reserve(MODBUS_MAX_PDU_SIZE);
+        let res = request_pdu(fun, &mut pdu);
+        let res = res.map(|(id, reply_len)| (self.frame(id, &pdu), reply_len));
+        self.pdu_buf = pdu;
+        res
+    }
+
+    /// 准备接收响应的缓冲区, PDU 的长度为 pdu_len
+    fn reply_buffer(&mut self, pdu_len: usize) -> BytesMut {
+        let len = self.header_size() + pdu_len + self.trailer_size();
+        let mut reply = take_buf(&mut self.reply_buf, len);
+        reply.resize(len, 0);
+        reply
+    }
+
+    fn check_request(&self, req: BytesMut, reply: BytesMut) -> Result<(Bytes, BytesMut)> {
+        if req.is_empty() {
+            return Err(Error::InvalidData(Reason::SendBufferEmpty));
+        }
+
+        let max_size = match self.protocol {
+            Protocol::Rtu | Protocol::Tcp => MODBUS_MAX_PACKET_SIZE,
+            Protocol::Ascii => MODBUS_ASCII_MAX_FRAME_SIZE,
+        };
+        if req.len() > max_size {
+            return Err(Error::InvalidData(Reason::SendBufferTooBig));
+        }
+
+        Ok((req.freeze(), reply))
+    }
+}
+
+/// 检查写操作的响应是否原样返回了请求中的 地址, 数值 或者 数量,
+/// 避免把总线上其它请求的响应当作这个请求的响应
+fn echo_matches(req: &[u8], reply: &[u8]) -> bool {
+    let echo = match req.first() {
+        // 写单个线圈/寄存器, 按位修改寄存器, 写文件记录: 响应与请求相同
+        Some(0x05 | 0x06 | 0x15 | 0x16) => req,
+        // 写多个线圈/寄存器: FUN(1) + 起始地址(2) + 数量(2)
+        Some(0x0F | 0x10) => req.get(..5).unwrap_or(req),
+        _ => return true,
+    };
+    reply == echo
+}
+
+/// 检查请求的数量和地址范围是否符合协议, 有的从设备收到不合规的请求时会返回错乱的数据
+fn validate_request(fun: &Function) -> Result<()> {
+    let check = |address: Address, quantity: usize, max: u16| {
+        let quantity_u16 = u16::try_from(quantity).unwrap_or(u16::MAX);
+        if quantity == 0 || quantity > max as usize {
+            return Err(Error::InvalidRequest(Reason::QuantityOutOfRange {
+                quantity: quantity_u16,
+                max,
+            }));
+        }
+        if address as usize + quantity > 0x10000 {
+            return Err(Error::InvalidRequest(Reason::AddressOverflow {
+                address,
+                quantity: quantity_u16,
+            }));
+        }
+        Ok(())
+    };
+    match fun {
+        Function::ReadCoils(_, address, quantity)
+        | Function::ReadDiscreteInputs(_, address, quantity) => {
+            check(*address, quantity.get() as usize, MAX_READ_COILS)
+        }
+        Function::ReadHoldingRegisters(_, address, quantity)
+        | Function::ReadInputRegisters(_, address, quantity) => {
+            check(*address, quantity.get() as usize, MAX_READ_REGISTERS)
+        }
+        Function::WriteMultipleRegisters(_, address, data) => {
+            check(*address, data.len(), MAX_WRITE_REGISTERS)
+        }
+        Function::ReadWriteMultipleRegisters(_, read_address, quantity, write_address, data) => {
+            check(*read_address, quantity.get() as usize, MAX_READ_REGISTERS)?;
+            check(*write_address, data.len(), MAX_READ_WRITE_REGISTERS)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 要写入的寄存器的 数量 和 字节数, 字节数超过一个字节能表示的范围时返回错误
+fn register_counts(data: &[Word], max: u16) -> Result<(u16, u8)> {
+    let out_of_range = || {
+        Error::InvalidRequest(Reason::QuantityOutOfRange {
+            quantity: u16::try_from(data.len()).unwrap_or(u16::MAX),
+            max,
+        })
+    };
+    let word_cnt = u16::try_from(data.len()).map_err(|_| out_of_range())?;
+    let byte_cnt = u8::try_from(2 * data.len()).map_err(|_| out_of_range())?;
+    Ok((word_cnt, byte_cnt))
+}
+
+/// 把请求的 PDU 写入 pdu, 返回 从设备ID 和 响应 PDU 的长度
+fn request_pdu(fun: Function, pdu: &mut BytesMut) -> Result<(Id, usize)> {
+    // 3 表示: FUN(1) + ADDR(2)
+    // reply_len 表示发送数据后, 返回的 PDU 的长度
+    Ok(match fun {
+        Function::WriteSingleRegister(id, addr, data) => {
+            // 2 表示: 需要2个字节, 用于保存一个word的data,
+            pdu.put_u8(0x06);
+            pdu.put_u16(addr);
+            pdu.put_u16(data);
+            (id, 5)
+        }
+        Function::WriteMultipleRegisters(id, addr, data) => {
+            // 2 表示: 需要2个字节, 用于保存 数据的数量 即word的数量
+            // 1 表示: 需要1个字节, 用于保存 要写的数据的字节数
+
+            // byte_cnt 表示: 需要 byte_cnt 个字节, 用于保存 要写的数据
+
+            let (word_cnt, byte_cnt) = register_counts(&data, MAX_WRITE_REGISTERS)?;
+            pdu.put_u8(0x10);
+            pdu.put_u16(addr);
+            pdu.put_u16(word_cnt);
+            pdu.put_u8(byte_cnt);
+            for d in data {
+                pdu.put_u16(d);
+            }
+            (id, 5)
+        }
+        Function::ReadCoils(id, addr, quantity) => {
+            // 2 表示: 需要2个字节, 用于保存 需要读取的线圈的数量
+            pdu.put_u8(0x01);
+            pdu.put_u16(addr);
+            pdu.put_u16(quantity.get());
+            (id, 2 + bit_bytes(quantity.get()))
+        }
+        Function::ReadDiscreteInputs(id, addr, quantity) => {
+            // 2 表示: 需要2个字节, 用于保存 需要读取的离散输入的数量
+            pdu.put_u8(0x02);
+            pdu.put_u16(addr);
+            pdu.put_u16(quantity.get());
+            (id, 2 + bit_bytes(quantity.get()))
+        }
+        Function::ReadHoldingRegisters(id, addr, quantity) => {
+            // 2 表示: 需要2个字节, 用于保存 需要读取的数据的数量
+            pdu.put_u8(0x03);
+            pdu.put_u16(addr);
+            pdu.put_u16(quantity.get());
+            (id, 2 + quantity.get() as usize * 2)
+        }
+        Function::ReadInputRegisters(id, addr, quantity) => {
+            // 2 表示: 需要2个字节, 用于保存 需要读取的数据的数量
+            pdu.put_u8(0x04);
+            pdu.put_u16(addr);
+            pdu.put_u16(quantity.get());
+            (id, 2 + quantity.get() as usize * 2)
+        }
+        Function::MaskWriteRegister(id, addr, and_mask, or_mask) => {
+            // 4 表示: AND掩码(2) + OR掩码(2), 响应与请求相同
+            pdu.put_u8(0x16);
+            pdu.put_u16(addr);
+            pdu.put_u16(and_mask);
+            pdu.put_u16(or_mask);
+            (id, 7)
+        }
+        Function::Diagnostics(id, sub_function, data) => {
+            // 子功能码(2) + 数据(2), 响应与请求相同
+            pdu.put_u8(0x08);
+            pdu.put_u16(sub_function);
+            pdu.put_u16(data);
+            (id, 5)
+        }
+        // 只有功能码, 响应: FUN(1) + 异常状态(1)
+        Function::ReadExceptionStatus(id) => {
+            pdu.put_u8(0x07);
+            (id, 2)
+        }
+        // 只有功能码, 响应: FUN(1) + 状态字(2) + 事件计数(2)
+        Function::GetCommEventCounter(id) => {
+            pdu.put_u8(0x0B);
+            (id, 5)
+        }
+        // 只有功能码, 响应: FUN(1) + 字节数(1) + 状态字(2) + 事件计数(2) + 报文计数(2) + 最多64个事件
+        Function::GetCommEventLog(id) => {
+            pdu.put_u8(0x0C);
+            (id, 8 + 64)
+        }
+        // 只有功能码, 响应: FUN(1) + 字节数(1) + 从设备ID + 运行状态(1) + 附加数据, 长度由设备决定
+        Function::ReportServerId(id) => {
+            pdu.put_u8(0x11);
+            (id, MODBUS_MAX_PDU_SIZE)
+        }
+        Function::ReadFifoQueue(id, addr) => {
+            pdu.put_u8(0x18);
+            pdu.put_u16(addr);
+            // FUN(1) + 字节数(2) + FIFO数量(2) + 最多31个数据
+            (id, 5 + 31 * 2)
+        }
+        Function::ReadFileRecord(id, requests) => {
+            pdu.put_u8(0x14);
+            file_record::encode_read(pdu, &requests);
+            // FUN(1) + 字节数(1) + 每个子响应: 长度(1) + 参考类型(1) + 记录数据
+            let data_len: usize = requests.iter().map(|r| 2 + 2 * r.length as usize).sum();
+            (id, 2 + data_len)
+        }
+        Function::WriteFileRecord(id, records) => {
+            pdu.put_u8(0x15);
+            file_record::encode_write(pdu, &records);
+            let reply_len = pdu.len();
+            (id, reply_len)
+        }
+        Function::ReadDeviceIdentification(id, code, object_id) => {
+            // MEI类型(1) + 读设备ID码(1) + 对象ID(1), 响应长度不固定, 按最大长度准备
+            pdu.put_u8(0x2B);
+            pdu.put_u8(0x0E);
+            pdu.put_u8(code);
+            pdu.put_u8(object_id);
+            (id, MODBUS_MAX_PDU_SIZE)
+        }
+        Function::ReadWriteMultipleRegisters(id, read_addr, quantity, write_addr, data) => {
+            // 读地址(2) + 读数量(2) + 写地址(2) + 写数量(2) + 字节数(1) + 写入的数据
+            let (word_cnt, byte_cnt) = register_counts(&data, MAX_READ_WRITE_REGISTERS)?;
+            pdu.put_u8(0x17);
+            pdu.put_u16(read_addr);
+            pdu.put_u16(quantity.get());
+            pdu.put_u16(write_addr);
+            pdu.put_u16(word_cnt);
+            pdu.put_u8(byte_cnt);
+            for d in data {
+                pdu.put_u16(d);
+            }
+            (id, 2 + quantity.get() as usize * 2)
+        }
+        Function::Custom(req) => {
+            pdu.put_u8(req.function_code);
+            pdu.put_slice(&req.payload);
+            let reply_len = match req.reply_length {
+                ReplyLength::Fixed(n) => 1 + n,
+                ReplyLength::ByteCount | ReplyLength::Unknown => MODBUS_MAX_PDU_SIZE,
+            };
+            (req.id, reply_len)
+        }
+    })
+}
+
+/// 编码为 Modbus ASCII 帧: ':' + 十六进制字符 + CRLF
+pub(crate) fn ascii_encode(frame: &[u8]) -> BytesMut {
+    let mut res = BytesMut::with_capacity(1 + frame.len() * 2 + 2);
+    ascii_encode_into(frame, &mut res);
+    res
+}
+
+/// 编码为 Modbus ASCII 帧, 追加到 res 之后
+fn ascii_encode_into(frame: &[u8], res: &mut BytesMut) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    res.put_u8(b':');
+    for b in frame {
+        res.put_u8(HEX[(b >> 4) as usize]);
+        res.put_u8(HEX[(b & 0x0f) as usize]);
+    }
+    res.put_slice(b"\r\n");
+}
+
+/// 解码 Modbus ASCII 帧, 返回二进制格式的 ID + PDU + LRC
+pub(crate) fn ascii_decode(frame: &[u8]) -> Result<BytesMut> {
+    let invalid = || Error::InvalidResponse(Reason::InvalidAsciiFrame);
+    let hex = frame
+        .strip_prefix(b":")
+        .and_then(|frame| frame.strip_suffix(b"\r\n"))
+        .ok_or_else(invalid)?;
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    let mut res = BytesMut::with_capacity(hex.len() / 2);
+    for pair in hex.chunks(2) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        let b = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+        res.put_u8(b);
+    }
+    Ok(res)
+}
@@ -0,0 +1,222 @@
+use std::{
+    io::{self, Read, Write},
+    net::{self, SocketAddr, TcpListener},
+    time::Duration,
+};
+
+use crate::{
+    error::{Error, ExceptionCode, Reason, Result},
+    server::ConnectionLimits,
+    shared::SharedClient,
+    MBAP_HEADER_SIZE,
+};
+
+/// Modbus TCP ⇄ RTU 网关
+///
+/// 接受 Modbus TCP 连接, 把请求中的单元标识作为从设备ID, 通过串口客户端转发,
+/// 再把响应封装为 Modbus TCP 帧返回. 每个连接使用一个线程, 串口上的请求依次执行.
+/// 连接数和空闲超时的限制与 [`TcpServer`](crate::server::TcpServer) 相同
+///
+/// 单元标识 0 在串口上作为广播转发: 写请求返回与请求对应的正常响应, 其它请求返回异常码 0x0B
+pub struct Gateway {
+    listener: TcpListener,
+    client: SharedClient,
+    limits: ConnectionLimits,
+}
+
+impl Gateway {
+    /// 监听 addr, 格式为 `host:port`. 默认最多 32 个连接, 每个客户端地址不限, 空闲 60s 后断开
+    pub fn bind(addr: &str, client: impl Into<SharedClient>) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            client: client.into(),
+            limits: ConnectionLimits::default(),
+        })
+    }
+
+    /// 同时连接的最大数量
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.limits.max_connections = max;
+        self
+    }
+
+    /// 每个客户端 IP 地址同时连接的最大数量
+    pub fn max_connections_per_peer(mut self, max: usize) -> Self {
+        self.limits.max_connections_per_peer = max;
+        self
+    }
+
+    /// 连接上没有收到请求的最长时间, None 或者 0 表示不断开
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.limits.set_idle_timeout(timeout);
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// 当前的连接数
+    pub fn connections(&self) -> usize {
+        self.limits.count()
+    }
+
+    /// 接受连接并转发请求, 一直运行. 接受连接失败 (例如文件描述符用完) 时只记录警告
+    pub fn run(&self) -> Result<()> {
+        let client = self.client.clone();
+        self.limits
+            .run(&self.listener, move |stream| serve(stream, &client));
+        Ok(())
+    }
+}
+
+/// 处理一个 TCP 连接上的所有请求
+fn serve(mut stream: net::TcpStream, client: &SharedClient) -> io::Result<()> {
+    loop {
+        let (header, pdu) = read_request(&mut stream)?;
+        let uid = header[6];
+        let reply = match client.lock().transact_pdu(uid, &pdu) {
+            // 串口上的广播请求没有响应, TCP 客户端仍然需要一个响应
+            Ok(reply) if reply.is_empty() => broadcast_reply(&pdu),
+            Ok(reply) => reply.to_vec(),
+            Err(e) => {
+                log::debug!("网关转发失败, ID: {}, E: {}", uid, e);
+                vec![pdu[0] | 0x80, exception_code(&e).code()]
+            }
+        };
+        write_reply(&mut stream, &header, &reply)?;
+    }
+}
+
+/// 读取一个 Modbus TCP 请求, 返回 MBAP报文头 和 PDU
+pub(crate) fn read_request(
+    stream: &mut impl Read,
+) -> io::Result<([u8; MBAP_HEADER_SIZE], Vec<u8>)> {
+    let mut header = [0u8; MBAP_HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+    // 协议标识必须为 0, 长度至少包含 单元标识 和 功能码
+    if header[2..4] != [0, 0] || len < 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "无效的MBAP报文头",
+        ));
+    }
+    let mut pdu = vec![0u8; len - 1];
+    stream.read_exact(&mut pdu)?;
+    Ok((header, pdu))
+}
+
+/// 使用请求的事务标识和单元标识发送响应 PDU
+pub(crate) fn write_reply(
+    stream: &mut impl Write,
+    header: &[u8; MBAP_HEADER_SIZE],
+    reply: &[u8],
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(MBAP_HEADER_SIZE + reply.len());
+    frame.extend_from_slice(&header[..4]);
+    frame.extend_from_slice(&(1 + reply.len() as u16).to_be_bytes());
+    frame.push(header[6]);
+    frame.extend_from_slice(reply);
+    stream.write_all(&frame)
+}
+
+/// 广播的写请求返回正常响应, 其它请求没有从设备响应, 返回异常码 0x0B
+fn broadcast_reply(pdu: &[u8]) -> Vec<u8> {
+    match pdu {
+        // 写单个线圈 / 寄存器: 原样返回请求
+        [0x05 | 0x06, ..] if pdu.len() == 5 => pdu.to_vec(),
+        // 写多个线圈 / 寄存器: 功能码 + 起始地址 + 数量
+        [0x0F | 0x10, ..] if pdu.len() > 5 => pdu[..5].to_vec(),
+        _ => vec![pdu[0] | 0x80, ExceptionCode::GatewayTarget.code()],
+    }
+}
+
+/// 转发失败时返回给 TCP 客户端的异常码
+fn exception_code(e: &Error) -> ExceptionCode {
+    match e {
+        Error::Exception(code) => *code,
+        // 目标设备没有响应, 或者响应无效
+        Error::Timeout | Error::Crc | Error::ShortFrame | Error::InvalidResponse(_) => {
+            ExceptionCode::GatewayTarget
+        }
+        Error::InvalidRequest(Reason::NoRoute(_)) => ExceptionCode::GatewayPath,
+        Error::InvalidData(_) | Error::InvalidRequest(_) => ExceptionCode::IllegalDataValue,
+        Error::Verification(_) => ExceptionCode::SlaveOrServerFailure,
+        Error::Cancelled | Error::Io(_) => ExceptionCode::GatewayPath,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpStream, sync::Arc, thread, time::Duration};
+
+    use super::{read_request, write_reply, Gateway};
+    use crate::{bank::RegisterBank, mock::MockStream, Client, Protocol};
+
+    /// 启动一个转发到模拟 RTU 从设备的网关, 返回连接到网关的 TCP 客户端
+    fn connect(bank: &Arc<RegisterBank>) -> TcpStream {
+        let stream = MockStream::with_bank(bank.clone(), Protocol::Rtu);
+        let mut client = Client::new(Box::new(stream)).unwrap();
+        client.set_timeout(Duration::from_millis(200)).unwrap();
+        let gateway = Gateway::bind("127.0.0.1:0", client).unwrap();
+        let addr = gateway.local_addr().unwrap();
+        thread::spawn(move || gateway.run());
+        let stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        stream
+    }
+
+    fn request(stream: &mut TcpStream, uid: u8, pdu: &[u8]) -> Vec<u8> {
+        write_reply(stream, &[0x12, 0x34, 0, 0, 0, 0, uid], pdu).unwrap();
+        let (header, reply) = read_request(stream).unwrap();
+        assert_eq!(header[..2], [0x12, 0x34]);
+        assert_eq!(header[6], uid);
+        reply
+    }
+
+    #[test]
+    fn forwards_requests() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 8));
+        bank.set_holding_register(0x0001, 0xABCD).unwrap();
+        let mut stream = connect(&bank);
+
+        let reply = request(&mut stream, 1, &[0x03, 0x00, 0x01, 0x00, 0x01]);
+        assert_eq!(reply, [0x03, 0x02, 0xAB, 0xCD]);
+        let reply = request(&mut stream, 1, &[0x06, 0x00, 0x02, 0x12, 0x34]);
+        assert_eq!(reply, [0x06, 0x00, 0x02, 0x12, 0x34]);
+        assert_eq!(bank.get_holding_register(0x0002).unwrap(), 0x1234);
+    }
+
+    #[test]
+    fn passes_exceptions_through() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 8));
+        let mut stream = connect(&bank);
+
+        // 地址超出范围
+        let reply = request(&mut stream, 1, &[0x03, 0x00, 0x10, 0x00, 0x01]);
+        assert_eq!(reply, [0x83, 0x02]);
+    }
+
+    #[test]
+    fn unit_id_zero_always_gets_a_reply() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 8));
+        let mut stream = connect(&bank);
+
+        let reply = request(&mut stream, 0, &[0x06, 0x00, 0x03, 0x00, 0x07]);
+        assert_eq!(reply, [0x06, 0x00, 0x03, 0x00, 0x07]);
+        let reply = request(
+            &mut stream,
+            0,
+            &[0x10, 0x00, 0x04, 0x00, 0x01, 0x02, 0x00, 0x08],
+        );
+        assert_eq!(reply, [0x10, 0x00, 0x04, 0x00, 0x01]);
+        assert_eq!(bank.get_holding_registers(0x0003, 2).unwrap(), [7, 8]);
+
+        // 读请求在串口上没有响应
+        let reply = request(&mut stream, 0, &[0x03, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(reply, [0x83, 0x0B]);
+    }
+}
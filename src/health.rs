@@ -0,0 +1,92 @@
+//! 通信健康状态: 按照连续失败的次数判断数据的质量
+//!
+//! [`Poller`](crate::poll::Poller) 为每个任务和每个从设备记录健康状态,
+//! 每次轮询的结果都带有按照 [`HealthPolicy`] 计算的 [`Quality`]
+
+use std::time::SystemTime;
+
+/// 数据的质量
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Quality {
+    /// 最近一次读取成功, 或者连续失败的次数还没有达到 stale_after
+    Good,
+    /// 最近的读取失败, 值为之前读取到的值
+    Stale,
+    /// 长时间读取失败, 或者从来没有读取成功
+    CommError,
+}
+
+/// 根据连续失败的次数判断质量的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HealthPolicy {
+    /// 连续失败多少次之后标记为 Stale, 默认为 1
+    pub stale_after: u32,
+    /// 连续失败多少次之后标记为 CommError, 默认为 3
+    pub comm_error_after: u32,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            stale_after: 1,
+            comm_error_after: 3,
+        }
+    }
+}
+
+impl HealthPolicy {
+    /// 连续失败 consecutive_failures 次时的质量, ever_good 为是否曾经读取成功
+    pub fn quality(&self, consecutive_failures: u32, ever_good: bool) -> Quality {
+        if consecutive_failures == 0 {
+            Quality::Good
+        } else if !ever_good || consecutive_failures >= self.comm_error_after {
+            Quality::CommError
+        } else if consecutive_failures >= self.stale_after {
+            Quality::Stale
+        } else {
+            Quality::Good
+        }
+    }
+}
+
+/// 一个任务或者一个从设备的健康状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Health {
+    /// 连续失败的次数, 成功后清零
+    pub consecutive_failures: u32,
+    /// 最后一次成功的时间
+    pub last_good: Option<SystemTime>,
+    /// 最后一次失败的时间
+    pub last_failure: Option<SystemTime>,
+    pub quality: Quality,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            consecutive_failures: 0,
+            last_good: None,
+            last_failure: None,
+            quality: Quality::CommError,
+        }
+    }
+}
+
+impl Health {
+    /// 记录一次读取的结果, 返回新的质量
+    pub(crate) fn record(&mut self, ok: bool, policy: &HealthPolicy) -> Quality {
+        let now = SystemTime::now();
+        if ok {
+            self.consecutive_failures = 0;
+            self.last_good = Some(now);
+        } else {
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+            self.last_failure = Some(now);
+        }
+        self.quality = policy.quality(self.consecutive_failures, self.last_good.is_some());
+        self.quality
+    }
+}
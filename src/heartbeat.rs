@@ -0,0 +1,106 @@
+//! 看门狗心跳: 按照间隔写入看门狗寄存器, 很多驱动器在一段时间没有收到写入时会报警停机
+
+use std::{
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    address::HoldingAddress, error::Error, poll::StopHandle, shared::SharedClient, Id, Word,
+};
+
+/// 每次心跳写入的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatValue {
+    /// 每次写入相同的值
+    Fixed(Word),
+    /// 交替写入两个值
+    Toggle(Word, Word),
+    /// 从 0 开始递增, 溢出后回到 0
+    Counter,
+}
+
+impl HeartbeatValue {
+    fn get(self, count: u64) -> Word {
+        match self {
+            HeartbeatValue::Fixed(value) => value,
+            HeartbeatValue::Toggle(a, b) => {
+                if count.is_multiple_of(2) {
+                    a
+                } else {
+                    b
+                }
+            }
+            HeartbeatValue::Counter => count as Word,
+        }
+    }
+}
+
+/// 在后台线程中按照间隔写入看门狗寄存器, 通过 [`SharedClient`] 与其它请求共享总线
+///
+/// 写入失败时调用 on_error 后继续. stop 或者 Drop 时停止线程并等待它结束
+pub struct Heartbeat {
+    stop: StopHandle,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    /// 启动心跳, 每隔 interval 向从设备 id 的保持寄存器 address 写入 value
+    pub fn start(
+        client: impl Into<SharedClient>,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        value: HeartbeatValue,
+        interval: Duration,
+        mut on_error: impl FnMut(&Error) + Send + 'static,
+    ) -> Self {
+        // 每次最多等待的时间, 保证能及时响应停止
+        const MAX_SLEEP: Duration = Duration::from_millis(50);
+        let client = client.into();
+        let address = address.into();
+        let stop = StopHandle::default();
+        let handle = stop.clone();
+        let thread = thread::spawn(move || {
+            let mut count = 0u64;
+            while !handle.is_stopped() {
+                let next_run = Instant::now() + interval;
+                if let Err(e) = client.write_single_register(id, address, value.get(count)) {
+                    on_error(&e);
+                }
+                count = count.wrapping_add(1);
+                while !handle.is_stopped() {
+                    let wait = next_run.saturating_duration_since(Instant::now());
+                    if wait.is_zero() {
+                        break;
+                    }
+                    thread::sleep(wait.min(MAX_SLEEP));
+                }
+            }
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// 心跳线程是否还在运行
+    pub fn is_running(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// 停止心跳并等待线程结束
+    pub fn stop(&mut self) {
+        self.stop.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,285 @@
+//! 历史数据记录: 把轮询读取到的数据写入按照大小或者时间滚动的 CSV 文件, 需要 `logger` feature
+//!
+//! 每行一个数据: `timestamp,point,value,quality`, 时间为 UTC, 例如
+//! ```text
+//! timestamp,point,value,quality
+//! 2024-05-01T12:00:01.123Z,holding:1:16,42,Good
+//! 2024-05-01T12:00:02.123Z,holding:1:16,,CommError
+//! 2024-05-01T12:00:02.456Z,motor_speed,1480.5,Good
+//! ```
+//! 轮询任务的数据点名称为 `数据区:从设备ID:地址`, 订阅的数据点使用数据点的名称
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::Result,
+    health::Quality,
+    poll::{PollFunction, PollTask, PollUpdate, PollValue, Poller, TaskId},
+    rtc::civil_from_days,
+    subscribe::Update,
+    Coil,
+};
+
+const HEADER: &str = "timestamp,point,value,quality";
+
+/// 文件滚动的条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// 只写一个文件
+    Never,
+    /// 文件大小超过指定的字节数
+    Size(u64),
+    /// 文件创建之后经过指定的时间, 例如每天一个文件
+    Interval(Duration),
+}
+
+struct CurrentFile {
+    writer: BufWriter<File>,
+    created: SystemTime,
+    size: u64,
+}
+
+/// 滚动的 CSV 文件, 文件名为 `前缀-年月日-时分秒.csv`
+pub struct CsvLogger {
+    dir: PathBuf,
+    prefix: String,
+    rotation: Rotation,
+    max_files: Option<usize>,
+    current: Option<CurrentFile>,
+}
+
+impl CsvLogger {
+    /// 在目录 dir 中创建文件, 目录不存在时自动创建. 默认每天滚动一次, 不限制文件数量
+    pub fn new(dir: impl AsRef<Path>, prefix: &str) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_string(),
+            rotation: Rotation::Interval(Duration::from_secs(86400)),
+            max_files: None,
+            current: None,
+        })
+    }
+
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// 最多保留的文件数量, 滚动时删除最旧的文件
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = Some(max_files.max(1));
+        self
+    }
+
+    /// 写入一个数据, value 为 None 时值为空
+    pub fn log(
+        &mut self,
+        timestamp: SystemTime,
+        point: &str,
+        value: Option<f64>,
+        quality: Quality,
+    ) -> Result<()> {
+        let value = value.map(|v| v.to_string()).unwrap_or_default();
+        let line = format!(
+            "{},{},{},{:?}\n",
+            format_timestamp(timestamp),
+            escape(point),
+            value,
+            quality
+        );
+        self.write_line(&line)
+    }
+
+    /// 写入一次轮询的结果, 每个寄存器或者线圈一行
+    ///
+    /// 读取失败时写入一行空值和轮询的质量, 质量仍然为 Good 的偶尔失败不写入
+    pub fn log_poll(&mut self, task: &PollTask, update: &PollUpdate) -> Result<()> {
+        let timestamp = SystemTime::now();
+        let point = |offset: u16| {
+            format!(
+                "{}:{}:{}",
+                area_name(task.function),
+                task.id,
+                task.address.wrapping_add(offset)
+            )
+        };
+        match &update.result {
+            Ok(PollValue::Words(words)) => {
+                for (offset, word) in words.iter().enumerate() {
+                    let value = Some(*word as f64);
+                    self.log(timestamp, &point(offset as u16), value, Quality::Good)?;
+                }
+            }
+            Ok(PollValue::Bits(bits)) => {
+                for (offset, bit) in bits.iter().enumerate() {
+                    let value = Some(if *bit == Coil::On { 1.0 } else { 0.0 });
+                    self.log(timestamp, &point(offset as u16), value, Quality::Good)?;
+                }
+            }
+            Err(_) if update.quality == Quality::Good => {}
+            Err(_) => self.log(timestamp, &point(0), None, update.quality)?,
+        }
+        Ok(())
+    }
+
+    /// 写入订阅的一次更新
+    pub fn log_update(&mut self, update: &Update) -> Result<()> {
+        self.log(update.timestamp, &update.name, update.value, update.quality)
+    }
+
+    /// 把缓冲的数据写入文件
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(current) = &mut self.current {
+            current.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if self.need_rotate() {
+            self.rotate()?;
+        }
+        let current = self.current.as_mut().expect("rotate 之后一定有文件");
+        current.writer.write_all(line.as_bytes())?;
+        current.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn need_rotate(&self) -> bool {
+        let Some(current) = &self.current else {
+            return true;
+        };
+        match self.rotation {
+            Rotation::Never => false,
+            Rotation::Size(size) => current.size >= size,
+            Rotation::Interval(interval) => current
+                .created
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed >= interval),
+        }
+    }
+
+    /// 关闭当前文件, 创建新的文件并写入表头
+    fn rotate(&mut self) -> Result<()> {
+        if let Some(mut current) = self.current.take() {
+            current.writer.flush()?;
+        }
+        let created = SystemTime::now();
+        let stamp = format_timestamp(created);
+        // 2024-05-01T12:00:01.123Z -> 20240501-120001
+        let stamp: String = stamp[..19]
+            .chars()
+            .filter(|c| *c != '-' && *c != ':')
+            .collect();
+        let stamp = stamp.replace('T', "-");
+        let mut path = self.dir.join(format!("{}-{}.csv", self.prefix, stamp));
+        let mut n = 1;
+        while path.exists() {
+            path = self
+                .dir
+                .join(format!("{}-{}-{}.csv", self.prefix, stamp, n));
+            n += 1;
+        }
+
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writeln!(writer, "{}", HEADER)?;
+        self.current = Some(CurrentFile {
+            writer,
+            created,
+            size: HEADER.len() as u64 + 1,
+        });
+        log::debug!("新的数据文件: {}", path.display());
+        self.remove_old_files()
+    }
+
+    /// 删除超过数量限制的最旧的文件, 文件名中的时间决定先后顺序
+    fn remove_old_files(&self) -> Result<()> {
+        let Some(max_files) = self.max_files else {
+            return Ok(());
+        };
+        let prefix = format!("{}-", self.prefix);
+        let mut files = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == "csv")
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(&prefix))
+            })
+            .collect::<Vec<_>>();
+        if files.len() <= max_files {
+            return Ok(());
+        }
+        files.sort();
+        for path in &files[..files.len() - max_files] {
+            if let Err(e) = fs::remove_file(path) {
+                log::warn!("删除数据文件 {} 失败, E: {}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CsvLogger {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl Poller {
+    /// 添加任务, 每次轮询的结果写入 logger. 写入失败时只记录警告
+    pub fn add_logged_task(&mut self, task: PollTask, logger: Arc<Mutex<CsvLogger>>) -> TaskId {
+        let logged = task.clone();
+        self.add_callback(task, move |update| {
+            let mut logger = logger.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = logger.log_poll(&logged, &update) {
+                log::warn!("写入数据文件失败, E: {}", e);
+            }
+        })
+    }
+}
+
+fn area_name(function: PollFunction) -> &'static str {
+    match function {
+        PollFunction::Coils => "coil",
+        PollFunction::DiscreteInputs => "discrete",
+        PollFunction::HoldingRegisters => "holding",
+        PollFunction::InputRegisters => "input",
+    }
+}
+
+/// 包含逗号或者引号的字段加上引号
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// 把时间格式化为 UTC 的 ISO 8601 格式, 例如 `2024-05-01T12:00:01.123Z`
+pub fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
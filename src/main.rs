@@ -0,0 +1,393 @@
+//! Modbus 命令行工具, 需要 `cli` feature
+//!
+//! ```text
+//! simple_modbus read-holding --port COM3 --baud 19200 --id 15 --addr 0x16 --count 2
+//! simple_modbus --tcp 192.168.1.10:502 write --addr 0x10 --type f32 12.5
+//! simple_modbus --port /dev/ttyUSB0 scan --from 1 --to 32
+//! simple_modbus --port /dev/ttyUSB0 --format json monitor --watch holding:0:4 --watch coil:0x10:8
+//! ```
+
+use std::{process::ExitCode, thread, time::Duration};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use simple_modbus::{
+    builder::ClientBuilder,
+    error::{Error, Reason, Result},
+    poll::PollFunction,
+    value::{ValueType, WordOrder},
+    watch::{format_time, Change, Watcher},
+    Client, Coil, Protocol,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "simple_modbus",
+    version,
+    about = "Modbus RTU / TCP / ASCII 命令行工具"
+)]
+struct Cli {
+    #[command(flatten)]
+    conn: Connection,
+    /// 输出格式
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct Connection {
+    /// 串口名称, 例如 COM3 或 /dev/ttyUSB0
+    #[arg(long, global = true, conflicts_with = "tcp")]
+    port: Option<String>,
+    /// 串口波特率
+    #[arg(long, global = true, default_value_t = 9600)]
+    baud: u32,
+    /// Modbus TCP 服务器地址, 例如 192.168.1.10:502
+    #[arg(long, global = true)]
+    tcp: Option<String>,
+    /// Modbus UDP 服务器地址, 例如 192.168.1.10:502
+    #[arg(long, global = true, conflicts_with_all = ["port", "tcp"])]
+    udp: Option<String>,
+    /// 帧格式, 默认串口为 rtu, TCP 和 UDP 为 tcp
+    #[arg(long, global = true, value_enum)]
+    protocol: Option<FrameFormat>,
+    /// 从设备ID
+    #[arg(long, global = true, default_value_t = 1)]
+    id: u8,
+    /// 超时时间, 毫秒
+    #[arg(long, global = true, default_value_t = 1000)]
+    timeout_ms: u64,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FrameFormat {
+    Rtu,
+    Tcp,
+    Ascii,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 读保持寄存器
+    ReadHolding(ReadArgs),
+    /// 读输入寄存器
+    ReadInput(ReadArgs),
+    /// 读线圈
+    ReadCoils(BitArgs),
+    /// 读离散输入
+    ReadDiscrete(BitArgs),
+    /// 写保持寄存器, 多个值按照类型编码后连续写入
+    Write(WriteArgs),
+    /// 扫描总线上的从设备
+    Scan(ScanArgs),
+    /// 周期性地读取寄存器, 只输出变化的值, json 格式时每行一个变化
+    Monitor(MonitorArgs),
+}
+
+#[derive(Args)]
+struct ReadArgs {
+    /// 起始地址, 十进制或者 0x 开头的十六进制
+    #[arg(long, value_parser = parse_u16)]
+    addr: u16,
+    /// 数值的个数
+    #[arg(long, default_value_t = 1)]
+    count: u16,
+    /// 数值类型: u16, i16, u32, i32, f32, u64, i64, f64
+    #[arg(long = "type", default_value = "u16", value_parser = parse_type)]
+    ty: ValueType,
+    /// 多个寄存器的字节顺序: abcd, cdab, badc, dcba
+    #[arg(long, default_value = "abcd", value_parser = parse_order)]
+    order: WordOrder,
+}
+
+#[derive(Args)]
+struct BitArgs {
+    #[arg(long, value_parser = parse_u16)]
+    addr: u16,
+    #[arg(long, default_value_t = 1)]
+    count: u16,
+}
+
+#[derive(Args)]
+struct WriteArgs {
+    #[arg(long, value_parser = parse_u16)]
+    addr: u16,
+    #[arg(long = "type", default_value = "u16", value_parser = parse_type)]
+    ty: ValueType,
+    #[arg(long, default_value = "abcd", value_parser = parse_order)]
+    order: WordOrder,
+    /// 写入的值
+    #[arg(required = true, allow_negative_numbers = true)]
+    values: Vec<f64>,
+}
+
+#[derive(Args)]
+struct ScanArgs {
+    #[arg(long, default_value_t = 1)]
+    from: u8,
+    #[arg(long, default_value_t = 247)]
+    to: u8,
+}
+
+#[derive(Args)]
+struct MonitorArgs {
+    /// 监视的保持寄存器的起始地址
+    #[arg(long, value_parser = parse_u16)]
+    addr: Option<u16>,
+    /// 与 --addr 一起使用, 监视的保持寄存器数量
+    #[arg(long, default_value_t = 1)]
+    count: u16,
+    /// 监视项, 格式为 数据区:地址[:数量], 数据区为 coil, discrete, input, holding. 可以重复
+    #[arg(long, value_parser = parse_watch)]
+    watch: Vec<(PollFunction, u16, u16)>,
+    /// 读取间隔, 毫秒
+    #[arg(long, default_value_t = 1000)]
+    interval_ms: u64,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    let mut client = connect(&cli.conn)?;
+    let id = cli.conn.id;
+    match &cli.command {
+        Command::ReadHolding(args) => {
+            let values = read_values(&mut client, id, args, true)?;
+            print_values(cli.format, args, &values);
+        }
+        Command::ReadInput(args) => {
+            let values = read_values(&mut client, id, args, false)?;
+            print_values(cli.format, args, &values);
+        }
+        Command::ReadCoils(args) => {
+            let bits = client.read_coils(id, args.addr, args.count)?;
+            print_bits(cli.format, args.addr, &bits);
+        }
+        Command::ReadDiscrete(args) => {
+            let bits = client.read_discrete_inputs(id, args.addr, args.count)?;
+            print_bits(cli.format, args.addr, &bits);
+        }
+        Command::Write(args) => {
+            let mut words = Vec::new();
+            for &value in &args.values {
+                words.extend(args.ty.encode(value, args.order)?);
+            }
+            if words.len() == 1 {
+                client.write_single_register(id, args.addr, words[0])?;
+            } else {
+                client.write_multiple_registers(id, args.addr, words.clone())?;
+            }
+            if cli.format == Format::Json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "address": args.addr, "written": words })
+                );
+            } else {
+                println!("写入 0x{:04X}: {} 个寄存器", args.addr, words.len());
+            }
+        }
+        Command::Scan(args) => {
+            let timeout = Duration::from_millis(cli.conn.timeout_ms);
+            let found = client.scan_bus(args.from..=args.to, timeout, |client, id| {
+                client.probe_holding_register(id)
+            })?;
+            if cli.format == Format::Json {
+                println!("{}", serde_json::json!({ "found": found }));
+            } else if found.is_empty() {
+                println!("没有找到从设备");
+            } else {
+                for id in found {
+                    println!("找到从设备: {}", id);
+                }
+            }
+        }
+        Command::Monitor(args) => {
+            let mut watcher = Watcher::new(Duration::from_millis(args.interval_ms));
+            if let Some(addr) = args.addr {
+                watcher.add(PollFunction::HoldingRegisters, id, addr, args.count);
+            }
+            for &(function, address, quantity) in &args.watch {
+                watcher.add(function, id, address, quantity);
+            }
+            if args.addr.is_none() && args.watch.is_empty() {
+                return Err(Error::InvalidData(Reason::InvalidConfig(
+                    "需要指定 --addr 或 --watch".to_string(),
+                )));
+            }
+            loop {
+                match watcher.poll(&mut client) {
+                    Ok(changes) => changes.iter().for_each(|c| print_change(cli.format, c)),
+                    Err(e) => eprintln!("读取失败: {}", e),
+                }
+                thread::sleep(watcher.interval());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn connect(conn: &Connection) -> Result<Client> {
+    let timeout = Duration::from_millis(conn.timeout_ms);
+    let mut builder = ClientBuilder::new()
+        .unit_id(conn.id)
+        .connect_timeout(timeout)
+        .read_timeout(timeout)
+        .write_timeout(timeout);
+    if let Some(protocol) = conn.protocol {
+        builder = builder.protocol(match protocol {
+            FrameFormat::Rtu => Protocol::Rtu,
+            FrameFormat::Tcp => Protocol::Tcp,
+            FrameFormat::Ascii => Protocol::Ascii,
+        });
+    }
+    match (&conn.port, &conn.tcp, &conn.udp) {
+        (Some(port), ..) => builder.open_serial(port, conn.baud),
+        (None, Some(addr), _) => builder.connect_tcp(addr),
+        (None, None, Some(addr)) => builder.connect_udp(addr),
+        (None, None, None) => Err(Error::InvalidData(Reason::InvalidConfig(
+            "需要指定 --port, --tcp 或 --udp".to_string(),
+        ))),
+    }
+}
+
+/// 读取 count 个数值, 返回 (地址, 寄存器, 数值)
+fn read_values(
+    client: &mut Client,
+    id: u8,
+    args: &ReadArgs,
+    holding: bool,
+) -> Result<Vec<(u16, Vec<u16>, f64)>> {
+    let words_per_value = args.ty.words();
+    let quantity = args.count.saturating_mul(words_per_value);
+    let words = if holding {
+        client.read_holding_registers(id, args.addr, quantity)?
+    } else {
+        client.read_input_registers(id, args.addr, quantity)?
+    };
+    words
+        .chunks(words_per_value as usize)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let address = args.addr.wrapping_add(i as u16 * words_per_value);
+            Ok((address, chunk.to_vec(), args.ty.decode(chunk, args.order)?))
+        })
+        .collect()
+}
+
+fn print_values(format: Format, args: &ReadArgs, values: &[(u16, Vec<u16>, f64)]) {
+    if format == Format::Json {
+        let values = values
+            .iter()
+            .map(|(address, words, value)| {
+                serde_json::json!({ "address": address, "raw": words, "value": value })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::Value::Array(values));
+        return;
+    }
+    println!("地址     寄存器                   数值 ({})", args.ty);
+    for (address, words, value) in values {
+        let raw = words
+            .iter()
+            .map(|w| format!("{:04X}", w))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!("0x{:04X}   {:<24} {}", address, raw, value);
+    }
+}
+
+fn print_bits(format: Format, address: u16, bits: &[Coil]) {
+    if format == Format::Json {
+        let bits = bits
+            .iter()
+            .enumerate()
+            .map(|(i, bit)| {
+                serde_json::json!({
+                    "address": address.wrapping_add(i as u16),
+                    "value": *bit == Coil::On,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::Value::Array(bits));
+        return;
+    }
+    for (i, bit) in bits.iter().enumerate() {
+        let value = if *bit == Coil::On { 1 } else { 0 };
+        println!("0x{:04X}   {}", address.wrapping_add(i as u16), value);
+    }
+}
+
+fn print_change(format: Format, change: &Change) {
+    if format == Format::Json {
+        let area = match change.function {
+            PollFunction::Coils => "coil",
+            PollFunction::DiscreteInputs => "discrete",
+            PollFunction::HoldingRegisters => "holding",
+            PollFunction::InputRegisters => "input",
+        };
+        println!(
+            "{}",
+            serde_json::json!({
+                "time": format_time(change.timestamp),
+                "area": area,
+                "id": change.id,
+                "address": change.address,
+                "old": change.old,
+                "new": change.new,
+            })
+        );
+    } else {
+        println!("{}", change);
+    }
+}
+
+fn parse_u16(s: &str) -> std::result::Result<u16, String> {
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|_| format!("无效的地址: {}", s))
+}
+
+fn parse_watch(s: &str) -> std::result::Result<(PollFunction, u16, u16), String> {
+    let fields = s.split(':').collect::<Vec<_>>();
+    if fields.len() < 2 || fields.len() > 3 {
+        return Err(format!("无效的监视项: {}", s));
+    }
+    let function = match fields[0] {
+        "coil" => PollFunction::Coils,
+        "discrete" => PollFunction::DiscreteInputs,
+        "input" => PollFunction::InputRegisters,
+        "holding" => PollFunction::HoldingRegisters,
+        area => return Err(format!("未知的数据区: {}", area)),
+    };
+    let quantity = match fields.get(2) {
+        Some(quantity) => parse_u16(quantity)?,
+        None => 1,
+    };
+    Ok((function, parse_u16(fields[1])?, quantity))
+}
+
+fn parse_type(s: &str) -> std::result::Result<ValueType, String> {
+    s.parse().map_err(|e: Error| e.to_string())
+}
+
+fn parse_order(s: &str) -> std::result::Result<WordOrder, String> {
+    s.parse().map_err(|e: Error| e.to_string())
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    bank::RegisterBank,
+    calc_crc,
+    checksum::{Checksum, SumComplement},
+    error::Result,
+    frame::{ascii_decode, ascii_encode},
+    profile::{DeviceProfile, Outcome, Simulator},
+    stream::Stream,
+    Protocol,
+};
+
+/// 用于单元测试的模拟传输, 不需要真实的串口或网络
+///
+/// 每次 `flush` 时, 把之前写入的数据当作一个完整的请求帧:
+/// - 预先放入的响应帧按顺序返回
+/// - 关联了 `RegisterBank` 时, 由它处理请求并生成响应帧
+///
+/// 没有可读的数据时, `read` 返回超时错误.
+/// 使用 [`DeviceProfile`] 时还会模拟延迟, 不响应, 校验错误和异常响应.
+///
+/// 克隆得到的 MockStream 共享同一份状态, 把一份交给 `Client` 之后,
+/// 仍然可以通过另一份放入响应帧或者检查收到的请求
+#[derive(Clone, Default)]
+pub struct MockStream {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    replies: VecDeque<Vec<u8>>,
+    bank: Option<(Arc<RegisterBank>, Protocol)>,
+    simulator: Option<Simulator>,
+    request: Vec<u8>,
+    requests: Vec<Vec<u8>>,
+    pending: VecDeque<u8>,
+    /// 读取 pending 中的响应之前的延迟
+    delay: Duration,
+    timeout: Option<Duration>,
+}
+
+impl MockStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用 RegisterBank 作为模拟的从设备, protocol 决定请求和响应的帧格式
+    pub fn with_bank(bank: Arc<RegisterBank>, protocol: Protocol) -> Self {
+        let stream = Self::new();
+        stream.inner.lock().unwrap().bank = Some((bank, protocol));
+        stream
+    }
+
+    /// 使用 RegisterBank 作为按照 profile 运行的模拟设备
+    ///
+    /// bank 通常由 [`DeviceProfile::bank`] 创建, profile 中的大小和初始值不会再次应用
+    pub fn with_profile(
+        bank: Arc<RegisterBank>,
+        protocol: Protocol,
+        profile: DeviceProfile,
+    ) -> Result<Self> {
+        let stream = Self::with_bank(bank, protocol);
+        stream.inner.lock().unwrap().simulator = Some(Simulator::new(profile)?);
+        Ok(stream)
+    }
+
+    /// 放入一个响应帧, 在收到下一个请求时返回
+    pub fn push_reply(&self, reply: &[u8]) {
+        self.inner.lock().unwrap().replies.push_back(reply.to_vec());
+    }
+
+    /// 到目前为止收到的所有请求帧
+    pub fn requests(&self) -> Vec<Vec<u8>> {
+        self.inner.lock().unwrap().requests.clone()
+    }
+}
+
+impl Inner {
+    fn respond(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        let (bank, protocol) = match &self.bank {
+            Some(bank) => bank,
+            None => return self.replies.pop_front(),
+        };
+        let simulator = &mut self.simulator;
+        let mut process = |pdu: &[u8]| simulate(simulator.as_mut(), bank, pdu);
+        match protocol {
+            Protocol::Rtu => {
+                // ID(1) + FUN(1) + CRC(2)
+                if request.len() < 4 {
+                    return None;
+                }
+                let (data, crc) = request.split_at(request.len() - 2);
+                if calc_crc(data).to_be_bytes() != crc {
+                    return None;
+                }
+                let id = data[0];
+                let pdu = process(&data[1..])?;
+                // 广播请求没有响应
+                if id == 0 {
+                    return None;
+                }
+                let mut reply = vec![id];
+                reply.extend_from_slice(&pdu);
+                let crc = calc_crc(&reply);
+                reply.extend_from_slice(&crc.to_be_bytes());
+                let last = reply.len() - 1;
+                corrupt(simulator.as_mut(), &mut reply, last);
+                Some(reply)
+            }
+            Protocol::Tcp => {
+                // MBAP(7) + FUN(1)
+                if request.len() < 8 {
+                    return None;
+                }
+                let pdu = process(&request[7..])?;
+                let mut reply = request[..7].to_vec();
+                reply[4..6].copy_from_slice(&(1 + pdu.len() as u16).to_be_bytes());
+                reply.extend_from_slice(&pdu);
+                // 没有校验值, 破坏协议标识
+                corrupt(simulator.as_mut(), &mut reply, 2);
+                Some(reply)
+            }
+            Protocol::Ascii => {
+                let data = ascii_decode(request).ok()?;
+                if data.len() < 3 || !SumComplement.verify(&data) {
+                    return None;
+                }
+                let id = data[0];
+                let pdu = process(&data[1..data.len() - 1])?;
+                if id == 0 {
+                    return None;
+                }
+                let mut reply = vec![id];
+                reply.extend_from_slice(&pdu);
+                reply.extend_from_slice(&SumComplement.calc(&reply));
+                let last = reply.len() - 1;
+                corrupt(simulator.as_mut(), &mut reply, last);
+                Some(ascii_encode(&reply).to_vec())
+            }
+        }
+    }
+}
+
+/// 由模拟设备处理请求 PDU, 返回响应 PDU, 不响应时返回 None
+fn simulate(simulator: Option<&mut Simulator>, bank: &RegisterBank, pdu: &[u8]) -> Option<Vec<u8>> {
+    let Some(simulator) = simulator else {
+        return Some(bank.process(pdu));
+    };
+    match simulator.handle(pdu) {
+        Outcome::Drop => None,
+        Outcome::Exception(code) => {
+            let function = pdu.first().copied().unwrap_or(0);
+            Some(vec![function | 0x80, code.code()])
+        }
+        Outcome::Process => Some(bank.process(pdu)),
+    }
+}
+
+/// 按照模拟设备的概率破坏响应中的一个字节
+fn corrupt(simulator: Option<&mut Simulator>, reply: &mut [u8], index: usize) {
+    if simulator.is_some_and(Simulator::corrupt) {
+        reply[index] ^= 0xFF;
+    }
+}
+
+impl Read for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let delay = std::mem::take(&mut self.inner.lock().unwrap().delay);
+        if !delay.is_zero() {
+            let timeout = self.inner.lock().unwrap().timeout;
+            match timeout {
+                // 超过读超时时间, 丢弃响应
+                Some(timeout) if delay > timeout => {
+                    thread::sleep(timeout);
+                    self.inner.lock().unwrap().pending.clear();
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "响应超时"));
+                }
+                _ => thread::sleep(delay),
+            }
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if inner.pending.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "没有可读的数据"));
+        }
+        let n = buf.len().min(inner.pending.len());
+        for (dst, src) in buf.iter_mut().zip(inner.pending.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.lock().unwrap().request.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.request.is_empty() {
+            return Ok(());
+        }
+        let request = std::mem::take(&mut inner.request);
+        if let Some(reply) = inner.respond(&request) {
+            inner.pending.extend(reply);
+            inner.delay = inner
+                .simulator
+                .as_ref()
+                .map_or(Duration::ZERO, Simulator::latency);
+        }
+        inner.requests.push(request);
+        Ok(())
+    }
+}
+
+impl Stream for MockStream {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.lock().unwrap().timeout = Some(timeout);
+        Ok(())
+    }
+
+    fn clear_input(&mut self) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.pending.clear();
+        inner.delay = Duration::ZERO;
+        Ok(())
+    }
+
+    /// 丢弃未读取的数据
+    fn reconnect(&mut self) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.request.clear();
+        inner.pending.clear();
+        inner.delay = Duration::ZERO;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+//! Modbus 到 MQTT 的桥接: 把轮询到的数据发布到 MQTT 服务器, 需要 `mqtt` feature
+//!
+//! 数据点的值发布到 `{prefix}/{名称}`, 负载为 JSON, 例如 `{"value":12.5,"unit":"rpm","timestamp":1700000000000}`.
+//! 在线状态发布到 `{prefix}/availability`, 连接时为 "online", 断开或者异常掉线 (遗嘱消息) 时为 "offline"
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use rumqttc::{LastWill, MqttOptions, QoS};
+
+use crate::{
+    error::{Error, Result},
+    poll::{PollUpdate, PollValue, StopHandle},
+    register_map::RegisterMap,
+    Client, Coil,
+};
+
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+/// MQTT 连接配置
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    /// 用户名和密码
+    pub credentials: Option<(String, String)>,
+    /// 所有主题的前缀, 例如 "modbus/device1"
+    pub topic_prefix: String,
+    pub keep_alive: Duration,
+    /// 值消息是否保留, 新的订阅者可以立即收到最后的值
+    pub retain: bool,
+}
+
+impl MqttConfig {
+    pub fn new(host: impl Into<String>, port: u16, topic_prefix: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            client_id: "simple_modbus".to_string(),
+            credentials: None,
+            topic_prefix: topic_prefix.into(),
+            keep_alive: Duration::from_secs(30),
+            retain: true,
+        }
+    }
+
+    fn availability_topic(&self) -> String {
+        format!("{}/availability", self.topic_prefix)
+    }
+}
+
+/// 把数据的变化发布到 MQTT 服务器
+///
+/// 只发布与上一次不同的值; 连接由后台线程维护, 断开后自动重连.
+/// Drop 时发布 "offline" 并断开连接
+pub struct MqttBridge {
+    config: MqttConfig,
+    client: rumqttc::Client,
+    last: HashMap<String, String>,
+    closed: Arc<AtomicBool>,
+}
+
+impl MqttBridge {
+    /// 连接 MQTT 服务器, 设置遗嘱消息并发布在线状态
+    pub fn connect(config: MqttConfig) -> Result<Self> {
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(config.keep_alive);
+        options.set_last_will(LastWill::new(
+            config.availability_topic(),
+            OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some((username, password)) = &config.credentials {
+            options.set_credentials(username, password);
+        }
+        let (client, mut connection) = rumqttc::Client::new(options, 64);
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let stop = closed.clone();
+        thread::spawn(move || {
+            for notification in connection.iter() {
+                match notification {
+                    Ok(_) => {}
+                    // 断开连接之后, 服务器关闭连接或者 Client 已经释放
+                    Err(_) if stop.load(Ordering::Relaxed) => break,
+                    Err(e) => {
+                        log::warn!("MQTT 连接断开, E: {}", e);
+                        // 下一次迭代时重新连接
+                        thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            }
+        });
+
+        let bridge = Self {
+            config,
+            client,
+            last: HashMap::new(),
+            closed,
+        };
+        bridge.publish_raw(&bridge.config.availability_topic(), ONLINE, true)?;
+        Ok(bridge)
+    }
+
+    pub fn config(&self) -> &MqttConfig {
+        &self.config
+    }
+
+    /// 发布一个值到 `{prefix}/{name}`, 与上一次发布的值相同时不发布. 返回是否发布
+    pub fn publish_value(&mut self, name: &str, value: f64, unit: Option<&str>) -> Result<bool> {
+        self.publish_json(name, serde_json::json!(value), unit)
+    }
+
+    /// 发布轮询任务的结果到 `{prefix}/task/{序号}`, 值为寄存器数组, 位数据为 0 或 1.
+    /// 读取失败时不发布
+    pub fn publish_update(&mut self, update: &PollUpdate) -> Result<bool> {
+        let Ok(value) = &update.result else {
+            return Ok(false);
+        };
+        let value = match value {
+            PollValue::Words(words) => serde_json::json!(words),
+            PollValue::Bits(bits) => {
+                serde_json::json!(bits
+                    .iter()
+                    .map(|b| (*b == Coil::On) as u8)
+                    .collect::<Vec<_>>())
+            }
+        };
+        self.publish_json(&format!("task/{}", update.task), value, None)
+    }
+
+    /// 读取寄存器表中的所有数据点, 发布变化的值, 返回发布的数量
+    ///
+    /// 读取失败的数据点记录日志后跳过; 发布失败时返回错误
+    pub fn publish_map(&mut self, client: &mut Client, map: &RegisterMap) -> Result<usize> {
+        let mut published = 0;
+        for point in map.points() {
+            match map.read(client, &point.name) {
+                Ok(value) => {
+                    if self.publish_value(&point.name, value, point.unit_name())? {
+                        published += 1;
+                    }
+                }
+                Err(e) => log::warn!("读取数据点 {} 失败, E: {}", point.name, e),
+            }
+        }
+        Ok(published)
+    }
+
+    /// 按照间隔循环执行 publish_map, 直到通过 StopHandle 停止或者发布失败
+    pub fn run_map(
+        &mut self,
+        client: &mut Client,
+        map: &RegisterMap,
+        interval: Duration,
+        stop: &StopHandle,
+    ) -> Result<()> {
+        // 每次最多等待的时间, 保证能及时响应停止
+        const MAX_SLEEP: Duration = Duration::from_millis(50);
+        while !stop.is_stopped() {
+            let next_run = Instant::now() + interval;
+            self.publish_map(client, map)?;
+            while !stop.is_stopped() {
+                let wait = next_run.saturating_duration_since(Instant::now());
+                if wait.is_zero() {
+                    break;
+                }
+                thread::sleep(wait.min(MAX_SLEEP));
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_json(
+        &mut self,
+        name: &str,
+        value: serde_json::Value,
+        unit: Option<&str>,
+    ) -> Result<bool> {
+        let key = value.to_string();
+        if self.last.get(name) == Some(&key) {
+            return Ok(false);
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut payload = serde_json::json!({ "value": value, "timestamp": timestamp });
+        if let Some(unit) = unit {
+            payload["unit"] = serde_json::json!(unit);
+        }
+        let topic = format!("{}/{}", self.config.topic_prefix, name);
+        self.publish_raw(&topic, &payload.to_string(), self.config.retain)?;
+        self.last.insert(name.to_string(), key);
+        Ok(true)
+    }
+
+    fn publish_raw(&self, topic: &str, payload: &str, retain: bool) -> Result<()> {
+        self.client
+            .publish(topic, QoS::AtLeastOnce, retain, payload.as_bytes())
+            .map_err(|e| Error::Io(io::Error::other(e)))
+    }
+}
+
+impl Drop for MqttBridge {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        let _ = self.publish_raw(&self.config.availability_topic(), OFFLINE, true);
+        let _ = self.client.disconnect();
+    }
+}
@@ -0,0 +1,289 @@
+//! 把捕获的 Modbus 帧导出为 pcap 文件, 使用 Wireshark 的 Modbus 解析器离线分析
+//!
+//! 两种封装格式:
+//! - [`Encapsulation::Tcp`]: 每一帧转换为 Modbus/TCP 报文, 加上虚构的 IPv4 和 TCP 报文头 (端口 502),
+//!   Wireshark 不需要任何设置即可解析. RTU 和 ASCII 帧去掉校验值之后加上 MBAP报文头
+//! - [`Encapsulation::Rtu`]: 原样写入帧的数据, 链路类型为 USER0 (147),
+//!   需要在 Wireshark 的 DLT_USER 设置中把 User 0 的协议设置为 `mbrtu`
+//!
+//! 与 [`Client::set_frame_observer`](crate::Client::set_frame_observer) 配合使用:
+//! ```no_run
+//! use std::sync::{Arc, Mutex};
+//! use simple_modbus::{pcap::{Encapsulation, PcapWriter}, Protocol};
+//! # fn run(client: &mut simple_modbus::Client) -> simple_modbus::error::Result<()> {
+//! let pcap = Arc::new(Mutex::new(PcapWriter::create("modbus.pcap", Encapsulation::Tcp)?));
+//! let protocol = client.protocol();
+//! client.set_frame_observer(move |event| {
+//!     let _ = pcap.lock().unwrap().write_frame(event, protocol);
+//! });
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+
+use crate::{
+    codec::{calc_crc, encode_request, Request, Response},
+    error::Result,
+    frame::ascii_decode,
+    sniffer::SnifferEvent,
+    trace::{Direction, FrameEvent},
+    Id, Protocol,
+};
+
+/// 链路类型: 原始 IPv4 报文
+const LINKTYPE_RAW: u32 = 101;
+/// 链路类型: 用户自定义 0
+const LINKTYPE_USER0: u32 = 147;
+
+/// 虚构的客户端和服务器的地址
+const CLIENT_IP: [u8; 4] = [10, 0, 0, 1];
+const SERVER_IP: [u8; 4] = [10, 0, 0, 2];
+const CLIENT_PORT: u16 = 49152;
+const SERVER_PORT: u16 = 502;
+
+/// pcap 文件中帧的封装格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encapsulation {
+    /// Modbus/TCP over IPv4
+    Tcp,
+    /// 原始的帧数据, 链路类型 USER0
+    Rtu,
+}
+
+/// pcap 文件的写入器
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    encapsulation: Encapsulation,
+    /// RTU 和 ASCII 帧转换为 Modbus/TCP 时使用的事务标识, 每个请求加 1
+    transaction_id: u16,
+    /// 两个方向的 TCP 序列号
+    client_seq: u32,
+    server_seq: u32,
+}
+
+impl PcapWriter<BufWriter<File>> {
+    /// 创建 pcap 文件, 已经存在的文件被覆盖
+    pub fn create(path: impl AsRef<Path>, encapsulation: Encapsulation) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), encapsulation)
+    }
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// 写入 pcap 文件头
+    pub fn new(mut writer: W, encapsulation: Encapsulation) -> Result<Self> {
+        let linktype = match encapsulation {
+            Encapsulation::Tcp => LINKTYPE_RAW,
+            Encapsulation::Rtu => LINKTYPE_USER0,
+        };
+        // 魔数, 版本 2.4, 时区, 时间戳精度, 最大长度, 链路类型
+        writer.write_all(&0xA1B2C3D4u32.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&4u16.to_le_bytes())?;
+        writer.write_all(&0i32.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&65535u32.to_le_bytes())?;
+        writer.write_all(&linktype.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            encapsulation,
+            transaction_id: 0,
+            client_seq: 1,
+            server_seq: 1,
+        })
+    }
+
+    /// 写入客户端发送或者收到的一帧, protocol 为帧的格式
+    ///
+    /// 转换为 Modbus/TCP 时, 无法解析的帧被忽略
+    pub fn write_frame(&mut self, event: &FrameEvent, protocol: Protocol) -> Result<()> {
+        match self.encapsulation {
+            Encapsulation::Rtu => self.write_record(event.timestamp, event.data),
+            Encapsulation::Tcp => {
+                let Some((id, pdu)) = split_frame(protocol, event.data) else {
+                    log::debug!("无法转换为 Modbus/TCP 的帧: {:02X?}", event.data);
+                    return Ok(());
+                };
+                if event.direction == Direction::Tx {
+                    self.transaction_id = self.transaction_id.wrapping_add(1);
+                }
+                let adu = match protocol {
+                    Protocol::Tcp => event.data.to_vec(),
+                    Protocol::Rtu | Protocol::Ascii => mbap(self.transaction_id, id, &pdu),
+                };
+                self.write_tcp(event.timestamp, event.direction, &adu)
+            }
+        }
+    }
+
+    /// 写入 [`Sniffer`](crate::sniffer::Sniffer) 得到的事件, 请求和响应分别写入一帧
+    pub fn write_sniffer_event(&mut self, event: &SnifferEvent) -> Result<()> {
+        match event {
+            SnifferEvent::Transaction {
+                request,
+                response,
+                timestamp,
+                elapsed,
+            } => {
+                self.write_request(*timestamp, request)?;
+                self.write_response(*timestamp + *elapsed, response)
+            }
+            SnifferEvent::Unanswered { request, timestamp } => {
+                self.write_request(*timestamp, request)
+            }
+            SnifferEvent::Unknown { data, timestamp } => match self.encapsulation {
+                Encapsulation::Rtu => self.write_record(*timestamp, data),
+                Encapsulation::Tcp => Ok(()),
+            },
+        }
+    }
+
+    fn write_request(&mut self, timestamp: SystemTime, request: &Request) -> Result<()> {
+        let protocol = match self.encapsulation {
+            Encapsulation::Tcp => Protocol::Tcp,
+            Encapsulation::Rtu => Protocol::Rtu,
+        };
+        // write_frame 写入请求时把事务标识加 1
+        let request = Request::new(
+            self.transaction_id.wrapping_add(1),
+            request.function.clone(),
+        );
+        let frame = encode_request(protocol, &request)?;
+        self.write_frame(
+            &FrameEvent {
+                direction: Direction::Tx,
+                timestamp,
+                data: &frame,
+            },
+            protocol,
+        )
+    }
+
+    fn write_response(&mut self, timestamp: SystemTime, response: &Response) -> Result<()> {
+        let pdu = match &response.result {
+            Ok(data) => [&[response.function_code], &data[..]].concat(),
+            Err(code) => vec![response.function_code | 0x80, code.code()],
+        };
+        match self.encapsulation {
+            Encapsulation::Tcp => {
+                let adu = mbap(self.transaction_id, response.id, &pdu);
+                self.write_tcp(timestamp, Direction::Rx, &adu)
+            }
+            Encapsulation::Rtu => {
+                let mut frame = [&[response.id], &pdu[..]].concat();
+                frame.extend_from_slice(&calc_crc(&frame).to_be_bytes());
+                self.write_record(timestamp, &frame)
+            }
+        }
+    }
+
+    /// 加上 IPv4 和 TCP 报文头写入 Modbus/TCP 报文
+    fn write_tcp(&mut self, timestamp: SystemTime, direction: Direction, adu: &[u8]) -> Result<()> {
+        let (src, dst, sport, dport, seq, ack) = match direction {
+            Direction::Tx => (
+                CLIENT_IP,
+                SERVER_IP,
+                CLIENT_PORT,
+                SERVER_PORT,
+                &mut self.client_seq,
+                self.server_seq,
+            ),
+            Direction::Rx => (
+                SERVER_IP,
+                CLIENT_IP,
+                SERVER_PORT,
+                CLIENT_PORT,
+                &mut self.server_seq,
+                self.client_seq,
+            ),
+        };
+
+        let total = 20 + 20 + adu.len();
+        let mut packet = Vec::with_capacity(total);
+        // IPv4: 版本和头长度, 服务类型, 总长度, 标识, 不分片, TTL, 协议 TCP, 校验和, 源地址, 目的地址
+        packet.extend_from_slice(&[0x45, 0]);
+        packet.extend_from_slice(&(total as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]);
+        packet.extend_from_slice(&src);
+        packet.extend_from_slice(&dst);
+        let checksum = ip_checksum(&packet);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        // TCP: 源端口, 目的端口, 序列号, 确认号, 头长度, PSH|ACK, 窗口, 校验和(不计算), 紧急指针
+        packet.extend_from_slice(&sport.to_be_bytes());
+        packet.extend_from_slice(&dport.to_be_bytes());
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&ack.to_be_bytes());
+        packet.extend_from_slice(&[0x50, 0x18, 0xFF, 0xFF, 0, 0, 0, 0]);
+        packet.extend_from_slice(adu);
+        *seq = seq.wrapping_add(adu.len() as u32);
+
+        self.write_record(timestamp, &packet)
+    }
+
+    /// 写入一条记录
+    fn write_record(&mut self, timestamp: SystemTime, data: &[u8]) -> Result<()> {
+        let time = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.writer
+            .write_all(&(time.as_secs() as u32).to_le_bytes())?;
+        self.writer.write_all(&time.subsec_micros().to_le_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// 从帧中取出 从设备ID 和 PDU, 不检查校验值
+fn split_frame(protocol: Protocol, frame: &[u8]) -> Option<(Id, Bytes)> {
+    let (id, pdu) = match protocol {
+        // MBAP报文头(7) + 功能码(1)
+        Protocol::Tcp => (*frame.get(6)?, frame.get(7..)?),
+        // ID(1) + 功能码(1) + CRC(2)
+        Protocol::Rtu => (*frame.first()?, frame.get(1..frame.len().checked_sub(2)?)?),
+        Protocol::Ascii => {
+            let frame = ascii_decode(frame).ok()?;
+            // ID(1) + 功能码(1) + LRC(1)
+            if frame.len() < 3 {
+                return None;
+            }
+            return Some((frame[0], Bytes::copy_from_slice(&frame[1..frame.len() - 1])));
+        }
+    };
+    (!pdu.is_empty()).then(|| (id, Bytes::copy_from_slice(pdu)))
+}
+
+/// 事务标识(2) + 协议标识(2) + 长度(2) + 单元标识(1) + PDU
+fn mbap(transaction_id: u16, id: Id, pdu: &[u8]) -> Vec<u8> {
+    let mut adu = Vec::with_capacity(7 + pdu.len());
+    adu.extend_from_slice(&transaction_id.to_be_bytes());
+    adu.extend_from_slice(&0u16.to_be_bytes());
+    adu.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+    adu.push(id);
+    adu.extend_from_slice(pdu);
+    adu
+}
+
+/// IPv4 报文头的校验和
+fn ip_checksum(header: &[u8]) -> u16 {
+    let sum = header
+        .chunks(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]) as u32)
+        .sum::<u32>();
+    let sum = (sum & 0xFFFF) + (sum >> 16);
+    !((sum & 0xFFFF) + (sum >> 16)) as u16
+}
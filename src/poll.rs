@@ -0,0 +1,454 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    client::split_request,
+    command::{CommandQueue, CommandSender, Priority},
+    error::{Error, Result},
+    health::{Health, HealthPolicy, Quality},
+    quantity::{Quantity, MAX_READ_COILS, MAX_READ_REGISTERS},
+    register_map::{Point, RegisterMap},
+    subscribe::{Subscriber, Update},
+    Client, Coil, Id, Word,
+};
+
+/// 任务的序号
+pub type TaskId = usize;
+
+/// 轮询的最小间隔, 更小的间隔 (包括 0) 按照这个值轮询, 避免占满总线和 CPU
+pub const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// 轮询使用的读功能
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PollFunction {
+    Coils,
+    DiscreteInputs,
+    HoldingRegisters,
+    InputRegisters,
+}
+
+/// 一个轮询任务
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PollTask {
+    pub function: PollFunction,
+    pub id: Id,
+    pub address: u16,
+    pub quantity: u16,
+    /// 两次轮询之间的间隔, 不小于 [`MIN_INTERVAL`]
+    pub interval: Duration,
+}
+
+/// 读取到的数据
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PollValue {
+    Bits(Vec<Coil>),
+    Words(Vec<Word>),
+}
+
+/// 一次轮询的结果
+#[derive(Debug)]
+pub struct PollUpdate {
+    pub task: TaskId,
+    pub timestamp: Instant,
+    pub result: Result<PollValue>,
+    /// 按照任务连续失败的次数计算的质量
+    pub quality: Quality,
+}
+
+/// 任务的统计数据
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TaskStats {
+    /// 轮询的总次数
+    pub polls: u64,
+    /// 失败的总次数
+    pub errors: u64,
+    /// 连续失败的次数, 成功后清零
+    pub consecutive_errors: u32,
+}
+
+enum Sink {
+    Channel(Sender<PollUpdate>),
+    Callback(Box<dyn FnMut(PollUpdate) + Send>),
+    Subscription(Subscriber),
+}
+
+struct TaskState {
+    task: PollTask,
+    sink: Sink,
+    next_run: Instant,
+    stats: TaskStats,
+    health: Health,
+}
+
+/// 停止 Poller::run 的句柄, 可以在其它线程中使用
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 轮询调度器, 按照每个任务的间隔读取数据, 通过 channel 或者回调函数发送结果
+pub struct Poller {
+    client: Client,
+    tasks: Vec<Option<TaskState>>,
+    stop: StopHandle,
+    policy: HealthPolicy,
+    slaves: HashMap<Id, Health>,
+    commands: CommandQueue,
+    max_chunk: u16,
+}
+
+impl Poller {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            tasks: Vec::new(),
+            stop: StopHandle::default(),
+            policy: HealthPolicy::default(),
+            slaves: HashMap::new(),
+            commands: CommandQueue::new(),
+            max_chunk: 0,
+        }
+    }
+
+    /// 设置每个请求最多读取的数量, 0 表示使用协议的限制. 默认为 0
+    ///
+    /// 数量超过限制的任务拆分为多个请求, 每两个请求之间可以插入优先的命令,
+    /// 较小的值可以减少命令等待的时间, 但是会增加请求的数量
+    pub fn set_max_chunk(&mut self, max_chunk: u16) {
+        self.max_chunk = max_chunk;
+    }
+
+    /// 设置根据连续失败的次数判断质量的策略
+    pub fn set_health_policy(&mut self, policy: HealthPolicy) {
+        self.policy = policy;
+    }
+
+    /// 添加任务, 结果发送到 sender. 接收端关闭后任务自动删除
+    pub fn add_task(&mut self, task: PollTask, sender: Sender<PollUpdate>) -> TaskId {
+        self.push(task, Sink::Channel(sender))
+    }
+
+    /// 添加任务, 每次轮询后调用 callback
+    pub fn add_callback(
+        &mut self,
+        task: PollTask,
+        callback: impl FnMut(PollUpdate) + Send + 'static,
+    ) -> TaskId {
+        self.push(task, Sink::Callback(Box::new(callback)))
+    }
+
+    /// 订阅从设备 id 的数据点, 按照 interval 读取
+    ///
+    /// 只在值的变化超过 deadband 或者质量变化时发送更新, 第一次读取的结果总是发送.
+    /// 接收端关闭后, 订阅在下一次发送更新时自动删除
+    pub fn subscribe(
+        &mut self,
+        id: Id,
+        point: Point,
+        deadband: f64,
+        interval: Duration,
+    ) -> Receiver<Update> {
+        let (sender, receiver) = mpsc::channel();
+        let subscriber = Subscriber::new(point, deadband, sender);
+        let task = subscriber.task(id, interval);
+        self.push(task, Sink::Subscription(subscriber));
+        receiver
+    }
+
+    /// 订阅寄存器表中的所有数据点, 所有数据点的更新发送到同一个 channel
+    pub fn subscribe_map(
+        &mut self,
+        map: &RegisterMap,
+        deadband: f64,
+        interval: Duration,
+    ) -> Receiver<Update> {
+        let (sender, receiver) = mpsc::channel();
+        for point in map.points() {
+            let subscriber = Subscriber::new(point.clone(), deadband, sender.clone());
+            let task = subscriber.task(map.id(), interval);
+            self.push(task, Sink::Subscription(subscriber));
+        }
+        receiver
+    }
+
+    fn push(&mut self, mut task: PollTask, sink: Sink) -> TaskId {
+        task.interval = task.interval.max(MIN_INTERVAL);
+        self.tasks.push(Some(TaskState {
+            task,
+            sink,
+            next_run: Instant::now(),
+            stats: TaskStats::default(),
+            health: Health::default(),
+        }));
+        self.tasks.len() - 1
+    }
+
+    pub fn remove_task(&mut self, task: TaskId) {
+        if let Some(state) = self.tasks.get_mut(task) {
+            *state = None;
+        }
+    }
+
+    pub fn stats(&self, task: TaskId) -> Option<TaskStats> {
+        Some(self.tasks.get(task)?.as_ref()?.stats)
+    }
+
+    /// 任务的健康状态
+    pub fn task_health(&self, task: TaskId) -> Option<Health> {
+        Some(self.tasks.get(task)?.as_ref()?.health.clone())
+    }
+
+    /// 从设备的健康状态, 汇总了这个从设备的所有任务, 还没有轮询过时返回 None
+    ///
+    /// 任何一个任务收到响应 (包括异常响应) 都会清零连续失败的次数
+    pub fn slave_health(&self, id: Id) -> Option<Health> {
+        self.slaves.get(&id).cloned()
+    }
+
+    /// 所有轮询过的从设备的健康状态, 按照 从设备ID 排序
+    pub fn slaves_health(&self) -> Vec<(Id, Health)> {
+        let mut slaves = self
+            .slaves
+            .iter()
+            .map(|(id, health)| (*id, health.clone()))
+            .collect::<Vec<_>>();
+        slaves.sort_by_key(|(id, _)| *id);
+        slaves
+    }
+
+    pub fn client(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// 提交按需命令的句柄, 见 [`command`](crate::command)
+    pub fn command_sender(&self) -> CommandSender {
+        self.commands.sender()
+    }
+
+    /// 执行队列中优先级不低于 min 的命令
+    fn run_commands(&mut self, min: Priority) {
+        while let Some(command) = self.commands.pop(min) {
+            let res = self.client.request(command.function);
+            let _ = command.reply.send(res);
+        }
+    }
+
+    /// 按照 max_chunk 拆分读取, 每两次读取之间执行 Normal 和 High 优先级的命令
+    fn read_task(&mut self, task: &PollTask) -> Result<PollValue> {
+        let max = match task.function {
+            PollFunction::Coils | PollFunction::DiscreteInputs => MAX_READ_COILS,
+            PollFunction::HoldingRegisters | PollFunction::InputRegisters => MAX_READ_REGISTERS,
+        };
+        let max = match self.max_chunk {
+            0 => max,
+            chunk => chunk.min(max),
+        };
+        let chunks = split_request(task.address, Quantity::from(task.quantity), max)?;
+        if chunks.len() == 1 {
+            return read(&mut self.client, task);
+        }
+
+        let (mut bits, mut words) = (Vec::new(), Vec::new());
+        for (i, (address, quantity)) in chunks.into_iter().enumerate() {
+            if i > 0 {
+                self.run_commands(Priority::Normal);
+            }
+            let chunk = PollTask {
+                address,
+                quantity: quantity.get(),
+                ..task.clone()
+            };
+            match read(&mut self.client, &chunk)? {
+                PollValue::Bits(values) => bits.extend(values),
+                PollValue::Words(values) => words.extend(values),
+            }
+        }
+        Ok(match task.function {
+            PollFunction::Coils | PollFunction::DiscreteInputs => PollValue::Bits(bits),
+            PollFunction::HoldingRegisters | PollFunction::InputRegisters => {
+                PollValue::Words(words)
+            }
+        })
+    }
+
+    /// 执行所有到期的任务, 返回距离下一个任务到期的时间, 没有任务时返回 None
+    ///
+    /// 每个轮询任务之前, 以及拆分的请求之间, 先执行 Normal 和 High 优先级的命令,
+    /// 最后执行 Low 优先级的命令
+    pub fn poll_once(&mut self) -> Option<Duration> {
+        for index in 0..self.tasks.len() {
+            let task = match &mut self.tasks[index] {
+                Some(state) if state.next_run <= Instant::now() => {
+                    let now = Instant::now();
+                    // 以计划的时间为基准, 避免误差累积; 落后太多时从现在开始重新计时
+                    state.next_run += state.task.interval;
+                    if state.next_run < now {
+                        state.next_run = now + state.task.interval;
+                    }
+                    state.task.clone()
+                }
+                _ => continue,
+            };
+            self.run_commands(Priority::Normal);
+            let result = self.read_task(&task);
+
+            let slot = &mut self.tasks[index];
+            let Some(state) = slot else {
+                continue;
+            };
+            state.stats.polls += 1;
+            if result.is_ok() {
+                state.stats.consecutive_errors = 0;
+            } else {
+                state.stats.errors += 1;
+                state.stats.consecutive_errors += 1;
+            }
+            let quality = state.health.record(result.is_ok(), &self.policy);
+            // 异常响应说明从设备在线, 只影响任务的质量
+            let responded = matches!(result, Ok(_) | Err(Error::Exception(_)));
+            self.slaves
+                .entry(state.task.id)
+                .or_default()
+                .record(responded, &self.policy);
+            let update = PollUpdate {
+                task: index,
+                timestamp: Instant::now(),
+                result,
+                quality,
+            };
+            match &mut state.sink {
+                Sink::Channel(sender) => {
+                    if sender.send(update).is_err() {
+                        *slot = None;
+                    }
+                }
+                Sink::Callback(callback) => callback(update),
+                Sink::Subscription(subscriber) => {
+                    if !subscriber.update(update.result, update.quality) {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+
+        self.run_commands(Priority::Low);
+
+        let next_run = self.tasks.iter().flatten().map(|s| s.next_run).min()?;
+        Some(next_run.saturating_duration_since(Instant::now()))
+    }
+
+    /// 循环执行任务, 直到通过 StopHandle 停止或者没有任务
+    pub fn run(&mut self) {
+        // 每次最多等待的时间, 保证能及时响应停止
+        const MAX_SLEEP: Duration = Duration::from_millis(50);
+        while !self.stop.is_stopped() {
+            match self.poll_once() {
+                // 等待期间收到命令时立即执行
+                Some(wait) => self.commands.wait(wait.min(MAX_SLEEP)),
+                None => break,
+            }
+        }
+    }
+}
+
+pub(crate) fn read(client: &mut Client, task: &PollTask) -> Result<PollValue> {
+    let (id, address, quantity) = (task.id, task.address, task.quantity);
+    Ok(match task.function {
+        PollFunction::Coils => PollValue::Bits(client.read_coils(id, address, quantity)?),
+        PollFunction::DiscreteInputs => {
+            PollValue::Bits(client.read_discrete_inputs(id, address, quantity)?)
+        }
+        PollFunction::HoldingRegisters => {
+            PollValue::Words(client.read_holding_registers(id, address, quantity)?)
+        }
+        PollFunction::InputRegisters => {
+            PollValue::Words(client.read_input_registers(id, address, quantity)?)
+        }
+    })
+}
+
+/// 序列化为 `{"task": 序号, "quality": 质量, "value": 数据}` 或者
+/// `{"task": 序号, "quality": 质量, "error": 错误}`, 不包含时间戳
+#[cfg(feature = "serde")]
+impl serde::Serialize for PollUpdate {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("PollUpdate", 3)?;
+        state.serialize_field("task", &self.task)?;
+        state.serialize_field("quality", &self.quality)?;
+        match &self.result {
+            Ok(value) => state.serialize_field("value", value)?,
+            Err(e) => state.serialize_field("error", e)?,
+        }
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bank::RegisterBank, mock::MockStream, register_map::Area, value::ValueType, Protocol,
+    };
+
+    #[test]
+    fn zero_interval_is_clamped() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 4));
+        let stream = MockStream::with_bank(bank, Protocol::Rtu);
+        let client = Client::new(Box::new(stream.clone())).unwrap();
+        let mut poller = Poller::new(client);
+        let (sender, receiver) = mpsc::channel();
+        let task = poller.add_task(
+            PollTask {
+                function: PollFunction::HoldingRegisters,
+                id: 1,
+                address: 0,
+                quantity: 2,
+                interval: Duration::ZERO,
+            },
+            sender,
+        );
+
+        let interval =
+            |poller: &Poller, task: TaskId| poller.tasks[task].as_ref().unwrap().task.interval;
+        assert_eq!(interval(&poller, task), MIN_INTERVAL);
+        let point = Point::new("speed", Area::HoldingRegister, 2, ValueType::U16);
+        let _updates = poller.subscribe(1, point, 0.0, Duration::ZERO);
+        assert_eq!(interval(&poller, task + 1), MIN_INTERVAL);
+
+        let wait = poller.poll_once().unwrap();
+        assert!(wait <= MIN_INTERVAL);
+        assert_eq!(
+            receiver.try_recv().unwrap().result.unwrap(),
+            PollValue::Words(vec![0, 0])
+        );
+        assert_eq!(stream.requests().len(), 2);
+    }
+}
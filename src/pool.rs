@@ -0,0 +1,133 @@
+//! Modbus TCP 连接池: 按照服务器地址管理多个连接, 用于轮询大量网关或者设备的程序
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    builder::ClientBuilder,
+    error::{Error, Result},
+    shared::SharedClient,
+    Client,
+};
+
+/// 连接默认的空闲超时时间
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+struct Entry {
+    client: SharedClient,
+    last_used: Instant,
+}
+
+/// Modbus TCP 连接池, 以服务器的地址为键
+///
+/// 第一次访问某个地址时才建立连接, 连接断开时客户端在下一个请求中自动重新连接.
+/// 超过空闲超时时间没有通过 [`client`](Self::client) 取用的连接会被关闭
+pub struct ConnectionPool {
+    builder: ClientBuilder,
+    idle_timeout: Duration,
+    connections: Mutex<HashMap<SocketAddr, Entry>>,
+}
+
+impl ConnectionPool {
+    /// 新的连接都按照 builder 的配置创建
+    pub fn new(builder: ClientBuilder) -> Self {
+        Self {
+            builder,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<SocketAddr, Entry>> {
+        self.connections.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 取得服务器的客户端, 还没有连接时建立连接, addr 格式为 `host:port`
+    pub fn client(&self, addr: &str) -> Result<SharedClient> {
+        let addr = resolve(addr)?;
+        self.evict_idle();
+        if let Some(entry) = self.lock().get_mut(&addr) {
+            entry.last_used = Instant::now();
+            return Ok(entry.client.clone());
+        }
+
+        // 建立连接时不持有锁, 避免阻塞其它服务器的请求
+        let mut client = self.builder.clone().connect_tcp(&addr.to_string())?;
+        client.set_auto_reconnect(1);
+        let client = SharedClient::new(client);
+        let mut connections = self.lock();
+        let entry = connections.entry(addr).or_insert(Entry {
+            client,
+            last_used: Instant::now(),
+        });
+        entry.last_used = Instant::now();
+        Ok(entry.client.clone())
+    }
+
+    /// 关闭到服务器的连接, 下一次访问时重新连接
+    pub fn remove(&self, addr: &str) -> Result<bool> {
+        let addr = resolve(addr)?;
+        Ok(self.lock().remove(&addr).is_some())
+    }
+
+    /// 关闭超过空闲超时时间的连接, 返回关闭的数量
+    pub fn evict_idle(&self) -> usize {
+        let mut connections = self.lock();
+        let before = connections.len();
+        connections.retain(|_, entry| entry.last_used.elapsed() < self.idle_timeout);
+        before - connections.len()
+    }
+
+    /// 对每个连接执行 check, 失败的连接被关闭, 下一次访问时重新连接
+    ///
+    /// Modbus 没有通用的心跳请求, check 通常读取一个已知存在的寄存器. 返回失败的服务器和错误
+    pub fn health_check(
+        &self,
+        mut check: impl FnMut(&mut Client) -> Result<()>,
+    ) -> Vec<(SocketAddr, Error)> {
+        let clients = self
+            .lock()
+            .iter()
+            .map(|(addr, entry)| (*addr, entry.client.clone()))
+            .collect::<Vec<_>>();
+        let failed = clients
+            .into_iter()
+            .filter_map(|(addr, client)| client.with(&mut check).err().map(|e| (addr, e)))
+            .collect::<Vec<_>>();
+        let mut connections = self.lock();
+        for (addr, e) in &failed {
+            log::warn!("连接 {} 检查失败, 关闭连接, E: {}", addr, e);
+            connections.remove(addr);
+        }
+        failed
+    }
+
+    /// 当前连接的服务器
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        self.lock().keys().copied().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+}
+
+fn resolve(addr: &str) -> Result<SocketAddr> {
+    addr.to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "无法解析服务器地址").into())
+}
@@ -0,0 +1,503 @@
+//! 模拟设备的配置: 寄存器初始值, 只读区域, 固定返回异常的地址, 延迟和随机故障
+//!
+//! 与 [`MockStream::with_profile`](crate::mock::MockStream::with_profile) 一起使用,
+//! 在集成测试中重现现场设备的不稳定行为. 随机故障由 seed 决定, 相同的配置和请求序列得到相同的结果
+
+use std::{ops::RangeInclusive, time::Duration};
+
+use crate::{
+    bank::RegisterBank,
+    config::invalid_config,
+    error::{ExceptionCode, Result},
+    register_map::Area,
+    Coil, Word,
+};
+
+/// 一段寄存器的初始值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitialValues {
+    pub area: Area,
+    pub address: u16,
+    /// 位数据区中非 0 为 `Coil::On`
+    pub values: Vec<Word>,
+}
+
+/// 访问某个地址时固定返回的异常
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionRule {
+    /// 为 None 时对所有数据区生效
+    pub area: Option<Area>,
+    pub address: u16,
+    pub code: ExceptionCode,
+}
+
+/// 模拟设备的配置
+///
+/// TOML 格式 (需要 `toml` feature), JSON 格式 (需要 `json` feature) 的字段相同,
+/// 除了 name 之外都可以省略:
+/// ```toml
+/// name = "flaky-meter"
+/// seed = 42
+/// latency_ms = 20
+/// # 不响应, 校验错误, 返回 SlaveOrServerBusy 异常的概率
+/// drop_rate = 0.05
+/// corrupt_rate = 0.01
+/// busy_rate = 0.0
+///
+/// [size]
+/// holding = 256
+///
+/// [[register]]
+/// area = "holding"
+/// address = 0x10
+/// values = [1, 2, 3]
+///
+/// [[read_only]]
+/// area = "holding"
+/// start = 0x00
+/// end = 0x0F
+///
+/// [[exception]]
+/// area = "input"
+/// address = 0x20
+/// code = 4
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceProfile {
+    pub name: String,
+    /// 各数据区的大小, 顺序为 线圈, 离散输入, 输入寄存器, 保持寄存器, 默认都为 0x10000
+    pub size: [usize; 4],
+    pub initial_values: Vec<InitialValues>,
+    /// 只读的地址范围, 写入时返回 `IllegalDataAddress` 异常
+    pub read_only: Vec<(Area, RangeInclusive<u16>)>,
+    pub exceptions: Vec<ExceptionRule>,
+    /// 每个响应的延迟, 超过读超时时间的响应被丢弃
+    pub latency: Duration,
+    /// 不响应的概率
+    pub drop_rate: f64,
+    /// 响应校验错误的概率, TCP 没有校验值, 改为破坏协议标识
+    pub corrupt_rate: f64,
+    /// 返回 `SlaveOrServerBusy` 异常的概率
+    pub busy_rate: f64,
+    /// 随机数种子
+    pub seed: u64,
+}
+
+impl DeviceProfile {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            size: [0x10000; 4],
+            initial_values: Vec::new(),
+            read_only: Vec::new(),
+            exceptions: Vec::new(),
+            latency: Duration::ZERO,
+            drop_rate: 0.0,
+            corrupt_rate: 0.0,
+            busy_rate: 0.0,
+            seed: 0,
+        }
+    }
+
+    pub fn size(mut self, area: Area, size: usize) -> Self {
+        self.size[area_index(area)] = size;
+        self
+    }
+
+    pub fn initial_values(mut self, area: Area, address: u16, values: &[Word]) -> Self {
+        self.initial_values.push(InitialValues {
+            area,
+            address,
+            values: values.to_vec(),
+        });
+        self
+    }
+
+    pub fn read_only(mut self, area: Area, range: RangeInclusive<u16>) -> Self {
+        self.read_only.push((area, range));
+        self
+    }
+
+    pub fn exception(mut self, area: Option<Area>, address: u16, code: ExceptionCode) -> Self {
+        self.exceptions.push(ExceptionRule {
+            area,
+            address,
+            code,
+        });
+        self
+    }
+
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn drop_rate(mut self, rate: f64) -> Self {
+        self.drop_rate = rate;
+        self
+    }
+
+    pub fn corrupt_rate(mut self, rate: f64) -> Self {
+        self.corrupt_rate = rate;
+        self
+    }
+
+    pub fn busy_rate(mut self, rate: f64) -> Self {
+        self.busy_rate = rate;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// 检查配置是否有效: 概率在 0..=1 之间, 初始值不超出数据区
+    pub fn validate(&self) -> Result<()> {
+        for rate in [self.drop_rate, self.corrupt_rate, self.busy_rate] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(invalid_config("概率必须在 0 到 1 之间"));
+            }
+        }
+        if self.size.iter().any(|&size| size > 0x10000) {
+            return Err(invalid_config("数据区的大小不能超过 0x10000"));
+        }
+        for init in &self.initial_values {
+            if init.address as usize + init.values.len() > self.size[area_index(init.area)] {
+                return Err(invalid_config(&format!(
+                    "初始值超出数据区: 0x{:04X}",
+                    init.address
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// 按照大小和初始值创建寄存器数据
+    pub fn bank(&self) -> Result<RegisterBank> {
+        self.validate()?;
+        let [coils, discrete_inputs, input_registers, holding_registers] = self.size;
+        let bank = RegisterBank::new(coils, discrete_inputs, input_registers, holding_registers);
+        for init in &self.initial_values {
+            let bits = || {
+                init.values
+                    .iter()
+                    .map(|&v| if v != 0 { Coil::On } else { Coil::Off })
+                    .collect::<Vec<_>>()
+            };
+            if init.values.is_empty() {
+                continue;
+            }
+            match init.area {
+                Area::Coil => bank.set_coils(init.address, &bits())?,
+                Area::DiscreteInput => bank.set_discrete_inputs(init.address, &bits())?,
+                Area::InputRegister => bank.set_input_registers(init.address, &init.values)?,
+                Area::HoldingRegister => bank.set_holding_registers(init.address, &init.values)?,
+            }
+        }
+        Ok(bank)
+    }
+
+    /// 从 TOML 文本加载
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let table = text
+            .parse::<toml::Table>()
+            .map_err(|e| invalid_config(&format!("TOML 格式错误, {}", e)))?;
+        Self::from_node(&toml::Value::Table(table))
+    }
+
+    /// 从 JSON 文本加载
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<Self> {
+        let value = serde_json::from_str::<serde_json::Value>(text)
+            .map_err(|e| invalid_config(&format!("JSON 格式错误, {}", e)))?;
+        Self::from_node(&value)
+    }
+
+    #[cfg(any(feature = "toml", feature = "json"))]
+    fn from_node<N: Node>(root: &N) -> Result<Self> {
+        let name = root
+            .get("name")
+            .and_then(N::as_str)
+            .ok_or_else(|| invalid_config("缺少字段: name"))?;
+        let mut profile = Self::new(name);
+        if let Some(seed) = root.get("seed") {
+            profile.seed = seed
+                .as_int()
+                .and_then(|seed| u64::try_from(seed).ok())
+                .ok_or_else(|| invalid_config("无效的 seed"))?;
+        }
+        if let Some(latency) = root.get("latency_ms") {
+            let latency = latency
+                .as_int()
+                .and_then(|ms| u64::try_from(ms).ok())
+                .ok_or_else(|| invalid_config("无效的 latency_ms"))?;
+            profile.latency = Duration::from_millis(latency);
+        }
+        profile.drop_rate = number(root, "drop_rate")?.unwrap_or(0.0);
+        profile.corrupt_rate = number(root, "corrupt_rate")?.unwrap_or(0.0);
+        profile.busy_rate = number(root, "busy_rate")?.unwrap_or(0.0);
+
+        if let Some(size) = root.get("size") {
+            for (key, area) in [
+                ("coil", Area::Coil),
+                ("discrete", Area::DiscreteInput),
+                ("input", Area::InputRegister),
+                ("holding", Area::HoldingRegister),
+            ] {
+                if let Some(value) = size.get(key) {
+                    profile.size[area_index(area)] = value
+                        .as_int()
+                        .and_then(|size| usize::try_from(size).ok())
+                        .ok_or_else(|| invalid_config(&format!("无效的大小: {}", key)))?;
+                }
+            }
+        }
+        for init in array(root, "register")? {
+            profile.initial_values.push(InitialValues {
+                area: area(init)?,
+                address: address(init, "address")?,
+                values: words(init, "register")?,
+            });
+        }
+        for range in array(root, "read_only")? {
+            let (start, end) = (address(range, "start")?, address(range, "end")?);
+            profile.read_only.push((area(range)?, start..=end));
+        }
+        for rule in array(root, "exception")? {
+            let code = rule
+                .get("code")
+                .and_then(N::as_int)
+                .and_then(|code| u8::try_from(code).ok())
+                .ok_or_else(|| invalid_config("无效的异常码"))?;
+            profile.exceptions.push(ExceptionRule {
+                area: rule.get("area").map(|_| area(rule)).transpose()?,
+                address: address(rule, "address")?,
+                code: code.into(),
+            });
+        }
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// 请求访问的 (数据区, 起始地址, 数量, 是否写入), 无法识别的请求返回空
+    fn accesses(pdu: &[u8]) -> Vec<(Area, u16, u16, bool)> {
+        if pdu.len() < 5 {
+            return Vec::new();
+        }
+        let word = |i: usize| u16::from_be_bytes([pdu[i], pdu[i + 1]]);
+        let (address, quantity) = (word(1), word(3));
+        match pdu[0] {
+            0x01 => vec![(Area::Coil, address, quantity, false)],
+            0x02 => vec![(Area::DiscreteInput, address, quantity, false)],
+            0x03 => vec![(Area::HoldingRegister, address, quantity, false)],
+            0x04 => vec![(Area::InputRegister, address, quantity, false)],
+            0x05 => vec![(Area::Coil, address, 1, true)],
+            0x06 | 0x16 => vec![(Area::HoldingRegister, address, 1, true)],
+            0x0F => vec![(Area::Coil, address, quantity, true)],
+            0x10 => vec![(Area::HoldingRegister, address, quantity, true)],
+            0x17 if pdu.len() >= 9 => vec![
+                (Area::HoldingRegister, address, quantity, false),
+                (Area::HoldingRegister, word(5), word(7), true),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    /// 按照只读区域和异常规则检查请求, 需要返回异常时返回异常码
+    fn check(&self, pdu: &[u8]) -> Option<ExceptionCode> {
+        for (area, address, quantity, write) in Self::accesses(pdu) {
+            let range = address as u32..address as u32 + quantity as u32;
+            for rule in &self.exceptions {
+                if rule.area.is_none_or(|a| a == area) && range.contains(&(rule.address as u32)) {
+                    return Some(rule.code);
+                }
+            }
+            let read_only = self.read_only.iter().any(|(a, r)| {
+                *a == area && range.start <= *r.end() as u32 && (*r.start() as u32) < range.end
+            });
+            if write && quantity > 0 && read_only {
+                return Some(ExceptionCode::IllegalDataAddress);
+            }
+        }
+        None
+    }
+}
+
+fn area_index(area: Area) -> usize {
+    match area {
+        Area::Coil => 0,
+        Area::DiscreteInput => 1,
+        Area::InputRegister => 2,
+        Area::HoldingRegister => 3,
+    }
+}
+
+/// 按照配置模拟一个从设备, 由 MockStream 使用
+#[derive(Debug)]
+pub(crate) struct Simulator {
+    profile: DeviceProfile,
+    rng: u64,
+}
+
+/// 模拟设备对一个请求的处理结果
+pub(crate) enum Outcome {
+    /// 不响应
+    Drop,
+    /// 返回异常, 不交给 RegisterBank 处理
+    Exception(ExceptionCode),
+    /// 交给 RegisterBank 处理
+    Process,
+}
+
+impl Simulator {
+    pub(crate) fn new(profile: DeviceProfile) -> Result<Self> {
+        profile.validate()?;
+        // xorshift 的状态不能为 0
+        let rng = profile.seed ^ 0x9E37_79B9_7F4A_7C15;
+        Ok(Self { profile, rng })
+    }
+
+    pub(crate) fn latency(&self) -> Duration {
+        self.profile.latency
+    }
+
+    pub(crate) fn handle(&mut self, pdu: &[u8]) -> Outcome {
+        if self.chance(self.profile.drop_rate) {
+            return Outcome::Drop;
+        }
+        if self.chance(self.profile.busy_rate) {
+            return Outcome::Exception(ExceptionCode::SlaveOrServerBusy);
+        }
+        match self.profile.check(pdu) {
+            Some(code) => Outcome::Exception(code),
+            None => Outcome::Process,
+        }
+    }
+
+    /// 是否破坏这个响应
+    pub(crate) fn corrupt(&mut self) -> bool {
+        self.chance(self.profile.corrupt_rate)
+    }
+
+    fn chance(&mut self, rate: f64) -> bool {
+        if rate <= 0.0 {
+            return false;
+        }
+        // xorshift64*
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let value = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        ((value >> 11) as f64 / (1u64 << 53) as f64) < rate
+    }
+}
+
+/// TOML 和 JSON 的值, 用于共用加载配置的代码
+#[cfg(any(feature = "toml", feature = "json"))]
+pub(crate) trait Node: Sized {
+    fn get(&self, key: &str) -> Option<&Self>;
+    fn as_str(&self) -> Option<&str>;
+    fn as_int(&self) -> Option<i64>;
+    fn as_float(&self) -> Option<f64>;
+    fn as_array(&self) -> Option<&[Self]>;
+}
+
+#[cfg(feature = "toml")]
+impl Node for toml::Value {
+    fn get(&self, key: &str) -> Option<&Self> {
+        toml::Value::get(self, key)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        toml::Value::as_str(self)
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        self.as_integer()
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        toml::Value::as_float(self).or_else(|| self.as_integer().map(|v| v as f64))
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        toml::Value::as_array(self).map(Vec::as_slice)
+    }
+}
+
+#[cfg(feature = "json")]
+impl Node for serde_json::Value {
+    fn get(&self, key: &str) -> Option<&Self> {
+        serde_json::Value::get(self, key)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        serde_json::Value::as_str(self)
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        self.as_i64()
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        self.as_f64()
+    }
+
+    fn as_array(&self) -> Option<&[Self]> {
+        serde_json::Value::as_array(self).map(Vec::as_slice)
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+fn number<N: Node>(node: &N, key: &str) -> Result<Option<f64>> {
+    node.get(key)
+        .map(|value| {
+            value
+                .as_float()
+                .ok_or_else(|| invalid_config(&format!("字段 {} 必须是数字", key)))
+        })
+        .transpose()
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+pub(crate) fn array<'a, N: Node>(node: &'a N, key: &str) -> Result<&'a [N]> {
+    match node.get(key) {
+        Some(value) => value
+            .as_array()
+            .ok_or_else(|| invalid_config(&format!("{} 必须是数组", key))),
+        None => Ok(&[]),
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+fn area<N: Node>(node: &N) -> Result<Area> {
+    node.get("area")
+        .and_then(N::as_str)
+        .ok_or_else(|| invalid_config("缺少字段: area"))?
+        .parse()
+}
+
+#[cfg(any(feature = "toml", feature = "json"))]
+pub(crate) fn address<N: Node>(node: &N, key: &str) -> Result<u16> {
+    node.get(key)
+        .and_then(N::as_int)
+        .and_then(|address| u16::try_from(address).ok())
+        .ok_or_else(|| invalid_config(&format!("无效的地址: {}", key)))
+}
+
+/// 读取 values 数组, name 用于错误信息
+#[cfg(any(feature = "toml", feature = "json"))]
+pub(crate) fn words<N: Node>(node: &N, name: &str) -> Result<Vec<Word>> {
+    node.get("values")
+        .and_then(N::as_array)
+        .ok_or_else(|| invalid_config(&format!("{} 缺少 values 数组", name)))?
+        .iter()
+        .map(|v| {
+            v.as_int()
+                .and_then(|v| u16::try_from(v).ok())
+                .ok_or_else(|| invalid_config("寄存器的值必须在 0..=0xFFFF 之间"))
+        })
+        .collect()
+}
@@ -0,0 +1,68 @@
+use crate::error::{Error, Reason, Result};
+use std::fmt;
+
+pub use crate::codec::{
+    MAX_READ_COILS, MAX_READ_REGISTERS, MAX_READ_WRITE_REGISTERS, MAX_WRITE_COILS,
+    MAX_WRITE_REGISTERS,
+};
+
+/// Modbus需要读或写的数据数量
+///
+/// 通过 `From<u16>` 构造时不做检查, 发送请求前会按照功能码检查.
+/// 需要提前检查时使用各功能类别对应的构造函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Quantity(u16);
+
+impl Quantity {
+    /// 读寄存器的数量, 范围 1..=125
+    pub fn read_registers(quantity: u16) -> Result<Self> {
+        Self::checked(quantity, MAX_READ_REGISTERS)
+    }
+
+    /// 写寄存器的数量, 范围 1..=123
+    pub fn write_registers(quantity: u16) -> Result<Self> {
+        Self::checked(quantity, MAX_WRITE_REGISTERS)
+    }
+
+    /// 读线圈或离散输入的数量, 范围 1..=2000
+    pub fn read_coils(quantity: u16) -> Result<Self> {
+        Self::checked(quantity, MAX_READ_COILS)
+    }
+
+    /// 写线圈的数量, 范围 1..=1968
+    pub fn write_coils(quantity: u16) -> Result<Self> {
+        Self::checked(quantity, MAX_WRITE_COILS)
+    }
+
+    pub const fn get(self) -> u16 {
+        self.0
+    }
+
+    fn checked(quantity: u16, max: u16) -> Result<Self> {
+        if quantity == 0 || quantity > max {
+            return Err(Error::InvalidData(Reason::QuantityOutOfRange {
+                quantity,
+                max,
+            }));
+        }
+        Ok(Self(quantity))
+    }
+}
+
+impl From<u16> for Quantity {
+    fn from(quantity: u16) -> Self {
+        Self(quantity)
+    }
+}
+
+impl From<Quantity> for u16 {
+    fn from(quantity: Quantity) -> u16 {
+        quantity.0
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,153 @@
+//! 请求速率限制: 令牌桶限制每秒的请求数量, 空闲比例保证总线上留有空闲时间
+//!
+//! 用于防止频繁的轮询占满 RS-485 总线, 或者让处理能力有限的 PLC 过载.
+//! 通过 [`Client::set_rate_limiter`](crate::Client::set_rate_limiter) 设置,
+//! 共享同一个 Client 的所有线程使用同一个限制
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::Id;
+
+/// 空闲比例的上限, 避免等待时间无限长
+const MAX_IDLE_RATIO: f64 = 0.95;
+
+/// 令牌桶
+#[derive(Debug, Clone)]
+struct Bucket {
+    /// 每秒补充的令牌数量
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: f64, burst: u32) -> Self {
+        let capacity = burst.max(1) as f64;
+        Self {
+            rate: rate.max(f64::EPSILON),
+            capacity,
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// 得到一个令牌需要等待的时间
+    fn delay(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+        }
+    }
+
+    fn take(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens -= 1.0;
+    }
+}
+
+/// 请求的速率限制
+///
+/// 总线的限制和从设备的限制同时生效, 例如
+/// ```
+/// use simple_modbus::rate_limit::RateLimiter;
+///
+/// // 总线每秒最多 50 个请求, 至少 30% 的时间空闲, 从设备 5 每秒最多 2 个请求
+/// let limiter = RateLimiter::new(50.0).min_idle_ratio(0.3).slave(5, 2.0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    bus: Option<Bucket>,
+    slaves: HashMap<Id, Bucket>,
+    min_idle_ratio: f64,
+    /// 上一次传输结束后需要空闲到的时间
+    idle_until: Option<Instant>,
+    total_delay: Duration,
+}
+
+impl RateLimiter {
+    /// 总线上每秒最多 requests_per_second 个请求, 不允许突发
+    pub fn new(requests_per_second: f64) -> Self {
+        Self {
+            bus: Some(Bucket::new(requests_per_second, 1)),
+            ..Self::default()
+        }
+    }
+
+    /// 总线允许连续发送的请求数量, 之后按照速率发送, 默认为 1
+    pub fn burst(mut self, burst: u32) -> Self {
+        if let Some(bus) = &self.bus {
+            self.bus = Some(Bucket::new(bus.rate, burst));
+        }
+        self
+    }
+
+    /// 总线空闲时间的最小比例 (0 ~ 0.95), 每次传输之后按照传输的时间等待
+    ///
+    /// 例如 0.5 表示传输之后至少空闲与传输相同的时间
+    pub fn min_idle_ratio(mut self, ratio: f64) -> Self {
+        self.min_idle_ratio = ratio.clamp(0.0, MAX_IDLE_RATIO);
+        self
+    }
+
+    /// 从设备 id 每秒最多 requests_per_second 个请求
+    pub fn slave(mut self, id: Id, requests_per_second: f64) -> Self {
+        self.slaves.insert(id, Bucket::new(requests_per_second, 1));
+        self
+    }
+
+    /// 等待限制而延迟的总时间
+    pub fn total_delay(&self) -> Duration {
+        self.total_delay
+    }
+
+    /// 发送到从设备 id 的请求需要等待的时间
+    pub fn delay(&mut self, id: Id) -> Duration {
+        let now = Instant::now();
+        let idle = self
+            .idle_until
+            .map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+        let bus = self.bus.as_mut().map_or(Duration::ZERO, |b| b.delay(now));
+        let slave = self
+            .slaves
+            .get_mut(&id)
+            .map_or(Duration::ZERO, |b| b.delay(now));
+        idle.max(bus).max(slave)
+    }
+
+    /// 等待到可以发送请求, 然后消耗一个令牌
+    pub(crate) fn acquire(&mut self, id: Id) {
+        let delay = self.delay(id);
+        if !delay.is_zero() {
+            log::trace!("速率限制, 等待 {:?}", delay);
+            std::thread::sleep(delay);
+            self.total_delay += delay;
+        }
+        let now = Instant::now();
+        if let Some(bus) = &mut self.bus {
+            bus.take(now);
+        }
+        if let Some(slave) = self.slaves.get_mut(&id) {
+            slave.take(now);
+        }
+    }
+
+    /// 记录一次传输占用总线的时间
+    pub(crate) fn record_busy(&mut self, busy: Duration) {
+        if self.min_idle_ratio > 0.0 {
+            let idle = busy.mul_f64(self.min_idle_ratio / (1.0 - self.min_idle_ratio));
+            self.idle_until = Some(Instant::now() + idle);
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! 录制和回放传输的数据, 在真实设备上遇到的问题录制一次之后, 可以在没有设备的环境中重现
+//!
+//! 录制文件为文本格式, 每行一个事件:
+//! ```text
+//! TX 01 03 00 00 00 02 C4 0B
+//! RX 01 03 04 00 01
+//! RX 00 02 2A 32
+//! TIMEOUT
+//! ```
+//! TX 为一次 flush 发送的请求, RX 为一次 read 收到的数据, TIMEOUT 为一次读超时.
+//! 空行和 `#` 开头的行被忽略
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{self, LineWriter, Read, Write},
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::{
+    config::invalid_config,
+    error::{Error, Result},
+    stream::Stream,
+};
+
+/// 录制的一个事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordEvent {
+    /// 发送的数据
+    Tx(Vec<u8>),
+    /// 收到的数据
+    Rx(Vec<u8>),
+    /// 读超时
+    Timeout,
+}
+
+impl fmt::Display for RecordEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, data) = match self {
+            RecordEvent::Tx(data) => ("TX", data),
+            RecordEvent::Rx(data) => ("RX", data),
+            RecordEvent::Timeout => return write!(f, "TIMEOUT"),
+        };
+        write!(f, "{}", name)?;
+        for byte in data {
+            write!(f, " {:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RecordEvent {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut fields = s.split_whitespace();
+        let invalid = || invalid_config(&format!("无效的录制数据: {}", s));
+        let name = fields.next().ok_or_else(invalid)?;
+        if name == "TIMEOUT" {
+            return Ok(RecordEvent::Timeout);
+        }
+        let data = fields
+            .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+        match name {
+            "TX" => Ok(RecordEvent::Tx(data)),
+            "RX" => Ok(RecordEvent::Rx(data)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// 解析录制文件的内容
+pub fn parse_events(text: &str) -> Result<Vec<RecordEvent>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::parse)
+        .collect()
+}
+
+/// 把经过的所有数据写入录制文件的传输
+///
+/// 包装一个真实的传输, 读写操作都交给它, 同时记录发送和收到的数据
+pub struct RecordingStream<S> {
+    inner: S,
+    writer: Box<dyn Write + Send>,
+    /// 还没有 flush 的发送数据
+    tx: Vec<u8>,
+}
+
+impl<S: Stream> RecordingStream<S> {
+    /// 录制到 writer, 每个事件写入一行
+    pub fn new(inner: S, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            writer: Box::new(writer),
+            tx: Vec::new(),
+        }
+    }
+
+    /// 录制到文件, 已经存在的文件被覆盖
+    pub fn create(inner: S, path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(inner, LineWriter::new(file)))
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn record(&mut self, event: RecordEvent) -> io::Result<()> {
+        writeln!(self.writer, "{}", event)
+    }
+}
+
+impl<S: Stream> Read for RecordingStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Ok(n) => {
+                if n > 0 {
+                    self.record(RecordEvent::Rx(buf[..n].to_vec()))?;
+                }
+                Ok(n)
+            }
+            Err(e) => {
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                ) {
+                    self.record(RecordEvent::Timeout)?;
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<S: Stream> Write for RecordingStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.tx.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        if !self.tx.is_empty() {
+            let tx = std::mem::take(&mut self.tx);
+            self.record(RecordEvent::Tx(tx))?;
+        }
+        self.writer.flush()
+    }
+}
+
+impl<S: Stream> Stream for RecordingStream<S> {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.set_write_timeout(timeout)
+    }
+
+    fn baud_rate(&self) -> Option<u32> {
+        self.inner.baud_rate()
+    }
+
+    fn clear_input(&mut self) -> Result<()> {
+        self.inner.clear_input()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.flush()?;
+        self.inner.close()
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        self.tx.clear();
+        self.inner.reconnect()
+    }
+}
+
+/// 按照录制文件回放的传输
+///
+/// 每次 flush 时检查发送的数据是否与录制的 TX 事件一致, 不一致时返回 InvalidData 错误;
+/// 之后的 RX 事件按照录制时的分段返回, TIMEOUT 事件返回超时错误.
+/// 录制的数据用完之后, 读操作返回超时错误, 发送返回 UnexpectedEof 错误
+#[derive(Debug, Clone)]
+pub struct ReplayStream {
+    events: VecDeque<RecordEvent>,
+    /// 当前 RX 事件中还没有读取的数据
+    rx: VecDeque<u8>,
+    tx: Vec<u8>,
+    baud_rate: Option<u32>,
+}
+
+impl ReplayStream {
+    pub fn new(events: Vec<RecordEvent>) -> Self {
+        Self {
+            events: events.into(),
+            rx: VecDeque::new(),
+            tx: Vec::new(),
+            baud_rate: None,
+        }
+    }
+
+    /// 加载录制文件
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::new(parse_events(&text)?))
+    }
+
+    /// 录制的是串口时, 设置相同的波特率以便使用相同的 RTU 帧间隔
+    pub fn with_baud_rate(mut self, baud_rate: u32) -> Self {
+        self.baud_rate = Some(baud_rate);
+        self
+    }
+
+    /// 录制的事件是否已经全部回放
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty() && self.rx.is_empty()
+    }
+
+    /// 还没有回放的事件
+    pub fn remaining(&self) -> &VecDeque<RecordEvent> {
+        &self.events
+    }
+}
+
+impl Read for ReplayStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rx.is_empty() {
+            match self.events.front() {
+                Some(RecordEvent::Rx(_)) => {
+                    if let Some(RecordEvent::Rx(data)) = self.events.pop_front() {
+                        self.rx.extend(data);
+                    }
+                }
+                Some(RecordEvent::Timeout) => {
+                    self.events.pop_front();
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "录制的读超时"));
+                }
+                _ => return Err(io::Error::new(io::ErrorKind::TimedOut, "没有可读的数据")),
+            }
+        }
+        let n = buf.len().min(self.rx.len());
+        for (dst, src) in buf.iter_mut().zip(self.rx.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for ReplayStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.tx.is_empty() {
+            return Ok(());
+        }
+        let tx = std::mem::take(&mut self.tx);
+        // 跳过客户端没有读取的数据
+        self.rx.clear();
+        while let Some(RecordEvent::Rx(_) | RecordEvent::Timeout) = self.events.front() {
+            self.events.pop_front();
+        }
+        match self.events.pop_front() {
+            Some(RecordEvent::Tx(expected)) if expected == tx => Ok(()),
+            Some(event) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("发送的数据与录制的不一致, 期望 {}", event),
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "录制的数据已经结束",
+            )),
+        }
+    }
+}
+
+impl Stream for ReplayStream {
+    fn set_timeout(&mut self, _timeout: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    fn baud_rate(&self) -> Option<u32> {
+        self.baud_rate
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        self.tx.clear();
+        self.rx.clear();
+        Ok(())
+    }
+}
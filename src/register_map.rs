@@ -0,0 +1,296 @@
+//! 寄存器表: 给数据点命名, 按名称读写, 不需要在代码中到处使用地址
+
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    config::invalid_config,
+    error::{Error, Reason, Result},
+    scale::ScaledRegister,
+    value::{ValueType, WordOrder},
+    Client, Coil, Id,
+};
+
+/// 数据点所在的数据区
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Area {
+    Coil,
+    DiscreteInput,
+    InputRegister,
+    HoldingRegister,
+}
+
+impl Area {
+    /// 线圈和离散输入为位数据
+    pub fn is_bit(&self) -> bool {
+        matches!(self, Area::Coil | Area::DiscreteInput)
+    }
+
+    /// 目前只支持写保持寄存器
+    pub fn is_writable(&self) -> bool {
+        matches!(self, Area::HoldingRegister)
+    }
+}
+
+impl FromStr for Area {
+    type Err = Error;
+
+    /// 不区分大小写: coil, discrete, input, holding
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "coil" => Ok(Area::Coil),
+            "discrete" => Ok(Area::DiscreteInput),
+            "input" => Ok(Area::InputRegister),
+            "holding" => Ok(Area::HoldingRegister),
+            _ => Err(invalid_config(&format!("未知的数据区: {}", s))),
+        }
+    }
+}
+
+/// 一个命名的数据点
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub name: String,
+    pub area: Area,
+    /// 地址, 类型, 换算和单位
+    pub register: ScaledRegister,
+}
+
+impl Point {
+    /// 位数据区的数据点类型固定为 `ValueType::Bool`
+    pub fn new(name: impl Into<String>, area: Area, address: u16, ty: ValueType) -> Self {
+        let ty = if area.is_bit() { ValueType::Bool } else { ty };
+        Self {
+            name: name.into(),
+            area,
+            register: ScaledRegister::new(address, ty),
+        }
+    }
+
+    pub fn scale(mut self, gain: f64) -> Self {
+        self.register.scaling.gain = gain;
+        self
+    }
+
+    pub fn offset(mut self, offset: f64) -> Self {
+        self.register.scaling.offset = offset;
+        self
+    }
+
+    pub fn unit(mut self, unit: impl Into<String>) -> Self {
+        self.register.unit = Some(unit.into());
+        self
+    }
+
+    pub fn word_order(mut self, word_order: WordOrder) -> Self {
+        self.register.word_order = word_order;
+        self
+    }
+
+    pub fn saturate(mut self, saturate: bool) -> Self {
+        self.register.saturate = saturate;
+        self
+    }
+
+    pub fn address(&self) -> u16 {
+        self.register.address
+    }
+
+    pub fn unit_name(&self) -> Option<&str> {
+        self.register.unit.as_deref()
+    }
+}
+
+/// 一个从设备的寄存器表
+///
+/// CSV 格式, 每行一个数据点, `#` 开头的行和以 name 开头的表头被忽略:
+/// `name,area,address,type[,scale[,unit[,order[,offset]]]]`, 例如
+/// `motor_speed,holding,0x0010,f32,1,rpm,cdab`
+///
+/// TOML 格式 (需要 `toml` feature), 每个数据点为一个 `[[point]]` 表, 字段与 CSV 相同,
+/// 其中 scale, unit, order, offset 可以省略
+#[derive(Debug, Clone)]
+pub struct RegisterMap {
+    id: Id,
+    points: Vec<Point>,
+    index: HashMap<String, usize>,
+}
+
+impl RegisterMap {
+    pub fn new(id: Id) -> Self {
+        Self {
+            id,
+            points: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// 添加数据点, 名称不能重复, scale 不能为 0
+    pub fn add(&mut self, point: Point) -> Result<()> {
+        if self.index.contains_key(&point.name) {
+            return Err(invalid_config(&format!("数据点重复: {}", point.name)));
+        }
+        point.register.scaling.validate()?;
+        let (address, quantity) = (point.address(), point.register.words());
+        if address as u32 + quantity as u32 > 0x10000 {
+            return Err(Error::InvalidData(Reason::AddressOverflow {
+                address,
+                quantity,
+            }));
+        }
+        self.index.insert(point.name.clone(), self.points.len());
+        self.points.push(point);
+        Ok(())
+    }
+
+    pub fn point(&self, name: &str) -> Option<&Point> {
+        self.index.get(name).map(|&i| &self.points[i])
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// 读取数据点, 返回换算之后的工程值, 位数据为 1.0 或 0.0
+    pub fn read(&self, client: &mut Client, name: &str) -> Result<f64> {
+        let point = self.get(name)?;
+        let (id, address) = (self.id, point.address());
+        match point.area {
+            Area::Coil => Ok(bit_value(client.read_coils(id, address, 1u16)?)),
+            Area::DiscreteInput => Ok(bit_value(client.read_discrete_inputs(id, address, 1u16)?)),
+            Area::InputRegister => point.register.read_input(client, id),
+            Area::HoldingRegister => point.register.read(client, id),
+        }
+    }
+
+    /// 写入数据点, 工程值反向换算之后按照类型编码, 只读的数据区返回错误
+    pub fn write(&self, client: &mut Client, name: &str, value: f64) -> Result<()> {
+        let point = self.get(name)?;
+        if !point.area.is_writable() {
+            return Err(invalid_config(&format!("数据点 {} 只读", name)));
+        }
+        point.register.write(client, self.id, value)
+    }
+
+    /// 从 CSV 文本加载寄存器表
+    pub fn from_csv(id: Id, csv: &str) -> Result<Self> {
+        let mut map = Self::new(id);
+        for line in csv.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with("name") {
+                continue;
+            }
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            if fields.len() < 4 {
+                return Err(invalid_config(&format!("无效的数据点定义: {}", line)));
+            }
+            let mut point = Point::new(
+                fields[0],
+                fields[1].parse()?,
+                parse_address(fields[2])?,
+                fields[3].parse()?,
+            );
+            if let Some(scale) = fields.get(4).filter(|s| !s.is_empty()) {
+                point = point.scale(parse_number(scale)?);
+            }
+            if let Some(unit) = fields.get(5).filter(|s| !s.is_empty()) {
+                point = point.unit(*unit);
+            }
+            if let Some(order) = fields.get(6).filter(|s| !s.is_empty()) {
+                point = point.word_order(order.parse()?);
+            }
+            if let Some(offset) = fields.get(7).filter(|s| !s.is_empty()) {
+                point = point.offset(parse_number(offset)?);
+            }
+            map.add(point)?;
+        }
+        Ok(map)
+    }
+
+    /// 从 TOML 文本加载寄存器表
+    #[cfg(feature = "toml")]
+    pub fn from_toml(id: Id, text: &str) -> Result<Self> {
+        let table = text
+            .parse::<toml::Table>()
+            .map_err(|e| invalid_config(&format!("TOML 格式错误, {}", e)))?;
+        let mut map = Self::new(id);
+        let Some(points) = table.get("point") else {
+            return Ok(map);
+        };
+        let points = points
+            .as_array()
+            .ok_or_else(|| invalid_config("point 必须是表的数组"))?;
+        for value in points {
+            let field = |key: &str| {
+                value
+                    .get(key)
+                    .ok_or_else(|| invalid_config(&format!("数据点缺少字段: {}", key)))
+            };
+            let str_field = |key: &str| {
+                field(key)?
+                    .as_str()
+                    .ok_or_else(|| invalid_config(&format!("字段 {} 必须是字符串", key)))
+            };
+            let address = field("address")?
+                .as_integer()
+                .and_then(|address| u16::try_from(address).ok())
+                .ok_or_else(|| invalid_config("无效的地址"))?;
+            let mut point = Point::new(
+                str_field("name")?,
+                str_field("area")?.parse()?,
+                address,
+                str_field("type")?.parse()?,
+            );
+            let number_field = |key: &str| {
+                let value = field(key)?;
+                value
+                    .as_float()
+                    .or_else(|| value.as_integer().map(|value| value as f64))
+                    .ok_or_else(|| invalid_config(&format!("字段 {} 必须是数字", key)))
+            };
+            if value.get("scale").is_some() {
+                point = point.scale(number_field("scale")?);
+            }
+            if value.get("offset").is_some() {
+                point = point.offset(number_field("offset")?);
+            }
+            if value.get("unit").is_some() {
+                point = point.unit(str_field("unit")?);
+            }
+            if value.get("order").is_some() {
+                point = point.word_order(str_field("order")?.parse()?);
+            }
+            map.add(point)?;
+        }
+        Ok(map)
+    }
+
+    fn get(&self, name: &str) -> Result<&Point> {
+        self.point(name)
+            .ok_or_else(|| Error::InvalidData(Reason::UnknownPoint(name.to_string())))
+    }
+}
+
+fn bit_value(bits: Vec<Coil>) -> f64 {
+    match bits.first() {
+        Some(Coil::On) => 1.0,
+        _ => 0.0,
+    }
+}
+
+fn parse_number(s: &str) -> Result<f64> {
+    s.parse()
+        .map_err(|_| invalid_config(&format!("无效的数字: {}", s)))
+}
+
+/// 十进制或者 0x 开头的十六进制地址
+fn parse_address(s: &str) -> Result<u16> {
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|_| invalid_config(&format!("无效的地址: {}", s)))
+}
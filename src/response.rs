@@ -0,0 +1,184 @@
+//! 按照请求的功能码解析的响应, 由 [`Client::request`](crate::Client::request) 返回
+//!
+//! 所有的请求和响应都使用 [`Function`] 和 [`Response`] 表示, 可以在它们之上实现通用的日志, 缓存等中间层
+
+use bytes::{Buf, Bytes};
+
+use crate::{
+    client::{pack_bytes, unpack_diagnostics, unpack_fifo, unpack_reply_bits},
+    device_id::DeviceIdentification,
+    diagnostics::{CommEventCounter, CommEventLog, ServerId},
+    error::{Error, ExceptionCode, Reason, Result},
+    file_record::{self, FileRecord},
+    Address, Coil, Function, Word,
+};
+
+/// 一个请求的响应
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// 读线圈
+    Coils(Vec<Coil>),
+    /// 读离散输入
+    DiscreteInputs(Vec<Coil>),
+    /// 读保持寄存器, 以及读写多个寄存器的读取结果
+    HoldingRegisters(Vec<Word>),
+    /// 读输入寄存器
+    InputRegisters(Vec<Word>),
+    /// 写单个寄存器的确认
+    WriteAck {
+        address: Address,
+        value: Word,
+    },
+    /// 写多个寄存器的确认
+    WriteMultipleAck {
+        address: Address,
+        quantity: u16,
+    },
+    /// 按位修改寄存器的确认
+    MaskWriteAck {
+        address: Address,
+        and_mask: Word,
+        or_mask: Word,
+    },
+    /// 诊断的子功能码和数据
+    Diagnostics {
+        sub_function: u16,
+        data: Word,
+    },
+    /// 8 个异常状态位
+    ExceptionStatus(u8),
+    CommEventCounter(CommEventCounter),
+    CommEventLog(CommEventLog),
+    ServerId(ServerId),
+    /// FIFO 队列中的数据
+    FifoQueue(Vec<Word>),
+    /// 按子请求的顺序读取到的文件记录
+    FileRecords(Vec<FileRecord>),
+    /// 写文件记录的确认
+    WriteFileRecordAck,
+    /// 一次读设备标识的结果, 还有后续对象时 next_object_id 为下一次请求的起始对象ID
+    DeviceIdentification {
+        identification: DeviceIdentification,
+        next_object_id: Option<u8>,
+    },
+    /// 定制功能码的响应, 见 [`Client::custom`](crate::Client::custom)
+    Custom(Bytes),
+    /// 异常响应
+    Exception(ExceptionCode),
+}
+
+impl Response {
+    pub fn is_exception(&self) -> bool {
+        matches!(self, Response::Exception(_))
+    }
+
+    /// 写请求的确认, 用于没有响应的广播请求. 读请求返回 None
+    pub(crate) fn ack(fun: &Function) -> Option<Self> {
+        let ack = match fun {
+            Function::WriteSingleRegister(_, address, value) => Response::WriteAck {
+                address: *address,
+                value: *value,
+            },
+            Function::WriteMultipleRegisters(_, address, values) => Response::WriteMultipleAck {
+                address: *address,
+                quantity: values.len() as u16,
+            },
+            Function::MaskWriteRegister(_, address, and_mask, or_mask) => Response::MaskWriteAck {
+                address: *address,
+                and_mask: *and_mask,
+                or_mask: *or_mask,
+            },
+            Function::WriteFileRecord(..) => Response::WriteFileRecordAck,
+            _ => return None,
+        };
+        Some(ack)
+    }
+
+    /// 解析响应 PDU 中功能码之后的数据
+    pub(crate) fn decode(fun: &Function, mut data: Bytes) -> Result<Self> {
+        let invalid = || Error::InvalidResponse(Reason::UnexpectedReplySize);
+        let response = match fun {
+            Function::ReadCoils(_, _, quantity) => {
+                Response::Coils(unpack_reply_bits(&byte_count(data)?, quantity.get())?)
+            }
+            Function::ReadDiscreteInputs(_, _, quantity) => {
+                Response::DiscreteInputs(unpack_reply_bits(&byte_count(data)?, quantity.get())?)
+            }
+            Function::ReadHoldingRegisters(..) | Function::ReadWriteMultipleRegisters(..) => {
+                Response::HoldingRegisters(pack_bytes(byte_count(data)?)?)
+            }
+            Function::ReadInputRegisters(..) => {
+                Response::InputRegisters(pack_bytes(byte_count(data)?)?)
+            }
+            Function::WriteSingleRegister(..) => {
+                if data.len() != 4 {
+                    return Err(invalid());
+                }
+                Response::WriteAck {
+                    address: data.get_u16(),
+                    value: data.get_u16(),
+                }
+            }
+            Function::WriteMultipleRegisters(..) => {
+                if data.len() != 4 {
+                    return Err(invalid());
+                }
+                Response::WriteMultipleAck {
+                    address: data.get_u16(),
+                    quantity: data.get_u16(),
+                }
+            }
+            Function::MaskWriteRegister(..) => {
+                if data.len() != 6 {
+                    return Err(invalid());
+                }
+                Response::MaskWriteAck {
+                    address: data.get_u16(),
+                    and_mask: data.get_u16(),
+                    or_mask: data.get_u16(),
+                }
+            }
+            Function::Diagnostics(_, sub_function, _) => Response::Diagnostics {
+                sub_function: *sub_function,
+                data: unpack_diagnostics(&data, *sub_function)?,
+            },
+            Function::ReadExceptionStatus(_) => match data[..] {
+                [status] => Response::ExceptionStatus(status),
+                _ => return Err(invalid()),
+            },
+            Function::GetCommEventCounter(_) => {
+                Response::CommEventCounter(CommEventCounter::parse(&data)?)
+            }
+            Function::GetCommEventLog(_) => {
+                Response::CommEventLog(CommEventLog::parse(&byte_count(data)?)?)
+            }
+            Function::ReportServerId(_) => Response::ServerId(ServerId::parse(&byte_count(data)?)?),
+            Function::ReadFifoQueue(..) => Response::FifoQueue(unpack_fifo(data)?),
+            Function::ReadFileRecord(_, requests) => {
+                Response::FileRecords(file_record::decode_read(&byte_count(data)?, requests)?)
+            }
+            Function::WriteFileRecord(..) => Response::WriteFileRecordAck,
+            Function::ReadDeviceIdentification(..) => {
+                let mut identification = DeviceIdentification::default();
+                let next_object_id = identification.parse(&data)?;
+                Response::DeviceIdentification {
+                    identification,
+                    next_object_id,
+                }
+            }
+            Function::Custom(_) => Response::Custom(data),
+        };
+        Ok(response)
+    }
+}
+
+/// 字节数(1) + 数据, 返回数据
+fn byte_count(mut data: Bytes) -> Result<Bytes> {
+    match data.first() {
+        Some(&n) if data.len() == 1 + n as usize => {
+            data.advance(1);
+            Ok(data)
+        }
+        _ => Err(Error::InvalidResponse(Reason::UnexpectedReplySize)),
+    }
+}
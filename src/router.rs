@@ -0,0 +1,152 @@
+//! 按照从设备ID路由请求: 不同ID范围的从设备位于不同的总线或者网关上, 通过同一个接口访问
+
+use std::ops::RangeInclusive;
+
+use crate::{
+    address::{CoilAddress, DiscreteAddress, HoldingAddress, InputAddress},
+    error::{Error, Reason, Result},
+    quantity::Quantity,
+    shared::SharedClient,
+    Client, Coil, Id, Word,
+};
+
+#[derive(Clone)]
+struct Route {
+    ids: RangeInclusive<Id>,
+    client: SharedClient,
+}
+
+/// 按照从设备ID把请求转发到对应的传输
+///
+/// 例如 ID 1..=10 位于 /dev/ttyUSB0, ID 11..=20 位于 TCP 网关 10.0.0.5.
+/// 每个传输是一个 [`SharedClient`], 同一个传输上的请求不会交错, 不同传输上的请求可以在多个线程中并行
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 把 ids 范围内的从设备路由到 client, 与已有的范围重叠时返回错误
+    pub fn add_route(
+        &mut self,
+        ids: RangeInclusive<Id>,
+        client: impl Into<SharedClient>,
+    ) -> Result<()> {
+        if ids.is_empty() {
+            return Err(Error::InvalidRequest(Reason::InvalidConfig(format!(
+                "从设备ID范围 {:?} 为空",
+                ids
+            ))));
+        }
+        if let Some(route) = self
+            .routes
+            .iter()
+            .find(|r| r.ids.start() <= ids.end() && ids.start() <= r.ids.end())
+        {
+            return Err(Error::InvalidRequest(Reason::InvalidConfig(format!(
+                "从设备ID范围 {:?} 与 {:?} 重叠",
+                ids, route.ids
+            ))));
+        }
+        self.routes.push(Route {
+            ids,
+            client: client.into(),
+        });
+        Ok(())
+    }
+
+    /// 与 [`add_route`](Self::add_route) 相同, 用于链式调用
+    pub fn route(
+        mut self,
+        ids: RangeInclusive<Id>,
+        client: impl Into<SharedClient>,
+    ) -> Result<Self> {
+        self.add_route(ids, client)?;
+        Ok(self)
+    }
+
+    /// 删除包含 id 的路由, 返回这个路由的客户端
+    pub fn remove_route(&mut self, id: Id) -> Option<SharedClient> {
+        let index = self.routes.iter().position(|r| r.ids.contains(&id))?;
+        Some(self.routes.remove(index).client)
+    }
+
+    /// 从设备所在的传输, 没有路由时返回 [`Reason::NoRoute`]
+    pub fn client(&self, id: Id) -> Result<&SharedClient> {
+        self.routes
+            .iter()
+            .find(|r| r.ids.contains(&id))
+            .map(|r| &r.client)
+            .ok_or(Error::InvalidRequest(Reason::NoRoute(id)))
+    }
+
+    /// 所有路由的ID范围
+    pub fn ranges(&self) -> impl Iterator<Item = &RangeInclusive<Id>> {
+        self.routes.iter().map(|r| &r.ids)
+    }
+
+    /// 锁定从设备所在的传输, 在一次锁定中执行 f
+    pub fn with<R>(&self, id: Id, f: impl FnOnce(&mut Client) -> Result<R>) -> Result<R> {
+        self.client(id)?.with(f)
+    }
+
+    pub fn read_coils(
+        &self,
+        id: Id,
+        address: impl Into<CoilAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        self.client(id)?.read_coils(id, address, quantity)
+    }
+
+    pub fn read_discrete_inputs(
+        &self,
+        id: Id,
+        address: impl Into<DiscreteAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        self.client(id)?.read_discrete_inputs(id, address, quantity)
+    }
+
+    pub fn read_holding_registers(
+        &self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        self.client(id)?
+            .read_holding_registers(id, address, quantity)
+    }
+
+    pub fn read_input_registers(
+        &self,
+        id: Id,
+        address: impl Into<InputAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        self.client(id)?.read_input_registers(id, address, quantity)
+    }
+
+    pub fn write_single_register(
+        &self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        value: Word,
+    ) -> Result<()> {
+        self.client(id)?.write_single_register(id, address, value)
+    }
+
+    pub fn write_multiple_registers(
+        &self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        values: Vec<Word>,
+    ) -> Result<()> {
+        self.client(id)?
+            .write_multiple_registers(id, address, values)
+    }
+}
@@ -0,0 +1,320 @@
+//! 从设备的实时时钟: 按照常见的寄存器布局读写日期时间, 计算时钟偏差
+//!
+//! 写入时使用一个写多个寄存器 (0x10) 请求, 从设备不会看到写了一半的时间
+//! ```no_run
+//! # use simple_modbus::{rtc::{Rtc, RtcLayout}, Client};
+//! # fn run(client: &mut Client) -> simple_modbus::error::Result<()> {
+//! // 从设备使用北京时间
+//! let rtc = Rtc::new(1, 0x0200, RtcLayout::Bcd).utc_offset(8 * 3600);
+//! if rtc.drift(client)?.abs() > 2.0 {
+//!     rtc.sync(client)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    error::{Error, Reason, Result},
+    value::WordOrder,
+    Address, Client, Id, Word,
+};
+
+/// 日期时间在寄存器中的布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcLayout {
+    /// 6 个寄存器, 每个寄存器一个字段: 年 (例如 2024), 月, 日, 时, 分, 秒
+    Binary,
+    /// 3 个寄存器, 每个寄存器两个字节: [年 (00 ~ 99), 月], [日, 时], [分, 秒]
+    PackedBinary,
+    /// 3 个寄存器, 与 PackedBinary 相同, 每个字节为 BCD 码, 例如 0x24 表示 24
+    Bcd,
+    /// 2 个寄存器, 1970-01-01 以来的秒数 (u32)
+    Unix(WordOrder),
+}
+
+impl RtcLayout {
+    pub fn words(&self) -> u16 {
+        match self {
+            RtcLayout::Binary => 6,
+            RtcLayout::PackedBinary | RtcLayout::Bcd => 3,
+            RtcLayout::Unix(_) => 2,
+        }
+    }
+}
+
+/// 日期时间的各个字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+/// 从设备的实时时钟
+#[derive(Debug, Clone)]
+pub struct Rtc {
+    id: Id,
+    address: Address,
+    layout: RtcLayout,
+    utc_offset: i32,
+}
+
+impl Rtc {
+    /// 从设备 id 的保持寄存器 address 处的时钟, 默认为 UTC 时间
+    pub fn new(id: Id, address: Address, layout: RtcLayout) -> Self {
+        Self {
+            id,
+            address,
+            layout,
+            utc_offset: 0,
+        }
+    }
+
+    /// 从设备的时钟与 UTC 相差的秒数, 例如北京时间为 8 * 3600. UNIX 时间的布局不使用这个设置
+    pub fn utc_offset(mut self, seconds: i32) -> Self {
+        self.utc_offset = seconds;
+        self
+    }
+
+    /// 把时间转换为寄存器, 不足 1 秒的部分被舍去
+    pub fn encode(&self, time: SystemTime) -> Result<Vec<Word>> {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| invalid_time("时间早于 1970 年"))?
+            .as_secs() as i64;
+        if let RtcLayout::Unix(order) = self.layout {
+            let secs = u32::try_from(secs).map_err(|_| invalid_time("时间超出了 u32 的范围"))?;
+            return Ok(order.encode(secs));
+        }
+
+        let t = date_time(secs + self.utc_offset as i64);
+        let year = match self.layout {
+            RtcLayout::Binary => {
+                return Ok(vec![
+                    t.year as Word,
+                    t.month as Word,
+                    t.day as Word,
+                    t.hour as Word,
+                    t.minute as Word,
+                    t.second as Word,
+                ])
+            }
+            _ if !(2000..2100).contains(&t.year) => {
+                return Err(invalid_time("两位数的年份只支持 2000 ~ 2099 年"))
+            }
+            _ => (t.year - 2000) as u32,
+        };
+        let byte = |value: u32| match self.layout {
+            RtcLayout::Bcd => (value / 10 * 16 + value % 10) as Word,
+            _ => value as Word,
+        };
+        Ok(vec![
+            byte(year) << 8 | byte(t.month),
+            byte(t.day) << 8 | byte(t.hour),
+            byte(t.minute) << 8 | byte(t.second),
+        ])
+    }
+
+    /// 把寄存器转换为时间, 字段超出范围时返回错误
+    pub fn decode(&self, words: &[Word]) -> Result<SystemTime> {
+        if words.len() != self.layout.words() as usize {
+            return Err(Error::InvalidData(Reason::UnexpectedReplySize));
+        }
+        let t = match self.layout {
+            RtcLayout::Unix(order) => {
+                let secs = order.decode::<u32>(words)?;
+                return Ok(UNIX_EPOCH + Duration::from_secs(secs as u64));
+            }
+            RtcLayout::Binary => DateTime {
+                year: words[0] as i64,
+                month: words[1] as u32,
+                day: words[2] as u32,
+                hour: words[3] as u32,
+                minute: words[4] as u32,
+                second: words[5] as u32,
+            },
+            RtcLayout::PackedBinary | RtcLayout::Bcd => {
+                let mut bytes = Vec::with_capacity(6);
+                for b in words.iter().flat_map(|w| w.to_be_bytes()) {
+                    bytes.push(match self.layout {
+                        RtcLayout::Bcd => from_bcd(b)?,
+                        _ => b as u32,
+                    });
+                }
+                DateTime {
+                    year: 2000 + bytes[0] as i64,
+                    month: bytes[1],
+                    day: bytes[2],
+                    hour: bytes[3],
+                    minute: bytes[4],
+                    second: bytes[5],
+                }
+            }
+        };
+        let valid = (1..=12).contains(&t.month)
+            && (1..=31).contains(&t.day)
+            && t.hour < 24
+            && t.minute < 60
+            && t.second < 60;
+        if !valid {
+            return Err(invalid_time(&format!("无效的时间: {:?}", t)));
+        }
+        let secs = days_from_civil(t.year, t.month, t.day) * 86400
+            + (t.hour * 3600 + t.minute * 60 + t.second) as i64
+            - self.utc_offset as i64;
+        if secs < 0 {
+            return Err(invalid_time("时间早于 1970 年"));
+        }
+        Ok(UNIX_EPOCH + Duration::from_secs(secs as u64))
+    }
+
+    /// 读取从设备的时间
+    pub fn read_time(&self, client: &mut Client) -> Result<SystemTime> {
+        let words = client.read_holding_registers(self.id, self.address, self.layout.words())?;
+        self.decode(&words)
+    }
+
+    /// 在一个请求中写入时间
+    pub fn write_time(&self, client: &mut Client, time: SystemTime) -> Result<()> {
+        let words = self.encode(time)?;
+        client.write_multiple_registers(self.id, self.address, words)
+    }
+
+    /// 把从设备的时间设置为本机的当前时间
+    pub fn sync(&self, client: &mut Client) -> Result<()> {
+        self.write_time(client, SystemTime::now())
+    }
+
+    /// 从设备的时间减去本机的时间, 单位为秒
+    ///
+    /// 本机的时间取请求和响应的中间时刻; 从设备的时间只精确到秒, 结果有 1 秒以内的误差
+    pub fn drift(&self, client: &mut Client) -> Result<f64> {
+        let start = Instant::now();
+        let sent = SystemTime::now();
+        let device = self.read_time(client)?;
+        let local = sent + start.elapsed() / 2;
+        Ok(match device.duration_since(local) {
+            Ok(ahead) => ahead.as_secs_f64(),
+            Err(e) => -e.duration().as_secs_f64(),
+        })
+    }
+}
+
+fn date_time(secs: i64) -> DateTime {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let secs = secs.rem_euclid(86400) as u32;
+    DateTime {
+        year,
+        month,
+        day,
+        hour: secs / 3600,
+        minute: secs / 60 % 60,
+        second: secs % 60,
+    }
+}
+
+fn from_bcd(b: u8) -> Result<u32> {
+    let (high, low) = (b >> 4, b & 0x0F);
+    if high > 9 || low > 9 {
+        return Err(invalid_time(&format!("无效的 BCD 码: 0x{:02X}", b)));
+    }
+    Ok((high * 10 + low) as u32)
+}
+
+fn invalid_time(reason: &str) -> Error {
+    Error::InvalidData(Reason::Custom(reason.to_string()))
+}
+
+/// 1970-01-01 之后的天数转换为 年, 月, 日
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// 年, 月, 日 转换为 1970-01-01 之后的天数
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{civil_from_days, days_from_civil, Rtc, RtcLayout};
+    use crate::value::WordOrder;
+
+    /// 2024-02-29 23:59:58 UTC
+    const LEAP_DAY: u64 = 1_709_251_198;
+
+    #[test]
+    fn civil_days_round_trip() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        for days in [-1, 0, 59, 11016, 19782, 47482] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn layouts() {
+        let time = UNIX_EPOCH + Duration::from_secs(LEAP_DAY);
+        let cases = [
+            (RtcLayout::Binary, vec![2024, 2, 29, 23, 59, 58]),
+            (RtcLayout::PackedBinary, vec![0x1802, 0x1D17, 0x3B3A]),
+            (RtcLayout::Bcd, vec![0x2402, 0x2923, 0x5958]),
+            (RtcLayout::Unix(WordOrder::CDAB), vec![0x1A7E, 0x65E1]),
+        ];
+        for (layout, words) in cases {
+            let rtc = Rtc::new(1, 0, layout);
+            assert_eq!(rtc.encode(time).unwrap(), words, "{:?}", layout);
+            assert_eq!(rtc.decode(&words).unwrap(), time, "{:?}", layout);
+        }
+    }
+
+    #[test]
+    fn utc_offset() {
+        let time = UNIX_EPOCH + Duration::from_secs(LEAP_DAY);
+        let rtc = Rtc::new(1, 0, RtcLayout::Bcd).utc_offset(8 * 3600);
+        // 北京时间 2024-03-01 07:59:58
+        let words = rtc.encode(time).unwrap();
+        assert_eq!(words, [0x2403, 0x0107, 0x5958]);
+        assert_eq!(rtc.decode(&words).unwrap(), time);
+    }
+
+    #[test]
+    fn invalid_registers() {
+        let rtc = Rtc::new(1, 0, RtcLayout::Bcd);
+        assert!(rtc.decode(&[0x2413, 0x0100, 0x0000]).is_err());
+        assert!(rtc.decode(&[0x240A, 0x0100, 0x0000]).is_err());
+        assert!(rtc.decode(&[0x2401, 0x0100]).is_err());
+        let rtc = Rtc::new(1, 0, RtcLayout::Binary);
+        assert!(rtc.decode(&[2024, 1, 1, 24, 0, 0]).is_err());
+        let time = UNIX_EPOCH + Duration::from_secs(4_200_000_000);
+        assert!(Rtc::new(1, 0, RtcLayout::PackedBinary)
+            .encode(time)
+            .is_err());
+        assert!(Rtc::new(1, 0, RtcLayout::Unix(WordOrder::ABCD))
+            .encode(UNIX_EPOCH + Duration::from_secs(1 << 32))
+            .is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! 线性换算和单位, 把寄存器中的原始值转换为工程值
+
+use crate::{
+    config::invalid_config,
+    error::{Error, Reason, Result},
+    value::{ValueType, WordOrder},
+    Client, Id, Word,
+};
+
+/// 线性换算: 工程值 = 原始值 * gain + offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scaling {
+    pub gain: f64,
+    pub offset: f64,
+}
+
+impl Scaling {
+    pub fn new(gain: f64, offset: f64) -> Self {
+        Self { gain, offset }
+    }
+
+    /// gain 不能为 0, 否则无法反向换算
+    pub fn validate(&self) -> Result<()> {
+        if self.gain == 0.0 || !self.gain.is_finite() || !self.offset.is_finite() {
+            return Err(invalid_config("无效的换算系数"));
+        }
+        Ok(())
+    }
+
+    /// 原始值 => 工程值
+    pub fn apply(&self, raw: f64) -> f64 {
+        raw * self.gain + self.offset
+    }
+
+    /// 工程值 => 原始值
+    pub fn inverse(&self, value: f64) -> f64 {
+        (value - self.offset) / self.gain
+    }
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Self::new(1.0, 0.0)
+    }
+}
+
+/// 带有换算和单位的寄存器数值, 例如 `ScaledRegister::new(0x10, ValueType::U16).scale(0.1).unit("°C")`
+/// 把原始值 235 读取为 23.5
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaledRegister {
+    pub address: u16,
+    pub ty: ValueType,
+    pub scaling: Scaling,
+    /// 单位, 只用于显示
+    pub unit: Option<String>,
+    pub word_order: WordOrder,
+    /// 写入的数值超出类型的范围时, 为 true 则取最接近的有效值, 否则返回错误
+    pub saturate: bool,
+}
+
+impl ScaledRegister {
+    pub fn new(address: u16, ty: ValueType) -> Self {
+        Self {
+            address,
+            ty,
+            scaling: Scaling::default(),
+            unit: None,
+            word_order: WordOrder::default(),
+            saturate: false,
+        }
+    }
+
+    pub fn scale(mut self, gain: f64) -> Self {
+        self.scaling.gain = gain;
+        self
+    }
+
+    pub fn offset(mut self, offset: f64) -> Self {
+        self.scaling.offset = offset;
+        self
+    }
+
+    pub fn unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    pub fn word_order(mut self, word_order: WordOrder) -> Self {
+        self.word_order = word_order;
+        self
+    }
+
+    pub fn saturate(mut self, saturate: bool) -> Self {
+        self.saturate = saturate;
+        self
+    }
+
+    /// 占用的寄存器数量
+    pub fn words(&self) -> u16 {
+        self.ty.words()
+    }
+
+    /// 把寄存器转换为工程值
+    pub fn decode(&self, words: &[Word]) -> Result<f64> {
+        let raw = self.ty.decode(words, self.word_order)?;
+        Ok(self.scaling.apply(raw))
+    }
+
+    /// 把工程值反向换算后转换为寄存器
+    pub fn encode(&self, value: f64) -> Result<Vec<Word>> {
+        self.scaling.validate()?;
+        if value.is_nan() {
+            return Err(Error::InvalidData(Reason::ValueOutOfRange(self.ty)));
+        }
+        let mut raw = self.scaling.inverse(value);
+        if self.saturate {
+            raw = self.ty.saturate(raw);
+        }
+        self.ty.encode(raw, self.word_order)
+    }
+
+    /// 从保持寄存器读取工程值
+    pub fn read(&self, client: &mut Client, id: Id) -> Result<f64> {
+        let words = client.read_holding_registers(id, self.address, self.words())?;
+        self.decode(&words)
+    }
+
+    /// 从输入寄存器读取工程值
+    pub fn read_input(&self, client: &mut Client, id: Id) -> Result<f64> {
+        let words = client.read_input_registers(id, self.address, self.words())?;
+        self.decode(&words)
+    }
+
+    /// 把工程值写入保持寄存器
+    pub fn write(&self, client: &mut Client, id: Id, value: f64) -> Result<()> {
+        let words = self.encode(value)?;
+        if words.len() == 1 {
+            client.write_single_register(id, self.address, words[0])
+        } else {
+            client.write_multiple_registers(id, self.address, words)
+        }
+    }
+}
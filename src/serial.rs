@@ -1,65 +1,511 @@
-use anyhow::Result;
-use serialport::SerialPort;
-use std::{
-    io::{Read, Write},
-    time::Duration,
-};
-
-use crate::stream::Stream;
-
-pub struct SerialStream {
-    inner: Box<dyn SerialPort>,
-}
-
-impl SerialStream {
-    pub fn new(port: &str, baud_rate: u32) -> Result<Self> {
-        // Self::available(port)?;
-
-        let inner_device = serialport::new(port, baud_rate)
-            .timeout(Duration::from_millis(5000))
-            .open()?;
-
-        Ok(Self {
-            inner: inner_device,
-        })
-    }
-
-    /// 设置 串口数据读写 的超时时间
-    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
-        self.inner.set_timeout(timeout)?;
-        Ok(())
-    }
-
-    /// 检查串口是否有效
-    pub fn available() -> Result<Vec<String>> {
-        let ports = serialport::available_ports()?;
-        let ports = ports
-            .iter()
-            .map(|port| port.port_name.clone())
-            .collect::<Vec<String>>();
-        Ok(ports)
-    }
-}
-
-impl Read for SerialStream {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.inner.read(buf)
-    }
-}
-
-impl Write for SerialStream {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.inner.write(buf)
-    }
-
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.inner.flush()
-    }
-}
-
-impl Stream for SerialStream {
-    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
-        self.inner.set_timeout(timeout)?;
-        Ok(())
-    }
-}
+use serialport::{
+    ClearBuffer, DataBits, FlowControl, Parity, SerialPort, SerialPortBuilder, SerialPortInfo,
+    SerialPortType, StopBits,
+};
+use std::{
+    io::{self, Read, Write},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    config::Config,
+    error::{Error, Result},
+    stream::Stream,
+    Client,
+};
+
+/// 自动检测时默认尝试的波特率, 从常用到不常用
+pub const COMMON_BAUD_RATES: [u32; 8] = [9600, 19200, 38400, 115200, 57600, 4800, 2400, 1200];
+
+/// 自动检测时尝试的校验位, Modbus RTU 规范默认使用偶校验
+const DETECT_PARITIES: [Parity; 3] = [Parity::Even, Parity::None, Parity::Odd];
+
+/// 自动检测时每个请求等待响应的时间
+const DETECT_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// RTU 帧的时间间隔
+///
+/// 一个字符按 11 位计算 (起始位 + 8位数据 + 校验位/停止位),
+/// 波特率高于 19200 时使用规范建议的固定值 750us 和 1750us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtuTiming {
+    /// 字符之间允许的最大间隔 t1.5
+    pub char_gap: Duration,
+    /// 帧之间最小的静默间隔 t3.5
+    pub frame_gap: Duration,
+}
+
+impl RtuTiming {
+    pub fn from_baud_rate(baud_rate: u32) -> Self {
+        if baud_rate > 19200 {
+            return Self {
+                char_gap: Duration::from_micros(750),
+                frame_gap: Duration::from_micros(1750),
+            };
+        }
+        // 一个字符的时间, 单位为微秒
+        let char_time = 11_000_000u64 / baud_rate.max(1) as u64;
+        Self {
+            char_gap: Duration::from_micros(char_time * 3 / 2),
+            frame_gap: Duration::from_micros(char_time * 7 / 2),
+        }
+    }
+}
+
+/// 切换 RS-485 收发方向的回调, 参数为 true 时使能发送, 例如设置 GPIO 的电平
+pub type DirectionHook = Box<dyn FnMut(bool) -> io::Result<()> + Send>;
+
+/// RS-485 驱动使能 (DE/RE) 的控制方式
+pub enum Direction {
+    /// 使用 RTS 引脚, 发送时 RTS 为 level
+    Rts { level: bool },
+    /// 用户提供的控制, 例如嵌入式 Linux 上的 GPIO
+    Custom(DirectionHook),
+}
+
+/// RS-485 收发方向控制
+///
+/// 发送第一个字节之前使能发送, flush 之后再等待一个字符的时间 (最后一个字节的停止位) 切换回接收
+pub struct Rs485 {
+    direction: Direction,
+    delay_before_tx: Duration,
+    delay_after_tx: Duration,
+}
+
+impl Rs485 {
+    /// 发送时 RTS 有效
+    pub fn rts() -> Self {
+        Self::new(Direction::Rts { level: true })
+    }
+
+    /// 使用 hook 切换方向
+    pub fn custom(hook: impl FnMut(bool) -> io::Result<()> + Send + 'static) -> Self {
+        Self::new(Direction::Custom(Box::new(hook)))
+    }
+
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            delay_before_tx: Duration::ZERO,
+            delay_after_tx: Duration::ZERO,
+        }
+    }
+
+    /// 使能发送之后, 发送第一个字节之前等待的时间, 给收发器的驱动器留出启动时间
+    pub fn delay_before_tx(mut self, delay: Duration) -> Self {
+        self.delay_before_tx = delay;
+        self
+    }
+
+    /// 最后一个字节发送完之后, 切换回接收之前额外等待的时间.
+    /// 一些 USB 转串口适配器在数据还在缓冲区中时 flush 就已经返回, 需要按照帧长度设置
+    pub fn delay_after_tx(mut self, delay: Duration) -> Self {
+        self.delay_after_tx = delay;
+        self
+    }
+
+    fn set_transmit(&mut self, port: &mut dyn SerialPort, transmit: bool) -> io::Result<()> {
+        match &mut self.direction {
+            Direction::Rts { level } => {
+                port.write_request_to_send(*level == transmit)?;
+                Ok(())
+            }
+            Direction::Custom(hook) => hook(transmit),
+        }
+    }
+}
+
+/// 串口的连接方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PortKind {
+    Usb,
+    Pci,
+    Bluetooth,
+    Unknown,
+}
+
+/// 系统中的一个串口, USB 串口带有设备的描述信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PortInfo {
+    /// 打开串口时使用的名称, 例如 COM3, /dev/ttyUSB0
+    pub name: String,
+    pub kind: PortKind,
+    /// USB 厂商ID
+    pub vid: Option<u16>,
+    /// USB 产品ID
+    pub pid: Option<u16>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    /// USB 设备的序列号, 同型号的多个适配器通过序列号区分
+    pub serial_number: Option<String>,
+}
+
+impl PortInfo {
+    pub fn is_usb(&self, vid: u16, pid: u16) -> bool {
+        self.vid == Some(vid) && self.pid == Some(pid)
+    }
+}
+
+impl From<SerialPortInfo> for PortInfo {
+    fn from(info: SerialPortInfo) -> Self {
+        let mut port = PortInfo {
+            name: info.port_name,
+            kind: PortKind::Unknown,
+            vid: None,
+            pid: None,
+            manufacturer: None,
+            product: None,
+            serial_number: None,
+        };
+        match info.port_type {
+            SerialPortType::UsbPort(usb) => {
+                port.kind = PortKind::Usb;
+                port.vid = Some(usb.vid);
+                port.pid = Some(usb.pid);
+                port.manufacturer = usb.manufacturer;
+                port.product = usb.product;
+                port.serial_number = usb.serial_number;
+            }
+            SerialPortType::PciPort => port.kind = PortKind::Pci,
+            SerialPortType::BluetoothPort => port.kind = PortKind::Bluetooth,
+            SerialPortType::Unknown => {}
+        }
+        port
+    }
+}
+
+/// 串口参数, 默认为 9600 8N1, 无流控, 超时时间 5s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    /// 数据读写的超时时间
+    pub timeout: Duration,
+}
+
+impl SerialConfig {
+    /// 指定波特率, 其它参数使用默认值
+    pub fn new(baud_rate: u32) -> Self {
+        Self {
+            baud_rate,
+            ..Default::default()
+        }
+    }
+
+    /// 8E1, Modbus RTU 规范推荐的格式
+    pub fn even_parity(baud_rate: u32) -> Self {
+        Self {
+            baud_rate,
+            parity: Parity::Even,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn builder(&self, port: &str) -> SerialPortBuilder {
+        serialport::new(port, self.baud_rate)
+            .data_bits(self.data_bits)
+            .parity(self.parity)
+            .stop_bits(self.stop_bits)
+            .flow_control(self.flow_control)
+            .timeout(self.timeout)
+    }
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            baud_rate: 9600,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            timeout: Duration::from_millis(5000),
+        }
+    }
+}
+
+pub struct SerialStream {
+    /// 重新打开串口时使用的参数
+    builder: SerialPortBuilder,
+    /// 串口关闭后 (例如重新打开失败) 为 None
+    inner: Option<Box<dyn SerialPort>>,
+    read_timeout: Duration,
+    write_timeout: Duration,
+    /// 串口当前使用的超时时间, 读写之前切换为对应的超时时间
+    current_timeout: Duration,
+    rs485: Option<Rs485>,
+    /// RS-485 处于发送状态
+    transmitting: bool,
+}
+
+impl SerialStream {
+    /// 以 8N1 格式打开串口
+    pub fn new(port: &str, baud_rate: u32) -> Result<Self> {
+        // Self::available(port)?;
+
+        Self::open(port, &SerialConfig::new(baud_rate))
+    }
+
+    /// 使用 Config 中的串口参数 和 读/写超时时间 打开串口
+    pub fn with_config(port: &str, baud_rate: u32, config: &Config) -> Result<Self> {
+        let mut stream = Self::open(port, &config.serial_config(baud_rate))?;
+        stream.write_timeout = config.write_timeout;
+        Ok(stream)
+    }
+
+    /// 按照指定的参数打开串口
+    pub fn open(port: &str, config: &SerialConfig) -> Result<Self> {
+        let builder = config.builder(port);
+        let inner_device = builder.clone().open()?;
+
+        Ok(Self {
+            builder,
+            inner: Some(inner_device),
+            read_timeout: config.timeout,
+            write_timeout: config.timeout,
+            current_timeout: config.timeout,
+            rs485: None,
+            transmitting: false,
+        })
+    }
+
+    /// 设置 串口数据读写 的超时时间
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.write_timeout = timeout;
+        self.set_read_timeout(timeout)
+    }
+
+    /// 设置等待响应的超时时间
+    pub fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.builder = self.builder.clone().timeout(timeout);
+        self.read_timeout = timeout;
+        self.switch_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// 设置发送请求的超时时间, RS-485 总线上发送失败时可以尽快返回
+    pub fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    /// 设置 RS-485 收发方向的控制, None 表示由适配器自动切换. 设置后立即切换到接收
+    pub fn set_rs485(&mut self, rs485: Option<Rs485>) -> Result<()> {
+        self.rs485 = rs485;
+        self.transmitting = false;
+        self.set_direction(false)?;
+        Ok(())
+    }
+
+    fn set_direction(&mut self, transmit: bool) -> io::Result<()> {
+        if let (Some(rs485), Some(port)) = (&mut self.rs485, &mut self.inner) {
+            rs485.set_transmit(port.as_mut(), transmit)?;
+        }
+        Ok(())
+    }
+
+    /// 使能发送
+    fn begin_transmit(&mut self) -> io::Result<()> {
+        if self.transmitting {
+            return Ok(());
+        }
+        let Some(rs485) = &self.rs485 else {
+            return Ok(());
+        };
+        let delay = rs485.delay_before_tx;
+        self.set_direction(true)?;
+        self.transmitting = true;
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    /// 等待最后一个字节发送完毕, 切换回接收
+    fn end_transmit(&mut self) -> io::Result<()> {
+        if !self.transmitting {
+            return Ok(());
+        }
+        self.transmitting = false;
+        let after = self
+            .rs485
+            .as_ref()
+            .map_or(Duration::ZERO, |r| r.delay_after_tx);
+        // 发送缓冲区清空时最后一个字节可能还在移位寄存器中
+        let char_time = match self.port()?.baud_rate() {
+            Ok(baud_rate) => Duration::from_micros(11_000_000 / baud_rate.max(1) as u64),
+            Err(_) => Duration::ZERO,
+        };
+        thread::sleep(char_time + after);
+        self.set_direction(false)
+    }
+
+    /// 串口只有一个超时时间, 读写之前切换
+    fn switch_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        if self.current_timeout != timeout {
+            self.port()?.set_timeout(timeout)?;
+            self.current_timeout = timeout;
+        }
+        Ok(())
+    }
+
+    /// 当前波特率下的 RTU 帧时间间隔
+    pub fn timing(&self) -> Result<RtuTiming> {
+        let baud_rate = match &self.inner {
+            Some(inner) => inner.baud_rate()?,
+            None => self.builder.clone().open()?.baud_rate()?,
+        };
+        Ok(RtuTiming::from_baud_rate(baud_rate))
+    }
+
+    /// 关闭并重新打开串口, 例如 USB 转串口设备被拔出后重新插入
+    pub fn reopen(&mut self) -> Result<()> {
+        // 先关闭原来的串口, 否则独占模式下无法再次打开
+        self.inner = None;
+        self.inner = Some(self.builder.clone().open()?);
+        self.current_timeout = self.read_timeout;
+        self.transmitting = false;
+        self.set_direction(false)?;
+        Ok(())
+    }
+
+    fn port(&mut self) -> io::Result<&mut Box<dyn SerialPort>> {
+        self.inner
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "串口已关闭"))
+    }
+
+    /// 系统中所有的串口, 按名称排序
+    pub fn available() -> Result<Vec<PortInfo>> {
+        let mut ports = serialport::available_ports()?
+            .into_iter()
+            .map(PortInfo::from)
+            .collect::<Vec<_>>();
+        ports.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ports)
+    }
+
+    /// 检测从设备使用的波特率和校验位, 返回第一个得到有效响应的串口参数, 都没有响应时返回 None
+    ///
+    /// 依次尝试 candidate_rates 中的每个波特率和 偶校验, 无校验, 奇校验, 每次打开串口后执行 probe.
+    /// probe 返回异常响应也认为参数正确, 因为响应的 CRC 校验已经通过
+    /// ```no_run
+    /// # use simple_modbus::serial::{SerialStream, COMMON_BAUD_RATES};
+    /// let config = SerialStream::detect_baud("/dev/ttyUSB0", &COMMON_BAUD_RATES, |client| {
+    ///     client.probe_holding_register(1)
+    /// })?;
+    /// # Ok::<(), simple_modbus::error::Error>(())
+    /// ```
+    pub fn detect_baud(
+        port: &str,
+        candidate_rates: &[u32],
+        probe: impl FnMut(&mut Client) -> Result<()>,
+    ) -> Result<Option<SerialConfig>> {
+        let candidates = candidate_rates.iter().flat_map(|&baud_rate| {
+            DETECT_PARITIES.into_iter().map(move |parity| SerialConfig {
+                baud_rate,
+                parity,
+                ..Default::default()
+            })
+        });
+        Self::detect(port, candidates, DETECT_TIMEOUT, probe)
+    }
+
+    /// 依次使用 candidates 中的参数打开串口并执行 probe, 返回第一个得到有效响应的参数
+    ///
+    /// 检测时每个请求的超时时间为 timeout, 返回的参数保持 candidates 中的超时时间.
+    /// 打开串口失败 或者 传输出错时停止检测
+    pub fn detect(
+        port: &str,
+        candidates: impl IntoIterator<Item = SerialConfig>,
+        timeout: Duration,
+        mut probe: impl FnMut(&mut Client) -> Result<()>,
+    ) -> Result<Option<SerialConfig>> {
+        for config in candidates {
+            let stream = Self::open(port, &SerialConfig { timeout, ..config })?;
+            let mut client = Client::new(Box::new(stream))?;
+            client.set_timeout(timeout)?;
+            match probe(&mut client) {
+                Ok(_) | Err(Error::Exception(_)) => return Ok(Some(config)),
+                Err(Error::Io(e)) => return Err(Error::Io(e)),
+                Err(e) => log::debug!(
+                    "检测串口参数 {} {:?}, 没有有效的响应, E: {}",
+                    config.baud_rate,
+                    config.parity,
+                    e
+                ),
+            }
+        }
+        Ok(None)
+    }
+
+    /// 按照 USB 的厂商ID 和 产品ID 查找串口, 有多个时返回名称最小的一个.
+    /// 同型号的多个适配器使用 [`SerialStream::available`] 按序列号选择
+    pub fn find_port(vid: u16, pid: u16) -> Result<Option<PortInfo>> {
+        Ok(Self::available()?
+            .into_iter()
+            .find(|port| port.is_usb(vid, pid)))
+    }
+}
+
+impl Read for SerialStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.transmitting {
+            self.flush()?;
+        }
+        self.switch_timeout(self.read_timeout)?;
+        self.port()?.read(buf)
+    }
+}
+
+impl Write for SerialStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.switch_timeout(self.write_timeout)?;
+        self.begin_transmit()?;
+        self.port()?.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.port()?.flush()?;
+        self.end_transmit()
+    }
+}
+
+impl Stream for SerialStream {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        SerialStream::set_timeout(self, timeout)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        SerialStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        SerialStream::set_write_timeout(self, timeout)
+    }
+
+    fn baud_rate(&self) -> Option<u32> {
+        self.inner.as_ref()?.baud_rate().ok()
+    }
+
+    fn clear_input(&mut self) -> Result<()> {
+        self.port()?.clear(ClearBuffer::Input)?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        let res = self.flush();
+        self.inner = None;
+        Ok(res?)
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        self.reopen()
+    }
+}
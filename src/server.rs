@@ -0,0 +1,325 @@
+//! Modbus TCP 服务端: 多个客户端同时连接, 所有连接访问同一个 [`RegisterBank`]
+//!
+//! ```no_run
+//! # use std::{sync::Arc, time::Duration};
+//! # use simple_modbus::{bank::RegisterBank, server::TcpServer};
+//! let bank = Arc::new(RegisterBank::new(64, 64, 64, 64));
+//! let server = TcpServer::bind("0.0.0.0:502", bank.clone())?
+//!     .max_connections(16)
+//!     .max_connections_per_peer(2)
+//!     .idle_timeout(Some(Duration::from_secs(60)));
+//! // 其它线程修改 bank 中的数据, 客户端读到的是最新的值
+//! server.run()?;
+//! # Ok::<(), simple_modbus::error::Error>(())
+//! ```
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{self, IpAddr, SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    bank::RegisterBank,
+    error::Result,
+    gateway::{read_request, write_reply},
+};
+
+/// 每个客户端地址的连接数
+type Connections = Arc<Mutex<HashMap<IpAddr, usize>>>;
+
+/// Modbus TCP 服务端, 每个连接使用一个线程
+///
+/// 连接数超过限制时, 新的连接被立即关闭; 连接空闲超过 idle_timeout 时被服务端关闭.
+/// 服务端响应所有单元标识的请求
+pub struct TcpServer {
+    listener: TcpListener,
+    bank: Arc<RegisterBank>,
+    limits: ConnectionLimits,
+}
+
+impl TcpServer {
+    /// 监听 addr, 格式为 `host:port`. 默认最多 32 个连接, 每个客户端地址不限, 空闲 60s 后断开
+    pub fn bind(addr: &str, bank: Arc<RegisterBank>) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            bank,
+            limits: ConnectionLimits::default(),
+        })
+    }
+
+    /// 同时连接的最大数量
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.limits.max_connections = max;
+        self
+    }
+
+    /// 每个客户端 IP 地址同时连接的最大数量
+    pub fn max_connections_per_peer(mut self, max: usize) -> Self {
+        self.limits.max_connections_per_peer = max;
+        self
+    }
+
+    /// 连接上没有收到请求的最长时间, None 或者 0 表示不断开
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.limits.set_idle_timeout(timeout);
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// 当前的连接数
+    pub fn connections(&self) -> usize {
+        self.limits.count()
+    }
+
+    /// 接受连接并处理请求, 一直运行. 接受连接失败 (例如文件描述符用完) 时只记录警告
+    pub fn run(&self) -> Result<()> {
+        let bank = self.bank.clone();
+        self.limits
+            .run(&self.listener, move |stream| serve(stream, &bank));
+        Ok(())
+    }
+}
+
+/// 连接数限制 和 空闲超时, `TcpServer` 和 `Gateway` 共用
+pub(crate) struct ConnectionLimits {
+    pub(crate) max_connections: usize,
+    pub(crate) max_connections_per_peer: usize,
+    idle_timeout: Option<Duration>,
+    connections: Connections,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_connections: 32,
+            max_connections_per_peer: usize::MAX,
+            idle_timeout: Some(Duration::from_secs(60)),
+            connections: Arc::default(),
+        }
+    }
+}
+
+impl ConnectionLimits {
+    /// None 或者 0 表示不断开
+    pub(crate) fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout.filter(|timeout| !timeout.is_zero());
+    }
+
+    /// 当前的连接数
+    pub(crate) fn count(&self) -> usize {
+        lock(&self.connections).values().sum()
+    }
+
+    /// 接受连接, 每个连接使用一个线程调用 handler, 一直运行
+    ///
+    /// 接受连接失败 (例如文件描述符用完) 时只记录警告; 超过连接数限制的连接被立即关闭
+    pub(crate) fn run<F>(&self, listener: &TcpListener, handler: F)
+    where
+        F: Fn(net::TcpStream) -> io::Result<()> + Clone + Send + 'static,
+    {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("接受连接失败, E: {}", e);
+                    continue;
+                }
+            };
+            let peer = match stream.peer_addr() {
+                Ok(peer) => peer,
+                Err(e) => {
+                    log::debug!("无法获取客户端地址, E: {}", e);
+                    continue;
+                }
+            };
+            let Some(guard) = self.accept(peer.ip()) else {
+                log::warn!("连接数超过限制, 拒绝 {}", peer);
+                continue;
+            };
+            let handler = handler.clone();
+            let idle_timeout = self.idle_timeout;
+            thread::spawn(move || {
+                let _guard = guard;
+                let res = stream
+                    .set_nodelay(true)
+                    .and_then(|_| stream.set_read_timeout(idle_timeout))
+                    .and_then(|_| handler(stream));
+                match res {
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        log::debug!("连接 {} 空闲超时, 断开", peer)
+                    }
+                    Err(e) if e.kind() != io::ErrorKind::UnexpectedEof => {
+                        log::debug!("连接 {} 断开, E: {}", peer, e)
+                    }
+                    _ => log::debug!("连接 {} 关闭", peer),
+                }
+            });
+        }
+    }
+
+    /// 检查连接数限制, 允许时记录这个连接
+    fn accept(&self, ip: IpAddr) -> Option<ConnectionGuard> {
+        let mut connections = lock(&self.connections);
+        let total: usize = connections.values().sum();
+        let count = connections.entry(ip).or_default();
+        if total >= self.max_connections || *count >= self.max_connections_per_peer {
+            if *count == 0 {
+                connections.remove(&ip);
+            }
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionGuard {
+            connections: self.connections.clone(),
+            ip,
+        })
+    }
+}
+
+/// 连接结束时减少连接数
+struct ConnectionGuard {
+    connections: Connections,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut connections = lock(&self.connections);
+        if let Some(count) = connections.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                connections.remove(&self.ip);
+            }
+        }
+    }
+}
+
+fn lock(connections: &Connections) -> std::sync::MutexGuard<'_, HashMap<IpAddr, usize>> {
+    connections.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 处理一个 TCP 连接上的所有请求
+fn serve(mut stream: net::TcpStream, bank: &RegisterBank) -> io::Result<()> {
+    loop {
+        let (header, pdu) = read_request(&mut stream)?;
+        let reply = bank.process(&pdu);
+        write_reply(&mut stream, &header, &reply)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read},
+        net::TcpStream,
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::TcpServer;
+    use crate::{
+        bank::RegisterBank,
+        gateway::{read_request, write_reply},
+    };
+
+    fn start(server: TcpServer) -> Arc<TcpServer> {
+        let server = Arc::new(server);
+        let running = server.clone();
+        thread::spawn(move || running.run());
+        server
+    }
+
+    fn connect(server: &TcpServer) -> TcpStream {
+        let stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        stream
+    }
+
+    /// 读一个保持寄存器, 连接被服务端关闭时返回 None
+    fn read_register(stream: &mut TcpStream) -> Option<Vec<u8>> {
+        write_reply(
+            stream,
+            &[0, 1, 0, 0, 0, 0, 1],
+            &[0x03, 0x00, 0x00, 0x00, 0x01],
+        )
+        .ok()?;
+        read_request(stream).ok().map(|(_, pdu)| pdu)
+    }
+
+    /// 服务端关闭连接时读到 EOF 或者连接被重置, 读超时表示连接仍然打开
+    fn is_closed(stream: &mut TcpStream) -> bool {
+        match stream.read(&mut [0u8; 1]) {
+            Ok(n) => n == 0,
+            Err(e) => !matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+        }
+    }
+
+    fn wait_until(timeout: Duration, f: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if f() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn extra_connection_is_refused() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 1));
+        let server = TcpServer::bind("127.0.0.1:0", bank)
+            .unwrap()
+            .max_connections(2);
+        let server = start(server);
+
+        let mut first = connect(&server);
+        let mut second = connect(&server);
+        assert_eq!(read_register(&mut first).unwrap(), [0x03, 0x02, 0, 0]);
+        assert_eq!(read_register(&mut second).unwrap(), [0x03, 0x02, 0, 0]);
+        assert_eq!(server.connections(), 2);
+
+        let mut third = connect(&server);
+        assert!(read_register(&mut third).is_none() || is_closed(&mut third));
+        assert_eq!(server.connections(), 2);
+
+        // 关闭一个连接之后可以再次连接
+        drop(first);
+        assert!(wait_until(Duration::from_secs(2), || server.connections() == 1));
+        let mut fourth = connect(&server);
+        assert_eq!(read_register(&mut fourth).unwrap(), [0x03, 0x02, 0, 0]);
+    }
+
+    #[test]
+    fn idle_connection_is_closed() {
+        let bank = Arc::new(RegisterBank::new(0, 0, 0, 1));
+        let server = TcpServer::bind("127.0.0.1:0", bank)
+            .unwrap()
+            .idle_timeout(Some(Duration::from_millis(100)));
+        let server = start(server);
+
+        let mut stream = connect(&server);
+        assert!(read_register(&mut stream).is_some());
+        assert_eq!(server.connections(), 1);
+        assert!(is_closed(&mut stream));
+        assert!(wait_until(Duration::from_secs(2), || server.connections() == 0));
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    address::{CoilAddress, DiscreteAddress, HoldingAddress, InputAddress},
+    error::Result,
+    quantity::Quantity,
+    response::Response,
+    Client, Coil, Function, Id, Word,
+};
+
+/// 可以在多个线程之间共享的客户端, clone 的开销很小
+///
+/// 每次调用都持有锁直到事务完成, 同一条总线上的请求不会交错.
+/// 需要连续执行多个请求而不被其它线程打断时, 使用 `with` 或 `lock`
+#[derive(Clone)]
+pub struct SharedClient {
+    inner: Arc<Mutex<Client>>,
+}
+
+impl SharedClient {
+    pub fn new(client: Client) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(client)),
+        }
+    }
+
+    /// 锁定客户端, 其它线程的请求会等待直到 guard 被释放
+    pub fn lock(&self) -> MutexGuard<'_, Client> {
+        // 其它线程在持有锁时 panic 不影响客户端本身的状态
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 在一次锁定中执行 f
+    pub fn with<R>(&self, f: impl FnOnce(&mut Client) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    pub fn request(&self, fun: Function) -> Result<Response> {
+        self.lock().request(fun)
+    }
+
+    pub fn read_coils(
+        &self,
+        id: Id,
+        address: impl Into<CoilAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        self.lock().read_coils(id, address, quantity)
+    }
+
+    pub fn read_discrete_inputs(
+        &self,
+        id: Id,
+        address: impl Into<DiscreteAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        self.lock().read_discrete_inputs(id, address, quantity)
+    }
+
+    pub fn read_holding_registers(
+        &self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        self.lock().read_holding_registers(id, address, quantity)
+    }
+
+    pub fn read_input_registers(
+        &self,
+        id: Id,
+        address: impl Into<InputAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        self.lock().read_input_registers(id, address, quantity)
+    }
+
+    pub fn write_single_register(
+        &self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        value: Word,
+    ) -> Result<()> {
+        self.lock().write_single_register(id, address, value)
+    }
+
+    pub fn write_multiple_registers(
+        &self,
+        id: Id,
+        address: impl Into<HoldingAddress>,
+        values: Vec<Word>,
+    ) -> Result<()> {
+        self.lock().write_multiple_registers(id, address, values)
+    }
+}
+
+impl From<Client> for SharedClient {
+    fn from(client: Client) -> Self {
+        Self::new(client)
+    }
+}
@@ -0,0 +1,97 @@
+//! 设备参数快照: 读取几段保持寄存器保存下来, 之后写回同一台或者同型号的其它设备
+
+use crate::Word;
+#[cfg(any(feature = "toml", feature = "json"))]
+use crate::{
+    config::invalid_config,
+    error::Result,
+    profile::{address, array, words, Node},
+};
+
+/// 一段连续的保持寄存器
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotBlock {
+    pub address: u16,
+    pub values: Vec<Word>,
+}
+
+/// 设备参数快照, 由 [`Client::snapshot`](crate::Client::snapshot) 读取,
+/// [`Client::restore`](crate::Client::restore) 写回
+///
+/// TOML 格式 (需要 `toml` feature), JSON 格式 (需要 `json` feature) 的字段相同:
+/// ```toml
+/// [[block]]
+/// address = 0x10
+/// values = [1, 2, 3]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceSnapshot {
+    pub blocks: Vec<SnapshotBlock>,
+}
+
+impl DeviceSnapshot {
+    /// 保存为 TOML 文本
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> String {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut table = toml::Table::new();
+                table.insert("address".into(), toml::Value::Integer(block.address.into()));
+                let values = block
+                    .values
+                    .iter()
+                    .map(|&v| toml::Value::Integer(v.into()))
+                    .collect();
+                table.insert("values".into(), toml::Value::Array(values));
+                toml::Value::Table(table)
+            })
+            .collect();
+        let mut root = toml::Table::new();
+        root.insert("block".into(), toml::Value::Array(blocks));
+        root.to_string()
+    }
+
+    /// 从 TOML 文本加载
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let table = text
+            .parse::<toml::Table>()
+            .map_err(|e| invalid_config(&format!("TOML 格式错误, {}", e)))?;
+        Self::from_node(&toml::Value::Table(table))
+    }
+
+    /// 保存为 JSON 文本
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|block| serde_json::json!({ "address": block.address, "values": block.values }))
+            .collect::<Vec<_>>();
+        serde_json::json!({ "block": blocks }).to_string()
+    }
+
+    /// 从 JSON 文本加载
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<Self> {
+        let value = serde_json::from_str::<serde_json::Value>(text)
+            .map_err(|e| invalid_config(&format!("JSON 格式错误, {}", e)))?;
+        Self::from_node(&value)
+    }
+
+    #[cfg(any(feature = "toml", feature = "json"))]
+    fn from_node<N: Node>(root: &N) -> Result<Self> {
+        let blocks = array(root, "block")?
+            .iter()
+            .map(|block| {
+                Ok(SnapshotBlock {
+                    address: address(block, "address")?,
+                    values: words(block, "block")?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { blocks })
+    }
+}
@@ -0,0 +1,319 @@
+//! RS-485 总线分析: 只监听总线上的数据, 不发送任何数据
+//!
+//! 根据帧之间的静默间隔和 CRC 把字节流切分为 RTU 帧, 再把请求和响应配对,
+//! 用于诊断第三方主站和从设备之间的通信
+
+use std::{
+    collections::VecDeque,
+    io,
+    time::{Duration, Instant, SystemTime},
+};
+
+use bytes::Bytes;
+
+use crate::{
+    codec::{calc_crc, decode_request, decode_response, rtu_remaining, Request, Response},
+    error::{Error, Reason, Result},
+    serial::{RtuTiming, SerialConfig, SerialStream},
+    stream::Stream,
+    Protocol, MODBUS_MAX_PACKET_SIZE,
+};
+
+/// 等待响应的默认时间, 超过这个时间没有响应的请求作为 [`SnifferEvent::Unanswered`] 输出
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 操作系统的串口超时精度有限, 帧间隔不小于这个值
+const MIN_FRAME_GAP: Duration = Duration::from_millis(2);
+
+/// 总线上的一个事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnifferEvent {
+    /// 一对请求和响应, elapsed 为收到请求到收到响应的时间
+    Transaction {
+        request: Request,
+        response: Response,
+        timestamp: SystemTime,
+        elapsed: Duration,
+    },
+    /// 没有响应的请求, 例如广播或者从设备超时
+    Unanswered {
+        request: Request,
+        timestamp: SystemTime,
+    },
+    /// 无法解码或者无法配对的数据, 例如 CRC 错误或者总线干扰
+    Unknown { data: Bytes, timestamp: SystemTime },
+}
+
+struct Pending {
+    request: Request,
+    function_code: u8,
+    timestamp: SystemTime,
+    received: Instant,
+}
+
+/// 总线监听器, 每次调用 [`next_event`](Self::next_event) 得到下一个事件
+///
+/// 也可以作为迭代器使用, 迭代器在读取串口出错时返回错误
+pub struct Sniffer {
+    stream: Box<dyn Stream>,
+    frame_gap: Duration,
+    response_timeout: Duration,
+    pending: Option<Pending>,
+    events: VecDeque<SnifferEvent>,
+}
+
+impl Sniffer {
+    /// 监听 stream 上的数据, 帧间隔根据 stream 的波特率计算
+    pub fn new(stream: Box<dyn Stream>) -> Self {
+        let frame_gap = stream
+            .baud_rate()
+            .map(|baud_rate| RtuTiming::from_baud_rate(baud_rate).frame_gap)
+            .unwrap_or(MIN_FRAME_GAP);
+        Self {
+            stream,
+            frame_gap: frame_gap.max(MIN_FRAME_GAP),
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT,
+            pending: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// 按照指定的参数打开串口并监听
+    pub fn open(port: &str, config: &SerialConfig) -> Result<Self> {
+        Ok(Self::new(Box::new(SerialStream::open(port, config)?)))
+    }
+
+    /// 设置判断帧结束的静默间隔
+    ///
+    /// USB 串口会延迟转发数据, 间隔太大时多个帧可能被合并, 合并的帧再根据 CRC 切分
+    pub fn set_frame_gap(&mut self, gap: Duration) {
+        self.frame_gap = gap;
+    }
+
+    /// 设置等待响应的时间, 默认为 1s
+    pub fn set_response_timeout(&mut self, timeout: Duration) {
+        self.response_timeout = timeout;
+    }
+
+    /// 等待下一个事件, 直到收到事件或者读取串口出错
+    pub fn next_event(&mut self) -> Result<SnifferEvent> {
+        loop {
+            if let Some(event) = self.poll_event(self.response_timeout)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// 最多等待 timeout, 没有事件时返回 None
+    pub fn poll_event(&mut self, timeout: Duration) -> Result<Option<SnifferEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+            if let Some(pending) = &self.pending {
+                if pending.received.elapsed() >= self.response_timeout {
+                    let pending = self.pending.take().unwrap();
+                    return Ok(Some(SnifferEvent::Unanswered {
+                        request: pending.request,
+                        timestamp: pending.timestamp,
+                    }));
+                }
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            self.read_frames(deadline)?;
+        }
+    }
+
+    /// 读取到静默间隔为止的数据, 在 deadline 之前没有收到数据时返回
+    fn read_frames(&mut self, deadline: Instant) -> Result<()> {
+        self.stream.set_read_timeout(self.frame_gap)?;
+        let mut chunk = Vec::new();
+        let mut buf = [0u8; MODBUS_MAX_PACKET_SIZE];
+        let mut timestamp = (SystemTime::now(), Instant::now());
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {}
+                Ok(n) => {
+                    if chunk.is_empty() {
+                        timestamp = (SystemTime::now(), Instant::now());
+                    }
+                    chunk.extend_from_slice(&buf[..n]);
+                    // 总线上一直有数据时, 也要定期切分
+                    if chunk.len() < 2 * MODBUS_MAX_PACKET_SIZE {
+                        continue;
+                    }
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) => {}
+                Err(e) => return Err(e.into()),
+            }
+            if !chunk.is_empty() {
+                self.split_frames(&chunk, timestamp);
+                return Ok(());
+            }
+            if Instant::now() >= deadline || self.pending_expired() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn pending_expired(&self) -> bool {
+        self.pending
+            .as_ref()
+            .is_some_and(|p| p.received.elapsed() >= self.response_timeout)
+    }
+
+    /// 把一段数据切分为帧, 找不到有效帧的字节作为 Unknown 输出
+    fn split_frames(&mut self, mut data: &[u8], timestamp: (SystemTime, Instant)) {
+        let mut garbage = Vec::new();
+        while !data.is_empty() {
+            match frame_len(data) {
+                Some(len) => {
+                    self.flush_garbage(&mut garbage, timestamp.0);
+                    self.on_frame(&data[..len], timestamp);
+                    data = &data[len..];
+                }
+                None => {
+                    garbage.push(data[0]);
+                    data = &data[1..];
+                }
+            }
+        }
+        self.flush_garbage(&mut garbage, timestamp.0);
+    }
+
+    fn flush_garbage(&mut self, garbage: &mut Vec<u8>, timestamp: SystemTime) {
+        if !garbage.is_empty() {
+            self.events.push_back(SnifferEvent::Unknown {
+                data: Bytes::from(std::mem::take(garbage)),
+                timestamp,
+            });
+        }
+    }
+
+    /// 处理一个 CRC 正确的帧: 先尝试作为等待中的请求的响应, 否则作为新的请求
+    fn on_frame(&mut self, frame: &[u8], (timestamp, received): (SystemTime, Instant)) {
+        if let Some(pending) = self.pending.take() {
+            let matches = frame[0] == pending.request.function.id()
+                && frame[1] & 0x7F == pending.function_code
+                && response_len(frame) == Some(frame.len());
+            if matches {
+                if let Ok(response) = decode_response(Protocol::Rtu, frame) {
+                    self.events.push_back(SnifferEvent::Transaction {
+                        request: pending.request,
+                        response,
+                        timestamp: pending.timestamp,
+                        elapsed: received.duration_since(pending.received),
+                    });
+                    return;
+                }
+            }
+            self.events.push_back(SnifferEvent::Unanswered {
+                request: pending.request,
+                timestamp: pending.timestamp,
+            });
+        }
+
+        // 异常响应不会是请求, 没有配对的异常响应作为 Unknown 输出
+        let request = if frame[1] & 0x80 == 0 {
+            decode_request(Protocol::Rtu, frame)
+        } else {
+            Err(Error::InvalidResponse(Reason::UnexpectedFunction))
+        };
+        match request {
+            // 广播请求没有响应
+            Ok(request) if request.function.id() == 0 => {
+                self.events
+                    .push_back(SnifferEvent::Unanswered { request, timestamp });
+            }
+            Ok(request) => {
+                self.pending = Some(Pending {
+                    request,
+                    function_code: frame[1],
+                    timestamp,
+                    received,
+                });
+            }
+            Err(e) => {
+                log::debug!("无法解码的帧: {:02X?}, E: {}", frame, e);
+                self.events.push_back(SnifferEvent::Unknown {
+                    data: Bytes::copy_from_slice(frame),
+                    timestamp,
+                });
+            }
+        }
+    }
+}
+
+impl Iterator for Sniffer {
+    type Item = Result<SnifferEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_event())
+    }
+}
+
+/// 数据开头的 CRC 正确的帧的长度
+///
+/// 先检查按照功能码计算的请求和响应长度, 再依次检查每一个可能的长度
+fn frame_len(data: &[u8]) -> Option<usize> {
+    let crc_ok = |len: usize| {
+        // ID(1) + FUN(1) + CRC(2)
+        len >= 4
+            && len <= data.len()
+            && calc_crc(&data[..len - 2]).to_be_bytes() == data[len - 2..len]
+    };
+    [request_len(data), response_len(data)]
+        .into_iter()
+        .flatten()
+        .find(|&len| crc_ok(len))
+        .or_else(|| (4..=data.len().min(MODBUS_MAX_PACKET_SIZE)).find(|&len| crc_ok(len)))
+}
+
+/// 按照功能码计算的响应帧长度, 包含 CRC
+fn response_len(data: &[u8]) -> Option<usize> {
+    let mut len = 0;
+    loop {
+        match rtu_remaining(&data[..len], 2)? {
+            0 => return Some(len),
+            remaining => {
+                len += remaining;
+                if len > data.len() {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// 按照功能码计算的请求帧长度, 包含 CRC
+fn request_len(data: &[u8]) -> Option<usize> {
+    let byte_count = |index: usize| data.get(index).map(|&n| n as usize);
+    let pdu = match *data.get(1)? {
+        // 功能码(1) + 地址(2) + 数量或数值(2)
+        0x01..=0x06 | 0x08 => Some(5),
+        // 功能码(1)
+        0x07 | 0x0B | 0x0C | 0x11 => Some(1),
+        // 功能码(1) + 地址(2) + 数量(2) + 字节数(1) + 数据
+        0x0F | 0x10 => byte_count(6).map(|n| 6 + n),
+        // 功能码(1) + 字节数(1) + 子请求
+        0x14 | 0x15 => byte_count(2).map(|n| 2 + n),
+        // 功能码(1) + 地址(2) + AND掩码(2) + OR掩码(2)
+        0x16 => Some(7),
+        // 功能码(1) + 读地址(2) + 读数量(2) + 写地址(2) + 写数量(2) + 字节数(1) + 数据
+        0x17 => byte_count(10).map(|n| 10 + n),
+        // 功能码(1) + FIFO地址(2)
+        0x18 => Some(3),
+        // 功能码(1) + MEI类型(1) + 读设备ID码(1) + 对象ID(1)
+        0x2B => Some(4),
+        _ => None,
+    }?;
+    // ID(1) + PDU + CRC(2)
+    Some(1 + pdu + 2)
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    error::{Error, ExceptionCode, Result},
+    Id,
+};
+
+/// 客户端的通信统计, 用于监控总线状态
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// 发送的请求数量, 包含重连后的重发
+    pub requests: u64,
+    /// 收到的响应数量, 包含异常响应
+    pub responses: u64,
+    /// CRC/LRC 校验错误数量
+    pub crc_errors: u64,
+    /// 超时数量
+    pub timeouts: u64,
+    /// 其它错误数量, 例如 IO 错误, 无效的响应
+    pub other_errors: u64,
+    /// 每种异常码的异常响应数量
+    pub exceptions: HashMap<ExceptionCode, u64>,
+    /// 所有响应的往返时间之和
+    pub total_round_trip: Duration,
+}
+
+impl Stats {
+    /// 平均往返时间, 还没有收到响应时返回 None
+    pub fn average_round_trip(&self) -> Option<Duration> {
+        (self.responses > 0).then(|| self.total_round_trip / self.responses as u32)
+    }
+
+    /// 异常响应的总数
+    pub fn exception_count(&self) -> u64 {
+        self.exceptions.values().sum()
+    }
+
+    /// 记录一次传输的结果, expect_reply 为 false 时 (广播或者不需要响应的写操作) 成功不计入响应数量
+    pub(crate) fn record(
+        &mut self,
+        id: Id,
+        res: &Result<()>,
+        expect_reply: bool,
+        round_trip: Duration,
+    ) {
+        #[cfg(feature = "metrics")]
+        export(id, res, expect_reply, round_trip);
+        #[cfg(not(feature = "metrics"))]
+        let _ = id;
+
+        self.requests += 1;
+        match res {
+            Ok(_) => {
+                if expect_reply {
+                    self.responses += 1;
+                    self.total_round_trip += round_trip;
+                }
+            }
+            Err(Error::Exception(code)) => {
+                self.responses += 1;
+                self.total_round_trip += round_trip;
+                *self.exceptions.entry(*code).or_default() += 1;
+            }
+            Err(Error::Crc) => self.crc_errors += 1,
+            Err(Error::Timeout) => self.timeouts += 1,
+            Err(_) => self.other_errors += 1,
+        }
+    }
+}
+
+/// 通过 `metrics` 门面输出指标, 标签 slave 为从站地址:
+///
+/// - `modbus_requests_total`: 请求数量
+/// - `modbus_responses_total`: 响应数量, 包含异常响应
+/// - `modbus_errors_total`: 错误数量, 标签 kind 为 crc, timeout, exception 或者 other
+/// - `modbus_exceptions_total`: 异常响应数量, 标签 code 为异常码的数值
+/// - `modbus_request_duration_seconds`: 收到响应的请求的往返时间直方图
+#[cfg(feature = "metrics")]
+fn export(id: Id, res: &Result<()>, expect_reply: bool, round_trip: Duration) {
+    let slave = id.to_string();
+    metrics::counter!("modbus_requests_total", "slave" => slave.clone()).increment(1);
+    let kind = match res {
+        Ok(_) if !expect_reply => return,
+        Ok(_) => None,
+        Err(Error::Exception(code)) => {
+            let code = code.code().to_string();
+            metrics::counter!("modbus_exceptions_total", "slave" => slave.clone(), "code" => code)
+                .increment(1);
+            Some("exception")
+        }
+        Err(Error::Crc) => Some("crc"),
+        Err(Error::Timeout) => Some("timeout"),
+        Err(_) => Some("other"),
+    };
+    if let Some(kind) = kind {
+        metrics::counter!("modbus_errors_total", "slave" => slave.clone(), "kind" => kind)
+            .increment(1);
+    }
+    if matches!(res, Ok(_) | Err(Error::Exception(_))) {
+        metrics::counter!("modbus_responses_total", "slave" => slave.clone()).increment(1);
+        metrics::histogram!("modbus_request_duration_seconds", "slave" => slave)
+            .record(round_trip.as_secs_f64());
+    }
+}
@@ -1,10 +1,45 @@
-use anyhow::Result;
+use crate::error::{Error, Result};
 use std::{
-    io::{Read, Write},
+    io::{self, Read, Write},
     time::Duration,
 };
 
-pub trait Stream: Read + Write {
+/// 数据传输, 需要实现 Send 以便 Client 可以在线程之间移动
+pub trait Stream: Read + Write + Send {
     /// 设置 数据传输 的超时时间
     fn set_timeout(&mut self, timeout: Duration) -> Result<()>;
+
+    /// 只设置读超时时间. 默认调用 set_timeout, 写超时时间也会改变
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.set_timeout(timeout)
+    }
+
+    /// 只设置写超时时间. 默认忽略, 不支持单独设置的传输使用 set_timeout 设置的时间
+    fn set_write_timeout(&mut self, _timeout: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    /// 串行链路的波特率, 用于计算 RTU 帧的时间间隔. 非串行的传输返回 None
+    fn baud_rate(&self) -> Option<u32> {
+        None
+    }
+
+    /// 丢弃已经收到但是还没有读取的数据, 在发送请求之前调用,
+    /// 避免之前超时的请求的响应被当作这一次的响应
+    fn clear_input(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 关闭传输, 立即释放串口或者断开连接, 之后可以通过 reconnect 重新建立. 默认什么都不做
+    fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 重新建立连接, 例如重新打开串口或者重新连接 TCP 服务器
+    fn reconnect(&mut self) -> Result<()> {
+        Err(Error::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "不支持重新连接",
+        )))
+    }
 }
@@ -0,0 +1,114 @@
+//! 变化订阅: 在 [`Poller`](crate::poll::Poller) 之上, 只在数据点的值变化超过死区或者质量变化时发送更新
+//!
+//! 读取失败时按照 [`HealthPolicy`](crate::health::HealthPolicy) 标记为 [`Quality::Stale`]
+//! 或者 [`Quality::CommError`], 保留最后一次的值
+
+use std::{
+    sync::mpsc::Sender,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    error::Result,
+    health::Quality,
+    poll::{PollFunction, PollTask, PollValue},
+    register_map::{Area, Point},
+    Coil, Id,
+};
+
+/// 一个数据点的更新
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Update {
+    /// 数据点的名称
+    pub name: String,
+    /// 换算之后的工程值, 位数据为 1.0 或 0.0. 从来没有读取成功时为 None
+    pub value: Option<f64>,
+    pub quality: Quality,
+    pub timestamp: SystemTime,
+}
+
+/// 一个订阅的状态
+pub(crate) struct Subscriber {
+    point: Point,
+    deadband: f64,
+    sender: Sender<Update>,
+    /// 最后一次发送的值和质量
+    value: Option<f64>,
+    quality: Option<Quality>,
+}
+
+impl Subscriber {
+    pub(crate) fn new(point: Point, deadband: f64, sender: Sender<Update>) -> Self {
+        Self {
+            point,
+            deadband,
+            sender,
+            value: None,
+            quality: None,
+        }
+    }
+
+    /// 读取数据点的任务
+    pub(crate) fn task(&self, id: Id, interval: Duration) -> PollTask {
+        let (function, quantity) = match self.point.area {
+            Area::Coil => (PollFunction::Coils, 1),
+            Area::DiscreteInput => (PollFunction::DiscreteInputs, 1),
+            Area::InputRegister => (PollFunction::InputRegisters, self.point.register.words()),
+            Area::HoldingRegister => (PollFunction::HoldingRegisters, self.point.register.words()),
+        };
+        PollTask {
+            function,
+            id,
+            address: self.point.address(),
+            quantity,
+            interval,
+        }
+    }
+
+    /// 处理一次轮询的结果和按照失败次数计算的质量, 接收端关闭时返回 false
+    pub(crate) fn update(&mut self, result: Result<PollValue>, quality: Quality) -> bool {
+        let (value, quality) = match result.and_then(|value| self.decode(&value)) {
+            Ok(value) => {
+                let changed = match self.value {
+                    Some(old) => {
+                        (value - old).abs() > self.deadband || value.is_nan() != old.is_nan()
+                    }
+                    None => true,
+                };
+                if !changed && self.quality == Some(Quality::Good) {
+                    return true;
+                }
+                (Some(value), Quality::Good)
+            }
+            Err(e) => {
+                log::debug!("读取数据点 {} 失败, E: {}", self.point.name, e);
+                // 失败的次数还没有达到 stale_after 时, 保留之前的值和质量
+                if quality == Quality::Good || self.quality == Some(quality) {
+                    return true;
+                }
+                (self.value, quality)
+            }
+        };
+        self.value = value;
+        self.quality = Some(quality);
+        self.sender
+            .send(Update {
+                name: self.point.name.clone(),
+                value,
+                quality,
+                timestamp: SystemTime::now(),
+            })
+            .is_ok()
+    }
+
+    fn decode(&self, value: &PollValue) -> Result<f64> {
+        match value {
+            PollValue::Bits(bits) => Ok(match bits.first() {
+                Some(Coil::On) => 1.0,
+                _ => 0.0,
+            }),
+            PollValue::Words(words) => self.point.register.decode(words),
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! SunSpec: 光伏逆变器和电能表的标准寄存器布局
+//!
+//! 设备在保持寄存器 40000, 50000 或者 0 处放置 `SunS` 标识, 之后是一串模型,
+//! 每个模型为 模型ID(1) + 长度(1) + 数据, 模型ID 为 0xFFFF 时结束.
+//! [`SunSpec::discover`] 找到所有的模型, 再按照模型解码为结构体
+//! ```no_run
+//! # use simple_modbus::{sunspec::SunSpec, Client};
+//! # fn run(client: &mut Client) -> simple_modbus::error::Result<()> {
+//! let sunspec = SunSpec::discover(client, 1)?;
+//! let common = sunspec.read_common(client)?;
+//! if let Some(inverter) = sunspec.read_inverter(client)? {
+//!     println!("{} {:?} W", common.model, inverter.power);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    error::{Error, Reason, Result},
+    value::unpack_string,
+    Address, Client, Id, Word,
+};
+
+/// 依次查找 `SunS` 标识的地址
+pub const BASE_ADDRESSES: [Address; 3] = [40000, 50000, 0];
+
+/// `SunS` 标识
+const MARKER: [Word; 2] = [0x5375, 0x6E53];
+
+/// 模型链结束的模型ID
+const END_MODEL: u16 = 0xFFFF;
+
+/// 公共模型
+pub const COMMON_MODEL: u16 = 1;
+/// 单相, 分相, 三相逆变器 (整数 + 比例因子)
+pub const INVERTER_MODELS: [u16; 3] = [101, 102, 103];
+/// 单相, 分相, 三相星形, 三相三角形电能表 (整数 + 比例因子)
+pub const METER_MODELS: [u16; 4] = [201, 202, 203, 204];
+
+/// 模型链中的一个模型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelHeader {
+    pub id: u16,
+    /// 模型数据的起始地址, 即长度字段之后的地址
+    pub address: Address,
+    /// 模型数据的寄存器数量
+    pub length: u16,
+}
+
+/// 公共模型 (1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Common {
+    pub manufacturer: String,
+    pub model: String,
+    pub options: String,
+    pub version: String,
+    pub serial_number: String,
+    pub device_address: u16,
+}
+
+/// 逆变器模型 (101, 102, 103), 已经乘以比例因子, 设备没有实现的值为 None
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inverter {
+    /// 交流电流, A
+    pub current: Option<f64>,
+    /// A 相电压, V
+    pub voltage: Option<f64>,
+    /// 交流有功功率, W
+    pub power: Option<f64>,
+    /// 频率, Hz
+    pub frequency: Option<f64>,
+    /// 视在功率, VA
+    pub apparent_power: Option<f64>,
+    /// 无功功率, var
+    pub reactive_power: Option<f64>,
+    /// 功率因数, %
+    pub power_factor: Option<f64>,
+    /// 累计发电量, Wh
+    pub energy: Option<f64>,
+    /// 直流电流, A
+    pub dc_current: Option<f64>,
+    /// 直流电压, V
+    pub dc_voltage: Option<f64>,
+    /// 直流功率, W
+    pub dc_power: Option<f64>,
+    /// 机箱温度, °C
+    pub cabinet_temperature: Option<f64>,
+    /// 运行状态, 1: 关机, 2: 休眠, 3: 启动, 4: 最大功率点跟踪, 5: 限功率, 6: 停机中, 7: 故障, 8: 待机
+    pub state: Option<u16>,
+    /// 事件标志位
+    pub events: u32,
+}
+
+/// 电能表模型 (201 ~ 204), 已经乘以比例因子, 设备没有实现的值为 None
+#[derive(Debug, Clone, PartialEq)]
+pub struct Meter {
+    /// 总电流, A
+    pub current: Option<f64>,
+    /// 平均相电压, V
+    pub voltage: Option<f64>,
+    /// 频率, Hz
+    pub frequency: Option<f64>,
+    /// 总有功功率, W
+    pub power: Option<f64>,
+    /// 总视在功率, VA
+    pub apparent_power: Option<f64>,
+    /// 总无功功率, var
+    pub reactive_power: Option<f64>,
+    /// 平均功率因数, %
+    pub power_factor: Option<f64>,
+    /// 累计输出的有功电能, Wh
+    pub energy_exported: Option<f64>,
+    /// 累计输入的有功电能, Wh
+    pub energy_imported: Option<f64>,
+}
+
+/// 解码之后的模型
+#[derive(Debug, Clone, PartialEq)]
+pub enum Model {
+    Common(Common),
+    Inverter(Inverter),
+    Meter(Meter),
+    /// 不支持解码的模型, 原样返回数据
+    Unknown {
+        id: u16,
+        data: Vec<Word>,
+    },
+}
+
+/// 一个 SunSpec 设备的模型链
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SunSpec {
+    slave: Id,
+    base: Address,
+    models: Vec<ModelHeader>,
+}
+
+impl SunSpec {
+    /// 在 [`BASE_ADDRESSES`] 中查找 `SunS` 标识, 读取整个模型链
+    pub fn discover(client: &mut Client, slave: Id) -> Result<Self> {
+        for base in BASE_ADDRESSES {
+            match client.read_holding_registers(slave, base, 2u16) {
+                Ok(marker) if marker == MARKER => return Self::walk(client, slave, base),
+                Ok(_) | Err(Error::Exception(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(not_sunspec("没有找到 SunSpec 标识"))
+    }
+
+    /// 从 base 处的 `SunS` 标识之后开始读取模型链
+    pub fn walk(client: &mut Client, slave: Id, base: Address) -> Result<Self> {
+        let mut models = Vec::new();
+        let mut address = base as u32 + 2;
+        loop {
+            if address + 2 > 0x10000 {
+                return Err(not_sunspec("模型链超出了地址范围"));
+            }
+            let header = client.read_holding_registers(slave, address as Address, 2u16)?;
+            let (id, length) = (header[0], header[1]);
+            if id == END_MODEL {
+                break;
+            }
+            let data = address + 2;
+            if data + length as u32 > 0x10000 {
+                return Err(not_sunspec("模型链超出了地址范围"));
+            }
+            models.push(ModelHeader {
+                id,
+                address: data as Address,
+                length,
+            });
+            address = data + length as u32;
+        }
+        log::debug!(
+            "SunSpec 模型: {:?}",
+            models.iter().map(|m| m.id).collect::<Vec<_>>()
+        );
+        Ok(Self {
+            slave,
+            base,
+            models,
+        })
+    }
+
+    pub fn slave(&self) -> Id {
+        self.slave
+    }
+
+    /// `SunS` 标识的地址
+    pub fn base(&self) -> Address {
+        self.base
+    }
+
+    pub fn models(&self) -> &[ModelHeader] {
+        &self.models
+    }
+
+    /// 第一个模型ID 在 ids 中的模型
+    pub fn find(&self, ids: &[u16]) -> Option<&ModelHeader> {
+        self.models.iter().find(|model| ids.contains(&model.id))
+    }
+
+    /// 读取模型的原始数据
+    pub fn read_raw(&self, client: &mut Client, model: &ModelHeader) -> Result<Vec<Word>> {
+        if model.length == 0 {
+            return Ok(Vec::new());
+        }
+        client.read_holding_registers(self.slave, model.address, model.length)
+    }
+
+    /// 读取并解码模型
+    pub fn read(&self, client: &mut Client, model: &ModelHeader) -> Result<Model> {
+        let data = self.read_raw(client, model)?;
+        decode(model.id, data)
+    }
+
+    /// 读取公共模型, 每个 SunSpec 设备的第一个模型都是公共模型
+    pub fn read_common(&self, client: &mut Client) -> Result<Common> {
+        let model = self
+            .find(&[COMMON_MODEL])
+            .ok_or_else(|| not_sunspec("没有公共模型"))?;
+        Common::parse(&self.read_raw(client, model)?)
+    }
+
+    /// 读取逆变器模型, 设备没有逆变器模型时返回 None
+    pub fn read_inverter(&self, client: &mut Client) -> Result<Option<Inverter>> {
+        match self.find(&INVERTER_MODELS) {
+            Some(model) => Ok(Some(Inverter::parse(&self.read_raw(client, model)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 读取电能表模型, 设备没有电能表模型时返回 None
+    pub fn read_meter(&self, client: &mut Client) -> Result<Option<Meter>> {
+        match self.find(&METER_MODELS) {
+            Some(model) => Ok(Some(Meter::parse(&self.read_raw(client, model)?)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// 按照模型ID 解码模型数据
+pub fn decode(id: u16, data: Vec<Word>) -> Result<Model> {
+    Ok(match id {
+        COMMON_MODEL => Model::Common(Common::parse(&data)?),
+        id if INVERTER_MODELS.contains(&id) => Model::Inverter(Inverter::parse(&data)?),
+        id if METER_MODELS.contains(&id) => Model::Meter(Meter::parse(&data)?),
+        id => Model::Unknown { id, data },
+    })
+}
+
+impl Common {
+    /// 模型数据至少为 65 个寄存器
+    pub fn parse(data: &[Word]) -> Result<Self> {
+        check_length(data, 65)?;
+        let string = |offset: usize, words: usize| {
+            unpack_string(&data[offset..offset + words], words * 2, false)
+        };
+        Ok(Self {
+            manufacturer: string(0, 16),
+            model: string(16, 16),
+            options: string(32, 8),
+            version: string(40, 8),
+            serial_number: string(48, 16),
+            device_address: data[64],
+        })
+    }
+}
+
+impl Inverter {
+    /// 模型数据至少为 50 个寄存器
+    pub fn parse(data: &[Word]) -> Result<Self> {
+        check_length(data, 50)?;
+        let r = Registers(data);
+        Ok(Self {
+            current: r.scaled(r.uint16(0), 4),
+            voltage: r.scaled(r.uint16(8), 11),
+            power: r.scaled(r.int16(12), 13),
+            frequency: r.scaled(r.uint16(14), 15),
+            apparent_power: r.scaled(r.int16(16), 17),
+            reactive_power: r.scaled(r.int16(18), 19),
+            power_factor: r.scaled(r.int16(20), 21),
+            energy: r.scaled(r.acc32(22), 24),
+            dc_current: r.scaled(r.uint16(25), 26),
+            dc_voltage: r.scaled(r.uint16(27), 28),
+            dc_power: r.scaled(r.int16(29), 30),
+            cabinet_temperature: r.scaled(r.int16(31), 35),
+            state: r.uint16(36).map(|state| state as u16),
+            events: (data[38] as u32) << 16 | data[39] as u32,
+        })
+    }
+}
+
+impl Meter {
+    /// 模型数据至少为 105 个寄存器
+    pub fn parse(data: &[Word]) -> Result<Self> {
+        check_length(data, 105)?;
+        let r = Registers(data);
+        Ok(Self {
+            current: r.scaled(r.int16(0), 4),
+            voltage: r.scaled(r.int16(5), 13),
+            frequency: r.scaled(r.int16(14), 15),
+            power: r.scaled(r.int16(16), 20),
+            apparent_power: r.scaled(r.int16(21), 25),
+            reactive_power: r.scaled(r.int16(26), 30),
+            power_factor: r.scaled(r.int16(31), 35),
+            energy_exported: r.scaled(r.acc32(36), 52),
+            energy_imported: r.scaled(r.acc32(44), 52),
+        })
+    }
+}
+
+/// 按照 SunSpec 的规则读取模型数据, 没有实现的值返回 None
+struct Registers<'a>(&'a [Word]);
+
+impl Registers<'_> {
+    fn uint16(&self, offset: usize) -> Option<f64> {
+        let value = self.0[offset];
+        (value != 0xFFFF).then_some(value as f64)
+    }
+
+    fn int16(&self, offset: usize) -> Option<f64> {
+        let value = self.0[offset] as i16;
+        (value != i16::MIN).then_some(value as f64)
+    }
+
+    /// 累加值, 0 表示没有实现
+    fn acc32(&self, offset: usize) -> Option<f64> {
+        let value = (self.0[offset] as u32) << 16 | self.0[offset + 1] as u32;
+        (value != 0).then_some(value as f64)
+    }
+
+    /// 乘以 offset 处的比例因子 (10 的幂)
+    fn scaled(&self, value: Option<f64>, offset: usize) -> Option<f64> {
+        let sf = self.0[offset] as i16;
+        if !(-10..=10).contains(&sf) {
+            return None;
+        }
+        value.map(|value| value * 10f64.powi(sf as i32))
+    }
+}
+
+fn check_length(data: &[Word], min: usize) -> Result<()> {
+    if data.len() < min {
+        return Err(not_sunspec(&format!(
+            "模型数据长度为 {}, 至少需要 {}",
+            data.len(),
+            min
+        )));
+    }
+    Ok(())
+}
+
+fn not_sunspec(reason: &str) -> Error {
+    Error::InvalidResponse(Reason::Custom(reason.to_string()))
+}
@@ -0,0 +1,282 @@
+use socket2::{Domain, SockRef, Socket, TcpKeepalive, Type};
+use std::{
+    io::{self, Read, Write},
+    net::{self, SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::{
+    config::{invalid_config, Config},
+    error::Result,
+    stream::Stream,
+};
+
+/// Modbus TCP 默认端口
+pub const MODBUS_TCP_PORT: u16 = 502;
+
+/// TCP 保活探测的参数
+///
+/// 连接空闲 time 之后开始探测, 每隔 interval 探测一次, 连续 retries 次没有回应时认为连接已经断开,
+/// 之后的读写返回错误. 默认约 25s 发现断开的网关, 操作系统的默认值通常为 2 小时以上
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keepalive {
+    pub time: Duration,
+    pub interval: Duration,
+    pub retries: u32,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self {
+            time: Duration::from_secs(10),
+            interval: Duration::from_secs(5),
+            retries: 3,
+        }
+    }
+}
+
+impl Keepalive {
+    fn params(&self) -> TcpKeepalive {
+        let params = TcpKeepalive::new().with_time(self.time);
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "windows"
+        ))]
+        let params = params
+            .with_interval(self.interval)
+            .with_retries(self.retries);
+        params
+    }
+}
+
+/// TCP 连接的套接字选项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// 禁用 Nagle 算法, 请求立即发送. 默认开启
+    pub nodelay: bool,
+    /// None 表示不开启保活探测. 默认不开启
+    pub keepalive: Option<Keepalive>,
+    /// 绑定到指定的网络接口 (例如 eth1), 只支持 Linux
+    pub interface: Option<String>,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            interface: None,
+        }
+    }
+}
+
+/// TCP 连接
+///
+/// 帧格式由 `Client` 的 `Protocol` 决定: `Protocol::Tcp` 为标准的 Modbus TCP,
+/// `Protocol::Rtu` 则是部分串口服务器使用的 RTU over TCP, 直接转发 RTU 帧
+pub struct TcpStream {
+    inner: net::TcpStream,
+    addr: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Duration,
+    write_timeout: Duration,
+    options: TcpOptions,
+}
+
+impl TcpStream {
+    /// 连接 Modbus TCP 服务器, addr 格式为 `host:port`
+    pub fn new(addr: &str) -> Result<Self> {
+        let timeout = Duration::from_millis(5000);
+        Self::open(addr, None, timeout, timeout, TcpOptions::default())
+    }
+
+    /// 使用 Config 中的 连接/读/写 超时时间 和 套接字选项连接服务器
+    pub fn with_config(addr: &str, config: &Config) -> Result<Self> {
+        Self::open(
+            addr,
+            Some(config.connect_timeout),
+            config.read_timeout,
+            config.write_timeout,
+            config.tcp.clone(),
+        )
+    }
+
+    fn open(
+        addr: &str,
+        connect_timeout: Option<Duration>,
+        read_timeout: Duration,
+        write_timeout: Duration,
+        options: TcpOptions,
+    ) -> Result<Self> {
+        if options.interface.is_some() && !cfg!(any(target_os = "linux", target_os = "android")) {
+            return Err(invalid_config("只有 Linux 支持绑定网络接口"));
+        }
+        let mut stream = Self {
+            inner: Self::connect(addr, connect_timeout, &options)?,
+            addr: addr.to_string(),
+            connect_timeout,
+            read_timeout,
+            write_timeout,
+            options,
+        };
+        stream.apply_options()?;
+        Ok(stream)
+    }
+
+    fn connect(
+        addr: &str,
+        timeout: Option<Duration>,
+        options: &TcpOptions,
+    ) -> io::Result<net::TcpStream> {
+        // 依次尝试解析出的每个地址, 返回最后一个错误
+        let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, "无法解析服务器地址");
+        for addr in addr.to_socket_addrs()? {
+            match Self::connect_addr(addr, timeout, options) {
+                Ok(inner) => return Ok(inner),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    fn connect_addr(
+        addr: SocketAddr,
+        timeout: Option<Duration>,
+        options: &TcpOptions,
+    ) -> io::Result<net::TcpStream> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(interface) = &options.interface {
+            socket.bind_device(Some(interface.as_bytes()))?;
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let _ = options;
+        match timeout {
+            Some(timeout) => socket.connect_timeout(&addr.into(), timeout)?,
+            None => socket.connect(&addr.into())?,
+        }
+        Ok(socket.into())
+    }
+
+    fn apply_options(&mut self) -> Result<()> {
+        self.inner.set_nodelay(self.options.nodelay)?;
+        if let Some(keepalive) = &self.options.keepalive {
+            let socket = SockRef::from(&self.inner);
+            socket.set_keepalive(true)?;
+            socket.set_tcp_keepalive(&keepalive.params())?;
+        }
+        self.inner.set_read_timeout(Some(self.read_timeout))?;
+        self.inner.set_write_timeout(Some(self.write_timeout))?;
+        Ok(())
+    }
+
+    /// 设置 数据读写 的超时时间
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.set_read_timeout(Some(timeout))?;
+        self.inner.set_write_timeout(Some(timeout))?;
+        self.read_timeout = timeout;
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    /// 设置等待响应的超时时间
+    pub fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.set_read_timeout(Some(timeout))?;
+        self.read_timeout = timeout;
+        Ok(())
+    }
+
+    /// 设置发送请求的超时时间
+    pub fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.set_write_timeout(Some(timeout))?;
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    /// 发送缓冲区中的数据后断开连接
+    pub fn close(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        match self.inner.shutdown(net::Shutdown::Both) {
+            // 对方已经断开
+            Err(e) if e.kind() == io::ErrorKind::NotConnected => Ok(()),
+            res => Ok(res?),
+        }
+    }
+
+    /// 断开并重新连接服务器
+    pub fn reconnect(&mut self) -> Result<()> {
+        let _ = self.inner.shutdown(net::Shutdown::Both);
+        self.inner = Self::connect(&self.addr, self.connect_timeout, &self.options)?;
+        self.apply_options()
+    }
+
+    /// 以非阻塞方式读取并丢弃接收缓冲区中的数据
+    pub fn clear_input(&mut self) -> Result<()> {
+        self.inner.set_nonblocking(true)?;
+        let mut buf = [0u8; 256];
+        let res = loop {
+            match self.inner.read(&mut buf) {
+                // 连接已经关闭, 留给之后的读操作报告错误
+                Ok(0) => break Ok(()),
+                Ok(n) => log::warn!("丢弃过期的数据: {:02X?}", &buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.inner.set_nonblocking(false)?;
+        Ok(res?)
+    }
+
+    /// 服务器的地址
+    pub fn peer_addr(&self) -> Result<net::SocketAddr> {
+        Ok(self.inner.peer_addr()?)
+    }
+}
+
+impl Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Stream for TcpStream {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        TcpStream::set_timeout(self, timeout)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+
+    fn clear_input(&mut self) -> Result<()> {
+        TcpStream::clear_input(self)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        TcpStream::close(self)
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        TcpStream::reconnect(self)
+    }
+}
@@ -0,0 +1,202 @@
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+    ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+};
+use std::{
+    io::{self, BufReader, Read, Write},
+    net,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{error::Result, stream::Stream};
+
+/// Modbus/TCP Security 默认端口
+pub const MODBUS_TLS_PORT: u16 = 802;
+
+/// TLS 连接的证书配置
+///
+/// Modbus/TCP Security 要求双向认证, 服务器通常会验证客户端证书
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    roots: RootCertStore,
+    client_cert: Option<(Vec<CertificateDer<'static>>, Arc<PrivateKeyDer<'static>>)>,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        Self {
+            roots: RootCertStore::empty(),
+            client_cert: None,
+        }
+    }
+
+    /// 添加信任的 CA 证书 (PEM 格式, 可以包含多个证书)
+    pub fn add_root_pem(&mut self, pem: &[u8]) -> Result<()> {
+        for cert in rustls_pemfile::certs(&mut BufReader::new(pem)) {
+            self.roots.add(cert?).map_err(tls_error)?;
+        }
+        Ok(())
+    }
+
+    /// 设置客户端证书链和私钥 (PEM 格式)
+    pub fn set_client_cert_pem(&mut self, cert_pem: &[u8], key_pem: &[u8]) -> Result<()> {
+        let certs =
+            rustls_pemfile::certs(&mut BufReader::new(cert_pem)).collect::<io::Result<Vec<_>>>()?;
+        let key = rustls_pemfile::private_key(&mut BufReader::new(key_pem))?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "没有找到私钥"))?;
+        self.client_cert = Some((certs, Arc::new(key)));
+        Ok(())
+    }
+
+    fn client_config(&self) -> Result<Arc<ClientConfig>> {
+        let builder = ClientConfig::builder().with_root_certificates(self.roots.clone());
+        let config = match &self.client_cert {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs.clone(), key.clone_key())
+                .map_err(tls_error)?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(Arc::new(config))
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 基于 TLS 的 Modbus/TCP (MBAPS) 传输, 帧格式与 Modbus TCP 相同
+pub struct TlsStream {
+    inner: StreamOwned<ClientConnection, net::TcpStream>,
+    addr: String,
+    server_name: ServerName<'static>,
+    config: Arc<ClientConfig>,
+    timeout: Duration,
+    write_timeout: Duration,
+}
+
+impl TlsStream {
+    /// 连接服务器并完成 TLS 握手, addr 格式为 `host:port`, server_name 用于验证服务器证书
+    pub fn connect(addr: &str, server_name: &str, config: &TlsConfig) -> Result<Self> {
+        let server_name = ServerName::try_from(server_name.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let config = config.client_config()?;
+        let timeout = Duration::from_millis(5000);
+        let inner = Self::handshake(addr, &server_name, &config, timeout)?;
+
+        Ok(Self {
+            inner,
+            addr: addr.to_string(),
+            server_name,
+            config,
+            timeout,
+            write_timeout: timeout,
+        })
+    }
+
+    fn handshake(
+        addr: &str,
+        server_name: &ServerName<'static>,
+        config: &Arc<ClientConfig>,
+        timeout: Duration,
+    ) -> Result<StreamOwned<ClientConnection, net::TcpStream>> {
+        let mut sock = net::TcpStream::connect(addr)?;
+        sock.set_nodelay(true)?;
+        sock.set_read_timeout(Some(timeout))?;
+        sock.set_write_timeout(Some(timeout))?;
+
+        let mut conn =
+            ClientConnection::new(config.clone(), server_name.clone()).map_err(tls_error)?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut sock)?;
+        }
+        Ok(StreamOwned::new(conn, sock))
+    }
+
+    /// 设置 数据读写 的超时时间
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.sock.set_read_timeout(Some(timeout))?;
+        self.inner.sock.set_write_timeout(Some(timeout))?;
+        self.timeout = timeout;
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    /// 设置等待响应的超时时间
+    pub fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.sock.set_read_timeout(Some(timeout))?;
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// 设置发送请求的超时时间
+    pub fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.sock.set_write_timeout(Some(timeout))?;
+        self.write_timeout = timeout;
+        Ok(())
+    }
+
+    /// 断开并重新连接服务器
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.inner.conn.send_close_notify();
+        let _ = self.inner.flush();
+        let _ = self.inner.sock.shutdown(net::Shutdown::Both);
+        self.inner = Self::handshake(&self.addr, &self.server_name, &self.config, self.timeout)?;
+        self.inner
+            .sock
+            .set_write_timeout(Some(self.write_timeout))?;
+        Ok(())
+    }
+
+    /// 服务器证书链, 可以用来读取证书中的角色等扩展信息
+    pub fn peer_certificates(&self) -> Option<&[CertificateDer<'static>]> {
+        self.inner.conn.peer_certificates()
+    }
+}
+
+fn tls_error(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Stream for TlsStream {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        TlsStream::set_timeout(self, timeout)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        TlsStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        TlsStream::set_write_timeout(self, timeout)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.conn.send_close_notify();
+        self.inner.flush()?;
+        let _ = self.inner.sock.shutdown(net::Shutdown::Both);
+        Ok(())
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        TlsStream::reconnect(self)
+    }
+}
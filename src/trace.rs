@@ -0,0 +1,84 @@
+use std::time::{Duration, SystemTime};
+
+/// 线路日志使用的 log target, 可以单独设置这个 target 的日志级别
+pub const WIRE_LOG_TARGET: &str = "simple_modbus::wire";
+
+/// 线路日志的级别, 发送和接收可以使用不同的级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireLogging {
+    pub tx: log::Level,
+    pub rx: log::Level,
+}
+
+impl Default for WireLogging {
+    fn default() -> Self {
+        Self {
+            tx: log::Level::Debug,
+            rx: log::Level::Debug,
+        }
+    }
+}
+
+/// 帧的传输方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// 发送的请求
+    Tx,
+    /// 收到的响应
+    Rx,
+}
+
+/// 发送或收到的一个原始帧, ASCII 帧为编码后的字符
+#[derive(Debug, Clone, Copy)]
+pub struct FrameEvent<'a> {
+    pub direction: Direction,
+    pub timestamp: SystemTime,
+    pub data: &'a [u8],
+}
+
+/// 帧的观察者, 可以用来实现协议分析或者调试日志
+pub type FrameObserver = Box<dyn Fn(&FrameEvent) + Send>;
+
+/// 通知观察者
+pub(crate) fn notify(observer: &Option<FrameObserver>, direction: Direction, data: &[u8]) {
+    if let Some(observer) = observer {
+        observer(&FrameEvent {
+            direction,
+            timestamp: SystemTime::now(),
+            data,
+        });
+    }
+}
+
+/// 输出一帧的线路日志, 例如 `RX id=1 fc=0x03 12.3ms: 01 03 02 00 07 F9 86`
+pub(crate) fn log_frame(
+    level: log::Level,
+    direction: Direction,
+    id: Option<u8>,
+    function_code: Option<u8>,
+    elapsed: Option<Duration>,
+    data: &[u8],
+) {
+    if !log::log_enabled!(target: WIRE_LOG_TARGET, level) {
+        return;
+    }
+    let mut line = match direction {
+        Direction::Tx => String::from("TX"),
+        Direction::Rx => String::from("RX"),
+    };
+    if let Some(id) = id {
+        line += &format!(" id={}", id);
+    }
+    if let Some(code) = function_code {
+        line += &format!(" fc=0x{:02X}", code);
+    }
+    if let Some(elapsed) = elapsed {
+        line += &format!(" {:.1}ms", elapsed.as_secs_f64() * 1000.0);
+    }
+    line.push(':');
+    for b in data {
+        line += &format!(" {:02X}", b);
+    }
+    log::log!(target: WIRE_LOG_TARGET, level, "{}", line);
+}
@@ -0,0 +1,227 @@
+use std::{
+    io::{self, Read, Write},
+    net::{self, SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use crate::{config::Config, error::Result, stream::Stream, MODBUS_ASCII_MAX_FRAME_SIZE};
+
+/// 等待响应超时后重新发送请求的默认次数
+pub const DEFAULT_UDP_RETRIES: u32 = 2;
+
+/// Modbus UDP 传输, 每个请求和响应都是一个数据报
+///
+/// 与 TCP 一样使用 MBAP 报文头 (`Protocol::Tcp`). 写入的数据在 flush 时作为一个数据报发送,
+/// 读取时一次接收一个完整的数据报. 数据报可能丢失, 等待响应超时后重新发送请求,
+/// 每次等待的时间为读超时时间平均分配到每次发送, 总的等待时间不超过读超时时间
+pub struct UdpStream {
+    inner: net::UdpSocket,
+    addr: String,
+    read_timeout: Duration,
+    write_timeout: Duration,
+    retries: u32,
+    /// 还没有发送的数据, 以及最近一次发送的请求
+    tx: Vec<u8>,
+    sent: Vec<u8>,
+    /// 收到的数据报中还没有读取的数据
+    rx: Vec<u8>,
+    rx_pos: usize,
+    /// 发送请求之后还没有收到任何响应, 此时超时可以重新发送
+    awaiting_reply: bool,
+}
+
+impl UdpStream {
+    /// 连接 Modbus UDP 服务器, addr 格式为 `host:port`
+    pub fn new(addr: &str) -> Result<Self> {
+        let timeout = Duration::from_millis(5000);
+        Self::open(addr, timeout, timeout)
+    }
+
+    /// 使用 Config 中的 读/写 超时时间
+    pub fn with_config(addr: &str, config: &Config) -> Result<Self> {
+        Self::open(addr, config.read_timeout, config.write_timeout)
+    }
+
+    fn open(addr: &str, read_timeout: Duration, write_timeout: Duration) -> Result<Self> {
+        let mut stream = Self {
+            inner: Self::connect(addr)?,
+            addr: addr.to_string(),
+            read_timeout,
+            write_timeout,
+            retries: DEFAULT_UDP_RETRIES,
+            tx: Vec::new(),
+            sent: Vec::new(),
+            rx: Vec::new(),
+            rx_pos: 0,
+            awaiting_reply: false,
+        };
+        stream.apply_options()?;
+        Ok(stream)
+    }
+
+    /// 绑定本地的任意端口, 只接收服务器发来的数据报
+    fn connect(addr: &str) -> io::Result<net::UdpSocket> {
+        let peer = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "无法解析服务器地址"))?;
+        let local: SocketAddr = match peer {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let socket = net::UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        Ok(socket)
+    }
+
+    fn apply_options(&mut self) -> Result<()> {
+        self.inner.set_read_timeout(Some(self.attempt_timeout()))?;
+        self.inner.set_write_timeout(Some(self.write_timeout))?;
+        Ok(())
+    }
+
+    /// 每次发送请求后等待响应的时间
+    fn attempt_timeout(&self) -> Duration {
+        (self.read_timeout / (self.retries + 1)).max(Duration::from_millis(1))
+    }
+
+    /// 设置等待响应超时后重新发送请求的次数, 默认为 2
+    pub fn set_retries(&mut self, retries: u32) -> Result<()> {
+        self.retries = retries;
+        self.apply_options()
+    }
+
+    /// 设置 数据读写 的超时时间
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.read_timeout = timeout;
+        self.write_timeout = timeout;
+        self.apply_options()
+    }
+
+    /// 设置等待响应的超时时间, 包括重新发送请求的时间
+    pub fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.read_timeout = timeout;
+        self.apply_options()
+    }
+
+    /// 设置发送请求的超时时间
+    pub fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.write_timeout = timeout;
+        self.apply_options()
+    }
+
+    /// 重新创建套接字
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.inner = Self::connect(&self.addr)?;
+        self.tx.clear();
+        self.rx.clear();
+        self.awaiting_reply = false;
+        self.apply_options()
+    }
+
+    /// 丢弃没有读取的数据报, 例如重新发送请求之后收到的重复响应
+    pub fn clear_input(&mut self) -> Result<()> {
+        self.rx.clear();
+        self.inner.set_nonblocking(true)?;
+        let mut buf = [0u8; MODBUS_ASCII_MAX_FRAME_SIZE];
+        let res = loop {
+            match self.inner.recv(&mut buf) {
+                Ok(n) => log::warn!("丢弃过期的数据报: {:02X?}", &buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.inner.set_nonblocking(false)?;
+        Ok(res?)
+    }
+
+    /// 服务器的地址
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.inner.peer_addr()?)
+    }
+
+    /// 接收一个数据报, 还没有收到响应时超时后重新发送请求
+    fn recv_datagram(&mut self) -> io::Result<()> {
+        let mut retries = if self.awaiting_reply { self.retries } else { 0 };
+        self.rx.resize(MODBUS_ASCII_MAX_FRAME_SIZE, 0);
+        loop {
+            match self.inner.recv(&mut self.rx) {
+                Ok(n) => {
+                    self.rx.truncate(n);
+                    self.rx_pos = 0;
+                    self.awaiting_reply = false;
+                    return Ok(());
+                }
+                Err(e)
+                    if retries > 0
+                        && matches!(
+                            e.kind(),
+                            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                        ) =>
+                {
+                    retries -= 1;
+                    log::warn!("等待 UDP 响应超时, 重新发送请求");
+                    self.inner.send(&self.sent)?;
+                }
+                Err(e) => {
+                    self.rx.clear();
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+impl Read for UdpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rx_pos >= self.rx.len() {
+            self.recv_datagram()?;
+        }
+        let n = buf.len().min(self.rx.len() - self.rx_pos);
+        buf[..n].copy_from_slice(&self.rx[self.rx_pos..self.rx_pos + n]);
+        self.rx_pos += n;
+        Ok(n)
+    }
+}
+
+impl Write for UdpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// 把写入的数据作为一个数据报发送
+    fn flush(&mut self) -> io::Result<()> {
+        if self.tx.is_empty() {
+            return Ok(());
+        }
+        self.inner.send(&self.tx)?;
+        std::mem::swap(&mut self.sent, &mut self.tx);
+        self.tx.clear();
+        self.awaiting_reply = true;
+        Ok(())
+    }
+}
+
+impl Stream for UdpStream {
+    fn set_timeout(&mut self, timeout: Duration) -> Result<()> {
+        UdpStream::set_timeout(self, timeout)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<()> {
+        UdpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Duration) -> Result<()> {
+        UdpStream::set_write_timeout(self, timeout)
+    }
+
+    fn clear_input(&mut self) -> Result<()> {
+        UdpStream::clear_input(self)
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        UdpStream::reconnect(self)
+    }
+}
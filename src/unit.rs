@@ -0,0 +1,148 @@
+//! 只和一个从设备通信时, 省略每次调用的从设备ID
+//!
+//! ```no_run
+//! # use simple_modbus::Client;
+//! # fn run(client: &mut Client) -> simple_modbus::error::Result<()> {
+//! client.set_unit_id(1);
+//! let mut unit = client.unit();
+//! let values = unit.read_holding_registers(0x0000, 4)?;
+//! unit.write_single_register(0x0010, values[0])?;
+//! // 其它从设备仍然可以使用带ID的方法
+//! client.write_single_register(2, 0x0010, 1)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    address::{CoilAddress, DiscreteAddress, HoldingAddress, InputAddress},
+    error::Result,
+    quantity::Quantity,
+    value::RegisterValue,
+    Client, Coil, Id, Word,
+};
+
+/// 借用客户端, 所有请求发送给同一个从设备, 通过 [`Client::unit`] 得到
+pub struct UnitClient<'a> {
+    client: &'a mut Client,
+    id: Id,
+}
+
+impl<'a> UnitClient<'a> {
+    pub fn new(client: &'a mut Client, id: Id) -> Self {
+        Self { client, id }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// 底层的客户端, 用于这里没有提供的请求
+    pub fn client(&mut self) -> &mut Client {
+        self.client
+    }
+
+    pub fn read_coils(
+        &mut self,
+        address: impl Into<CoilAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        self.client.read_coils(self.id, address, quantity)
+    }
+
+    pub fn read_discrete_inputs(
+        &mut self,
+        address: impl Into<DiscreteAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Coil>> {
+        self.client.read_discrete_inputs(self.id, address, quantity)
+    }
+
+    pub fn read_holding_registers(
+        &mut self,
+        address: impl Into<HoldingAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        self.client
+            .read_holding_registers(self.id, address, quantity)
+    }
+
+    pub fn read_input_registers(
+        &mut self,
+        address: impl Into<InputAddress>,
+        quantity: impl Into<Quantity>,
+    ) -> Result<Vec<Word>> {
+        self.client.read_input_registers(self.id, address, quantity)
+    }
+
+    pub fn write_single_register(
+        &mut self,
+        address: impl Into<HoldingAddress>,
+        value: Word,
+    ) -> Result<()> {
+        self.client.write_single_register(self.id, address, value)
+    }
+
+    pub fn write_multiple_registers(
+        &mut self,
+        address: impl Into<HoldingAddress>,
+        values: Vec<Word>,
+    ) -> Result<()> {
+        self.client
+            .write_multiple_registers(self.id, address, values)
+    }
+
+    pub fn mask_write_register(
+        &mut self,
+        address: impl Into<HoldingAddress>,
+        and_mask: Word,
+        or_mask: Word,
+    ) -> Result<()> {
+        self.client
+            .mask_write_register(self.id, address, and_mask, or_mask)
+    }
+
+    pub fn read_write_multiple_registers(
+        &mut self,
+        read_address: impl Into<HoldingAddress>,
+        read_quantity: impl Into<Quantity>,
+        write_address: impl Into<HoldingAddress>,
+        values: &[Word],
+    ) -> Result<Vec<Word>> {
+        self.client.read_write_multiple_registers(
+            self.id,
+            read_address,
+            read_quantity,
+            write_address,
+            values,
+        )
+    }
+
+    /// 从保持寄存器读取一个数值, 按照客户端的字节顺序解码
+    pub fn read_value<T: RegisterValue>(
+        &mut self,
+        address: impl Into<HoldingAddress>,
+    ) -> Result<T> {
+        self.client.read_value(self.id, address)
+    }
+
+    /// 按照客户端的字节顺序编码, 写入保持寄存器
+    pub fn write_value<T: RegisterValue>(
+        &mut self,
+        address: impl Into<HoldingAddress>,
+        value: T,
+    ) -> Result<()> {
+        self.client.write_value(self.id, address, value)
+    }
+
+    pub fn read_string(
+        &mut self,
+        address: impl Into<HoldingAddress>,
+        byte_len: usize,
+    ) -> Result<String> {
+        self.client.read_string(self.id, address, byte_len)
+    }
+
+    pub fn write_string(&mut self, address: impl Into<HoldingAddress>, s: &str) -> Result<()> {
+        self.client.write_string(self.id, address, s)
+    }
+}
@@ -0,0 +1,378 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    config::invalid_config,
+    error::{Error, Reason, Result},
+    Word,
+};
+
+/// 多个寄存器组成一个数值时的字节顺序
+///
+/// 以 32 位数值 0xAABBCCDD 为例, A 为最高字节, 寄存器的内容分别为:
+/// ABCD: [0xAABB, 0xCCDD], CDAB: [0xCCDD, 0xAABB],
+/// BADC: [0xBBAA, 0xDDCC], DCBA: [0xDDCC, 0xBBAA].
+/// 64 位数值按同样的规则处理 4 个寄存器
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WordOrder {
+    /// 大端, Modbus 规范的顺序
+    #[default]
+    ABCD,
+    /// 寄存器顺序颠倒, 寄存器内为大端
+    CDAB,
+    /// 寄存器顺序不变, 寄存器内的两个字节交换
+    BADC,
+    /// 小端
+    DCBA,
+}
+
+impl WordOrder {
+    fn swap_words(&self) -> bool {
+        matches!(self, WordOrder::CDAB | WordOrder::DCBA)
+    }
+
+    fn swap_bytes(&self) -> bool {
+        matches!(self, WordOrder::BADC | WordOrder::DCBA)
+    }
+
+    /// 把大端的字节数据转换为寄存器
+    fn words_from(&self, bytes: &[u8]) -> Vec<Word> {
+        let mut words = bytes
+            .chunks(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .map(|w| if self.swap_bytes() { w.swap_bytes() } else { w })
+            .collect::<Vec<_>>();
+        if self.swap_words() {
+            words.reverse();
+        }
+        words
+    }
+
+    /// 把寄存器转换为大端的字节数据
+    fn bytes_from(&self, words: &[Word]) -> Vec<u8> {
+        let mut words = words.to_vec();
+        if self.swap_words() {
+            words.reverse();
+        }
+        words
+            .into_iter()
+            .map(|w| if self.swap_bytes() { w.swap_bytes() } else { w })
+            .flat_map(u16::to_be_bytes)
+            .collect()
+    }
+
+    /// 把寄存器转换为数值, words 的长度必须为 `T::WORDS`
+    pub fn decode<T: RegisterValue>(&self, words: &[Word]) -> Result<T> {
+        if words.len() != T::WORDS {
+            return Err(Error::InvalidData(Reason::UnexpectedReplySize));
+        }
+        Ok(T::from_be_bytes(&self.bytes_from(words)))
+    }
+
+    /// 把数值转换为寄存器
+    pub fn encode<T: RegisterValue>(&self, value: T) -> Vec<Word> {
+        self.words_from(&value.to_be_bytes())
+    }
+}
+
+impl FromStr for WordOrder {
+    type Err = Error;
+
+    /// 不区分大小写, 例如 "cdab"
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "ABCD" => Ok(WordOrder::ABCD),
+            "CDAB" => Ok(WordOrder::CDAB),
+            "BADC" => Ok(WordOrder::BADC),
+            "DCBA" => Ok(WordOrder::DCBA),
+            _ => Err(invalid_config(&format!("未知的字节顺序: {}", s))),
+        }
+    }
+}
+
+/// 运行时确定的数值类型, 用于寄存器表等从配置中读取类型的场合
+///
+/// 所有类型都与 f64 相互转换, 64 位整数超过 2^53 时会损失精度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValueType {
+    /// 线圈或离散输入, 在寄存器中时非 0 为 true
+    Bool,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    U64,
+    I64,
+    F64,
+}
+
+impl ValueType {
+    /// 占用的寄存器数量
+    pub fn words(&self) -> u16 {
+        match self {
+            ValueType::Bool | ValueType::U16 | ValueType::I16 => 1,
+            ValueType::U32 | ValueType::I32 | ValueType::F32 => 2,
+            ValueType::U64 | ValueType::I64 | ValueType::F64 => 4,
+        }
+    }
+
+    /// 把寄存器转换为数值, words 的长度必须为 `self.words()`
+    pub fn decode(&self, words: &[Word], order: WordOrder) -> Result<f64> {
+        Ok(match self {
+            ValueType::Bool => {
+                let value: u16 = order.decode(words)?;
+                if value != 0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ValueType::U16 => order.decode::<u16>(words)? as f64,
+            ValueType::I16 => order.decode::<i16>(words)? as f64,
+            ValueType::U32 => order.decode::<u32>(words)? as f64,
+            ValueType::I32 => order.decode::<i32>(words)? as f64,
+            ValueType::F32 => order.decode::<f32>(words)? as f64,
+            ValueType::U64 => order.decode::<u64>(words)? as f64,
+            ValueType::I64 => order.decode::<i64>(words)? as f64,
+            ValueType::F64 => order.decode::<f64>(words)?,
+        })
+    }
+
+    /// 把数值限制在类型的范围之内, 整数类型四舍五入
+    pub fn saturate(&self, value: f64) -> f64 {
+        let (min, max) = match self {
+            ValueType::Bool => return value,
+            ValueType::U16 => (0.0, u16::MAX as f64),
+            ValueType::I16 => (i16::MIN as f64, i16::MAX as f64),
+            ValueType::U32 => (0.0, u32::MAX as f64),
+            ValueType::I32 => (i32::MIN as f64, i32::MAX as f64),
+            ValueType::F32 => (f32::MIN as f64, f32::MAX as f64),
+            ValueType::U64 => (0.0, u64::MAX as f64),
+            ValueType::I64 => (i64::MIN as f64, i64::MAX as f64),
+            ValueType::F64 => return value,
+        };
+        let value = match self {
+            ValueType::F32 => value,
+            _ => value.round(),
+        };
+        value.clamp(min, max)
+    }
+
+    /// 把数值转换为寄存器, 整数类型四舍五入, 超出类型的范围时返回错误
+    pub fn encode(&self, value: f64, order: WordOrder) -> Result<Vec<Word>> {
+        let out_of_range = || Error::InvalidData(Reason::ValueOutOfRange(*self));
+        let int = |min: f64, max: f64| {
+            let value = value.round();
+            if value.is_nan() || value < min || value > max {
+                return Err(out_of_range());
+            }
+            Ok(value)
+        };
+        Ok(match self {
+            ValueType::Bool => order.encode((value != 0.0) as u16),
+            ValueType::U16 => order.encode(int(0.0, u16::MAX as f64)? as u16),
+            ValueType::I16 => order.encode(int(i16::MIN as f64, i16::MAX as f64)? as i16),
+            ValueType::U32 => order.encode(int(0.0, u32::MAX as f64)? as u32),
+            ValueType::I32 => order.encode(int(i32::MIN as f64, i32::MAX as f64)? as i32),
+            ValueType::F32 => {
+                if value.is_finite() && value.abs() > f32::MAX as f64 {
+                    return Err(out_of_range());
+                }
+                order.encode(value as f32)
+            }
+            ValueType::U64 => order.encode(int(0.0, u64::MAX as f64)? as u64),
+            ValueType::I64 => order.encode(int(i64::MIN as f64, i64::MAX as f64)? as i64),
+            ValueType::F64 => order.encode(value),
+        })
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::Bool => "bool",
+            ValueType::U16 => "u16",
+            ValueType::I16 => "i16",
+            ValueType::U32 => "u32",
+            ValueType::I32 => "i32",
+            ValueType::F32 => "f32",
+            ValueType::U64 => "u64",
+            ValueType::I64 => "i64",
+            ValueType::F64 => "f64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = Error;
+
+    /// 不区分大小写, 例如 "f32"
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "bool" => Ok(ValueType::Bool),
+            "u16" => Ok(ValueType::U16),
+            "i16" => Ok(ValueType::I16),
+            "u32" => Ok(ValueType::U32),
+            "i32" => Ok(ValueType::I32),
+            "f32" => Ok(ValueType::F32),
+            "u64" => Ok(ValueType::U64),
+            "i64" => Ok(ValueType::I64),
+            "f64" => Ok(ValueType::F64),
+            _ => Err(invalid_config(&format!("未知的数据类型: {}", s))),
+        }
+    }
+}
+
+/// 可以保存在连续的寄存器中的数值类型
+pub trait RegisterValue: Sized {
+    /// 占用的寄存器数量
+    const WORDS: usize;
+
+    fn from_be_bytes(bytes: &[u8]) -> Self;
+
+    fn to_be_bytes(&self) -> Vec<u8>;
+}
+
+macro_rules! register_value {
+    ($($ty:ty),*) => {
+        $(
+            impl RegisterValue for $ty {
+                const WORDS: usize = std::mem::size_of::<$ty>() / 2;
+
+                fn from_be_bytes(bytes: &[u8]) -> Self {
+                    let mut buf = [0u8; std::mem::size_of::<$ty>()];
+                    buf.copy_from_slice(bytes);
+                    <$ty>::from_be_bytes(buf)
+                }
+
+                fn to_be_bytes(&self) -> Vec<u8> {
+                    <$ty>::to_be_bytes(*self).to_vec()
+                }
+            }
+        )*
+    };
+}
+
+register_value!(u16, i16, u32, i32, f32, u64, i64, f64);
+
+/// 把字符串按每个寄存器两个字节打包, 长度为奇数时末尾补 0
+///
+/// swap_bytes 为 false 时第一个字符在寄存器的高字节
+pub fn pack_string(s: &str, swap_bytes: bool) -> Vec<Word> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|b| u16::from_be_bytes([b[0], b.get(1).copied().unwrap_or(0)]))
+        .map(|w| if swap_bytes { w.swap_bytes() } else { w })
+        .collect()
+}
+
+/// 从寄存器中取出 byte_len 个字节的字符串, 去掉末尾填充的 0 和空格
+pub fn unpack_string(words: &[Word], byte_len: usize, swap_bytes: bool) -> String {
+    let bytes = words
+        .iter()
+        .map(|w| if swap_bytes { w.swap_bytes() } else { *w })
+        .flat_map(u16::to_be_bytes)
+        .take(byte_len)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes)
+        .trim_end_matches(['\0', ' '])
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pack_string, unpack_string, ValueType, WordOrder};
+
+    const ORDERS: [WordOrder; 4] = [
+        WordOrder::ABCD,
+        WordOrder::CDAB,
+        WordOrder::BADC,
+        WordOrder::DCBA,
+    ];
+
+    #[test]
+    fn word_layout() {
+        let expected = [
+            [0xAABB, 0xCCDD],
+            [0xCCDD, 0xAABB],
+            [0xBBAA, 0xDDCC],
+            [0xDDCC, 0xBBAA],
+        ];
+        for (order, words) in ORDERS.into_iter().zip(expected) {
+            assert_eq!(order.encode(0xAABB_CCDDu32), words, "{:?}", order);
+            assert_eq!(order.decode::<u32>(&words).unwrap(), 0xAABB_CCDD);
+        }
+        assert_eq!(
+            WordOrder::CDAB.encode(0x1122_3344_5566_7788u64),
+            [0x7788, 0x5566, 0x3344, 0x1122]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for order in ORDERS {
+            assert_eq!(
+                order.decode::<u16>(&order.encode(0xA55Au16)).unwrap(),
+                0xA55A
+            );
+            assert_eq!(order.decode::<i16>(&order.encode(-2i16)).unwrap(), -2);
+            assert_eq!(
+                order.decode::<i32>(&order.encode(-123_456i32)).unwrap(),
+                -123_456
+            );
+            assert_eq!(order.decode::<f32>(&order.encode(3.25f32)).unwrap(), 3.25);
+            let v = 0x0102_0304_0506_0708u64;
+            assert_eq!(order.decode::<u64>(&order.encode(v)).unwrap(), v);
+            assert_eq!(
+                order.decode::<i64>(&order.encode(i64::MIN)).unwrap(),
+                i64::MIN
+            );
+            assert_eq!(order.decode::<f64>(&order.encode(-0.1f64)).unwrap(), -0.1);
+        }
+    }
+
+    #[test]
+    fn decode_wrong_length() {
+        assert!(WordOrder::ABCD.decode::<u32>(&[1]).is_err());
+        assert!(WordOrder::DCBA.decode::<f64>(&[1, 2]).is_err());
+    }
+
+    #[test]
+    fn value_type_round_trip() {
+        for order in ORDERS {
+            for (ty, value) in [
+                (ValueType::I16, -300.0),
+                (ValueType::U32, 4_000_000_000.0),
+                (ValueType::F32, 1.5),
+                (ValueType::I64, -1.0),
+                (ValueType::F64, 1e300),
+            ] {
+                let words = ty.encode(value, order).unwrap();
+                assert_eq!(words.len(), ty.words() as usize);
+                assert_eq!(ty.decode(&words, order).unwrap(), value);
+            }
+        }
+        assert!(ValueType::U16.encode(65536.0, WordOrder::ABCD).is_err());
+        assert!(ValueType::I32.encode(f64::NAN, WordOrder::ABCD).is_err());
+    }
+
+    #[test]
+    fn parse_names() {
+        assert_eq!("cdab".parse::<WordOrder>().unwrap(), WordOrder::CDAB);
+        assert!("abdc".parse::<WordOrder>().is_err());
+        assert_eq!("F32".parse::<ValueType>().unwrap(), ValueType::F32);
+    }
+
+    #[test]
+    fn strings() {
+        let words = pack_string("ABC", false);
+        assert_eq!(words, [0x4142, 0x4300]);
+        assert_eq!(unpack_string(&words, 4, false), "ABC");
+        let words = pack_string("ABC", true);
+        assert_eq!(words, [0x4241, 0x0043]);
+        assert_eq!(unpack_string(&words, 3, true), "ABC");
+    }
+}
@@ -0,0 +1,162 @@
+//! 监视寄存器: 按照间隔重复读取, 只报告变化的值
+
+use std::{
+    fmt, thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::Result,
+    poll::{read, PollFunction, PollTask, PollValue, StopHandle},
+    Client, Coil, Id, Word,
+};
+
+/// 一个寄存器的值发生了变化
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Change {
+    /// 监视项的序号
+    pub item: usize,
+    pub function: PollFunction,
+    pub id: Id,
+    pub address: u16,
+    /// 第一次读取时为 None
+    pub old: Option<Word>,
+    /// 线圈和离散输入为 0 或 1
+    pub new: Word,
+    pub timestamp: SystemTime,
+}
+
+impl fmt::Display for Change {
+    /// 例如 `12:00:01.123 holding 1@0x0010: 0x0001 -> 0x0002`, 时间为 UTC
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let area = match self.function {
+            PollFunction::Coils => "coil",
+            PollFunction::DiscreteInputs => "discrete",
+            PollFunction::HoldingRegisters => "holding",
+            PollFunction::InputRegisters => "input",
+        };
+        write!(
+            f,
+            "{} {} {}@0x{:04X}: ",
+            format_time(self.timestamp),
+            area,
+            self.id,
+            self.address
+        )?;
+        match self.old {
+            Some(old) => write!(f, "0x{:04X} -> 0x{:04X}", old, self.new),
+            None => write!(f, "0x{:04X}", self.new),
+        }
+    }
+}
+
+/// 把时间格式化为 UTC 的 `时:分:秒.毫秒`
+pub fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() % 86400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+struct WatchItem {
+    task: PollTask,
+    last: Option<Vec<Word>>,
+}
+
+/// 监视一组寄存器, 每次读取后与上一次的值比较, 只报告变化的寄存器
+pub struct Watcher {
+    items: Vec<WatchItem>,
+    interval: Duration,
+}
+
+impl Watcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            items: Vec::new(),
+            interval,
+        }
+    }
+
+    /// 添加监视项, 返回它的序号
+    pub fn add(&mut self, function: PollFunction, id: Id, address: u16, quantity: u16) -> usize {
+        self.items.push(WatchItem {
+            task: PollTask {
+                function,
+                id,
+                address,
+                quantity,
+                interval: self.interval,
+            },
+            last: None,
+        });
+        self.items.len() - 1
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// 读取所有监视项, 返回变化的寄存器. 第一次读取时返回所有的值
+    ///
+    /// 某一项读取失败时返回错误, 已经读取的项目保留新的值
+    pub fn poll(&mut self, client: &mut Client) -> Result<Vec<Change>> {
+        let mut changes = Vec::new();
+        for (index, item) in self.items.iter_mut().enumerate() {
+            let values = match read(client, &item.task)? {
+                PollValue::Words(words) => words,
+                PollValue::Bits(bits) => {
+                    bits.into_iter().map(|b| (b == Coil::On) as Word).collect()
+                }
+            };
+            let timestamp = SystemTime::now();
+            for (i, &new) in values.iter().enumerate() {
+                let old = item.last.as_ref().and_then(|last| last.get(i).copied());
+                if old == Some(new) {
+                    continue;
+                }
+                changes.push(Change {
+                    item: index,
+                    function: item.task.function,
+                    id: item.task.id,
+                    address: item.task.address.wrapping_add(i as u16),
+                    old,
+                    new,
+                    timestamp,
+                });
+            }
+            item.last = Some(values);
+        }
+        Ok(changes)
+    }
+
+    /// 循环读取, 直到通过 StopHandle 停止. 每个变化调用一次 on_change, 读取失败时记录日志后继续
+    pub fn run(
+        &mut self,
+        client: &mut Client,
+        stop: &StopHandle,
+        mut on_change: impl FnMut(&Change),
+    ) {
+        // 每次最多等待的时间, 保证能及时响应停止
+        const MAX_SLEEP: Duration = Duration::from_millis(50);
+        while !stop.is_stopped() {
+            let next_run = Instant::now() + self.interval;
+            match self.poll(client) {
+                Ok(changes) => changes.iter().for_each(&mut on_change),
+                Err(e) => log::warn!("监视寄存器, 读取失败, E: {}", e),
+            }
+            while !stop.is_stopped() {
+                let wait = next_run.saturating_duration_since(Instant::now());
+                if wait.is_zero() {
+                    break;
+                }
+                thread::sleep(wait.min(MAX_SLEEP));
+            }
+        }
+    }
+}